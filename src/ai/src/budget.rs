@@ -0,0 +1,348 @@
+//! Usage budgets for metered providers: request and token limits per World and per account,
+//! counted in fixed time windows.
+//!
+//! [`Budgets`] is checked before every provider call and charged after it. It holds usage in
+//! memory and marks changed scopes dirty; the Engine persists them and restores them at boot.
+//! Checks and charges are separate steps, so concurrent calls can overshoot a limit by at most
+//! the calls already in flight.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AiError, AiResult, Completion, CompletionRequest};
+
+/// Scope key for usage shared by the whole World.
+pub const WORLD_SCOPE: &str = "world";
+
+/// Request and token caps for one scope. `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetLimit {
+    pub requests: Option<u64>,
+    pub tokens: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    /// Length of each usage window. Windows are aligned to the Unix epoch, so a one-day
+    /// window resets at midnight UTC.
+    #[serde(with = "secs")]
+    pub window: Duration,
+    pub world: BudgetLimit,
+    /// Applied to each account separately.
+    pub account: BudgetLimit,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        BudgetConfig {
+            window: Duration::from_secs(24 * 60 * 60),
+            world: BudgetLimit::default(),
+            account: BudgetLimit::default(),
+        }
+    }
+}
+
+mod secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+/// Usage of one scope within the window starting at `window_start` (Unix seconds).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub window_start: u64,
+    pub requests: u64,
+    pub tokens: u64,
+}
+
+/// Usage of one scope next to its limit, as reported to operators.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BudgetReport {
+    pub scope: String,
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub limit: BudgetLimit,
+    pub resets_at: u64,
+}
+
+pub struct Budgets {
+    config: RwLock<BudgetConfig>,
+    usage: Mutex<BTreeMap<String, Usage>>,
+    dirty: Mutex<BTreeSet<String>>,
+}
+
+impl Budgets {
+    pub fn new(config: BudgetConfig) -> Self {
+        Budgets {
+            config: RwLock::new(config),
+            usage: Mutex::new(BTreeMap::new()),
+            dirty: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn config(&self) -> BudgetConfig {
+        *self.config.read().expect("lock poisoning")
+    }
+
+    /// Change the limits. Usage recorded so far is kept.
+    pub fn set_config(&self, config: BudgetConfig) {
+        *self.config.write().expect("lock poisoning") = config;
+    }
+
+    /// Fail with [`AiError::BudgetExhausted`] if a call by `account` sending `tokens` prompt
+    /// tokens would exceed the World's or the account's budget.
+    pub fn check(&self, account: Option<&str>, tokens: u64, now: SystemTime) -> AiResult<()> {
+        let config = self.config();
+        let start = window_start(config.window, now);
+        let usage = self.usage.lock().expect("lock poisoning");
+        for (scope, limit) in scopes(&config, account) {
+            let used = current(usage.get(&scope), start);
+            let over_requests = limit.requests.is_some_and(|max| used.requests >= max);
+            let over_tokens = limit.tokens.is_some_and(|max| used.tokens + tokens > max);
+            if over_requests || over_tokens {
+                return Err(AiError::BudgetExhausted {
+                    scope,
+                    resets_at: start + config.window.as_secs(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Charge one request and `tokens` tokens to the World and to `account`.
+    pub fn record(&self, account: Option<&str>, tokens: u64, now: SystemTime) {
+        let config = self.config();
+        let start = window_start(config.window, now);
+        let mut usage = self.usage.lock().expect("lock poisoning");
+        let mut dirty = self.dirty.lock().expect("lock poisoning");
+        for (scope, _) in scopes(&config, account) {
+            let entry = usage.entry(scope.clone()).or_default();
+            *entry = current(Some(entry), start);
+            entry.requests += 1;
+            entry.tokens += tokens;
+            dirty.insert(scope);
+        }
+    }
+
+    /// Check the budget, run `call`, and charge the prompt plus whatever text came back. A
+    /// failed call is still charged its request and prompt tokens.
+    pub fn metered(
+        &self,
+        account: Option<&str>,
+        request: &CompletionRequest,
+        call: impl FnOnce() -> AiResult<Completion>,
+    ) -> AiResult<Completion> {
+        let prompt = estimate_tokens(&request.system) + estimate_tokens(&request.prompt);
+        self.check(account, prompt, SystemTime::now())?;
+        let result = call();
+        let reply = result
+            .as_ref()
+            .map(|completion| estimate_tokens(&completion.text))
+            .unwrap_or(0);
+        self.record(account, prompt + reply, SystemTime::now());
+        result
+    }
+
+    /// Usage of `scope` in the current window.
+    pub fn usage(&self, scope: &str, now: SystemTime) -> Usage {
+        let start = window_start(self.config().window, now);
+        current(self.usage.lock().expect("lock poisoning").get(scope), start)
+    }
+
+    /// Every scope with usage in the current window, plus the World scope, ordered by scope.
+    pub fn report(&self, now: SystemTime) -> Vec<BudgetReport> {
+        let config = self.config();
+        let start = window_start(config.window, now);
+        let usage = self.usage.lock().expect("lock poisoning");
+        let mut report = vec![BudgetReport {
+            scope: WORLD_SCOPE.into(),
+            usage: current(usage.get(WORLD_SCOPE), start),
+            limit: config.world,
+            resets_at: start + config.window.as_secs(),
+        }];
+        report.extend(
+            usage
+                .iter()
+                .filter(|(scope, used)| scope.as_str() != WORLD_SCOPE && used.window_start == start)
+                .map(|(scope, used)| BudgetReport {
+                    scope: scope.clone(),
+                    usage: *used,
+                    limit: config.account,
+                    resets_at: start + config.window.as_secs(),
+                }),
+        );
+        report
+    }
+
+    /// Load persisted usage, e.g. at boot.
+    pub fn restore(&self, scope: &str, usage: Usage) {
+        self.usage
+            .lock()
+            .expect("lock poisoning")
+            .insert(scope.to_string(), usage);
+    }
+
+    /// Forget usage of `scope` in the current window. Returns `false` if it had none.
+    pub fn reset(&self, scope: &str) -> bool {
+        let removed = self
+            .usage
+            .lock()
+            .expect("lock poisoning")
+            .insert(scope.to_string(), Usage::default());
+        self.dirty
+            .lock()
+            .expect("lock poisoning")
+            .insert(scope.to_string());
+        removed.is_some_and(|usage| usage != Usage::default())
+    }
+
+    /// Scopes changed since the last call, with their usage, for persisting.
+    pub fn take_dirty(&self) -> Vec<(String, Usage)> {
+        let scopes = std::mem::take(&mut *self.dirty.lock().expect("lock poisoning"));
+        let usage = self.usage.lock().expect("lock poisoning");
+        scopes
+            .into_iter()
+            .map(|scope| {
+                let used = usage.get(&scope).copied().unwrap_or_default();
+                (scope, used)
+            })
+            .collect()
+    }
+}
+
+impl Default for Budgets {
+    fn default() -> Self {
+        Budgets::new(BudgetConfig::default())
+    }
+}
+
+/// Scope key for one account's usage.
+pub fn account_scope(account: &str) -> String {
+    format!("account/{}", account)
+}
+
+/// Rough token count for budgeting: about four characters per token.
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+fn scopes(config: &BudgetConfig, account: Option<&str>) -> Vec<(String, BudgetLimit)> {
+    let mut scopes = vec![(WORLD_SCOPE.to_string(), config.world)];
+    if let Some(account) = account.filter(|account| !account.is_empty()) {
+        scopes.push((account_scope(account), config.account));
+    }
+    scopes
+}
+
+fn window_start(window: Duration, now: SystemTime) -> u64 {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let window = window.as_secs().max(1);
+    now - now % window
+}
+
+/// `usage` if it belongs to the window starting at `start`, otherwise an empty window.
+fn current(usage: Option<&Usage>, start: u64) -> Usage {
+    match usage {
+        Some(usage) if usage.window_start == start => *usage,
+        _ => Usage {
+            window_start: start,
+            ..Usage::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn budgets() -> Budgets {
+        Budgets::new(BudgetConfig {
+            window: Duration::from_secs(100),
+            world: BudgetLimit {
+                requests: Some(3),
+                tokens: None,
+            },
+            account: BudgetLimit {
+                requests: None,
+                tokens: Some(50),
+            },
+        })
+    }
+
+    #[test]
+    fn world_and_account_limits_apply_per_window() {
+        let budgets = budgets();
+        budgets.record(Some("alice"), 40, at(1_000));
+        assert!(budgets.check(Some("alice"), 10, at(1_010)).is_ok());
+        assert_eq!(
+            budgets.check(Some("alice"), 11, at(1_010)),
+            Err(AiError::BudgetExhausted {
+                scope: "account/alice".into(),
+                resets_at: 1_100
+            })
+        );
+        assert!(budgets.check(Some("bob"), 11, at(1_010)).is_ok());
+
+        budgets.record(Some("bob"), 1, at(1_020));
+        budgets.record(None, 1, at(1_030));
+        assert!(matches!(
+            budgets.check(Some("bob"), 0, at(1_040)),
+            Err(AiError::BudgetExhausted { scope, .. }) if scope == "world"
+        ));
+        // A new window starts empty.
+        assert!(budgets.check(Some("alice"), 50, at(1_100)).is_ok());
+        assert_eq!(budgets.usage("world", at(1_100)).requests, 0);
+    }
+
+    #[test]
+    fn usage_is_reported_and_marked_dirty_for_persisting() {
+        let budgets = budgets();
+        let request = CompletionRequest::new("narration", "abcdefgh");
+        let completion = budgets
+            .metered(Some("alice"), &request, || {
+                Ok(Completion {
+                    text: "1234".into(),
+                    provider: "test".into(),
+                    model: "test".into(),
+                })
+            })
+            .unwrap();
+        assert_eq!(completion.text, "1234");
+        let now = SystemTime::now();
+        let dirty = budgets.take_dirty();
+        assert_eq!(
+            dirty
+                .iter()
+                .map(|(scope, _)| scope.as_str())
+                .collect::<Vec<_>>(),
+            vec!["account/alice", "world"]
+        );
+        assert_eq!((dirty[1].1.requests, dirty[1].1.tokens), (1, 3));
+        assert!(budgets.take_dirty().is_empty());
+
+        let report = budgets.report(now);
+        assert_eq!(report[0].scope, "world");
+        assert_eq!(report[1].limit.tokens, Some(50));
+
+        let restored = Budgets::new(budgets.config());
+        restored.restore("world", dirty[1].1);
+        assert_eq!(restored.usage("world", now).requests, 1);
+        assert!(restored.reset("world"));
+        assert_eq!(restored.usage("world", now).requests, 0);
+    }
+}
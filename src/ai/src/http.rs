@@ -0,0 +1,557 @@
+//! Provider for OpenAI-compatible `POST <endpoint>/chat/completions` APIs over plain HTTP/1.1.
+//!
+//! Point it at a local model server or a stand-in, or at a TLS-terminating proxy for cloud
+//! APIs. The API key is sent as a bearer token and never appears in errors or logs.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::{
+    AiError, AiProvider, AiResult, Capabilities, Completion, CompletionRequest, RetryPolicy, Secret,
+};
+
+/// Largest response body accepted from a provider, streamed or not.
+const MAX_RESPONSE_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct HttpProvider {
+    host: String,
+    port: u16,
+    base_path: String,
+    model: String,
+    api_key: Option<Secret>,
+    timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl HttpProvider {
+    /// `endpoint` is an `http://host[:port][/base]` URL, e.g. `http://127.0.0.1:8080/v1`.
+    pub fn new(endpoint: &str, model: impl Into<String>) -> AiResult<Self> {
+        let rest = match endpoint.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some(("https", _)) => {
+                return Err(AiError::Config(
+                    "https endpoints need a TLS-terminating proxy; use its http:// address".into(),
+                ))
+            }
+            _ => {
+                return Err(AiError::Config(format!(
+                    "'{}' is not an http:// URL",
+                    endpoint
+                )))
+            }
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => rest.split_at(idx),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| AiError::Config(format!("invalid port in '{}'", endpoint)))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(AiError::Config(format!("missing host in '{}'", endpoint)));
+        }
+        Ok(HttpProvider {
+            host: host.to_string(),
+            port,
+            base_path: path.trim_end_matches('/').to_string(),
+            model: model.into(),
+            api_key: None,
+            timeout: Duration::from_secs(30),
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    pub fn with_api_key(mut self, key: Secret) -> Self {
+        self.api_key = Some(key);
+        self
+    }
+
+    /// Bound on one whole request, from connecting through the last byte of the response,
+    /// retries included. A provider that keeps trickling bytes is cut off at it too.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn body(&self, request: &CompletionRequest, stream: bool) -> Value {
+        let mut messages = Vec::new();
+        if !request.system.is_empty() {
+            messages.push(json!({ "role": "system", "content": request.system }));
+        }
+        messages.push(json!({ "role": "user", "content": request.prompt }));
+        json!({
+            "model": self.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens,
+            "stream": stream,
+        })
+    }
+
+    /// Send the request and return a reader over the response body once a 2xx status has
+    /// arrived. Connection failures and retryable statuses are retried here, before any
+    /// output reaches the caller. Every read, including those the caller makes on the body,
+    /// fails with a timeout once `deadline` has passed, and the body ends after
+    /// [`MAX_RESPONSE_BYTES`] + 1 bytes so callers can tell an oversized one apart.
+    fn open(&self, body: &Value, deadline: Instant) -> AiResult<Box<dyn BufRead>> {
+        self.retry.run(|| {
+            let addr = (self.host.as_str(), self.port)
+                .to_socket_addrs()
+                .map_err(io_error)?
+                .next()
+                .ok_or_else(|| AiError::Unavailable(format!("cannot resolve {}", self.host)))?;
+            let left = remaining(deadline).map_err(io_error)?;
+            let mut stream = TcpStream::connect_timeout(&addr, left).map_err(io_error)?;
+            stream
+                .set_write_timeout(Some(remaining(deadline).map_err(io_error)?))
+                .map_err(io_error)?;
+            let payload = body.to_string();
+            let mut head = format!(
+                "POST {}/chat/completions HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nAccept: application/json, text/event-stream\r\nConnection: close\r\nContent-Length: {}\r\n",
+                self.base_path,
+                self.host,
+                self.port,
+                payload.len()
+            );
+            if let Some(key) = &self.api_key {
+                head.push_str(&format!("Authorization: Bearer {}\r\n", key.expose()));
+            }
+            head.push_str("\r\n");
+            stream.write_all(head.as_bytes()).map_err(io_error)?;
+            stream.write_all(payload.as_bytes()).map_err(io_error)?;
+
+            let mut reader = BufReader::new(DeadlineStream { stream, deadline });
+            let (status, chunked, length) = read_head(&mut reader)?;
+            let cap = MAX_RESPONSE_BYTES + 1;
+            let body: Box<dyn BufRead> = if chunked {
+                let chunks = Chunked {
+                    inner: reader,
+                    left: 0,
+                    done: false,
+                };
+                Box::new(BufReader::new(chunks.take(cap)))
+            } else {
+                Box::new(reader.take(length.map_or(cap, |length| length.min(cap))))
+            };
+            if (200..300).contains(&status) {
+                return Ok(body);
+            }
+            let mut text = String::new();
+            let _ = body.take(4096).read_to_string(&mut text);
+            let message = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|value| {
+                    value
+                        .pointer("/error/message")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                })
+                .unwrap_or_else(|| format!("HTTP {}", status));
+            Err(match status {
+                408 | 429 | 500..=599 => AiError::Unavailable(message),
+                _ => AiError::Rejected(message),
+            })
+        })
+    }
+
+    fn completion(&self, text: String) -> Completion {
+        Completion {
+            text,
+            provider: self.name().to_string(),
+            model: self.model.clone(),
+        }
+    }
+}
+
+impl AiProvider for HttpProvider {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            streaming: true,
+            local: false,
+            deterministic: false,
+        }
+    }
+
+    fn complete(&self, request: &CompletionRequest) -> AiResult<Completion> {
+        let deadline = Instant::now() + self.timeout;
+        let mut text = String::new();
+        self.open(&self.body(request, false), deadline)?
+            .read_to_string(&mut text)
+            .map_err(io_error)?;
+        if text.len() as u64 > MAX_RESPONSE_BYTES {
+            return Err(too_large());
+        }
+        let value: Value =
+            serde_json::from_str(&text).map_err(|err| AiError::InvalidResponse(err.to_string()))?;
+        let content = value
+            .pointer("/choices/0/message/content")
+            .and_then(Value::as_str)
+            .ok_or_else(|| AiError::InvalidResponse("missing choices[0].message.content".into()))?;
+        Ok(self.completion(content.to_string()))
+    }
+
+    /// Reads `data:` server-sent events until `[DONE]`, forwarding each `delta.content`.
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        on_chunk: &mut dyn FnMut(&str),
+    ) -> AiResult<Completion> {
+        let deadline = Instant::now() + self.timeout;
+        let mut reader = self.open(&self.body(request, true), deadline)?;
+        let mut text = String::new();
+        let mut line = String::new();
+        let mut received = 0;
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line).map_err(io_error)?;
+            if read == 0 {
+                break;
+            }
+            received += read as u64;
+            if received > MAX_RESPONSE_BYTES {
+                return Err(too_large());
+            }
+            let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+            if data == "[DONE]" {
+                break;
+            }
+            let event: Value = serde_json::from_str(data)
+                .map_err(|err| AiError::InvalidResponse(err.to_string()))?;
+            if let Some(chunk) = event
+                .pointer("/choices/0/delta/content")
+                .and_then(Value::as_str)
+                .filter(|chunk| !chunk.is_empty())
+            {
+                on_chunk(chunk);
+                text.push_str(chunk);
+            }
+        }
+        Ok(self.completion(text))
+    }
+}
+
+/// Status code, whether the body is chunked, and its `Content-Length`.
+fn read_head(reader: &mut impl BufRead) -> AiResult<(u16, bool, Option<u64>)> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(io_error)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| AiError::InvalidResponse(format!("bad status line '{}'", line.trim())))?;
+    let (mut chunked, mut length) = (false, None);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(io_error)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            } else if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse().ok();
+            }
+        }
+    }
+    Ok((status, chunked, length))
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body.
+struct Chunked<R> {
+    inner: R,
+    left: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.left == 0 {
+            let mut size = String::new();
+            self.inner.read_line(&mut size)?;
+            let size = size.split(';').next().unwrap_or_default().trim();
+            self.left = usize::from_str_radix(size, 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+            if self.left == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let limit = buf.len().min(self.left);
+        let read = self.inner.read(&mut buf[..limit])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.left -= read;
+        if self.left == 0 {
+            let mut crlf = String::new();
+            self.inner.read_line(&mut crlf)?;
+        }
+        Ok(read)
+    }
+}
+
+/// A connection whose reads share one deadline, so each read waits only for the time left.
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream
+            .set_read_timeout(Some(remaining(self.deadline)?))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Time left before `deadline`; a `TimedOut` error once none is.
+fn remaining(deadline: Instant) -> io::Result<Duration> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|left| !left.is_zero())
+        .ok_or_else(|| io::ErrorKind::TimedOut.into())
+}
+
+fn too_large() -> AiError {
+    AiError::InvalidResponse(format!(
+        "response body exceeds {} bytes",
+        MAX_RESPONSE_BYTES
+    ))
+}
+
+fn io_error(err: io::Error) -> AiError {
+    match err.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => AiError::Timeout,
+        _ => AiError::Unavailable(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Serve one canned response per connection, reporting each raw request.
+    fn stand_in(responses: Vec<String>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.strip_prefix("Content-Length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                tx.send(request).unwrap();
+                let mut stream = stream;
+                if !response.is_empty() {
+                    // The client hangs up early on an oversized body.
+                    let _ = stream.write_all(response.as_bytes());
+                } else {
+                    thread::sleep(Duration::from_millis(300));
+                }
+            }
+        });
+        (endpoint, rx)
+    }
+
+    fn json_response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 2,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn completes_with_model_and_bearer_key() {
+        let (endpoint, requests) = stand_in(vec![json_response(
+            "200 OK",
+            r#"{"choices":[{"message":{"role":"assistant","content":"A damp cellar."}}]}"#,
+        )]);
+        let provider = HttpProvider::new(&endpoint, "tiny")
+            .unwrap()
+            .with_api_key(Secret::new("sk-test"));
+        let completion = provider
+            .complete(
+                &CompletionRequest::new("room_description", "cellar").with_system("Be brief."),
+            )
+            .unwrap();
+        assert_eq!(completion.text, "A damp cellar.");
+        assert_eq!(completion.model, "tiny");
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions HTTP/1.1"));
+        assert!(request.contains("Authorization: Bearer sk-test"));
+        assert!(request.contains(r#""model":"tiny""#));
+        assert!(request.contains(r#""role":"system""#));
+    }
+
+    #[test]
+    fn streams_chunked_server_sent_events() {
+        let events = [
+            r#"data: {"choices":[{"delta":{"content":"Fog "}}]}"#,
+            r#"data: {"choices":[{"delta":{"content":"rolls in."}}]}"#,
+            "data: [DONE]",
+        ];
+        let mut body = String::from(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n",
+        );
+        for event in events {
+            let event = format!("{}\n\n", event);
+            body.push_str(&format!("{:x}\r\n{}\r\n", event.len(), event));
+        }
+        body.push_str("0\r\n\r\n");
+        let (endpoint, requests) = stand_in(vec![body]);
+        let provider = HttpProvider::new(&endpoint, "tiny").unwrap();
+        let mut chunks = Vec::new();
+        let completion = provider
+            .stream(&CompletionRequest::new("narration", "fog"), &mut |chunk| {
+                chunks.push(chunk.to_string())
+            })
+            .unwrap();
+        assert_eq!(chunks, vec!["Fog ", "rolls in."]);
+        assert_eq!(completion.text, "Fog rolls in.");
+        assert!(requests.recv().unwrap().contains(r#""stream":true"#));
+    }
+
+    #[test]
+    fn retries_unavailable_but_not_rejected() {
+        let ok = json_response("200 OK", r#"{"choices":[{"message":{"content":"ok"}}]}"#);
+        let (endpoint, _requests) = stand_in(vec![
+            json_response("503 Service Unavailable", "{}"),
+            ok,
+            json_response(
+                "400 Bad Request",
+                r#"{"error":{"message":"prompt too long"}}"#,
+            ),
+        ]);
+        let provider = HttpProvider::new(&endpoint, "tiny")
+            .unwrap()
+            .with_retry(fast_retry());
+        let request = CompletionRequest::new("narration", "x");
+        assert_eq!(provider.complete(&request).unwrap().text, "ok");
+        assert_eq!(
+            provider.complete(&request),
+            Err(AiError::Rejected("prompt too long".into()))
+        );
+    }
+
+    #[test]
+    fn slow_providers_time_out() {
+        let (endpoint, _requests) = stand_in(vec![String::new()]);
+        let provider = HttpProvider::new(&endpoint, "tiny")
+            .unwrap()
+            .with_timeout(Duration::from_millis(50))
+            .with_retry(RetryPolicy::none());
+        assert_eq!(
+            provider.complete(&CompletionRequest::new("narration", "x")),
+            Err(AiError::Timeout)
+        );
+    }
+
+    #[test]
+    fn oversized_bodies_are_rejected() {
+        let chunk = format!("{}\n", "x".repeat(64 * 1024 - 1));
+        let mut chunked = String::from("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n");
+        for _ in 0..(MAX_RESPONSE_BYTES / chunk.len() as u64 + 2) {
+            chunked.push_str(&format!("{:x}\r\n{}\r\n", chunk.len(), chunk));
+        }
+        chunked.push_str("0\r\n\r\n");
+        let long = "x".repeat(MAX_RESPONSE_BYTES as usize + 1);
+        let (endpoint, _requests) = stand_in(vec![
+            chunked.clone(),
+            chunked,
+            json_response("200 OK", &long),
+        ]);
+        let provider = HttpProvider::new(&endpoint, "tiny")
+            .unwrap()
+            .with_retry(RetryPolicy::none());
+        let request = CompletionRequest::new("narration", "x");
+        let expected = Err(too_large());
+        assert_eq!(provider.complete(&request), expected);
+        assert_eq!(provider.stream(&request, &mut |_| {}), expected);
+        assert_eq!(provider.complete(&request), expected);
+    }
+
+    #[test]
+    fn the_timeout_bounds_the_whole_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n");
+            // Each byte arrives well inside the timeout, but the body never ends.
+            for _ in 0..100 {
+                thread::sleep(Duration::from_millis(20));
+                if stream.write_all(b"1\r\n:\r\n").is_err() {
+                    break;
+                }
+            }
+        });
+        let provider = HttpProvider::new(&endpoint, "tiny")
+            .unwrap()
+            .with_timeout(Duration::from_millis(200))
+            .with_retry(RetryPolicy::none());
+        let started = Instant::now();
+        assert_eq!(
+            provider.stream(&CompletionRequest::new("narration", "x"), &mut |_| {}),
+            Err(AiError::Timeout)
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn endpoints_must_be_plain_http() {
+        assert!(HttpProvider::new("https://api.example.com/v1", "m").is_err());
+        assert!(HttpProvider::new("localhost:8080", "m").is_err());
+        let provider = HttpProvider::new("http://localhost:8080/v1/", "m").unwrap();
+        assert_eq!((provider.port, provider.base_path.as_str()), (8080, "/v1"));
+    }
+}
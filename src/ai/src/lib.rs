@@ -0,0 +1,291 @@
+//! AI crate: the pluggable AI Provider contract used by builder assists and runtime narration.
+//!
+//! Providers run server-side only. Secrets come from the Engine's configuration, are never
+//! serialized, and never reach the browser or the Kernel. Two providers ship here: a
+//! deterministic template provider for local play and tests, and an HTTP provider for
+//! OpenAI-compatible chat-completion endpoints. Guardrails filter what providers return, and
+//! budgets cap how much metered providers are used.
+
+pub mod budget;
+pub mod guardrail;
+pub mod http;
+pub mod local;
+
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+pub use budget::{
+    account_scope, estimate_tokens, BudgetConfig, BudgetLimit, BudgetReport, Budgets, Usage,
+    WORLD_SCOPE,
+};
+pub use guardrail::{GuardrailConfig, Guardrails, Violation, ViolationKind, WorldFacts};
+pub use http::HttpProvider;
+pub use local::TemplateProvider;
+
+/// What the caller wants generated. `task` names the kind of output (for example
+/// `room_description`) so providers can pick a template or system prompt.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    pub task: String,
+    #[serde(default)]
+    pub system: String,
+    pub prompt: String,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+}
+
+fn default_max_tokens() -> u32 {
+    256
+}
+
+impl CompletionRequest {
+    pub fn new(task: impl Into<String>, prompt: impl Into<String>) -> Self {
+        CompletionRequest {
+            task: task.into(),
+            system: String::new(),
+            prompt: prompt.into(),
+            max_tokens: default_max_tokens(),
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = system.into();
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Completion {
+    pub text: String,
+    pub provider: String,
+    pub model: String,
+}
+
+/// Feature flags callers check before relying on a provider behaviour.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Chunks arrive incrementally; otherwise `stream` yields the whole text as one chunk.
+    pub streaming: bool,
+    /// Runs in-process without network access.
+    pub local: bool,
+    /// The same request always produces the same text.
+    pub deterministic: bool,
+}
+
+/// How many times a retryable failure is attempted, and how long to wait in between. The
+/// wait doubles after every failed attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(250),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that tries once.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    /// Run `attempt` until it succeeds, fails with a non-retryable error, or attempts run out.
+    pub fn run<T>(&self, mut attempt: impl FnMut() -> AiResult<T>) -> AiResult<T> {
+        let mut backoff = self.backoff;
+        let mut tries = 1;
+        loop {
+            match attempt() {
+                Err(err) if err.is_retryable() && tries < self.max_attempts => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    tries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A credential held in memory only. `Debug` and `Display` redact it, and it has no serde
+/// implementation, so it cannot leak into logs, records, or responses by accident.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+/// A text generator. Implementations must be safe to share across the control plane and the
+/// narration workers.
+pub trait AiProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    fn complete(&self, request: &CompletionRequest) -> AiResult<Completion>;
+
+    /// Generate incrementally, passing each chunk to `on_chunk`, and return the full
+    /// completion. Providers without streaming deliver the whole text as one chunk.
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        on_chunk: &mut dyn FnMut(&str),
+    ) -> AiResult<Completion> {
+        let completion = self.complete(request)?;
+        on_chunk(&completion.text);
+        Ok(completion)
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum AiError {
+    #[error("provider timed out")]
+    Timeout,
+    #[error("provider unavailable: {0}")]
+    Unavailable(String),
+    #[error("provider rejected the request: {0}")]
+    Rejected(String),
+    #[error("provider returned an invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("invalid AI provider configuration: {0}")]
+    Config(String),
+    #[error("AI budget for {scope} is exhausted until {resets_at}")]
+    BudgetExhausted { scope: String, resets_at: u64 },
+}
+
+impl AiError {
+    /// Whether trying the same request again might succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AiError::Timeout | AiError::Unavailable(_))
+    }
+}
+
+pub type AiResult<T> = Result<T, AiError>;
+
+/// Which provider to build and how to reach it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProviderConfig {
+    /// `local` (the deterministic template provider) or `http`.
+    pub kind: String,
+    /// Base URL for `http`, e.g. `http://127.0.0.1:8080/v1`.
+    pub endpoint: Option<String>,
+    pub model: String,
+    pub api_key: Option<Secret>,
+    pub timeout: Duration,
+    pub max_attempts: u32,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        ProviderConfig {
+            kind: "local".into(),
+            endpoint: None,
+            model: "default".into(),
+            api_key: None,
+            timeout: Duration::from_secs(30),
+            max_attempts: RetryPolicy::default().max_attempts,
+        }
+    }
+}
+
+impl ProviderConfig {
+    pub fn build(&self) -> AiResult<Arc<dyn AiProvider>> {
+        match self.kind.as_str() {
+            "local" => Ok(Arc::new(TemplateProvider::default())),
+            "http" => {
+                let endpoint = self
+                    .endpoint
+                    .as_deref()
+                    .ok_or_else(|| AiError::Config("the http provider needs an endpoint".into()))?;
+                let mut provider = HttpProvider::new(endpoint, self.model.clone())?
+                    .with_timeout(self.timeout)
+                    .with_retry(RetryPolicy {
+                        max_attempts: self.max_attempts.max(1),
+                        ..RetryPolicy::default()
+                    });
+                if let Some(key) = &self.api_key {
+                    provider = provider.with_api_key(key.clone());
+                }
+                Ok(Arc::new(provider))
+            }
+            other => Err(AiError::Config(format!("unknown AI provider '{}'", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn retries_only_retryable_errors() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::ZERO,
+        };
+        let calls = Cell::new(0);
+        let result: AiResult<()> = policy.run(|| {
+            calls.set(calls.get() + 1);
+            Err(AiError::Timeout)
+        });
+        assert_eq!((result, calls.get()), (Err(AiError::Timeout), 3));
+
+        calls.set(0);
+        let result: AiResult<()> = policy.run(|| {
+            calls.set(calls.get() + 1);
+            Err(AiError::Rejected("bad prompt".into()))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+
+        calls.set(0);
+        let result = policy.run(|| {
+            calls.set(calls.get() + 1);
+            if calls.get() < 2 {
+                Err(AiError::Unavailable("503".into()))
+            } else {
+                Ok("done")
+            }
+        });
+        assert_eq!(result, Ok("done"));
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let secret = Secret::new("sk-live-123");
+        assert_eq!(format!("{:?} {}", secret, secret), "Secret(***) ***");
+        assert_eq!(secret.expose(), "sk-live-123");
+    }
+}
@@ -0,0 +1,174 @@
+//! Deterministic, template-based provider. It needs no network or model weights, so local
+//! worlds and tests get stable output; the same task and prompt always yield the same text.
+
+use std::collections::BTreeMap;
+
+use crate::guardrail::unfence;
+use crate::{AiProvider, AiResult, Capabilities, Completion, CompletionRequest};
+
+/// Fills `{prompt}` in one of the templates registered for the request's task. The variant is
+/// picked by hashing the task and prompt, so output varies across prompts but never between
+/// calls. Tasks without templates use the `default` entry. Fenced prompts are unwrapped first.
+#[derive(Clone, Debug)]
+pub struct TemplateProvider {
+    templates: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for TemplateProvider {
+    fn default() -> Self {
+        let mut provider = TemplateProvider {
+            templates: BTreeMap::new(),
+        };
+        for (task, variants) in [
+            (
+                "room_description",
+                &[
+                    "A quiet space shaped by {prompt}. Dust drifts through the still air, and every corner hints at something older than it looks.",
+                    "Here, {prompt} sets the mood. The walls carry the marks of long use, and faint sounds echo from somewhere beyond.",
+                ][..],
+            ),
+            (
+                "npc_dialogue",
+                &[
+                    "\"You want to know about {prompt}? Few ask, fewer listen.\"",
+                    "\"Ah, {prompt}. I have stories, if you have the time.\"",
+                ][..],
+            ),
+            (
+                "item_set",
+                &["Worn satchel: A leather satchel, its strap frayed by {prompt}.\nBrass key: A small key, tarnished by {prompt}.\nFolded map: A map of nearby passages, its edges marked by {prompt}."][..],
+            ),
+            (
+                "narration",
+                &[
+                    "The world shifts: {prompt}.",
+                    "Somewhere nearby, {prompt}.",
+                ][..],
+            ),
+            ("default", &["{prompt}"][..]),
+        ] {
+            provider = provider.with_templates(task, variants.iter().copied());
+        }
+        provider
+    }
+}
+
+impl TemplateProvider {
+    /// Replace the templates for `task`.
+    pub fn with_templates<'a>(
+        mut self,
+        task: &str,
+        variants: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        self.templates.insert(
+            task.to_string(),
+            variants.into_iter().map(str::to_string).collect(),
+        );
+        self
+    }
+
+    fn render(&self, request: &CompletionRequest) -> String {
+        let variants = self
+            .templates
+            .get(&request.task)
+            .or_else(|| self.templates.get("default"))
+            .filter(|variants| !variants.is_empty());
+        let Some(variants) = variants else {
+            return request.prompt.trim().to_string();
+        };
+        let prompt = unfence(&request.prompt).trim();
+        let seed = fnv1a(request.task.bytes().chain([0]).chain(prompt.bytes()));
+        let template = &variants[(seed % variants.len() as u64) as usize];
+        let text = template.replace("{prompt}", prompt);
+        // Words stand in for tokens so `max_tokens` still bounds the output.
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.len() > request.max_tokens as usize {
+            words[..request.max_tokens as usize].join(" ")
+        } else {
+            text
+        }
+    }
+}
+
+impl AiProvider for TemplateProvider {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            streaming: true,
+            local: true,
+            deterministic: true,
+        }
+    }
+
+    fn complete(&self, request: &CompletionRequest) -> AiResult<Completion> {
+        Ok(Completion {
+            text: self.render(request),
+            provider: self.name().to_string(),
+            model: "template-v1".into(),
+        })
+    }
+
+    /// Streams the rendered text one word at a time.
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        on_chunk: &mut dyn FnMut(&str),
+    ) -> AiResult<Completion> {
+        let completion = self.complete(request)?;
+        for (idx, word) in completion.text.split(' ').enumerate() {
+            if idx == 0 {
+                on_chunk(word);
+            } else {
+                on_chunk(&format!(" {}", word));
+            }
+        }
+        Ok(completion)
+    }
+}
+
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_deterministic_and_bounded() {
+        let provider = TemplateProvider::default();
+        let request = CompletionRequest::new("room_description", "a flooded crypt");
+        let first = provider.complete(&request).unwrap();
+        assert_eq!(first, provider.complete(&request).unwrap());
+        assert!(first.text.contains("a flooded crypt"));
+
+        let short = CompletionRequest {
+            max_tokens: 3,
+            ..request.clone()
+        };
+        assert_eq!(
+            provider.complete(&short).unwrap().text.split(' ').count(),
+            3
+        );
+        let unknown = CompletionRequest::new("haiku", "moss");
+        assert_eq!(provider.complete(&unknown).unwrap().text, "moss");
+    }
+
+    #[test]
+    fn streaming_chunks_reassemble_the_completion() {
+        let provider = TemplateProvider::default().with_templates("echo", ["one two {prompt}"]);
+        let mut chunks = Vec::new();
+        let completion = provider
+            .stream(&CompletionRequest::new("echo", "three"), &mut |chunk| {
+                chunks.push(chunk.to_string())
+            })
+            .unwrap();
+        assert_eq!(chunks, vec!["one", " two", " three"]);
+        assert_eq!(chunks.concat(), completion.text);
+    }
+}
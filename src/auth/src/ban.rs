@@ -0,0 +1,174 @@
+//! Account and IP-range bans. Bans are persisted as `auth.ban` records keyed by their target so
+//! banning the same target twice replaces the earlier entry.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use aqevia_storage::WorldRecord;
+use serde::{Deserialize, Serialize};
+
+use crate::AuthError;
+
+/// Storage `kind` used for persisted bans.
+pub const BAN_KIND: &str = "auth.ban";
+
+/// An IPv4 or IPv6 network in CIDR notation. A bare address is a single-host range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                mask(u32::from(ip) as u128, self.prefix, 32) == u32::from(network) as u128
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                mask(u128::from(ip), self.prefix, 128) == u128::from(network)
+            }
+            (IpAddr::V4(_), IpAddr::V6(ip)) => ip
+                .to_ipv4_mapped()
+                .is_some_and(|ip| self.contains(ip.into())),
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+/// Keep the top `prefix` bits of a `width`-bit address.
+fn mask(bits: u128, prefix: u8, width: u8) -> u128 {
+    if prefix == 0 {
+        return 0;
+    }
+    let shift = width - prefix;
+    (bits >> shift) << shift
+}
+
+impl FromStr for IpRange {
+    type Err = AuthError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || AuthError::InvalidBan(format!("'{}' is not an IP address or CIDR range", value));
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+        let address: IpAddr = address.trim().parse().map_err(|_| invalid())?;
+        let width = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= width)
+                .ok_or_else(invalid)?,
+            None => width,
+        };
+        let network = match address {
+            IpAddr::V4(ip) => IpAddr::V4((mask(u32::from(ip) as u128, prefix, 32) as u32).into()),
+            IpAddr::V6(ip) => IpAddr::V6(mask(u128::from(ip), prefix, 128).into()),
+        };
+        Ok(IpRange { network, prefix })
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = AuthError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<IpRange> for String {
+    fn from(range: IpRange) -> Self {
+        range.to_string()
+    }
+}
+
+/// What a ban applies to. Serialized as `{"account":"..."}` or `{"ip":"10.0.0.0/8"}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BanTarget {
+    Account(String),
+    Ip(IpRange),
+}
+
+impl BanTarget {
+    /// Stable identity used as the storage key, e.g. `account:alice` or `ip:10.0.0.0/8`.
+    pub fn key(&self) -> String {
+        match self {
+            BanTarget::Account(username) => format!("account:{}", username),
+            BanTarget::Ip(range) => format!("ip:{}", range),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    #[serde(flatten)]
+    pub target: BanTarget,
+    #[serde(default)]
+    pub reason: String,
+    pub banned_by: String,
+    pub created_at: u64,
+}
+
+impl Ban {
+    pub fn to_record(&self, world_id: &str) -> WorldRecord {
+        let payload = serde_json::to_string(self).expect("ban serializes");
+        WorldRecord::new(world_id, BAN_KIND, self.target.key(), payload)
+    }
+
+    pub fn from_record(record: &WorldRecord) -> Result<Self, AuthError> {
+        serde_json::from_str(&record.payload)
+            .map_err(|err| AuthError::CorruptRecord(record.key.clone(), err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_parse_normalize_and_match() {
+        let range: IpRange = "10.1.2.3/8".parse().unwrap();
+        assert_eq!(range.to_string(), "10.0.0.0/8");
+        assert!(range.contains("10.200.0.1".parse().unwrap()));
+        assert!(!range.contains("11.0.0.1".parse().unwrap()));
+        assert!(range.contains("::ffff:10.0.0.9".parse().unwrap()));
+
+        let host: IpRange = "192.168.1.5".parse().unwrap();
+        assert_eq!(host.to_string(), "192.168.1.5/32");
+        assert!(!host.contains("192.168.1.6".parse().unwrap()));
+
+        let v6: IpRange = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains("2001:db8:1::1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("nonsense".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn bans_serialize_with_flattened_targets() {
+        let ban = Ban {
+            target: BanTarget::Ip("10.0.0.0/8".parse().unwrap()),
+            reason: "spam".into(),
+            banned_by: "root".into(),
+            created_at: 1,
+        };
+        let record = ban.to_record("world");
+        assert_eq!(record.key, "ip:10.0.0.0/8");
+        assert!(record.payload.contains(r#""ip":"10.0.0.0/8""#));
+        assert_eq!(Ban::from_record(&record).unwrap(), ban);
+    }
+}
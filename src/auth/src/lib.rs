@@ -0,0 +1,695 @@
+//! Auth crate: accounts, password hashing, session tokens, roles, and bans for the control
+//! plane. Accounts and bans are persisted through the storage contract; sessions live in
+//! memory and are lost on restart.
+
+pub mod ban;
+pub mod password;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aqevia_storage::WorldRecord;
+use serde::{Deserialize, Serialize};
+
+pub use ban::{Ban, BanTarget, IpRange, BAN_KIND};
+
+/// Storage `kind` used for persisted accounts.
+pub const ACCOUNT_KIND: &str = "auth.account";
+
+/// Roles are ordered: an admin may do everything a builder may, and a builder everything a player may.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Player,
+    Builder,
+    Admin,
+}
+
+impl Role {
+    /// Whether a principal holding `self` satisfies a `required` role.
+    pub fn allows(self, required: Role) -> bool {
+        self >= required
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Builder => "builder",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = AuthError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "player" => Ok(Role::Player),
+            "builder" => Ok(Role::Builder),
+            "admin" => Ok(Role::Admin),
+            other => Err(AuthError::UnknownRole(other.to_string())),
+        }
+    }
+}
+
+/// A persisted user account.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub username: String,
+    pub role: Role,
+    pub password_hash: String,
+    pub created_at: u64,
+}
+
+impl Account {
+    pub fn to_record(&self, world_id: &str) -> WorldRecord {
+        let payload = serde_json::to_string(self).expect("account serializes");
+        WorldRecord::new(world_id, ACCOUNT_KIND, &self.username, payload)
+    }
+
+    pub fn from_record(record: &WorldRecord) -> Result<Self, AuthError> {
+        serde_json::from_str(&record.payload)
+            .map_err(|err| AuthError::CorruptAccount(record.key.clone(), err.to_string()))
+    }
+
+    /// The account without its password hash, as the control plane shows it.
+    pub fn info(&self) -> AccountInfo {
+        AccountInfo {
+            username: self.username.clone(),
+            role: self.role,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AccountInfo {
+    pub username: String,
+    pub role: Role,
+    pub created_at: u64,
+}
+
+/// The authenticated caller attached to a request or WebSocket session.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Principal {
+    pub username: String,
+    pub role: Role,
+    pub expires_at: u64,
+}
+
+impl Principal {
+    pub fn require(&self, role: Role) -> Result<(), AuthError> {
+        if self.role.allows(role) {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden {
+                required: role,
+                actual: self.role,
+            })
+        }
+    }
+}
+
+/// Token handed to a client after a successful login.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionGrant {
+    pub token: String,
+    pub principal: Principal,
+}
+
+/// Tunables for hashing cost and session lifetime.
+#[derive(Clone, Copy, Debug)]
+pub struct AuthConfig {
+    pub session_ttl: Duration,
+    pub hash_iterations: u32,
+    /// Whether anyone may register a player account without an admin.
+    pub open_registration: bool,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            session_ttl: Duration::from_secs(12 * 60 * 60),
+            hash_iterations: 100_000,
+            open_registration: true,
+        }
+    }
+}
+
+struct SessionEntry {
+    username: String,
+    expires_at: SystemTime,
+}
+
+/// Account registry plus in-memory session tokens. Shared across transport threads.
+pub struct AuthService {
+    config: RwLock<AuthConfig>,
+    accounts: RwLock<HashMap<String, Account>>,
+    sessions: Mutex<HashMap<String, SessionEntry>>,
+    bans: RwLock<BTreeMap<String, Ban>>,
+    /// Hash checked when a login names no account, so the reply takes as long as a wrong
+    /// password and does not reveal which usernames exist. Rebuilt when the cost changes.
+    decoy_hash: Mutex<Option<(u32, String)>>,
+}
+
+impl AuthService {
+    pub fn new(config: AuthConfig) -> Self {
+        AuthService {
+            config: RwLock::new(config),
+            accounts: RwLock::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+            bans: RwLock::new(BTreeMap::new()),
+            decoy_hash: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> AuthConfig {
+        *self.config.read().expect("lock poisoning")
+    }
+
+    /// Change the tunables. Sessions already issued keep their expiry and existing password
+    /// hashes keep their cost; new logins and new passwords use the new values.
+    pub fn set_config(&self, config: AuthConfig) {
+        *self.config.write().expect("lock poisoning") = config;
+    }
+
+    /// Replace the in-memory account table with persisted `auth.account` records.
+    pub fn load_accounts(&self, records: &[WorldRecord]) -> Result<usize, AuthError> {
+        let mut loaded = HashMap::with_capacity(records.len());
+        for record in records.iter().filter(|record| record.kind == ACCOUNT_KIND) {
+            let account = Account::from_record(record)?;
+            loaded.insert(account.username.clone(), account);
+        }
+        let count = loaded.len();
+        *self.accounts.write().expect("lock poisoning") = loaded;
+        Ok(count)
+    }
+
+    /// Register a new account. The caller is responsible for persisting the returned account.
+    pub fn create_account(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+    ) -> Result<Account, AuthError> {
+        validate_username(username)?;
+        if password.chars().count() < 8 {
+            return Err(AuthError::WeakPassword);
+        }
+        if self.account(username).is_some() {
+            return Err(AuthError::AccountExists(username.to_string()));
+        }
+        // Hash before taking the write lock so logins are not held up for its duration.
+        let account = Account {
+            username: username.to_string(),
+            role,
+            password_hash: password::hash_password(password, self.config().hash_iterations),
+            created_at: unix_seconds(SystemTime::now()),
+        };
+        let mut accounts = self.accounts.write().expect("lock poisoning");
+        if accounts.contains_key(username) {
+            return Err(AuthError::AccountExists(username.to_string()));
+        }
+        accounts.insert(account.username.clone(), account.clone());
+        Ok(account)
+    }
+
+    /// Register a player account on the caller's own behalf, if registration is open.
+    pub fn register(&self, username: &str, password: &str) -> Result<Account, AuthError> {
+        if !self.config().open_registration {
+            return Err(AuthError::RegistrationClosed);
+        }
+        self.create_account(username, password, Role::Player)
+    }
+
+    /// Give an account a new role, effective on its next request. The caller is responsible
+    /// for persisting the returned account.
+    pub fn set_role(&self, username: &str, role: Role) -> Result<Account, AuthError> {
+        let mut accounts = self.accounts.write().expect("lock poisoning");
+        require_other_admin(&accounts, username, Some(role))?;
+        let account = accounts
+            .get_mut(username)
+            .ok_or_else(|| AuthError::UnknownAccount(username.to_string()))?;
+        account.role = role;
+        Ok(account.clone())
+    }
+
+    /// Delete an account and revoke its tokens. The caller is responsible for persisting the
+    /// deletion and disconnecting live sessions.
+    pub fn delete_account(&self, username: &str) -> Result<Account, AuthError> {
+        let removed = {
+            let mut accounts = self.accounts.write().expect("lock poisoning");
+            require_other_admin(&accounts, username, None)?;
+            accounts
+                .remove(username)
+                .ok_or_else(|| AuthError::UnknownAccount(username.to_string()))?
+        };
+        self.revoke_account(username);
+        Ok(removed)
+    }
+
+    /// Every account, ordered by username.
+    pub fn accounts(&self) -> Vec<AccountInfo> {
+        let accounts = self.accounts.read().expect("lock poisoning");
+        let mut listed: Vec<AccountInfo> = accounts.values().map(Account::info).collect();
+        listed.sort_by(|a, b| a.username.cmp(&b.username));
+        listed
+    }
+
+    pub fn account(&self, username: &str) -> Option<Account> {
+        self.accounts
+            .read()
+            .expect("lock poisoning")
+            .get(username)
+            .cloned()
+    }
+
+    pub fn account_count(&self) -> usize {
+        self.accounts.read().expect("lock poisoning").len()
+    }
+
+    /// Number of accounts holding exactly `role`.
+    pub fn role_count(&self, role: Role) -> usize {
+        self.accounts
+            .read()
+            .expect("lock poisoning")
+            .values()
+            .filter(|account| account.role == role)
+            .count()
+    }
+
+    /// Verify credentials and mint a session token.
+    pub fn login(&self, username: &str, password: &str) -> Result<SessionGrant, AuthError> {
+        self.login_at(username, password, SystemTime::now())
+    }
+
+    fn login_at(
+        &self,
+        username: &str,
+        password: &str,
+        now: SystemTime,
+    ) -> Result<SessionGrant, AuthError> {
+        let account = self.account(username);
+        let hash = match &account {
+            Some(account) => account.password_hash.clone(),
+            None => self.decoy_hash(),
+        };
+        let verified = password::verify_password(password, &hash);
+        let account = account
+            .filter(|_| verified)
+            .ok_or(AuthError::InvalidCredentials)?;
+        self.check_account(&account.username)?;
+        let token = password::generate_token();
+        let expires_at = now + self.config().session_ttl;
+        let mut sessions = self.sessions.lock().expect("lock poisoning");
+        sessions.retain(|_, entry| entry.expires_at > now);
+        sessions.insert(
+            password::token_digest(&token),
+            SessionEntry {
+                username: account.username.clone(),
+                expires_at,
+            },
+        );
+        Ok(SessionGrant {
+            token,
+            principal: Principal {
+                username: account.username,
+                role: account.role,
+                expires_at: unix_seconds(expires_at),
+            },
+        })
+    }
+
+    fn decoy_hash(&self) -> String {
+        let iterations = self.config().hash_iterations;
+        let mut decoy = self.decoy_hash.lock().expect("lock poisoning");
+        match decoy.as_ref() {
+            Some((cost, hash)) if *cost == iterations => hash.clone(),
+            _ => {
+                let hash = password::hash_password(&password::generate_token(), iterations);
+                *decoy = Some((iterations, hash.clone()));
+                hash
+            }
+        }
+    }
+
+    /// Resolve a bearer token into a principal, rejecting unknown, revoked, or expired tokens.
+    pub fn authenticate(&self, token: &str) -> Result<Principal, AuthError> {
+        self.authenticate_at(token, SystemTime::now())
+    }
+
+    fn authenticate_at(&self, token: &str, now: SystemTime) -> Result<Principal, AuthError> {
+        let digest = password::token_digest(token);
+        let mut sessions = self.sessions.lock().expect("lock poisoning");
+        let entry = sessions.get(&digest).ok_or(AuthError::InvalidToken)?;
+        if entry.expires_at <= now {
+            sessions.remove(&digest);
+            return Err(AuthError::Expired);
+        }
+        let account = self
+            .account(&entry.username)
+            .ok_or(AuthError::InvalidToken)?;
+        self.check_account(&account.username)?;
+        Ok(Principal {
+            username: account.username,
+            role: account.role,
+            expires_at: unix_seconds(entry.expires_at),
+        })
+    }
+
+    /// Revoke a single token. Returns `false` when the token was not active.
+    pub fn revoke(&self, token: &str) -> bool {
+        self.sessions
+            .lock()
+            .expect("lock poisoning")
+            .remove(&password::token_digest(token))
+            .is_some()
+    }
+
+    /// Revoke every token held by `username`, returning how many were dropped.
+    pub fn revoke_account(&self, username: &str) -> usize {
+        let mut sessions = self.sessions.lock().expect("lock poisoning");
+        let before = sessions.len();
+        sessions.retain(|_, entry| entry.username != username);
+        before - sessions.len()
+    }
+
+    /// Replace the in-memory ban list with persisted `auth.ban` records.
+    pub fn load_bans(&self, records: &[WorldRecord]) -> Result<usize, AuthError> {
+        let mut loaded = BTreeMap::new();
+        for record in records.iter().filter(|record| record.kind == BAN_KIND) {
+            let ban = Ban::from_record(record)?;
+            loaded.insert(ban.target.key(), ban);
+        }
+        let count = loaded.len();
+        *self.bans.write().expect("lock poisoning") = loaded;
+        Ok(count)
+    }
+
+    /// Add or replace a ban. Account bans also revoke the account's tokens. The caller is
+    /// responsible for persisting the ban and disconnecting live sessions.
+    pub fn add_ban(&self, ban: Ban) -> Ban {
+        if let BanTarget::Account(username) = &ban.target {
+            self.revoke_account(username);
+        }
+        self.bans
+            .write()
+            .expect("lock poisoning")
+            .insert(ban.target.key(), ban.clone());
+        ban
+    }
+
+    /// Lift a ban by its key (`account:<name>` or `ip:<range>`).
+    pub fn remove_ban(&self, key: &str) -> Option<Ban> {
+        self.bans.write().expect("lock poisoning").remove(key)
+    }
+
+    /// Active bans ordered by key.
+    pub fn bans(&self) -> Vec<Ban> {
+        self.bans
+            .read()
+            .expect("lock poisoning")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Reject accounts that are banned.
+    pub fn check_account(&self, username: &str) -> Result<(), AuthError> {
+        let key = BanTarget::Account(username.to_string()).key();
+        match self.bans.read().expect("lock poisoning").get(&key) {
+            Some(ban) => Err(AuthError::Banned(ban.target.key())),
+            None => Ok(()),
+        }
+    }
+
+    /// Reject addresses inside a banned range.
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), AuthError> {
+        let bans = self.bans.read().expect("lock poisoning");
+        let matched = bans.values().find(|ban| match &ban.target {
+            BanTarget::Ip(range) => range.contains(ip),
+            BanTarget::Account(_) => false,
+        });
+        match matched {
+            Some(ban) => Err(AuthError::Banned(ban.target.key())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for AuthService {
+    fn default() -> Self {
+        AuthService::new(AuthConfig::default())
+    }
+}
+
+/// Refuse a change that would leave `username`, the last admin, without the admin role.
+fn require_other_admin(
+    accounts: &HashMap<String, Account>,
+    username: &str,
+    next: Option<Role>,
+) -> Result<(), AuthError> {
+    let demoted = accounts
+        .get(username)
+        .is_some_and(|account| account.role == Role::Admin && next != Some(Role::Admin));
+    let admins = accounts
+        .values()
+        .filter(|account| account.role == Role::Admin)
+        .count();
+    if demoted && admins == 1 {
+        Err(AuthError::LastAdmin(username.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_username(username: &str) -> Result<(), AuthError> {
+    let valid_len = (3..=32).contains(&username.len());
+    let valid_chars = username
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-');
+    if valid_len && valid_chars {
+        Ok(())
+    } else {
+        Err(AuthError::InvalidUsername(username.to_string()))
+    }
+}
+
+fn unix_seconds(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Errors returned by account and session operations.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum AuthError {
+    #[error("invalid username or password")]
+    InvalidCredentials,
+    #[error("missing or unknown session token")]
+    InvalidToken,
+    #[error("session token expired")]
+    Expired,
+    #[error("role '{actual}' may not access a '{required}' resource")]
+    Forbidden { required: Role, actual: Role },
+    #[error("account '{0}' already exists")]
+    AccountExists(String),
+    #[error("account '{0}' not found")]
+    UnknownAccount(String),
+    #[error("account '{0}' is the last admin")]
+    LastAdmin(String),
+    #[error("registration is closed; ask an admin for an account")]
+    RegistrationClosed,
+    #[error("username '{0}' must be 3-32 characters of [A-Za-z0-9_-]")]
+    InvalidUsername(String),
+    #[error("password must be at least 8 characters")]
+    WeakPassword,
+    #[error("unknown role '{0}'")]
+    UnknownRole(String),
+    #[error("stored account '{0}' is unreadable: {1}")]
+    CorruptAccount(String, String),
+    #[error("stored record '{0}' is unreadable: {1}")]
+    CorruptRecord(String, String),
+    #[error("access denied by ban '{0}'")]
+    Banned(String),
+    #[error("{0}")]
+    InvalidBan(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> AuthService {
+        AuthService::new(AuthConfig {
+            session_ttl: Duration::from_secs(60),
+            hash_iterations: 16,
+            open_registration: true,
+        })
+    }
+
+    #[test]
+    fn roles_are_hierarchical() {
+        assert!(Role::Admin.allows(Role::Builder));
+        assert!(Role::Builder.allows(Role::Player));
+        assert!(!Role::Player.allows(Role::Builder));
+        assert_eq!("builder".parse::<Role>().unwrap(), Role::Builder);
+        assert!("root".parse::<Role>().is_err());
+    }
+
+    #[test]
+    fn login_issues_token_that_authenticates() {
+        let auth = service();
+        auth.create_account("alice", "hunter2hunter2", Role::Builder)
+            .unwrap();
+        assert_eq!(
+            auth.login("alice", "wrong-password").unwrap_err(),
+            AuthError::InvalidCredentials
+        );
+        assert_eq!(
+            auth.login("mallory", "hunter2hunter2").unwrap_err(),
+            AuthError::InvalidCredentials
+        );
+        assert!(auth.decoy_hash().starts_with("pbkdf2-sha256$16$"));
+        let grant = auth.login("alice", "hunter2hunter2").unwrap();
+        let principal = auth.authenticate(&grant.token).unwrap();
+        assert_eq!(principal.username, "alice");
+        assert_eq!(principal.role, Role::Builder);
+        assert!(principal.require(Role::Admin).is_err());
+    }
+
+    #[test]
+    fn tokens_expire_and_can_be_revoked() {
+        let auth = service();
+        auth.create_account("bob", "longenough", Role::Player)
+            .unwrap();
+        let now = SystemTime::now();
+        let grant = auth.login_at("bob", "longenough", now).unwrap();
+        assert_eq!(
+            auth.authenticate_at(&grant.token, now + Duration::from_secs(61)),
+            Err(AuthError::Expired)
+        );
+        let grant = auth.login("bob", "longenough").unwrap();
+        assert!(auth.revoke(&grant.token));
+        assert_eq!(
+            auth.authenticate(&grant.token),
+            Err(AuthError::InvalidToken)
+        );
+        auth.login("bob", "longenough").unwrap();
+        auth.login("bob", "longenough").unwrap();
+        assert_eq!(auth.revoke_account("bob"), 2);
+    }
+
+    #[test]
+    fn accounts_round_trip_through_records() {
+        let auth = service();
+        let account = auth
+            .create_account("carol", "password123", Role::Admin)
+            .unwrap();
+        assert_eq!(
+            auth.create_account("carol", "password123", Role::Admin)
+                .unwrap_err(),
+            AuthError::AccountExists("carol".into())
+        );
+        let record = account.to_record("world");
+        assert_eq!(record.kind, ACCOUNT_KIND);
+        let restored = service();
+        assert_eq!(restored.load_accounts(&[record]).unwrap(), 1);
+        assert!(restored.login("carol", "password123").is_ok());
+    }
+
+    #[test]
+    fn account_creation_validates_input() {
+        let auth = service();
+        assert!(matches!(
+            auth.create_account("a b", "password123", Role::Player),
+            Err(AuthError::InvalidUsername(_))
+        ));
+        assert_eq!(
+            auth.create_account("dave", "short", Role::Player)
+                .unwrap_err(),
+            AuthError::WeakPassword
+        );
+    }
+
+    #[test]
+    fn roles_change_and_accounts_are_deleted_but_the_last_admin_stays() {
+        let auth = service();
+        auth.create_account("root", "password123", Role::Admin)
+            .unwrap();
+        let player = auth.register("frank", "password123").unwrap();
+        assert_eq!(player.role, Role::Player);
+        let grant = auth.login("frank", "password123").unwrap();
+        auth.set_role("frank", Role::Builder).unwrap();
+        assert_eq!(auth.authenticate(&grant.token).unwrap().role, Role::Builder);
+        assert_eq!(
+            auth.set_role("root", Role::Player).unwrap_err(),
+            AuthError::LastAdmin("root".into())
+        );
+        assert_eq!(
+            auth.delete_account("root").unwrap_err(),
+            AuthError::LastAdmin("root".into())
+        );
+        assert_eq!(
+            auth.set_role("nobody", Role::Player).unwrap_err(),
+            AuthError::UnknownAccount("nobody".into())
+        );
+        auth.delete_account("frank").unwrap();
+        assert_eq!(
+            auth.authenticate(&grant.token),
+            Err(AuthError::InvalidToken)
+        );
+        let names: Vec<String> = auth.accounts().into_iter().map(|a| a.username).collect();
+        assert_eq!(names, ["root"]);
+
+        auth.set_config(AuthConfig {
+            open_registration: false,
+            ..auth.config()
+        });
+        assert_eq!(
+            auth.register("grace", "password123").unwrap_err(),
+            AuthError::RegistrationClosed
+        );
+    }
+
+    #[test]
+    fn bans_block_login_tokens_and_addresses() {
+        let auth = service();
+        auth.create_account("eve", "password123", Role::Player)
+            .unwrap();
+        let grant = auth.login("eve", "password123").unwrap();
+        auth.add_ban(Ban {
+            target: BanTarget::Account("eve".into()),
+            reason: "griefing".into(),
+            banned_by: "root".into(),
+            created_at: 0,
+        });
+        assert!(auth.authenticate(&grant.token).is_err());
+        assert_eq!(
+            auth.login("eve", "password123").unwrap_err(),
+            AuthError::Banned("account:eve".into())
+        );
+        let ban = auth.add_ban(Ban {
+            target: BanTarget::Ip("203.0.113.0/24".parse().unwrap()),
+            reason: String::new(),
+            banned_by: "root".into(),
+            created_at: 0,
+        });
+        assert!(auth.check_ip("203.0.113.7".parse().unwrap()).is_err());
+        assert!(auth.check_ip("198.51.100.1".parse().unwrap()).is_ok());
+
+        let restored = service();
+        assert_eq!(restored.load_bans(&[ban.to_record("world")]).unwrap(), 1);
+        assert!(restored.remove_ban("ip:203.0.113.0/24").is_some());
+        assert!(restored.bans().is_empty());
+    }
+}
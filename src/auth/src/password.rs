@@ -0,0 +1,138 @@
+//! Password hashing helpers (PBKDF2-HMAC-SHA256 with a per-account random salt).
+
+use sha2::{Digest, Sha256};
+
+const SCHEME: &str = "pbkdf2-sha256";
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+/// Most PBKDF2 rounds a stored hash may ask for, ten times the default. A tampered or
+/// imported record with a larger count would otherwise tie up a worker on every login.
+pub const MAX_ITERATIONS: u32 = 1_000_000;
+
+/// Hash `password` into the self-describing `pbkdf2-sha256$<iterations>$<salt>$<hash>` format.
+/// `iterations` is capped at [`MAX_ITERATIONS`] so the result always verifies.
+pub fn hash_password(password: &str, iterations: u32) -> String {
+    let iterations = iterations.min(MAX_ITERATIONS);
+    let salt = random_bytes::<SALT_LEN>();
+    let hash = derive(password, &salt, iterations);
+    format!(
+        "{}${}${}${}",
+        SCHEME,
+        iterations,
+        to_hex(&salt),
+        to_hex(&hash)
+    )
+}
+
+/// Check `password` against a value produced by [`hash_password`].
+///
+/// Malformed hashes, and hashes asking for more than [`MAX_ITERATIONS`] rounds, never verify.
+pub fn verify_password(password: &str, encoded: &str) -> bool {
+    let mut parts = encoded.split('$');
+    let (Some(SCHEME), Some(iterations), Some(salt), Some(expected), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+    let (Ok(iterations), Some(salt), Some(expected)) = (
+        iterations.parse::<u32>(),
+        from_hex(salt),
+        from_hex(expected),
+    ) else {
+        return false;
+    };
+    if !(1..=MAX_ITERATIONS).contains(&iterations) || expected.len() != HASH_LEN {
+        return false;
+    }
+    constant_time_eq(&derive(password, &salt, iterations), &expected)
+}
+
+/// Generate an opaque, URL-safe session token.
+pub fn generate_token() -> String {
+    to_hex(&random_bytes::<32>())
+}
+
+/// Digest used to index session tokens so raw tokens are never kept in memory maps.
+pub fn token_digest(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn derive(password: &str, salt: &[u8], iterations: u32) -> [u8; HASH_LEN] {
+    let mut out = [0u8; HASH_LEN];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut out);
+    out
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut buf = [0u8; N];
+    getrandom::getrandom(&mut buf).expect("operating system random source unavailable");
+    buf
+}
+
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(value.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_round_trips_and_rejects_wrong_password() {
+        let encoded = hash_password("correct horse", 16);
+        assert!(encoded.starts_with("pbkdf2-sha256$16$"));
+        assert!(verify_password("correct horse", &encoded));
+        assert!(!verify_password("battery staple", &encoded));
+    }
+
+    #[test]
+    fn salts_differ_between_hashes() {
+        assert_ne!(hash_password("same", 16), hash_password("same", 16));
+    }
+
+    #[test]
+    fn malformed_hashes_never_verify() {
+        assert!(!verify_password("x", ""));
+        assert!(!verify_password("x", "plain$1$00$00"));
+        assert!(!verify_password("x", "pbkdf2-sha256$0$00$00"));
+        assert!(!verify_password("x", "pbkdf2-sha256$1$zz$00"));
+    }
+
+    #[test]
+    fn iteration_counts_above_the_cap_are_refused() {
+        let encoded = hash_password("correct horse", 16);
+        let tampered = encoded.replacen("$16$", &format!("${}$", u32::MAX), 1);
+        let started = std::time::Instant::now();
+        assert!(!verify_password("correct horse", &tampered));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        let over = encoded.replacen("$16$", &format!("${}$", MAX_ITERATIONS + 1), 1);
+        assert!(!verify_password("correct horse", &over));
+
+        let capped = hash_password("correct horse", u32::MAX);
+        assert!(capped.starts_with(&format!("pbkdf2-sha256${}$", MAX_ITERATIONS)));
+    }
+}
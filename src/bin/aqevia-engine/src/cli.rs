@@ -0,0 +1,391 @@
+//! Command-line parsing for `aqevia-engine`. Flags select the command and its operands, and
+//! the global options choose the config file and override individual settings.
+
+use std::path::PathBuf;
+
+use aqevia_engine::IntegrityMode;
+
+pub const USAGE: &str = "\
+Usage: aqevia-engine [COMMAND] [OPTIONS]
+
+Runs one Aqevia World, or performs an operational task against its store.
+
+Commands:
+  serve          Run the World (default when no command is given)
+  migrate        Run or inspect storage schema migrations
+  export         Write the World's content to a bundle directory
+  import         Load a bundle directory into the World
+  check          Validate configuration and storage
+  backup         Write a consistent copy of the store
+  restore        Replace the store with a backup
+  create-admin   Create the first admin account
+  config dump    Print the effective configuration with secrets redacted
+  version        Print the Engine version
+
+Options:
+  --config <PATH>      TOML config file (default: $AQEVIA_CONFIG, else none)
+  --set <KEY=VALUE>    Override one setting, e.g. --set tick.interval_ms=100; repeatable
+  --db <PATH>          SQLite store to use; same as --set storage.sqlite_path=<PATH>
+  -h, --help           Print help for the Engine or a command
+
+Settings are layered: defaults, then the config file, then environment variables, then
+--set and --db (see docs/engine/configuration.md).
+
+Exit codes: 0 on success, 1 when the command fails, 2 on invalid usage.
+";
+
+const SERVE_HELP: &str = "\
+Usage: aqevia-engine serve [--db <PATH>]
+
+Open the store and run the World: the tick loop, the control plane, the observability
+endpoints, and the Web UI. A missing store is created and an older one this build can
+upgrade in place is upgraded; any other schema version is refused until `migrate
+--allow-reset`. Tunables come from the layered configuration (see
+docs/engine/configuration.md).
+";
+
+const MIGRATE_HELP: &str = "\
+Usage: aqevia-engine migrate [--status] [--allow-reset] [--db <PATH>]
+
+Bring the store to the schema version this build writes.
+
+Options:
+  --status        Report the stored and expected schema versions without changing anything
+  --allow-reset   Permit migrating a store stamped with another version, which discards
+                  its records
+";
+
+const EXPORT_HELP: &str = "\
+Usage: aqevia-engine export <DIR> [--db <PATH>]
+
+Write every room, exit, item, and NPC template to DIR as canonical JSON, one file per
+collection (see docs/engine/world-bundles.md).
+";
+
+const IMPORT_HELP: &str = "\
+Usage: aqevia-engine import <DIR> [--dry-run] [--db <PATH>]
+
+Validate the bundle in DIR and write it to the store in one transaction. Every failing
+record is reported and nothing is written. Refuses to write while an Engine is serving the
+store; import into a running World with POST /api/admin/world/import instead.
+
+Options:
+  --dry-run   Validate and report without writing
+";
+
+const CHECK_HELP: &str = "\
+Usage: aqevia-engine check [--repair | --quarantine] [--json] [--db <PATH>]
+
+Validate the configuration, the store's schema version and SQLite integrity, and every
+stored content record: that it decodes, and that the rooms and containers it names exist.
+Without --repair or --quarantine nothing is changed; with either, the check refuses to run
+while an Engine is serving the store.
+
+Options:
+  --repair       Clear references to missing rooms and containers, and quarantine records
+                 that still fail
+  --quarantine   Move every failing record to the integrity.quarantine kind
+  --json         Print the findings as one JSON object on standard output
+";
+
+const BACKUP_HELP: &str = "\
+Usage: aqevia-engine backup [FILE] [--db <PATH>]
+
+Write a consistent copy of the store while the World keeps running. Without FILE the copy
+is a timestamped snapshot in backup.dir, and snapshots beyond backup.keep are deleted,
+oldest first. FILE must not exist yet.
+";
+
+const RESTORE_HELP: &str = "\
+Usage: aqevia-engine restore <FILE> [--db <PATH>]
+
+Replace the store with the backup in FILE. The backup must pass SQLite's integrity check and
+carry the schema version this build writes; otherwise the store is left alone. The replaced
+store is kept as <PATH>.before-restore. Stop the World first.
+";
+
+const CREATE_ADMIN_HELP: &str = "\
+Usage: aqevia-engine create-admin <USERNAME> [--db <PATH>]
+
+Create the first admin account. The password is read from $AQEVIA_ADMIN_PASSWORD, or
+else from the first line of standard input. Fails if an admin account already exists, or
+while an Engine is serving the store.
+";
+
+const CONFIG_HELP: &str = "\
+Usage: aqevia-engine config dump [--config <PATH>] [--set <KEY=VALUE>]...
+
+Print the effective configuration as TOML after layering the config file, the environment,
+and overrides. Secrets are printed as \"***\"; replace them before reusing the output as
+a config file.
+";
+
+const VERSION_HELP: &str = "\
+Usage: aqevia-engine version
+
+Print the Engine version.
+";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Serve,
+    Migrate { status: bool, allow_reset: bool },
+    Export { dir: PathBuf },
+    Import { dir: PathBuf, dry_run: bool },
+    Check { mode: IntegrityMode, json: bool },
+    Backup { file: Option<PathBuf> },
+    Restore { file: PathBuf },
+    CreateAdmin { username: String },
+    ConfigDump,
+    Version,
+}
+
+/// Options accepted by every command.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub config: Option<PathBuf>,
+    pub db: Option<PathBuf>,
+    /// `--set` overrides as `(key, value)`, in command-line order.
+    pub overrides: Vec<(String, String)>,
+}
+
+/// A parsed command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Invocation {
+    Run {
+        command: Command,
+        options: Options,
+    },
+    /// `--help` was given; print this text and exit successfully.
+    Help(&'static str),
+}
+
+/// Parse the arguments after the program name. Errors are messages for invalid usage.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Invocation, String> {
+    let mut args = args.into_iter().peekable();
+    let name = match args.peek().map(String::as_str) {
+        None => "serve".to_string(),
+        Some("-h" | "--help") => return Ok(Invocation::Help(USAGE)),
+        Some(arg) if arg.starts_with('-') => "serve".to_string(),
+        Some(_) => args.next().expect("peeked"),
+    };
+    let help = match name.as_str() {
+        "serve" => SERVE_HELP,
+        "migrate" => MIGRATE_HELP,
+        "export" => EXPORT_HELP,
+        "import" => IMPORT_HELP,
+        "check" => CHECK_HELP,
+        "backup" => BACKUP_HELP,
+        "restore" => RESTORE_HELP,
+        "create-admin" => CREATE_ADMIN_HELP,
+        "config" => CONFIG_HELP,
+        "version" => VERSION_HELP,
+        "help" => return Ok(Invocation::Help(USAGE)),
+        other => return Err(format!("unknown command '{}'", other)),
+    };
+    let mut options = Options::default();
+    let mut flags = Vec::new();
+    let mut operands = Vec::new();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        if !matches!(flag, "--db" | "--config" | "--set") {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Invocation::Help(help)),
+                flag if flag.starts_with('-') => flags.push(arg),
+                _ => operands.push(arg),
+            }
+            continue;
+        }
+        let value = match inline {
+            Some(value) => value,
+            None => args
+                .next()
+                .ok_or_else(|| format!("{} needs a value", flag))?,
+        };
+        match flag {
+            "--db" => options.db = Some(PathBuf::from(value)),
+            "--config" => options.config = Some(PathBuf::from(value)),
+            _ => {
+                let (key, value) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("--set expects KEY=VALUE, got '{}'", value))?;
+                options
+                    .overrides
+                    .push((key.trim().to_string(), value.to_string()));
+            }
+        }
+    }
+    let mut flag = |wanted: &str| {
+        let before = flags.len();
+        flags.retain(|flag| flag != wanted);
+        flags.len() != before
+    };
+    let command = match name.as_str() {
+        "serve" => Command::Serve,
+        "migrate" => Command::Migrate {
+            status: flag("--status"),
+            allow_reset: flag("--allow-reset"),
+        },
+        "export" => Command::Export {
+            dir: operand(&mut operands, &name, "DIR")?.into(),
+        },
+        "import" => Command::Import {
+            dry_run: flag("--dry-run"),
+            dir: operand(&mut operands, &name, "DIR")?.into(),
+        },
+        "check" => Command::Check {
+            mode: match (flag("--repair"), flag("--quarantine")) {
+                (true, true) => return Err("--repair and --quarantine cannot be combined".into()),
+                (true, false) => IntegrityMode::Repair,
+                (false, true) => IntegrityMode::Quarantine,
+                (false, false) => IntegrityMode::Report,
+            },
+            json: flag("--json"),
+        },
+        "backup" => Command::Backup {
+            file: (!operands.is_empty()).then(|| operands.remove(0).into()),
+        },
+        "restore" => Command::Restore {
+            file: operand(&mut operands, &name, "FILE")?.into(),
+        },
+        "create-admin" => Command::CreateAdmin {
+            username: operand(&mut operands, &name, "USERNAME")?,
+        },
+        "config" => match operand(&mut operands, &name, "dump")?.as_str() {
+            "dump" => Command::ConfigDump,
+            other => return Err(format!("unknown config action '{}'", other)),
+        },
+        _ => Command::Version,
+    };
+    if let Some(flag) = flags.first() {
+        return Err(format!("unknown option '{}' for {}", flag, name));
+    }
+    if let Some(extra) = operands.first() {
+        return Err(format!("unexpected argument '{}' for {}", extra, name));
+    }
+    Ok(Invocation::Run { command, options })
+}
+
+fn operand(operands: &mut Vec<String>, command: &str, name: &str) -> Result<String, String> {
+    if operands.is_empty() {
+        return Err(format!("{} needs a {} argument", command, name));
+    }
+    Ok(operands.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Invocation, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn commands_parse_with_operands_and_flags() {
+        assert_eq!(
+            run(&[]),
+            Ok(Invocation::Run {
+                command: Command::Serve,
+                options: Options::default()
+            })
+        );
+        assert_eq!(
+            run(&["import", "--dry-run", "world", "--db", "w.sqlite"]),
+            Ok(Invocation::Run {
+                command: Command::Import {
+                    dir: "world".into(),
+                    dry_run: true
+                },
+                options: Options {
+                    db: Some("w.sqlite".into()),
+                    ..Options::default()
+                }
+            })
+        );
+        assert_eq!(
+            run(&["migrate", "--status"]),
+            Ok(Invocation::Run {
+                command: Command::Migrate {
+                    status: true,
+                    allow_reset: false
+                },
+                options: Options::default()
+            })
+        );
+        assert_eq!(
+            run(&["backup", "--help"]),
+            Ok(Invocation::Help(BACKUP_HELP))
+        );
+        assert_eq!(run(&["--help"]), Ok(Invocation::Help(USAGE)));
+        assert_eq!(
+            run(&["check", "--json", "--repair"]),
+            Ok(Invocation::Run {
+                command: Command::Check {
+                    mode: IntegrityMode::Repair,
+                    json: true
+                },
+                options: Options::default()
+            })
+        );
+        assert_eq!(
+            run(&["backup"]),
+            Ok(Invocation::Run {
+                command: Command::Backup { file: None },
+                options: Options::default()
+            })
+        );
+        assert_eq!(
+            run(&["restore", "snap.sqlite"]),
+            Ok(Invocation::Run {
+                command: Command::Restore {
+                    file: "snap.sqlite".into()
+                },
+                options: Options::default()
+            })
+        );
+        assert_eq!(
+            run(&[
+                "config",
+                "dump",
+                "--config=engine.toml",
+                "--set",
+                "ai.guardrails.blocklist=a,b",
+                "--set=tick.interval_ms=5"
+            ]),
+            Ok(Invocation::Run {
+                command: Command::ConfigDump,
+                options: Options {
+                    config: Some("engine.toml".into()),
+                    db: None,
+                    overrides: vec![
+                        ("ai.guardrails.blocklist".into(), "a,b".into()),
+                        ("tick.interval_ms".into(), "5".into())
+                    ]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_usage_is_reported() {
+        assert!(run(&["export"]).unwrap_err().contains("DIR"));
+        assert!(run(&["restore"]).unwrap_err().contains("FILE"));
+        assert!(run(&["launch"]).unwrap_err().contains("unknown command"));
+        assert!(run(&["check", "--fast"]).unwrap_err().contains("--fast"));
+        assert!(run(&["check", "--repair", "--quarantine"])
+            .unwrap_err()
+            .contains("cannot be combined"));
+        assert!(run(&["version", "extra"]).unwrap_err().contains("extra"));
+        assert!(run(&["config", "show"]).unwrap_err().contains("show"));
+        assert!(run(&["serve", "--set", "tick"])
+            .unwrap_err()
+            .contains("KEY=VALUE"));
+        assert!(run(&["serve", "--config"])
+            .unwrap_err()
+            .contains("needs a value"));
+    }
+}
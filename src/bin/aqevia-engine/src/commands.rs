@@ -0,0 +1,543 @@
+//! What each `aqevia-engine` command does. Every command returns an error for the caller to
+//! print and turn into a non-zero exit code.
+
+use std::env;
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use aqevia_ai::Guardrails;
+use aqevia_auth::Role;
+use aqevia_engine::{
+    logging, ConfigError, Engine, EngineConfig, IntegrityAction, IntegrityMode, IntegrityReport,
+    WorldBundle, CONFIG_ENV,
+};
+use aqevia_storage::{Recovery, StorageBackend};
+use aqevia_storage_sqlite::{
+    snapshot, upgrades_in_place, JournalMode, Snapshots, SqliteOptions, SqliteStorage, StoreLock,
+    Synchronous, SCHEMA_VERSION,
+};
+use aqevia_transport::{LogBuffer, ObservabilityServer, ObservabilityState, StaticAssets};
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::cli::{Command, Options};
+
+pub type CommandResult = Result<(), Box<dyn Error>>;
+
+const WORLD_ID: &str = "aqevia-default-world";
+
+pub fn run(command: Command, options: Options) -> CommandResult {
+    let config = load_config(&options);
+    if let Command::Check { mode, json } = command {
+        return check(config, mode, json);
+    }
+    let config = config?;
+    let db = config.storage.sqlite_path.as_path();
+    match command {
+        Command::Serve => serve(&config, options),
+        Command::Migrate {
+            status,
+            allow_reset,
+        } => migrate(&config, status, allow_reset),
+        Command::Export { dir } => export(&config, &dir),
+        Command::Import { dir, dry_run } => import(&config, &dir, dry_run),
+        Command::Check { .. } => unreachable!("handled above"),
+        Command::Backup { file } => backup(&config, file.as_deref()),
+        Command::Restore { file } => restore(db, &file),
+        Command::CreateAdmin { username } => create_admin(&config, &username),
+        Command::ConfigDump => {
+            print!("{}", config.dump());
+            Ok(())
+        }
+        Command::Version => {
+            println!("aqevia-engine {}", project_version());
+            Ok(())
+        }
+    }
+}
+
+/// The config file from `--config` or `$AQEVIA_CONFIG`, then the environment, then `--set`
+/// and `--db`.
+fn load_config(options: &Options) -> Result<EngineConfig, ConfigError> {
+    let file = options
+        .config
+        .clone()
+        .or_else(|| env::var_os(CONFIG_ENV).map(PathBuf::from));
+    let mut overrides = options.overrides.clone();
+    if let Some(db) = &options.db {
+        overrides.push((
+            "storage.sqlite_path".to_string(),
+            db.to_string_lossy().into_owned(),
+        ));
+    }
+    let config = EngineConfig::load(file.as_deref(), |name| env::var(name).ok(), &overrides)?;
+    sqlite_options(&config)?;
+    Ok(config)
+}
+
+/// Pragmas for the store's connection from the `storage.*` settings.
+fn sqlite_options(config: &EngineConfig) -> Result<SqliteOptions, ConfigError> {
+    let storage = &config.storage;
+    let mut problems = Vec::new();
+    let journal_mode = storage
+        .journal_mode
+        .parse::<JournalMode>()
+        .map_err(|err| problems.push(format!("storage.journal_mode: {}", err)));
+    let synchronous = storage
+        .synchronous
+        .parse::<Synchronous>()
+        .map_err(|err| problems.push(format!("storage.synchronous: {}", err)));
+    match (journal_mode, synchronous) {
+        (Ok(journal_mode), Ok(synchronous)) => Ok(SqliteOptions {
+            journal_mode,
+            synchronous,
+            busy_timeout: Duration::from_millis(storage.busy_timeout_ms),
+            cache_size_kib: storage.cache_size_kib,
+            read_pool_size: storage.read_pool_size,
+        }),
+        _ => Err(ConfigError::Validation(problems)),
+    }
+}
+
+/// Open (or create) the configured store with the configured pragmas.
+fn open_store(config: &EngineConfig) -> Result<SqliteStorage, Box<dyn Error>> {
+    Ok(SqliteStorage::with_options(
+        &config.storage.sqlite_path,
+        sqlite_options(config)?,
+    )?)
+}
+
+/// The repository's `VERSION` file as it was when the binary was built.
+const VERSION_FILE: &str = include_str!("../../../../VERSION");
+
+/// First non-comment line of the built-in `VERSION`, or the crate version if it has none.
+pub fn project_version() -> &'static str {
+    VERSION_FILE
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or(env!("CARGO_PKG_VERSION"))
+}
+
+/// Open the Engine on an existing, migrated store without the network side.
+fn open_engine(config: &EngineConfig) -> Result<Engine<SqliteStorage>, Box<dyn Error>> {
+    let db = config.storage.sqlite_path.as_path();
+    require_store(db)?;
+    let version = open_store(config)?.schema_version()?;
+    if version != Some(SCHEMA_VERSION) {
+        return Err(outdated(version).into());
+    }
+    new_engine(config)
+}
+
+fn new_engine(config: &EngineConfig) -> Result<Engine<SqliteStorage>, Box<dyn Error>> {
+    let observability = Arc::new(ObservabilityState::new(
+        project_version(),
+        WORLD_ID,
+        "sqlite",
+    ));
+    Ok(Engine::with_auth(
+        open_store(config)?,
+        config.storage_config(),
+        config.auth_config(),
+        observability,
+    )?)
+}
+
+fn require_store(db: &Path) -> CommandResult {
+    if !db.exists() {
+        return Err(format!("no store at {}", db.display()).into());
+    }
+    Ok(())
+}
+
+/// Run the World, logging to standard error. SIGHUP reloads the configuration from the same
+/// file, environment, and flags it was started with.
+fn serve(config: &EngineConfig, options: Options) -> CommandResult {
+    let logs = Arc::new(LogBuffer::new(config.log.buffer_events));
+    logging::init(&config.log.level, config.log.format, logs.clone())?;
+    // Held until the process exits, so `restore` and `migrate` refuse to run under it.
+    let _lock = StoreLock::acquire(&config.storage.sqlite_path)?;
+    require_servable(config)?;
+    let mut engine = new_engine(config)?;
+    engine
+        .core()
+        .set_config_source(config.clone(), Box::new(move || load_config(&options)));
+    let hangup = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, hangup.clone())?;
+    let observability = engine.core().observability().clone();
+    engine.core().set_audit_retention(config.audit_retention());
+    engine.set_ai_provider(config.provider_config().build()?);
+    engine.set_guardrails(Arc::new(Guardrails::new(config.guardrail_config())?));
+    engine.set_ai_budgets(config.budget_config());
+    engine.configure_narration(config.narration_config());
+
+    let ui_dir = config.observability.ui_dir.clone();
+    let control_plane = engine
+        .control_plane()
+        .with_logs(logs)
+        .with_handler(Arc::new(StaticAssets::from_dir(ui_dir)));
+    let _server = ObservabilityServer::start_with_control_plane(
+        observability,
+        control_plane,
+        config.observability.addr,
+    )?;
+    let output = engine.run_one_world("ready")?;
+    info!(addr = %config.observability.addr, %output, "server running");
+    loop {
+        // The reload logs its own outcome.
+        if hangup.swap(false, Ordering::SeqCst) {
+            let _ = engine.reload_config();
+        }
+        match engine.tick() {
+            // Writing on to a store that cannot be trusted could only make it worse.
+            Err(err) if err.recovery() == Recovery::Abort => {
+                error!(error = %err, "storage cannot be trusted; stopping");
+                return Err(err.into());
+            }
+            Err(err) => error!(error = %err, "tick failed"),
+            Ok(_) => {}
+        }
+        let tick = engine.core().config().map(EngineConfig::tick_interval);
+        thread::sleep(tick.unwrap_or_else(|| config.tick_interval()));
+    }
+}
+
+fn migrate(config: &EngineConfig, status: bool, allow_reset: bool) -> CommandResult {
+    let db = config.storage.sqlite_path.as_path();
+    let current = if db.exists() {
+        open_store(config)?.schema_version()?
+    } else {
+        None
+    };
+    let plan = match current {
+        None => "not initialized; migrating creates the schema".to_string(),
+        Some(version) if version == SCHEMA_VERSION => "up to date".to_string(),
+        Some(version) if upgrades_in_place(version) => format!(
+            "stamped with version {}; migrating upgrades it in place and keeps its records",
+            version
+        ),
+        Some(version) => format!(
+            "stamped with version {}; migrating resets the schema and discards its records",
+            version
+        ),
+    };
+    if status {
+        println!("store: {}", db.display());
+        println!("schema version: {}", describe_version(current));
+        println!("expected version: {}", SCHEMA_VERSION);
+        println!("status: {}", plan);
+        return Ok(());
+    }
+    let _lock = StoreLock::acquire(db)?;
+    let mut store = open_store(config)?;
+    if current.is_some_and(|version| !servable(version)) {
+        if !allow_reset {
+            return Err(format!(
+                "{} is {}; rerun with --allow-reset after taking a backup",
+                db.display(),
+                plan
+            )
+            .into());
+        }
+        store.reset_schema()?;
+    } else {
+        store.init()?;
+    }
+    println!("{} is at schema version {}", db.display(), SCHEMA_VERSION);
+    Ok(())
+}
+
+/// Whether opening the Engine brings a store at `version` to [`SCHEMA_VERSION`] without
+/// discarding anything.
+fn servable(version: i64) -> bool {
+    version == SCHEMA_VERSION || upgrades_in_place(version)
+}
+
+/// Refuse to serve a store that only `migrate --allow-reset` can bring to this schema version.
+fn require_servable(config: &EngineConfig) -> CommandResult {
+    let db = config.storage.sqlite_path.as_path();
+    if !db.exists() {
+        return Ok(());
+    }
+    match open_store(config)?.schema_version()? {
+        Some(version) if !servable(version) => Err(format!(
+            "{} is at schema version {}, which this build cannot upgrade (expected {}); \
+             run `aqevia-engine migrate --allow-reset` after taking a backup",
+            db.display(),
+            version,
+            SCHEMA_VERSION
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+fn outdated(version: Option<i64>) -> String {
+    format!(
+        "schema version is {}, expected {}; run `aqevia-engine migrate`",
+        describe_version(version),
+        SCHEMA_VERSION
+    )
+}
+
+fn describe_version(version: Option<i64>) -> String {
+    version.map_or_else(|| "none".to_string(), |version| version.to_string())
+}
+
+fn export(config: &EngineConfig, dir: &Path) -> CommandResult {
+    let engine = open_engine(config)?;
+    let bundle = engine.core().export_bundle();
+    let written = bundle.write_dir(dir)?;
+    println!(
+        "exported {} records to {} ({} files)",
+        bundle.len(),
+        dir.display(),
+        written.len()
+    );
+    Ok(())
+}
+
+fn import(config: &EngineConfig, dir: &Path, dry_run: bool) -> CommandResult {
+    let bundle = WorldBundle::read_dir(dir)?;
+    let db = config.storage.sqlite_path.as_path();
+    require_store(db)?;
+    // A running Engine would never see the write and could later overwrite it.
+    let _lock = (!dry_run).then(|| StoreLock::acquire(db)).transpose()?;
+    let engine = open_engine(config)?;
+    let report = engine.core().import_bundle(None, &bundle, dry_run)?;
+    for error in &report.errors {
+        let id = error
+            .id
+            .as_deref()
+            .map(|id| format!(" ({})", id))
+            .unwrap_or_default();
+        eprintln!(
+            "{} record {}{}: {}",
+            error.file, error.index, id, error.message
+        );
+    }
+    if !report.errors.is_empty() {
+        return Err(format!(
+            "{} record(s) failed validation; nothing was imported",
+            report.errors.len()
+        )
+        .into());
+    }
+    let verb = if report.applied {
+        "imported"
+    } else {
+        "would import"
+    };
+    println!(
+        "{}: {} created, {} updated, {} unchanged",
+        verb, report.created, report.updated, report.unchanged
+    );
+    Ok(())
+}
+
+/// Configuration problems are reported alongside store problems; the store is only inspected
+/// once the configuration that names it is valid.
+fn check(
+    config: Result<EngineConfig, ConfigError>,
+    mode: IntegrityMode,
+    json: bool,
+) -> CommandResult {
+    let config = match config {
+        Ok(config) => config,
+        Err(err) if json => {
+            println!("{}", json!({ "configuration": err.to_string() }));
+            return Err("1 problem(s) found; the store was not checked".into());
+        }
+        Err(err) => {
+            println!("configuration: invalid");
+            eprintln!("  {}", err);
+            return Err("1 problem(s) found; the store was not checked".into());
+        }
+    };
+    let (mut problems, mut integrity) = (Vec::new(), None);
+    match check_store(&config, mode) {
+        Ok((store, report)) => {
+            problems.extend(store);
+            integrity = report;
+        }
+        Err(err) => problems.push(format!("store: {}", err)),
+    }
+    let unresolved = problems.len() + integrity.as_ref().map_or(0, IntegrityReport::unresolved);
+    if json {
+        println!(
+            "{}",
+            json!({ "configuration": "ok", "store": problems, "integrity": integrity })
+        );
+    } else {
+        println!("configuration: ok");
+        let db = config.storage.sqlite_path.as_path();
+        println!(
+            "store {}: {}",
+            db.display(),
+            if unresolved == 0 { "ok" } else { "invalid" }
+        );
+        for problem in &problems {
+            eprintln!("  {}", problem);
+        }
+        for issue in integrity.iter().flat_map(|report| &report.issues) {
+            let action = match issue.action {
+                IntegrityAction::None => "",
+                IntegrityAction::Repaired => " (repaired)",
+                IntegrityAction::Quarantined => " (quarantined)",
+            };
+            eprintln!(
+                "  content: {} '{}': {}{}",
+                issue.kind, issue.key, issue.message, action
+            );
+        }
+        if let Some(report) = integrity
+            .as_ref()
+            .filter(|report| report.mode != IntegrityMode::Report)
+        {
+            println!(
+                "content: {} repaired, {} quarantined",
+                report.repaired, report.quarantined
+            );
+        }
+    }
+    if unresolved == 0 {
+        Ok(())
+    } else {
+        Err(format!("{} problem(s) found", unresolved).into())
+    }
+}
+
+/// Problems with the store, and the content integrity report once the store is readable at
+/// this schema version; an error when it cannot be inspected at all.
+fn check_store(
+    config: &EngineConfig,
+    mode: IntegrityMode,
+) -> Result<(Vec<String>, Option<IntegrityReport>), Box<dyn Error>> {
+    let db = config.storage.sqlite_path.as_path();
+    require_store(db)?;
+    // Repairs write the store, so they wait for a running Engine like any other write.
+    let _lock = (mode != IntegrityMode::Report)
+        .then(|| StoreLock::acquire(db))
+        .transpose()?;
+    let storage = open_store(config)?;
+    let mut problems: Vec<String> = storage
+        .integrity_check()?
+        .into_iter()
+        .map(|problem| format!("sqlite: {}", problem))
+        .collect();
+    let version = storage.schema_version()?;
+    if version != Some(SCHEMA_VERSION) {
+        problems.push(outdated(version));
+        return Ok((problems, None));
+    }
+    drop(storage);
+    let engine = open_engine(config)?;
+    let report = engine.core().check_integrity(None, mode)?;
+    Ok((problems, Some(report)))
+}
+
+/// Copy the store to `file`, or else take a snapshot in the backup directory and rotate.
+fn backup(config: &EngineConfig, file: Option<&Path>) -> CommandResult {
+    let db = config.storage.sqlite_path.as_path();
+    require_store(db)?;
+    let Some(file) = file else {
+        let report = Snapshots::new(config.backup_dir(), config.backup.keep).take(db)?;
+        println!("backed up {} to {}", db.display(), report.path.display());
+        for removed in report.removed {
+            println!("removed old backup {}", removed.display());
+        }
+        return Ok(());
+    };
+    snapshot(db, file)?;
+    println!("backed up {} to {}", db.display(), file.display());
+    Ok(())
+}
+
+fn restore(db: &Path, file: &Path) -> CommandResult {
+    let previous = aqevia_storage_sqlite::restore(file, db)?;
+    println!("restored {} from {}", db.display(), file.display());
+    if let Some(previous) = previous {
+        println!("previous store kept as {}", previous.display());
+    }
+    Ok(())
+}
+
+fn create_admin(config: &EngineConfig, username: &str) -> CommandResult {
+    let _lock = StoreLock::acquire(&config.storage.sqlite_path)?;
+    let password = match env::var("AQEVIA_ADMIN_PASSWORD") {
+        Ok(password) => password,
+        Err(_) => {
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    // A new store is created; an existing one must already be migrated.
+    let mut engine = if config.storage.sqlite_path.exists() {
+        open_engine(config)?
+    } else {
+        new_engine(config)?
+    };
+    if engine.auth().role_count(Role::Admin) > 0 {
+        return Err(
+            "an admin account already exists; manage accounts through /api/admin/accounts".into(),
+        );
+    }
+    engine.create_account(username, &password, Role::Admin)?;
+    println!("created admin account '{}'", username);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqlite_settings_default_to_the_backend_defaults() {
+        let mut config = EngineConfig::default();
+        assert_eq!(sqlite_options(&config).unwrap(), SqliteOptions::default());
+        config.storage.journal_mode = "memory".into();
+        config.storage.synchronous = "sometimes".into();
+        match sqlite_options(&config) {
+            Err(ConfigError::Validation(problems)) => {
+                assert_eq!(problems.len(), 2);
+                assert!(problems[0].starts_with("storage.journal_mode"));
+            }
+            other => panic!("expected validation errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn store_writes_wait_for_a_running_engine() {
+        let dir = env::temp_dir().join(format!("aqevia_commands_lock_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut config = EngineConfig::default();
+        config.storage.sqlite_path = dir.join("store.sqlite");
+        config.auth.hash_iterations = 16;
+        new_engine(&config).unwrap();
+        let bundle = dir.join("bundle");
+        export(&config, &bundle).unwrap();
+
+        let serving = StoreLock::acquire(&config.storage.sqlite_path).unwrap();
+        let busy = |result: CommandResult| result.unwrap_err().to_string();
+        assert!(busy(import(&config, &bundle, false)).contains("in use by a running Engine"));
+        assert!(busy(create_admin(&config, "root")).contains("in use by a running Engine"));
+        for mode in [IntegrityMode::Repair, IntegrityMode::Quarantine] {
+            let repair = check_store(&config, mode).map(drop);
+            assert!(busy(repair).contains("in use by a running Engine"));
+        }
+        assert!(check_store(&config, IntegrityMode::Report).is_ok());
+        assert!(import(&config, &bundle, true).is_ok());
+
+        drop(serving);
+        assert!(import(&config, &bundle, false).is_ok());
+        assert!(check_store(&config, IntegrityMode::Repair).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,57 +1,30 @@
-//! Entry point for the Aqevia Engine binary that hosts a single World per deployment unit.
+//! Entry point for the Aqevia Engine binary that hosts a single World per deployment unit, plus
+//! the operational commands that act on its store.
 
-use std::env;
-use std::error::Error;
-use std::net::SocketAddr;
-use std::path::PathBuf;
-use std::sync::Arc;
-
-use aqevia_engine::Engine;
-use aqevia_storage::StorageConfig;
-use aqevia_storage_sqlite::SqliteStorage;
-use aqevia_transport::{ObservabilityServer, ObservabilityState};
+mod cli;
+mod commands;
 
-fn read_project_version() -> Result<String, std::io::Error> {
-    let contents = std::fs::read_to_string("VERSION")?;
-    Ok(contents
-        .lines()
-        .find(|line| !line.starts_with('#'))
-        .unwrap_or("0.2.0")
-        .trim()
-        .to_string())
-}
+use std::env;
+use std::process::ExitCode;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let version = read_project_version()?;
-    let world_id = "aqevia-default-world";
-    let storage_path = env::var("AQEVIA_SQLITE_PATH").unwrap_or_else(|_| "storage.sqlite".into());
-    let storage = SqliteStorage::new(PathBuf::from(storage_path))?;
-    let observability = Arc::new(ObservabilityState::new(version.clone(), world_id, "sqlite"));
-    let flush_interval_ms = env::var("PERSIST_FLUSH_INTERVAL_MS")
-        .ok()
-        .and_then(|value| value.parse().ok())
-        .unwrap_or(1000);
-    let batch_capacity = env::var("PERSIST_BATCH_CAPACITY")
-        .ok()
-        .and_then(|value| value.parse().ok())
-        .unwrap_or(10);
+use cli::Invocation;
 
-    let mut engine = Engine::new(
-        storage,
-        StorageConfig {
-            flush_interval_ms,
-            batch_capacity,
+fn main() -> ExitCode {
+    match cli::parse(env::args().skip(1)) {
+        Ok(Invocation::Help(text)) => {
+            print!("{}", text);
+            ExitCode::SUCCESS
+        }
+        Ok(Invocation::Run { command, options }) => match commands::run(command, options) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::FAILURE
+            }
         },
-        observability.clone(),
-    )?;
-
-    let addr: SocketAddr = env::var("AQEVIA_OBSERVABILITY_ADDR")
-        .unwrap_or_else(|_| "127.0.0.1:7878".into())
-        .parse()?;
-    let mut server = ObservabilityServer::start(observability.clone(), addr)?;
-    let output = engine.run_one_world("ready")?;
-    println!("Server running: {}", output);
-    engine.flush_all()?;
-    server.shutdown();
-    Ok(())
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            ExitCode::from(2)
+        }
+    }
 }
@@ -0,0 +1,376 @@
+//! Account management over the control plane. Players register themselves with
+//! `POST /api/auth/register` while `auth.open_registration` is on; admins list, create,
+//! re-role, and delete accounts under `/api/admin/accounts`. Every change is persisted as an
+//! `auth.account` record and audited.
+
+use std::sync::Arc;
+
+use aqevia_auth::{Account, AuthService, Principal, Role, ACCOUNT_KIND};
+use aqevia_storage::{StorageBackend, WorldRecord};
+use aqevia_transport::{HttpHandler, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::builder::BuilderError;
+use crate::{EngineCore, SharedCore};
+
+#[derive(Deserialize)]
+pub(crate) struct NewAccount {
+    username: String,
+    password: String,
+    /// Defaults to `player`.
+    #[serde(default)]
+    role: Option<Role>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RoleChange {
+    role: Role,
+}
+
+#[derive(Deserialize)]
+struct Registration {
+    username: String,
+    password: String,
+}
+
+/// `POST /api/auth/register`.
+pub struct RegistrationApi<B: StorageBackend> {
+    core: SharedCore<B>,
+    auth: Arc<AuthService>,
+}
+
+impl<B: StorageBackend> RegistrationApi<B> {
+    pub fn new(core: SharedCore<B>, auth: Arc<AuthService>) -> Self {
+        RegistrationApi { core, auth }
+    }
+
+    fn register(&self, body: Registration) -> Result<HttpResponse, BuilderError> {
+        let account = self.auth.register(&body.username, &body.password)?;
+        let mut core = self.core.lock().expect("lock poisoning");
+        save(&mut core, None, "auth.register", &account, Value::Null)?;
+        Ok(HttpResponse::json(201, &account.info()))
+    }
+}
+
+impl<B: StorageBackend> HttpHandler for RegistrationApi<B> {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        if request.path != "/api/auth/register" {
+            return None;
+        }
+        let result = match request.method.as_str() {
+            "POST" => match request.json::<Registration>() {
+                Ok(body) => self.register(body),
+                Err(response) => Ok(response),
+            },
+            _ => Ok(HttpResponse::error(
+                405,
+                "method_not_allowed",
+                "method not allowed",
+            )),
+        };
+        Some(result.unwrap_or_else(|err| err.to_response()))
+    }
+}
+
+/// `GET /api/admin/accounts`: every account without its password hash.
+pub(crate) fn list(auth: &AuthService) -> HttpResponse {
+    HttpResponse::json(200, &json!({ "accounts": auth.accounts() }))
+}
+
+/// `POST /api/admin/accounts`.
+pub(crate) fn create<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    auth: &AuthService,
+    actor: Option<&Principal>,
+    body: NewAccount,
+) -> Result<HttpResponse, BuilderError> {
+    let role = body.role.unwrap_or(Role::Player);
+    let account = auth.create_account(&body.username, &body.password, role)?;
+    save(
+        core,
+        actor,
+        "admin.account.create",
+        &account,
+        json!({ "role": role }),
+    )?;
+    Ok(HttpResponse::json(201, &account.info()))
+}
+
+/// `PATCH /api/admin/accounts/<username>`. The new role applies to the account's next
+/// request; tokens already issued stay valid.
+pub(crate) fn set_role<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    auth: &AuthService,
+    actor: Option<&Principal>,
+    username: &str,
+    body: RoleChange,
+) -> Result<HttpResponse, BuilderError> {
+    let from = auth.account(username).map(|account| account.role);
+    let account = auth.set_role(username, body.role)?;
+    save(
+        core,
+        actor,
+        "admin.account.role",
+        &account,
+        json!({ "from": from, "to": account.role }),
+    )?;
+    Ok(HttpResponse::json(200, &account.info()))
+}
+
+/// `DELETE /api/admin/accounts/<username>`: revokes the account's tokens and closes its live
+/// sessions. The last admin cannot be deleted.
+pub(crate) fn delete<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    auth: &AuthService,
+    actor: Option<&Principal>,
+    username: &str,
+) -> Result<HttpResponse, BuilderError> {
+    let account = auth.delete_account(username)?;
+    let disconnected = core
+        .transport
+        .router()
+        .sessions()
+        .disconnect_account(username, "account deleted");
+    let tombstone = WorldRecord::tombstone(core.world_id(), ACCOUNT_KIND, username);
+    core.record(tombstone)?;
+    core.audit(
+        actor,
+        "admin.account.delete",
+        &target(username),
+        json!({ "disconnected": disconnected }),
+    );
+    core.flush_all()?;
+    Ok(HttpResponse::json(200, &account.info()))
+}
+
+/// Persist a new or changed account together with its audit entry.
+fn save<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    actor: Option<&Principal>,
+    action: &str,
+    account: &Account,
+    detail: Value,
+) -> Result<(), BuilderError> {
+    let record = account.to_record(core.world_id());
+    core.record(record)?;
+    core.audit(actor, action, &target(&account.username), detail);
+    core.flush_all()?;
+    Ok(())
+}
+
+fn target(username: &str) -> String {
+    format!("{}/{}", ACCOUNT_KIND, username)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{DummyBackend, Harness};
+    use aqevia_auth::AuthConfig;
+    use aqevia_router::SessionEvent;
+
+    #[test]
+    fn accounts_are_created_re_roled_and_deleted() {
+        let harness = Harness::admin(DummyBackend::default());
+        let (status, body) = harness.call(
+            "POST",
+            "/api/admin/accounts",
+            r#"{"username":"carol","password":"password123","role":"builder"}"#,
+        );
+        assert_eq!(status, 201);
+        assert_eq!(body["role"], "builder");
+        assert!(body.get("password_hash").is_none());
+        let (status, body) = harness.call(
+            "POST",
+            "/api/admin/accounts",
+            r#"{"username":"dave","password":"password123"}"#,
+        );
+        assert_eq!(status, 201);
+        assert_eq!(body["role"], "player");
+
+        let (status, body) =
+            harness.call("PATCH", "/api/admin/accounts/dave", r#"{"role":"builder"}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["role"], "builder");
+        let (status, _) = harness.call(
+            "PATCH",
+            "/api/admin/accounts/nobody",
+            r#"{"role":"builder"}"#,
+        );
+        assert_eq!(status, 404);
+        let (status, _) = harness.call("PATCH", "/api/admin/accounts/dave", r#"{"role":"god"}"#);
+        assert_eq!(status, 400);
+
+        let (_, events) = harness.plane.sessions().open("carol", None);
+        let token = harness
+            .engine
+            .auth()
+            .login("carol", "password123")
+            .unwrap()
+            .token;
+        let (status, _) = harness.call("DELETE", "/api/admin/accounts/carol", "");
+        assert_eq!(status, 200);
+        assert_eq!(
+            events.recv().unwrap(),
+            SessionEvent::Close("account deleted".into())
+        );
+        assert!(harness.engine.auth().authenticate(&token).is_err());
+        let (status, _) = harness.call("DELETE", "/api/admin/accounts/carol", "");
+        assert_eq!(status, 404);
+        let (status, _) = harness.call("PUT", "/api/admin/accounts/dave", "");
+        assert_eq!(status, 405);
+
+        let (_, body) = harness.call("GET", "/api/admin/accounts", "");
+        let names: Vec<&str> = body["accounts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|account| account["username"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["dave", "root"]);
+        assert_eq!(
+            harness.audit_actions(),
+            vec![
+                "admin.account.create",
+                "admin.account.create",
+                "admin.account.role",
+                "admin.account.delete",
+            ]
+        );
+
+        let records = harness
+            .engine
+            .core()
+            .storage()
+            .load_records(ACCOUNT_KIND)
+            .unwrap();
+        let reloaded = Harness::admin(DummyBackend::with_records(records));
+        assert!(reloaded.engine.auth().account("carol").is_none());
+        assert_eq!(
+            reloaded.engine.auth().account("dave").unwrap().role,
+            Role::Builder
+        );
+    }
+
+    #[test]
+    fn usernames_are_unique_and_passwords_checked() {
+        let harness = Harness::admin(DummyBackend::default());
+        let create = |body: &str| harness.call("POST", "/api/admin/accounts", body).0;
+        assert_eq!(
+            create(r#"{"username":"carol","password":"password123"}"#),
+            201
+        );
+        assert_eq!(
+            create(r#"{"username":"carol","password":"password456","role":"admin"}"#),
+            409
+        );
+        assert_eq!(
+            create(r#"{"username":"root","password":"password123"}"#),
+            409
+        );
+        assert_eq!(create(r#"{"username":"x","password":"password123"}"#), 400);
+        assert_eq!(create(r#"{"username":"erin","password":"short"}"#), 400);
+        assert_eq!(create(r#"{"username":"erin"}"#), 400);
+
+        // The duplicate left the first account as it was.
+        let carol = harness.engine.auth().account("carol").unwrap();
+        assert_eq!(carol.role, Role::Player);
+        assert!(harness.engine.auth().login("carol", "password123").is_ok());
+        assert_eq!(harness.audit_actions(), vec!["admin.account.create"]);
+    }
+
+    #[test]
+    fn the_last_admin_cannot_be_deleted_or_demoted() {
+        let harness = Harness::admin(DummyBackend::default());
+        let (status, body) =
+            harness.call("PATCH", "/api/admin/accounts/root", r#"{"role":"builder"}"#);
+        assert_eq!(status, 409);
+        assert_eq!(body["status"], "conflict");
+        assert_eq!(
+            harness.call("DELETE", "/api/admin/accounts/root", "").0,
+            409
+        );
+        assert_eq!(
+            harness.engine.auth().account("root").unwrap().role,
+            Role::Admin
+        );
+        assert!(harness.audit_actions().is_empty());
+
+        // With a second admin either one may step down, but not both.
+        let (status, _) = harness.call(
+            "POST",
+            "/api/admin/accounts",
+            r#"{"username":"ada","password":"password123","role":"admin"}"#,
+        );
+        assert_eq!(status, 201);
+        let (status, body) =
+            harness.call("PATCH", "/api/admin/accounts/ada", r#"{"role":"builder"}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["role"], "builder");
+        assert_eq!(
+            harness.call("DELETE", "/api/admin/accounts/root", "").0,
+            409
+        );
+        assert_eq!(
+            harness
+                .call("PATCH", "/api/admin/accounts/ada", r#"{"role":"admin"}"#)
+                .0,
+            200
+        );
+        assert_eq!(harness.call("DELETE", "/api/admin/accounts/ada", "").0, 200);
+        assert_eq!(harness.engine.auth().role_count(Role::Admin), 1);
+    }
+
+    #[test]
+    fn players_register_only_while_registration_is_open() {
+        let harness = Harness::admin(DummyBackend::default());
+        let register = |body: &str| {
+            harness
+                .plane
+                .dispatch(HttpRequest::new("POST", "/api/auth/register").with_body(body))
+        };
+        let response = register(r#"{"username":"erin","password":"password123"}"#);
+        assert_eq!(response.status, 201);
+        assert_eq!(
+            harness.engine.auth().account("erin").unwrap().role,
+            Role::Player
+        );
+        assert!(harness.engine.auth().login("erin", "password123").is_ok());
+        assert_eq!(
+            register(r#"{"username":"erin","password":"password123"}"#).status,
+            409
+        );
+        assert_eq!(
+            register(r#"{"username":"root","password":"password123"}"#).status,
+            409
+        );
+        // A role in the body is ignored; registration only creates players.
+        let response = register(r#"{"username":"gina","password":"password123","role":"admin"}"#);
+        assert_eq!(response.status, 201);
+        assert_eq!(
+            harness.engine.auth().account("gina").unwrap().role,
+            Role::Player
+        );
+        assert_eq!(
+            harness
+                .plane
+                .dispatch(HttpRequest::new("GET", "/api/auth/register"))
+                .status,
+            405
+        );
+        assert_eq!(
+            harness.audit_actions(),
+            vec!["auth.register", "auth.register"]
+        );
+
+        harness.engine.auth().set_config(AuthConfig {
+            open_registration: false,
+            ..harness.engine.auth().config()
+        });
+        let response = register(r#"{"username":"frank","password":"password123"}"#);
+        assert_eq!(response.status, 403);
+        assert!(harness.engine.auth().account("frank").is_none());
+        assert_eq!(harness.audit_actions().len(), 2);
+    }
+}
@@ -0,0 +1,842 @@
+//! Admin control-plane API under `/api/admin/*`: session moderation, bans, broadcasts, tick
+//! loop control, account management, World import, export, and integrity checks, storage
+//! flushes, AI budgets, configuration reloads, and audit log queries. Every action is written to the audit log.
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aqevia_auth::{AuthService, Ban, BanTarget, IpRange, Principal, Role, BAN_KIND};
+use aqevia_storage::{StorageBackend, WorldRecord};
+use aqevia_transport::auth::{auth_error_response, require_role};
+use aqevia_transport::{HttpHandler, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::accounts::{self, NewAccount, RoleChange};
+use crate::audit::AuditQuery;
+use crate::builder::BuilderError;
+use crate::bundle::WorldBundle;
+use crate::integrity::IntegrityMode;
+use crate::{EngineCore, SharedCore};
+
+/// Entries returned by the audit query when no `limit` is given, and the most it will return.
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+const MAX_AUDIT_LIMIT: usize = 1000;
+
+#[derive(Deserialize, Default)]
+struct ReasonBody {
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct BanBody {
+    #[serde(flatten)]
+    target: BanTarget,
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct BroadcastBody {
+    message: String,
+}
+
+pub struct AdminApi<B: StorageBackend> {
+    core: SharedCore<B>,
+    auth: Arc<AuthService>,
+}
+
+impl<B: StorageBackend> AdminApi<B> {
+    pub fn new(core: SharedCore<B>, auth: Arc<AuthService>) -> Self {
+        AdminApi { core, auth }
+    }
+
+    fn route(&self, request: &HttpRequest, rest: &[&str]) -> Option<HttpResponse> {
+        let actor = request.principal.as_ref();
+        let mut core = self.core.lock().expect("lock poisoning");
+        let result = match (request.method.as_str(), rest) {
+            ("GET", ["sessions"]) => {
+                let sessions = core.transport.router().sessions().list();
+                Ok(HttpResponse::json(200, &json!({ "sessions": sessions })))
+            }
+            ("POST", ["sessions", id, action @ ("kick" | "mute" | "unmute")]) => {
+                session_action(&mut core, actor, request, id, action)
+            }
+            ("GET", ["bans"]) => Ok(HttpResponse::json(
+                200,
+                &json!({ "bans": self.auth.bans() }),
+            )),
+            ("POST", ["bans"]) => match request.json::<BanBody>() {
+                Ok(body) => self.ban(&mut core, actor, body),
+                Err(response) => Ok(response),
+            },
+            ("DELETE", ["bans", kind, value @ ..]) if !value.is_empty() => {
+                self.unban(&mut core, actor, kind, &value.join("/"))
+            }
+            ("GET", ["accounts"]) => Ok(accounts::list(&self.auth)),
+            ("POST", ["accounts"]) => match request.json::<NewAccount>() {
+                Ok(body) => accounts::create(&mut core, &self.auth, actor, body),
+                Err(response) => Ok(response),
+            },
+            ("PATCH", ["accounts", username]) => match request.json::<RoleChange>() {
+                Ok(body) => accounts::set_role(&mut core, &self.auth, actor, username, body),
+                Err(response) => Ok(response),
+            },
+            ("DELETE", ["accounts", username]) => {
+                accounts::delete(&mut core, &self.auth, actor, username)
+            }
+            ("POST", ["broadcast"]) => match request.json::<BroadcastBody>() {
+                Ok(body) if body.message.trim().is_empty() => Err(BuilderError::BadRequest(
+                    "broadcast message must not be empty".into(),
+                )),
+                Ok(body) => {
+                    let message = format!("[system] {}", body.message.trim());
+                    let delivered = core.transport.router().sessions().broadcast(&message);
+                    core.audit(
+                        actor,
+                        "admin.broadcast",
+                        "sessions",
+                        json!({ "message": body.message, "delivered": delivered }),
+                    );
+                    flushed(&mut core, json!({ "delivered": delivered }))
+                }
+                Err(response) => Ok(response),
+            },
+            ("GET", ["audit"]) => audit_query(&mut core, request),
+            ("GET", ["world"]) => Ok(HttpResponse::json(200, &world_status(&core))),
+            ("GET", ["world", "export"]) => {
+                Ok(HttpResponse::json(200, &core.export_bundle().to_json()))
+            }
+            ("POST", ["world", "import"]) => import(&mut core, actor, request),
+            ("POST", ["world", "check"]) => check(&mut core, actor, request),
+            ("POST", ["world", action @ ("pause" | "resume")]) => {
+                core.set_paused(*action == "pause");
+                core.audit(
+                    actor,
+                    &format!("admin.world.{}", action),
+                    "world",
+                    Value::Null,
+                );
+                let status = world_status(&core);
+                flushed(&mut core, status)
+            }
+            ("POST", ["storage", "flush"]) => {
+                let pending = core.storage().pending().len();
+                core.audit(
+                    actor,
+                    "admin.storage.flush",
+                    "storage",
+                    json!({ "pending": pending }),
+                );
+                core.flush_all()
+                    .map(|()| {
+                        HttpResponse::json(
+                            200,
+                            &json!({
+                                "status": "flushed",
+                                "flush_count": core.storage().stats().flush_count,
+                            }),
+                        )
+                    })
+                    .map_err(BuilderError::from)
+            }
+            ("GET", ["ai", "budgets"]) => Ok(HttpResponse::json(200, &budget_status(&core))),
+            ("DELETE", ["ai", "budgets", scope @ ..]) if !scope.is_empty() => {
+                let scope = scope.join("/");
+                if !core.budgets().reset(&scope) {
+                    return Some(
+                        BuilderError::Missing(format!("no AI usage for '{}'", scope)).to_response(),
+                    );
+                }
+                core.audit(actor, "admin.ai.reset_budget", &scope, Value::Null);
+                core.sync_ai_usage();
+                let status = budget_status(&core);
+                flushed(&mut core, status)
+            }
+            ("POST", ["config", "reload"]) => core
+                .reload_config(actor, &self.auth)
+                .map_err(BuilderError::from)
+                .and_then(|report| flushed(&mut core, json!(report))),
+            (
+                _,
+                ["sessions"]
+                | ["accounts"]
+                | ["accounts", _]
+                | ["bans"]
+                | ["broadcast"]
+                | ["audit"]
+                | ["world"]
+                | ["world", _]
+                | ["ai", "budgets"]
+                | ["config", "reload"],
+            ) => Ok(HttpResponse::error(
+                405,
+                "method_not_allowed",
+                "method not allowed",
+            )),
+            _ => return None,
+        };
+        Some(result.unwrap_or_else(|err| err.to_response()))
+    }
+
+    fn ban(
+        &self,
+        core: &mut EngineCore<B>,
+        actor: Option<&Principal>,
+        body: BanBody,
+    ) -> Result<HttpResponse, BuilderError> {
+        let ban = self.auth.add_ban(Ban {
+            target: body.target,
+            reason: body.reason,
+            banned_by: actor
+                .map(|principal| principal.username.clone())
+                .unwrap_or_default(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        });
+        let sessions = core.transport.router().sessions().clone();
+        let disconnected = match &ban.target {
+            BanTarget::Account(username) => sessions.disconnect_account(username, "banned"),
+            BanTarget::Ip(range) => sessions
+                .list()
+                .into_iter()
+                .filter(|info| info.ip.is_some_and(|ip| range.contains(ip)))
+                .filter(|info| sessions.disconnect(info.id, "banned"))
+                .count(),
+        };
+        let record = ban.to_record(core.world_id());
+        core.record(record)?;
+        core.audit(
+            actor,
+            "admin.ban.add",
+            &ban.target.key(),
+            json!({ "reason": ban.reason, "disconnected": disconnected }),
+        );
+        core.flush_all()?;
+        Ok(HttpResponse::json(201, &ban))
+    }
+
+    fn unban(
+        &self,
+        core: &mut EngineCore<B>,
+        actor: Option<&Principal>,
+        kind: &str,
+        value: &str,
+    ) -> Result<HttpResponse, BuilderError> {
+        let target = match kind {
+            "account" => BanTarget::Account(value.to_string()),
+            "ip" => match value.parse::<IpRange>() {
+                Ok(range) => BanTarget::Ip(range),
+                Err(err) => return Ok(auth_error_response(&err)),
+            },
+            _ => {
+                return Err(BuilderError::Missing(format!(
+                    "unknown ban type '{}'",
+                    kind
+                )))
+            }
+        };
+        let key = target.key();
+        let ban = self
+            .auth
+            .remove_ban(&key)
+            .ok_or_else(|| BuilderError::Missing(format!("ban '{}' not found", key)))?;
+        let tombstone = WorldRecord::tombstone(core.world_id(), BAN_KIND, &key);
+        core.record(tombstone)?;
+        core.audit(actor, "admin.ban.remove", &key, Value::Null);
+        core.flush_all()?;
+        Ok(HttpResponse::json(200, &ban))
+    }
+}
+
+fn session_action<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    actor: Option<&Principal>,
+    request: &HttpRequest,
+    id: &str,
+    action: &str,
+) -> Result<HttpResponse, BuilderError> {
+    let id: u64 = id
+        .parse()
+        .map_err(|_| BuilderError::BadRequest(format!("'{}' is not a session id", id)))?;
+    let body = if request.body.is_empty() {
+        ReasonBody::default()
+    } else {
+        request
+            .json::<ReasonBody>()
+            .map_err(|_| BuilderError::BadRequest("body must be a JSON object".into()))?
+    };
+    let sessions = core.transport.router().sessions().clone();
+    let info = sessions
+        .get(id)
+        .ok_or_else(|| BuilderError::Missing(format!("session {} not found", id)))?;
+    match action {
+        "kick" => {
+            let reason = if body.reason.is_empty() {
+                "kicked by an administrator"
+            } else {
+                &body.reason
+            };
+            sessions.disconnect(id, reason);
+        }
+        _ => {
+            sessions.set_muted(id, action == "mute");
+        }
+    }
+    core.audit(
+        actor,
+        &format!("admin.session.{}", action),
+        &format!("session:{}", id),
+        json!({ "account": info.account, "reason": body.reason }),
+    );
+    flushed(
+        core,
+        json!({ "status": action, "id": id, "account": info.account }),
+    )
+}
+
+/// `GET /api/admin/audit?actor=&target=&since=&until=&limit=`; times are Unix seconds.
+fn audit_query<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    request: &HttpRequest,
+) -> Result<HttpResponse, BuilderError> {
+    let number = |name: &str| {
+        request
+            .query_param(name)
+            .map(|value| {
+                value.parse::<u64>().map_err(|_| {
+                    BuilderError::BadRequest(format!("'{}' must be a non-negative integer", name))
+                })
+            })
+            .transpose()
+    };
+    let limit = number("limit")?.unwrap_or(DEFAULT_AUDIT_LIMIT as u64) as usize;
+    let query = AuditQuery {
+        actor: request.query_param("actor").map(str::to_string),
+        target: request.query_param("target").map(str::to_string),
+        since: number("since")?,
+        until: number("until")?,
+        limit: limit.min(MAX_AUDIT_LIMIT),
+    };
+    let entries = core.query_audit(&query)?;
+    Ok(HttpResponse::json(200, &json!({ "entries": entries })))
+}
+
+fn world_status<B: StorageBackend>(core: &EngineCore<B>) -> Value {
+    json!({
+        "paused": core.paused(),
+        "sessions": core.transport.router().sessions().len(),
+        "pending_records": core.storage().pending().len(),
+    })
+}
+
+/// `POST /api/admin/world/import[?dry_run=true]` with a bundle object as the body.
+fn import<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    actor: Option<&Principal>,
+    request: &HttpRequest,
+) -> Result<HttpResponse, BuilderError> {
+    let body: Value = match request.json() {
+        Ok(body) => body,
+        Err(response) => return Ok(response),
+    };
+    let bundle =
+        WorldBundle::from_json(&body).map_err(|err| BuilderError::BadRequest(err.to_string()))?;
+    let dry_run = matches!(request.query_param("dry_run"), Some("true" | "1"));
+    let report = core.import_bundle(actor, &bundle, dry_run)?;
+    if report.errors.is_empty() {
+        return Ok(HttpResponse::json(200, &report));
+    }
+    let mut body = json!(report);
+    body["status"] = json!("validation_failed");
+    body["message"] = json!(format!(
+        "{} bundle record(s) failed validation",
+        report.errors.len()
+    ));
+    Ok(HttpResponse::json(422, &body))
+}
+
+/// `POST /api/admin/world/check[?mode=report|repair|quarantine]`.
+fn check<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    actor: Option<&Principal>,
+    request: &HttpRequest,
+) -> Result<HttpResponse, BuilderError> {
+    let mode = request
+        .query_param("mode")
+        .map(str::parse::<IntegrityMode>)
+        .transpose()
+        .map_err(BuilderError::BadRequest)?
+        .unwrap_or_default();
+    let report = core.check_integrity(actor, mode)?;
+    if mode == IntegrityMode::Report {
+        core.audit(actor, "admin.world.check", "world", report.summary());
+    }
+    flushed(core, json!(report))
+}
+
+fn budget_status<B: StorageBackend>(core: &EngineCore<B>) -> Value {
+    let config = core.budgets().config();
+    json!({
+        "window_seconds": config.window.as_secs(),
+        "limits": { "world": config.world, "account": config.account },
+        "usage": core.budgets().report(SystemTime::now()),
+    })
+}
+
+/// Flush the audit entry for an action that already took effect and report `body`.
+fn flushed<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    body: Value,
+) -> Result<HttpResponse, BuilderError> {
+    core.flush_all()?;
+    Ok(HttpResponse::json(200, &body))
+}
+
+impl<B: StorageBackend> HttpHandler for AdminApi<B> {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let segments = request.segments();
+        let ["api", "admin", rest @ ..] = segments.as_slice() else {
+            return None;
+        };
+        if let Err(rejection) = require_role(request, Role::Admin) {
+            return Some(rejection);
+        }
+        self.route(request, rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigError, EngineConfig};
+    use crate::integrity::QUARANTINE_KIND;
+    use crate::reload::CONFIG_RELOADS_METRIC;
+    use crate::test_support::{DummyBackend, Harness};
+    use aqevia_auth::Role;
+    use aqevia_kernel::ContentKind;
+    use aqevia_router::SessionEvent;
+    use aqevia_transport::AccessPolicy;
+
+    #[test]
+    fn sessions_can_be_listed_muted_and_kicked() {
+        let harness = Harness::admin(DummyBackend::default());
+        let sessions = harness.plane.sessions().clone();
+        let (id, events) = sessions.open("alice", Some("198.51.100.4".parse().unwrap()));
+        let (_, body) = harness.call("GET", "/api/admin/sessions", "");
+        assert_eq!(body["sessions"][0]["account"], "alice");
+        assert_eq!(body["sessions"][0]["ip"], "198.51.100.4");
+
+        let (status, _) = harness.call("POST", &format!("/api/admin/sessions/{}/mute", id), "");
+        assert_eq!(status, 200);
+        assert!(sessions.get(id).unwrap().muted);
+        let (status, _) = harness.call(
+            "POST",
+            &format!("/api/admin/sessions/{}/kick", id),
+            r#"{"reason":"spam"}"#,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(events.recv().unwrap(), SessionEvent::Close("spam".into()));
+        let (status, _) = harness.call("POST", &format!("/api/admin/sessions/{}/kick", id), "");
+        assert_eq!(status, 404);
+        assert_eq!(
+            harness.audit_actions(),
+            vec!["admin.session.mute", "admin.session.kick"]
+        );
+    }
+
+    #[test]
+    fn bans_persist_disconnect_and_block_login() {
+        let mut harness = Harness::admin(DummyBackend::default());
+        harness
+            .engine
+            .create_account("mallory", "password123", Role::Player)
+            .unwrap();
+        let (_, events) = harness.plane.sessions().open("mallory", None);
+        let (status, body) = harness.call(
+            "POST",
+            "/api/admin/bans",
+            r#"{"account":"mallory","reason":"griefing"}"#,
+        );
+        assert_eq!(status, 201);
+        assert_eq!(body["banned_by"], "root");
+        assert_eq!(events.recv().unwrap(), SessionEvent::Close("banned".into()));
+        assert!(harness
+            .engine
+            .auth()
+            .login("mallory", "password123")
+            .is_err());
+        let (status, _) = harness.call("POST", "/api/admin/bans", r#"{"ip":"203.0.113.0/24"}"#);
+        assert_eq!(status, 201);
+        let (status, _) = harness.call("POST", "/api/admin/bans", r#"{"ip":"not-an-ip"}"#);
+        assert_eq!(status, 400);
+
+        let mut records = Vec::new();
+        for kind in ["auth.account", BAN_KIND] {
+            records.extend(harness.engine.core().storage().load_records(kind).unwrap());
+        }
+        let reloaded = Harness::admin(DummyBackend::with_records(records));
+        let (_, body) = reloaded.call("GET", "/api/admin/bans", "");
+        assert_eq!(body["bans"].as_array().unwrap().len(), 2);
+        let (status, _) = reloaded.call("DELETE", "/api/admin/bans/ip/203.0.113.0/24", "");
+        assert_eq!(status, 200);
+        let (status, _) = reloaded.call("DELETE", "/api/admin/bans/account/nobody", "");
+        assert_eq!(status, 404);
+        assert!(reloaded
+            .engine
+            .auth()
+            .check_ip("203.0.113.9".parse().unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn non_canonical_paths_cannot_skip_the_role_check() {
+        let harness = Harness::admin(DummyBackend::default());
+        let anonymous = |method: &str, path: &str, body: &str| {
+            harness
+                .plane
+                .dispatch(HttpRequest::new(method, path).with_body(body))
+                .status
+        };
+        let create = r#"{"username":"mallory","password":"password123","role":"admin"}"#;
+        assert_eq!(anonymous("POST", "//api/admin/accounts", create), 400);
+        assert_eq!(anonymous("POST", "/api/admin//accounts", create), 400);
+        assert_eq!(anonymous("GET", "//api/admin/accounts", ""), 400);
+        assert_eq!(anonymous("GET", "/api//builder/rooms", ""), 400);
+        assert_eq!(anonymous("GET", "/api/./admin/accounts", ""), 400);
+        assert!(harness.engine.auth().account("mallory").is_none());
+        assert_eq!(harness.call("GET", "/api/admin/accounts/", "").0, 200);
+
+        // Handlers refuse on their own when a policy fails to cover them.
+        let unguarded = harness
+            .engine
+            .control_plane()
+            .with_policy(AccessPolicy::new(Vec::new()));
+        let anonymous = |path: &str| unguarded.dispatch(HttpRequest::new("GET", path)).status;
+        assert_eq!(anonymous("/api/admin/accounts"), 401);
+        assert_eq!(anonymous("/api/builder/rooms"), 401);
+        assert_eq!(anonymous("/api/builder/changesets"), 401);
+        assert_eq!(anonymous("/api/builder/assist/proposals"), 401);
+        harness
+            .engine
+            .auth()
+            .create_account("builder", "password123", Role::Builder)
+            .unwrap();
+        let token = harness
+            .engine
+            .auth()
+            .login("builder", "password123")
+            .unwrap();
+        let as_builder = HttpRequest::new("GET", "/api/admin/accounts")
+            .with_header("Authorization", &format!("Bearer {}", token.token));
+        assert_eq!(unguarded.dispatch(as_builder).status, 403);
+    }
+
+    #[test]
+    fn broadcast_pause_and_flush() {
+        let mut harness = Harness::admin(DummyBackend::default());
+        let (id, events) = harness.plane.sessions().open("bob", None);
+        let (_, body) = harness.call(
+            "POST",
+            "/api/admin/broadcast",
+            r#"{"message":"Reboot at noon"}"#,
+        );
+        assert_eq!(body["delivered"], 1);
+        assert_eq!(
+            events.recv().unwrap(),
+            SessionEvent::Message("[system] Reboot at noon".into())
+        );
+
+        let (_, body) = harness.call("POST", "/api/admin/world/pause", "");
+        assert_eq!(body["paused"], true);
+        harness.plane.sessions().submit(id, "look");
+        assert!(!harness.engine.tick().unwrap());
+        assert!(events.try_recv().is_err());
+        harness.call("POST", "/api/admin/world/resume", "");
+        assert!(harness.engine.tick().unwrap());
+        assert!(matches!(events.recv().unwrap(), SessionEvent::Message(_)));
+
+        let (status, body) = harness.call("POST", "/api/admin/storage/flush", "");
+        assert_eq!((status, body["status"].as_str()), (200, Some("flushed")));
+        let (status, _) = harness.call("GET", "/api/admin/broadcast", "");
+        assert_eq!(status, 405);
+    }
+
+    #[test]
+    fn ai_budgets_are_reported_persisted_and_reset() {
+        let harness = Harness::admin(DummyBackend::default());
+        {
+            let mut core = harness.engine.core();
+            core.budgets().record(Some("alice"), 40, SystemTime::now());
+            core.sync_ai_usage();
+            core.flush_all().unwrap();
+        }
+        let (status, body) = harness.call("GET", "/api/admin/ai/budgets", "");
+        assert_eq!(status, 200);
+        assert_eq!(body["window_seconds"], 86_400);
+        assert_eq!(body["usage"][0]["scope"], "world");
+        assert_eq!(body["usage"][1]["scope"], "account/alice");
+        assert_eq!(body["usage"][1]["tokens"], 40);
+
+        let mut records = Vec::new();
+        for kind in ["auth.account", crate::AI_USAGE_KIND] {
+            records.extend(harness.engine.core().storage().load_records(kind).unwrap());
+        }
+        let reloaded = Harness::admin(DummyBackend::with_records(records));
+        let (_, body) = reloaded.call("GET", "/api/admin/ai/budgets", "");
+        assert_eq!(body["usage"][0]["requests"], 1);
+        let (status, body) = reloaded.call("DELETE", "/api/admin/ai/budgets/account/alice", "");
+        assert_eq!(status, 200);
+        assert_eq!(body["usage"].as_array().unwrap().len(), 1);
+        let (status, _) = reloaded.call("DELETE", "/api/admin/ai/budgets/account/alice", "");
+        assert_eq!(status, 404);
+        assert_eq!(reloaded.audit_actions(), vec!["admin.ai.reset_budget"]);
+    }
+
+    #[test]
+    fn world_bundles_import_and_export_over_http() {
+        let harness = Harness::admin(DummyBackend::default());
+        let bundle = r#"{"rooms":[{"id":"hall","name":"Hall"}]}"#;
+        let (status, body) = harness.call("POST", "/api/admin/world/import?dry_run=true", bundle);
+        assert_eq!((status, body["created"].as_u64()), (200, Some(1)));
+        assert_eq!(body["applied"], false);
+        let (status, body) = harness.call(
+            "POST",
+            "/api/admin/world/import",
+            r#"{"exits":[{"id":"x","from":"hall","to":"hall","direction":"up"}]}"#,
+        );
+        assert_eq!(
+            (status, body["status"].as_str()),
+            (422, Some("validation_failed"))
+        );
+        assert_eq!(body["errors"][0]["file"], "exits");
+        let (status, body) = harness.call("POST", "/api/admin/world/import", bundle);
+        assert_eq!((status, body["applied"].as_bool()), (200, Some(true)));
+        let (_, exported) = harness.call("GET", "/api/admin/world/export", "");
+        assert_eq!(exported["rooms"][0]["id"], "hall");
+        assert_eq!(exported["exits"], json!([]));
+        assert_eq!(
+            harness.audit_actions(),
+            vec!["builder.import", "admin.world.import"]
+        );
+    }
+
+    #[test]
+    fn integrity_checks_report_repair_and_quarantine() {
+        let record = |kind: &str, key: &str, payload: Value| {
+            WorldRecord::new("world", kind, key, payload.to_string())
+        };
+        let harness = Harness::admin(DummyBackend::with_records(vec![
+            record("core.room", "hall", json!({"id": "hall", "name": "Hall"})),
+            record(
+                "core.exit",
+                "down",
+                json!({"id": "down", "from": "hall", "to": "cellar", "direction": "down"}),
+            ),
+            record(
+                "core.item",
+                "coin",
+                json!({"id": "coin", "name": "Coin", "container": "purse"}),
+            ),
+            WorldRecord::new("world", "core.item", "torn", "{not json"),
+        ]));
+        let (status, report) = harness.call("POST", "/api/admin/world/check", "");
+        assert_eq!(status, 200);
+        assert_eq!(report["checked"], 4);
+        let problems: Vec<(&str, &str, &str)> = report["issues"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|issue| {
+                (
+                    issue["key"].as_str().unwrap(),
+                    issue["problem"].as_str().unwrap(),
+                    issue["action"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                ("torn", "undecodable", "none"),
+                ("down", "dangling_reference", "none"),
+                ("coin", "dangling_reference", "none"),
+            ]
+        );
+        assert_eq!(
+            report["issues"][1]["missing"],
+            json!([{"field": "to", "kind": "core.room", "id": "cellar"}])
+        );
+        let (status, _) = harness.call("POST", "/api/admin/world/check?mode=fix", "");
+        assert_eq!(status, 400);
+
+        let (_, report) = harness.call("POST", "/api/admin/world/check?mode=repair", "");
+        assert_eq!(
+            (report["repaired"].as_u64(), report["quarantined"].as_u64()),
+            (Some(1), Some(2))
+        );
+        {
+            let core = harness.engine.core();
+            let content = core.kernel().content();
+            assert!(!content.contains(ContentKind::Exit, "down"));
+            assert_eq!(
+                content.get(ContentKind::Item, "coin").unwrap()["container"],
+                Value::Null
+            );
+            let held = core.storage().load_records(QUARANTINE_KIND).unwrap();
+            let keys: Vec<&str> = held.iter().map(|record| record.key.as_str()).collect();
+            assert_eq!(keys, ["core.exit/down", "core.item/torn"]);
+            let torn: Value = serde_json::from_str(&held[1].payload).unwrap();
+            assert_eq!(torn["payload"], "{not json");
+        }
+        let (_, report) = harness.call("POST", "/api/admin/world/check", "");
+        assert_eq!(
+            (
+                report["checked"].as_u64(),
+                report["issues"].as_array().map(Vec::len)
+            ),
+            (Some(2), Some(0))
+        );
+        assert_eq!(
+            harness.audit_actions(),
+            [
+                "admin.world.check",
+                "admin.world.check",
+                "admin.world.check"
+            ]
+        );
+    }
+
+    #[test]
+    fn audit_log_filters_and_prunes() {
+        let harness = Harness::admin(DummyBackend::default());
+        let (id, _events) = harness.plane.sessions().open("bob", None);
+        harness.call("POST", &format!("/api/admin/sessions/{}/mute", id), "");
+        harness.call("POST", "/api/admin/world/pause", "");
+        harness.call("POST", "/api/admin/world/resume", "");
+
+        let (status, body) = harness.call("GET", "/api/admin/audit?limit=2", "");
+        assert_eq!(status, 200);
+        let entries = body["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["action"], "admin.world.resume");
+        assert_eq!(entries[0]["role"], "admin");
+
+        let (_, body) = harness.call("GET", "/api/admin/audit?target=session:1&actor=root", "");
+        assert_eq!(body["entries"][0]["action"], "admin.session.mute");
+        let (_, body) = harness.call("GET", "/api/admin/audit?actor=nobody", "");
+        assert!(body["entries"].as_array().unwrap().is_empty());
+        let (_, body) = harness.call("GET", "/api/admin/audit?until=1", "");
+        assert!(body["entries"].as_array().unwrap().is_empty());
+        let (status, _) = harness.call("GET", "/api/admin/audit?since=yesterday", "");
+        assert_eq!(status, 400);
+
+        let mut core = harness.engine.core();
+        assert_eq!(core.prune_audit(SystemTime::now()).unwrap(), 0);
+        let later = SystemTime::now() + crate::DEFAULT_AUDIT_RETENTION * 2;
+        assert_eq!(core.prune_audit(later).unwrap(), 3);
+        core.set_audit_retention(None);
+        core.audit(None, "test", "world", Value::Null);
+        assert_eq!(core.prune_audit(later).unwrap(), 0);
+    }
+
+    #[test]
+    fn builders_cannot_reach_admin_routes() {
+        let mut harness = Harness::admin(DummyBackend::default());
+        harness
+            .engine
+            .create_account("maker", "password123", Role::Builder)
+            .unwrap();
+        let token = harness
+            .engine
+            .auth()
+            .login("maker", "password123")
+            .unwrap()
+            .token;
+        let response = harness.plane.dispatch(
+            HttpRequest::new("GET", "/api/admin/sessions")
+                .with_header("Authorization", &format!("Bearer {}", token)),
+        );
+        assert_eq!(response.status, 403);
+        assert!(harness.audit_actions().is_empty());
+    }
+
+    #[test]
+    fn config_reload_applies_reloadable_settings_only() {
+        let mut harness = Harness::admin(DummyBackend::default());
+        let (status, body) = harness.call("POST", "/api/admin/config/reload", "");
+        assert_eq!(
+            (status, body["status"].as_str()),
+            (409, Some("reload_unavailable"))
+        );
+
+        let source = Arc::new(std::sync::Mutex::new(Some(EngineConfig::default())));
+        let loader = source.clone();
+        harness.engine.core().set_config_source(
+            EngineConfig::default(),
+            Box::new(move || {
+                loader
+                    .lock()
+                    .unwrap()
+                    .clone()
+                    .ok_or_else(|| ConfigError::Validation(vec!["bad".into()]))
+            }),
+        );
+        let edit = |change: &dyn Fn(&mut EngineConfig)| {
+            let mut config = EngineConfig::default();
+            change(&mut config);
+            *source.lock().unwrap() = Some(config);
+        };
+        edit(&|config| {
+            config.storage.batch_capacity = 99;
+            config.sessions.ttl_secs = 60;
+            config.ai.guardrails.blocklist = vec!["dragon".into()];
+        });
+        let (status, body) = harness.call("POST", "/api/admin/config/reload", "");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body["changed"],
+            json!([
+                "storage.batch_capacity",
+                "sessions.ttl_secs",
+                "ai.guardrails.blocklist"
+            ])
+        );
+        assert_eq!(harness.engine.core().storage().config().batch_capacity, 99);
+        assert_eq!(harness.engine.auth().config().session_ttl.as_secs(), 60);
+        harness.engine.tick().unwrap();
+        assert!(harness
+            .engine
+            .narration()
+            .guardrails()
+            .check("A dragon waits.", &Default::default())
+            .is_err());
+
+        edit(&|config| {
+            config.storage.batch_capacity = 5;
+            config.observability.addr = "0.0.0.0:9000".parse().unwrap();
+        });
+        let (status, body) = harness.call("POST", "/api/admin/config/reload", "");
+        assert_eq!(status, 409);
+        assert_eq!(
+            body["message"],
+            "changing observability.addr requires a restart; nothing was applied"
+        );
+        assert_eq!(harness.engine.core().storage().config().batch_capacity, 99);
+
+        *source.lock().unwrap() = None;
+        let (status, _) = harness.call("POST", "/api/admin/config/reload", "");
+        assert_eq!(status, 422);
+        assert_eq!(harness.audit_actions(), vec!["admin.config.reload"]);
+        let observability = harness.engine.core().observability().clone();
+        for (result, count) in [("applied", 1), ("rejected", 1), ("invalid", 1)] {
+            assert_eq!(
+                observability.metric(CONFIG_RELOADS_METRIC, &[("result", result)]),
+                count
+            );
+        }
+    }
+}
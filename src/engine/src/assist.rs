@@ -0,0 +1,781 @@
+//! AI Assist for builders under `/api/builder/assist`: "AI suggests, Builder decides".
+//!
+//! A draft request sends a prompt plus the surrounding World context to the AI Provider and
+//! turns the reply into a proposal: full records checked against the same kernel schema as
+//! manual edits. Proposals never touch the live World. Accepting one stages its records into
+//! a draft changeset, which still has to be published like any other.
+//!
+//! Builder prompts are fenced before they reach the provider, and replies must pass the
+//! guardrails before they become proposals. Each call is charged to the World's and the
+//! builder's AI budget; once either runs out, drafts fail with `429` until the window resets.
+//!
+//! The provider is called without holding the engine lock, so a slow provider never stalls
+//! the tick loop. Proposals live in memory until accepted or rejected, and at most
+//! [`MAX_PENDING_PROPOSALS`] wait at once.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aqevia_ai::{AiError, AiProvider, Completion, CompletionRequest, Guardrails};
+use aqevia_auth::{Principal, Role};
+use aqevia_kernel::{ContentKind, WorldContent};
+use aqevia_storage::StorageBackend;
+use aqevia_transport::auth::require_role;
+use aqevia_transport::{HttpHandler, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::budget::note_exhausted;
+use crate::builder::BuilderError;
+use crate::changeset::{author_name, Changeset};
+use crate::guardrail::{report_rejection, world_facts};
+use crate::{EngineCore, SharedCore};
+
+/// What a draft request asks the provider to write. The snake_case name doubles as the
+/// provider `task`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssistTask {
+    /// A new description for the target room.
+    RoomDescription,
+    /// Extra dialogue lines for the target NPC template.
+    NpcDialogue,
+    /// New items placed in the target room, one per `Name: description` line.
+    ItemSet,
+}
+
+impl AssistTask {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AssistTask::RoomDescription => "room_description",
+            AssistTask::NpcDialogue => "npc_dialogue",
+            AssistTask::ItemSet => "item_set",
+        }
+    }
+
+    /// Collection the task's target id refers to.
+    pub fn target_kind(self) -> ContentKind {
+        match self {
+            AssistTask::NpcDialogue => ContentKind::Npc,
+            AssistTask::RoomDescription | AssistTask::ItemSet => ContentKind::Room,
+        }
+    }
+
+    fn instructions(self) -> &'static str {
+        match self {
+            AssistTask::RoomDescription => {
+                "Write a room description of two or three sentences in second-person present tense. Reply with the description only."
+            }
+            AssistTask::NpcDialogue => {
+                "Write up to three lines this character might say, one per line. Reply with the lines only."
+            }
+            AssistTask::ItemSet => {
+                "Suggest up to five items found here, one per line as `Name: short description`. Reply with the lines only."
+            }
+        }
+    }
+}
+
+/// Pending proposals kept at once; a new draft beyond this drops the oldest.
+pub const MAX_PENDING_PROPOSALS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    Pending,
+    Rejected,
+}
+
+/// One record a proposal would write, in the same shape as a staged change.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProposedRecord {
+    pub collection: String,
+    pub id: String,
+    pub after: Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Proposal {
+    pub id: String,
+    pub task: AssistTask,
+    pub target: String,
+    pub prompt: String,
+    pub author: String,
+    pub status: ProposalStatus,
+    pub created_at: u64,
+    pub provider: String,
+    pub model: String,
+    /// The provider's raw reply.
+    pub text: String,
+    pub records: Vec<ProposedRecord>,
+}
+
+impl Proposal {
+    fn seq(&self) -> u64 {
+        self.id
+            .strip_prefix("ap-")
+            .and_then(|seq| seq.parse().ok())
+            .unwrap_or_default()
+    }
+
+    fn audit_target(&self) -> String {
+        format!("assist.proposal/{}", self.id)
+    }
+}
+
+#[derive(Deserialize)]
+struct DraftBody {
+    task: AssistTask,
+    target: String,
+    #[serde(default)]
+    prompt: String,
+}
+
+#[derive(Deserialize, Default)]
+struct AcceptBody {
+    #[serde(default)]
+    changeset: Option<String>,
+}
+
+impl<B: StorageBackend> EngineCore<B> {
+    /// Proposals still waiting for a decision.
+    pub fn proposals(&self) -> impl Iterator<Item = &Proposal> {
+        self.proposals.values()
+    }
+
+    /// A pending proposal. Accepted and rejected ones are gone, as are those dropped to keep
+    /// within [`MAX_PENDING_PROPOSALS`].
+    pub fn proposal(&self, id: &str) -> Result<&Proposal, BuilderError> {
+        self.proposals
+            .get(id)
+            .ok_or_else(|| BuilderError::Missing(format!("proposal '{}' not found", id)))
+    }
+
+    /// Provider request for a draft: task instructions plus the target and its surroundings.
+    pub fn assist_request(
+        &self,
+        task: AssistTask,
+        target: &str,
+        prompt: &str,
+    ) -> Result<CompletionRequest, BuilderError> {
+        let content = self.kernel().content();
+        let kind = task.target_kind();
+        let (record, room) = self.assist_target(task, target)?;
+        let mut context = vec![format!("{}: {}", kind.record_kind(), record)];
+        if let Some(room) = room.as_deref() {
+            context.extend(room_context(content, room, target));
+        }
+        let user_prompt = if prompt.trim().is_empty() {
+            record["name"].as_str().unwrap_or(target).to_string()
+        } else {
+            prompt.trim().to_string()
+        };
+        let mut request = CompletionRequest::new(task.as_str(), user_prompt).with_system(format!(
+            "You are helping a builder author a text world. {}\n\nWorld context:\n{}",
+            task.instructions(),
+            context.join("\n")
+        ));
+        request.max_tokens = 300;
+        Ok(Guardrails::fence(request))
+    }
+
+    /// The target record and the room it is set in.
+    fn assist_target(
+        &self,
+        task: AssistTask,
+        target: &str,
+    ) -> Result<(Value, Option<String>), BuilderError> {
+        let kind = task.target_kind();
+        let record = self.kernel().content().get(kind, target).ok_or_else(|| {
+            BuilderError::Missing(format!("{} '{}' not found", kind.record_kind(), target))
+        })?;
+        let room = match kind {
+            ContentKind::Npc => record["room"].as_str().map(str::to_string),
+            _ => Some(target.to_string()),
+        };
+        Ok((record, room))
+    }
+
+    /// Turn a provider reply into a pending proposal. The reply must pass the guardrails, and
+    /// every record is validated against the live World exactly as a manual edit would be.
+    pub fn record_proposal(
+        &mut self,
+        actor: Option<&Principal>,
+        task: AssistTask,
+        target: &str,
+        prompt: &str,
+        completion: Completion,
+    ) -> Result<Proposal, BuilderError> {
+        let (_, room) = self.assist_target(task, target)?;
+        let facts = world_facts(self.kernel().content(), room.as_deref());
+        let text = self
+            .guardrails
+            .check(&completion.text, &facts)
+            .map_err(|violations| {
+                report_rejection(&self.observability, "assist", &violations);
+                BuilderError::Rejected(violations.iter().map(ToString::to_string).collect())
+            })?;
+        let records = self.proposed_records(task, target, &text)?;
+        let content = self.kernel().content();
+        let errors: Vec<String> = records
+            .iter()
+            .filter_map(|record| {
+                let kind = ContentKind::from_collection(&record.collection)?;
+                content.validate(kind, &record.after).err()
+            })
+            .map(|err| err.to_string())
+            .collect();
+        if !errors.is_empty() {
+            return Err(BuilderError::Rejected(errors));
+        }
+        self.proposal_seq += 1;
+        let proposal = Proposal {
+            id: format!("ap-{}", self.proposal_seq),
+            task,
+            target: target.to_string(),
+            prompt: prompt.to_string(),
+            author: author_name(actor),
+            status: ProposalStatus::Pending,
+            created_at: now_secs(),
+            provider: completion.provider,
+            model: completion.model,
+            text,
+            records,
+        };
+        self.audit(
+            actor,
+            "builder.assist.draft",
+            &proposal.audit_target(),
+            json!({
+                "task": task,
+                "target": target,
+                "provider": proposal.provider,
+                "model": proposal.model,
+            }),
+        );
+        self.proposals.insert(proposal.id.clone(), proposal.clone());
+        while self.proposals.len() > MAX_PENDING_PROPOSALS {
+            let oldest = self
+                .proposals
+                .values()
+                .min_by_key(|proposal| proposal.seq())
+                .map(|proposal| proposal.id.clone())
+                .expect("over the cap, so not empty");
+            self.proposals.remove(&oldest);
+        }
+        self.flush_all()?;
+        Ok(proposal)
+    }
+
+    /// Stage a pending proposal's records into `changeset_id`, or into a new changeset when
+    /// none is given. All records are checked against the changeset's staged view first, so a
+    /// proposal is staged completely or not at all. The accepted proposal is dropped; its
+    /// records live on in the changeset.
+    pub fn accept_proposal(
+        &mut self,
+        actor: Option<&Principal>,
+        proposal_id: &str,
+        changeset_id: Option<&str>,
+    ) -> Result<Changeset, BuilderError> {
+        let proposal = self.proposal(proposal_id)?.clone();
+        let mut view = match changeset_id {
+            Some(id) => self.staged_content(self.changeset(id)?).0,
+            None => self.kernel().content().clone(),
+        };
+        let mut staged = Vec::new();
+        for record in &proposal.records {
+            let kind = ContentKind::from_collection(&record.collection).ok_or_else(|| {
+                BuilderError::BadRequest(format!("unknown collection '{}'", record.collection))
+            })?;
+            let (_, normalized) = view.validate(kind, &record.after)?;
+            view.insert_unchecked(kind, &normalized)?;
+            staged.push((kind, record));
+        }
+        let changeset_id = match changeset_id {
+            Some(id) => id.to_string(),
+            None => {
+                let title = format!(
+                    "AI draft: {} for {}",
+                    proposal.task.as_str(),
+                    proposal.target
+                );
+                self.create_changeset(actor, &title)?.id
+            }
+        };
+        for (kind, record) in staged {
+            self.stage_change(
+                actor,
+                &changeset_id,
+                kind,
+                &record.id,
+                Some(record.after.clone()),
+            )?;
+        }
+        self.audit(
+            actor,
+            "builder.assist.accept",
+            &proposal.audit_target(),
+            json!({ "changeset": changeset_id }),
+        );
+        self.proposals.remove(&proposal.id);
+        self.flush_all()?;
+        Ok(self.changeset(&changeset_id)?.clone())
+    }
+
+    /// Drop a pending proposal, returning it marked rejected.
+    pub fn reject_proposal(
+        &mut self,
+        actor: Option<&Principal>,
+        proposal_id: &str,
+    ) -> Result<Proposal, BuilderError> {
+        let mut proposal = self.proposal(proposal_id)?.clone();
+        proposal.status = ProposalStatus::Rejected;
+        self.audit(
+            actor,
+            "builder.assist.reject",
+            &proposal.audit_target(),
+            Value::Null,
+        );
+        self.proposals.remove(&proposal.id);
+        self.flush_all()?;
+        Ok(proposal)
+    }
+
+    fn proposed_records(
+        &self,
+        task: AssistTask,
+        target: &str,
+        text: &str,
+    ) -> Result<Vec<ProposedRecord>, BuilderError> {
+        let content = self.kernel().content();
+        let kind = task.target_kind();
+        let mut record = content.get(kind, target).ok_or_else(|| {
+            BuilderError::Missing(format!("{} '{}' not found", kind.record_kind(), target))
+        })?;
+        let lines: Vec<&str> = text
+            .lines()
+            .map(|line| line.trim().trim_start_matches(['-', '*', '•']).trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if lines.is_empty() {
+            return Err(BuilderError::Rejected(vec![
+                "the AI provider returned no usable text".into(),
+            ]));
+        }
+        let records = match task {
+            AssistTask::RoomDescription => {
+                record["description"] = Value::String(lines.join(" "));
+                vec![(ContentKind::Room, target.to_string(), record)]
+            }
+            AssistTask::NpcDialogue => {
+                let mut dialogue = record["dialogue"].as_array().cloned().unwrap_or_default();
+                dialogue.extend(lines.iter().map(|line| Value::String(line.to_string())));
+                record["dialogue"] = Value::Array(dialogue);
+                vec![(ContentKind::Npc, target.to_string(), record)]
+            }
+            AssistTask::ItemSet => {
+                let mut taken: BTreeSet<String> = BTreeSet::new();
+                lines
+                    .iter()
+                    .filter_map(|line| {
+                        let (name, description) = line.split_once(':').unwrap_or((line, ""));
+                        let name = name.trim().trim_matches(['*', '"']).trim();
+                        let base = slug(name);
+                        if base.is_empty() {
+                            return None;
+                        }
+                        let id = (1..)
+                            .map(|n| match n {
+                                1 => base.clone(),
+                                n => format!("{}-{}", base, n),
+                            })
+                            .find(|id| {
+                                !taken.contains(id) && !content.contains(ContentKind::Item, id)
+                            })?;
+                        taken.insert(id.clone());
+                        let item = json!({
+                            "id": id,
+                            "name": name,
+                            "description": description.trim(),
+                            "room": target,
+                        });
+                        Some((ContentKind::Item, id, item))
+                    })
+                    .collect()
+            }
+        };
+        Ok(records
+            .into_iter()
+            .map(|(kind, id, after)| ProposedRecord {
+                collection: kind.collection().to_string(),
+                id,
+                after,
+            })
+            .collect())
+    }
+}
+
+/// Lines describing a room's exits, items, and NPCs, skipping `exclude`.
+fn room_context(content: &WorldContent, room: &str, exclude: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(record) = content.room(room).filter(|_| room != exclude) {
+        lines.push(format!("room: {} — {}", record.name, record.description));
+    }
+    for exit in content.exits().filter(|exit| exit.from == room) {
+        let name = content
+            .room(&exit.to)
+            .map(|to| to.name.as_str())
+            .unwrap_or(&exit.to);
+        lines.push(format!("exit {} leads to {}", exit.direction, name));
+    }
+    for item in content
+        .items()
+        .filter(|item| item.room.as_deref() == Some(room))
+    {
+        lines.push(format!("item here: {}", item.name));
+    }
+    for npc in content
+        .npcs()
+        .filter(|npc| npc.room.as_deref() == Some(room) && npc.id != exclude)
+    {
+        lines.push(format!("character here: {}", npc.name));
+    }
+    lines
+}
+
+/// Lowercase ASCII id made of `[a-z0-9-]`, e.g. `Brass Key` → `brass-key`.
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').chars().take(48).collect()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// `POST /api/builder/assist/drafts` plus `/api/builder/assist/proposals[/{id}[/accept|reject]]`.
+pub struct AssistApi<B: StorageBackend> {
+    core: SharedCore<B>,
+    provider: Arc<dyn AiProvider>,
+}
+
+impl<B: StorageBackend> AssistApi<B> {
+    pub fn new(core: SharedCore<B>, provider: Arc<dyn AiProvider>) -> Self {
+        AssistApi { core, provider }
+    }
+
+    fn draft(&self, request: &HttpRequest) -> Result<HttpResponse, BuilderError> {
+        let body: DraftBody = match request.json() {
+            Ok(body) => body,
+            Err(response) => return Ok(response),
+        };
+        let (completion_request, budgets, observability) = {
+            let core = self.core.lock().expect("lock poisoning");
+            let completion_request = core.assist_request(body.task, &body.target, &body.prompt)?;
+            (
+                completion_request,
+                core.budgets().clone(),
+                core.observability().clone(),
+            )
+        };
+        let account = request
+            .principal
+            .as_ref()
+            .map(|principal| principal.username.as_str());
+        let completion = budgets
+            .metered(account, &completion_request, || {
+                self.provider.complete(&completion_request)
+            })
+            .inspect_err(|err| {
+                if let AiError::BudgetExhausted { scope, .. } = err {
+                    note_exhausted(&observability, "assist", scope);
+                }
+            })?;
+        let proposal = self.core.lock().expect("lock poisoning").record_proposal(
+            request.principal.as_ref(),
+            body.task,
+            &body.target,
+            &body.prompt,
+            completion,
+        )?;
+        Ok(HttpResponse::json(201, &proposal))
+    }
+
+    fn route(
+        &self,
+        request: &HttpRequest,
+        rest: &[&str],
+    ) -> Option<Result<HttpResponse, BuilderError>> {
+        let actor = request.principal.as_ref();
+        if let ("POST", ["drafts"]) = (request.method.as_str(), rest) {
+            return Some(self.draft(request));
+        }
+        let mut core = self.core.lock().expect("lock poisoning");
+        let result = match (request.method.as_str(), rest) {
+            ("GET", ["proposals"]) => {
+                let proposals: Vec<_> = core.proposals().cloned().collect();
+                Ok(HttpResponse::json(200, &json!({ "proposals": proposals })))
+            }
+            ("GET", ["proposals", id]) => core
+                .proposal(id)
+                .map(|proposal| HttpResponse::json(200, proposal)),
+            ("POST", ["proposals", id, "accept"]) => {
+                let body = if request.body.is_empty() {
+                    AcceptBody::default()
+                } else {
+                    match request.json::<AcceptBody>() {
+                        Ok(body) => body,
+                        Err(response) => return Some(Ok(response)),
+                    }
+                };
+                core.accept_proposal(actor, id, body.changeset.as_deref())
+                    .map(|changeset| HttpResponse::json(200, &changeset))
+            }
+            ("POST", ["proposals", id, "reject"]) => core
+                .reject_proposal(actor, id)
+                .map(|proposal| HttpResponse::json(200, &proposal)),
+            (_, ["drafts"] | ["proposals"] | ["proposals", _] | ["proposals", _, _]) => Ok(
+                HttpResponse::error(405, "method_not_allowed", "method not allowed"),
+            ),
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+impl<B: StorageBackend> HttpHandler for AssistApi<B> {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let segments = request.segments();
+        let ["api", "builder", "assist", rest @ ..] = segments.as_slice() else {
+            return None;
+        };
+        if let Err(rejection) = require_role(request, Role::Builder) {
+            return Some(rejection);
+        }
+        self.route(request, rest)
+            .map(|result| result.unwrap_or_else(|err| err.to_response()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{engine, DummyBackend, Harness};
+    use aqevia_ai::{AiResult, BudgetConfig, BudgetLimit, Capabilities, TemplateProvider};
+    use aqevia_auth::Role;
+
+    /// Signed in as a builder, with a `cellar` room and `provider` in place of the default.
+    fn harness(provider: Option<Arc<dyn AiProvider>>) -> Harness {
+        let mut engine = engine(DummyBackend::default());
+        if let Some(provider) = provider {
+            engine.set_ai_provider(provider);
+        }
+        let harness = Harness::with_engine(engine, "builder", Role::Builder);
+        harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"cellar","name":"Cellar","description":"Dark."}"#,
+        );
+        harness
+    }
+
+    fn live_room(harness: &Harness) -> Value {
+        harness.live(ContentKind::Room, "cellar").unwrap()
+    }
+
+    struct Failing;
+
+    impl AiProvider for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+
+        fn complete(&self, _request: &CompletionRequest) -> AiResult<Completion> {
+            Err(AiError::Timeout)
+        }
+    }
+
+    #[test]
+    fn drafts_become_proposals_until_accepted_into_a_changeset() {
+        let harness = harness(None);
+        let (status, proposal) = harness.call(
+            "POST",
+            "/api/builder/assist/drafts",
+            r#"{"task":"room_description","target":"cellar","prompt":"dripping water"}"#,
+        );
+        assert_eq!(status, 201);
+        assert_eq!(proposal["status"], "pending");
+        assert_eq!(proposal["provider"], "local");
+        let drafted = proposal["records"][0]["after"]["description"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(drafted.contains("dripping water"));
+        assert_eq!(live_room(&harness)["description"], "Dark.");
+
+        let (status, changeset) =
+            harness.call("POST", "/api/builder/assist/proposals/ap-1/accept", "");
+        assert_eq!(status, 200);
+        assert_eq!(changeset["changes"][0]["after"]["description"], drafted);
+        assert_eq!(live_room(&harness)["description"], "Dark.");
+        let (status, _) = harness.call("POST", "/api/builder/assist/proposals/ap-1/accept", "");
+        assert_eq!(status, 404);
+        assert_eq!(harness.engine.core().proposals().count(), 0);
+
+        let id = changeset["id"].as_str().unwrap();
+        harness.call(
+            "POST",
+            &format!("/api/builder/changesets/{}/publish", id),
+            "",
+        );
+        assert_eq!(live_room(&harness)["description"], drafted);
+    }
+
+    #[test]
+    fn item_sets_validate_and_stage_into_an_existing_changeset() {
+        let harness = harness(None);
+        harness.call("POST", "/api/builder/changesets", r#"{"title":"Stock"}"#);
+        let (status, proposal) = harness.call(
+            "POST",
+            "/api/builder/assist/drafts",
+            r#"{"task":"item_set","target":"cellar","prompt":"damp"}"#,
+        );
+        assert_eq!(status, 201);
+        let records = proposal["records"].as_array().unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(records
+            .iter()
+            .all(|record| record["collection"] == "items" && record["after"]["room"] == "cellar"));
+
+        let (status, changeset) = harness.call(
+            "POST",
+            "/api/builder/assist/proposals/ap-1/accept",
+            r#"{"changeset":"cs-1"}"#,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(changeset["id"], "cs-1");
+        assert_eq!(changeset["changes"].as_array().unwrap().len(), 3);
+
+        let (status, _) = harness.call(
+            "POST",
+            "/api/builder/assist/drafts",
+            r#"{"task":"npc_dialogue","target":"ghost"}"#,
+        );
+        assert_eq!(status, 404);
+        harness.call(
+            "POST",
+            "/api/builder/assist/drafts",
+            r#"{"task":"room_description","target":"cellar"}"#,
+        );
+        let (_, rejected) = harness.call("POST", "/api/builder/assist/proposals/ap-2/reject", "");
+        assert_eq!(rejected["status"], "rejected");
+        let (status, _) = harness.call("GET", "/api/builder/assist/proposals/ap-2", "");
+        assert_eq!(status, 404);
+        let (status, _) = harness.call("POST", "/api/builder/assist/proposals/ap-2/reject", "");
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn pending_proposals_are_capped_oldest_first() {
+        let harness = harness(None);
+        let draft = r#"{"task":"room_description","target":"cellar"}"#;
+        for _ in 0..MAX_PENDING_PROPOSALS + 2 {
+            let (status, _) = harness.call("POST", "/api/builder/assist/drafts", draft);
+            assert_eq!(status, 201);
+        }
+        let core = harness.engine.core();
+        assert_eq!(core.proposals().count(), MAX_PENDING_PROPOSALS);
+        assert!(core.proposal("ap-1").is_err());
+        assert!(core.proposal("ap-2").is_err());
+        assert!(core.proposal("ap-3").is_ok());
+        assert!(core
+            .proposal(&format!("ap-{}", MAX_PENDING_PROPOSALS + 2))
+            .is_ok());
+    }
+
+    #[test]
+    fn guardrail_rejections_are_reported_as_validation_failures() {
+        let provider = TemplateProvider::default().with_templates(
+            "room_description",
+            ["A door to the east is the only way out of {prompt}."],
+        );
+        let harness = harness(Some(Arc::new(provider)));
+        let (status, body) = harness.call(
+            "POST",
+            "/api/builder/assist/drafts",
+            r#"{"task":"room_description","target":"cellar"}"#,
+        );
+        assert_eq!(status, 422);
+        assert_eq!(
+            body["errors"][0],
+            "unknown_exit: mentions an exit 'east' that does not exist"
+        );
+        let core = harness.engine.core();
+        let labels = [("source", "assist"), ("reason", "unknown_exit")];
+        assert_eq!(
+            core.observability()
+                .metric(crate::REJECTIONS_METRIC, &labels),
+            1
+        );
+        assert_eq!(core.proposals().count(), 0);
+    }
+
+    #[test]
+    fn provider_failures_surface_without_side_effects() {
+        let harness = harness(Some(Arc::new(Failing)));
+        let (status, body) = harness.call(
+            "POST",
+            "/api/builder/assist/drafts",
+            r#"{"task":"room_description","target":"cellar"}"#,
+        );
+        assert_eq!((status, body["status"].as_str()), (504, Some("ai_timeout")));
+        assert_eq!(harness.engine.core().proposals().count(), 0);
+    }
+
+    #[test]
+    fn exhausted_budgets_fail_drafts_until_reset() {
+        let harness = harness(None);
+        harness.engine.set_ai_budgets(BudgetConfig {
+            account: BudgetLimit {
+                requests: Some(1),
+                tokens: None,
+            },
+            ..BudgetConfig::default()
+        });
+        let draft = r#"{"task":"room_description","target":"cellar"}"#;
+        let (status, _) = harness.call("POST", "/api/builder/assist/drafts", draft);
+        assert_eq!(status, 201);
+        let (status, body) = harness.call("POST", "/api/builder/assist/drafts", draft);
+        assert_eq!(
+            (status, body["status"].as_str()),
+            (429, Some("ai_budget_exhausted"))
+        );
+        let core = harness.engine.core();
+        let labels = [("source", "assist"), ("scope", "account")];
+        assert_eq!(
+            core.observability()
+                .metric(crate::BUDGET_EXHAUSTED_METRIC, &labels),
+            1
+        );
+        assert_eq!(core.proposals().count(), 1);
+        assert!(core.budgets().reset("account/builder"));
+        drop(core);
+        let (status, _) = harness.call("POST", "/api/builder/assist/drafts", draft);
+        assert_eq!(status, 201);
+    }
+}
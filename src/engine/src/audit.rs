@@ -0,0 +1,329 @@
+//! Audit trail of builder mutations and admin actions. Entries are append-only `audit.entry`
+//! records keyed by a zero-padded sequence number, so storage order matches the order actions
+//! happened in. Nothing rewrites an entry; the retention policy only deletes whole entries
+//! once they age out.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aqevia_auth::{Principal, Role};
+use aqevia_storage::{payload_hash, StorageBackend, StorageResult, WorldRecord};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{error, info, warn};
+
+use crate::EngineCore;
+
+/// Storage `kind` for audit entries.
+pub const AUDIT_KIND: &str = "audit.entry";
+
+/// Counter of audit entries whose `detail` was replaced by a summary to fit the record limits.
+pub const AUDIT_SUMMARIZED_METRIC: &str = "aqevia_audit_details_summarized_total";
+
+/// How long entries are kept when no retention is configured.
+pub const DEFAULT_AUDIT_RETENTION: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub at: u64,
+    pub actor: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    pub action: String,
+    /// What the action touched, e.g. `core.room/lobby`, `session:4`, or `account:alice`.
+    pub target: String,
+    /// [`content_hash`] of the target record before and after the action, when it changed one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub detail: Value,
+}
+
+impl AuditEntry {
+    pub fn to_record(&self, world_id: &str) -> WorldRecord {
+        let payload = serde_json::to_string(self).expect("audit entries serialize");
+        WorldRecord::new(world_id, AUDIT_KIND, format!("{:020}", self.seq), payload)
+    }
+}
+
+/// Stand-in for an audit detail too large to store: its size and hash, and the names of its
+/// fields when it is an object.
+fn summarize(detail: &Value) -> Value {
+    let fields: Vec<&String> = detail
+        .as_object()
+        .map(|object| object.keys().collect())
+        .unwrap_or_default();
+    json!({
+        "summarized": true,
+        "bytes": detail.to_string().len(),
+        "hash": content_hash(detail),
+        "fields": fields,
+    })
+}
+
+/// SHA-256 of a record's canonical JSON, hex encoded: the `payload_hash` storage keeps for it.
+/// `serde_json` orders object keys, so equal records hash equally regardless of the order
+/// fields were submitted in.
+pub fn content_hash(value: &Value) -> String {
+    payload_hash(&value.to_string())
+}
+
+/// Filters for `GET /api/admin/audit`. `target` matches exactly or as a `/`-separated prefix,
+/// so `core.room` selects every room entry.
+#[derive(Clone, Debug, Default)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub target: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub limit: usize,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        let target_matches = self.target.as_deref().is_none_or(|target| {
+            entry.target == target
+                || entry
+                    .target
+                    .strip_prefix(target)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        });
+        self.actor
+            .as_deref()
+            .is_none_or(|actor| entry.actor == actor)
+            && target_matches
+            && self.since.is_none_or(|since| entry.at >= since)
+            && self.until.is_none_or(|until| entry.at <= until)
+    }
+}
+
+impl<B: StorageBackend> EngineCore<B> {
+    /// Queue an audit entry for an action that did not change a content record.
+    pub fn audit(
+        &mut self,
+        actor: Option<&Principal>,
+        action: &str,
+        target: &str,
+        detail: Value,
+    ) -> AuditEntry {
+        let entry = self.audit_entry(actor, action, target, None, None, detail);
+        self.queue_audit(&entry);
+        entry
+    }
+
+    /// Queue an audit entry for a record change, hashing the record's old and new values.
+    pub fn audit_change(
+        &mut self,
+        actor: Option<&Principal>,
+        action: &str,
+        target: &str,
+        before: Option<&Value>,
+        after: Option<&Value>,
+    ) -> AuditEntry {
+        let entry = self.audit_entry(actor, action, target, before, after, Value::Null);
+        self.queue_audit(&entry);
+        entry
+    }
+
+    /// [`EngineCore::audit_entry`] keeps entries within the limits by summarizing their
+    /// detail, so this only fails when the limits are too small for the summary itself.
+    /// [`EngineCore::record`] has logged and counted the refusal by then; the action stands.
+    fn queue_audit(&mut self, entry: &AuditEntry) {
+        if self.record(entry.to_record(&self.world_id)).is_err() {
+            error!(seq = entry.seq, action = %entry.action, "audit entry dropped");
+        }
+    }
+
+    /// Build the next entry without queueing it, for callers that write it in their own batch.
+    /// A `detail` that would put the entry over the storage limits is replaced by a summary
+    /// of its size, hash, and top-level fields, which is logged and counted in
+    /// `aqevia_audit_details_summarized_total`.
+    pub(crate) fn audit_entry(
+        &mut self,
+        actor: Option<&Principal>,
+        action: &str,
+        target: &str,
+        before: Option<&Value>,
+        after: Option<&Value>,
+        detail: Value,
+    ) -> AuditEntry {
+        self.audit_seq += 1;
+        info!(
+            seq = self.audit_seq,
+            actor = actor.map_or("", |principal| principal.username.as_str()),
+            action,
+            resource = target,
+            "audit"
+        );
+        let mut entry = AuditEntry {
+            seq: self.audit_seq,
+            at: unix_now(),
+            actor: actor
+                .map(|principal| principal.username.clone())
+                .unwrap_or_default(),
+            role: actor.map(|principal| principal.role),
+            action: action.to_string(),
+            target: target.to_string(),
+            before_hash: before.map(content_hash),
+            after_hash: after.map(content_hash),
+            detail,
+        };
+        let payload = serde_json::to_string(&entry).expect("audit entries serialize");
+        if let Err(err) = self.storage.config().limits.check(&payload) {
+            warn!(seq = entry.seq, action, error = %err, "audit detail summarized");
+            self.observability.add_counter(
+                AUDIT_SUMMARIZED_METRIC,
+                "Audit entries whose detail was replaced by a summary to fit the record limits.",
+                &[],
+                1,
+            );
+            entry.detail = summarize(&entry.detail);
+        }
+        entry
+    }
+
+    /// Matching entries, newest first. Pending entries are flushed first so the result
+    /// includes every action that has already responded.
+    pub fn query_audit(&mut self, query: &AuditQuery) -> StorageResult<Vec<AuditEntry>> {
+        self.flush_all()?;
+        let mut entries: Vec<AuditEntry> = self
+            .storage
+            .load_records(AUDIT_KIND)?
+            .iter()
+            .rev()
+            .filter_map(|record| serde_json::from_str(&record.payload).ok())
+            .filter(|entry| query.matches(entry))
+            .take(query.limit)
+            .collect();
+        entries.sort_by(|a, b| b.seq.cmp(&a.seq));
+        Ok(entries)
+    }
+
+    /// Delete entries older than the retention window, returning how many were removed.
+    pub fn prune_audit(&mut self, now: SystemTime) -> StorageResult<usize> {
+        let Some(retention) = self.audit_retention else {
+            return Ok(0);
+        };
+        let cutoff = now
+            .checked_sub(retention)
+            .unwrap_or(UNIX_EPOCH)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let expired: Vec<WorldRecord> = self
+            .storage
+            .load_records(AUDIT_KIND)?
+            .into_iter()
+            .filter(|record| {
+                serde_json::from_str::<AuditEntry>(&record.payload)
+                    .is_ok_and(|entry| entry.at < cutoff)
+            })
+            .map(|record| WorldRecord::tombstone(&self.world_id, AUDIT_KIND, record.key))
+            .collect();
+        let pruned = expired.len();
+        if pruned > 0 {
+            self.storage.commit_batch(expired)?;
+        }
+        Ok(pruned)
+    }
+
+    /// Resume the sequence after the highest persisted entry.
+    pub(crate) fn load_audit_seq(&mut self) -> StorageResult<u64> {
+        self.audit_seq = self
+            .storage
+            .load_records(AUDIT_KIND)?
+            .last()
+            .and_then(|record| record.key.parse().ok())
+            .unwrap_or(0);
+        Ok(self.audit_seq)
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{auth_config, observability, DummyBackend};
+    use crate::Engine;
+    use aqevia_storage::{RecordLimits, StorageConfig};
+
+    fn entry(seq: u64, actor: &str, target: &str, at: u64) -> AuditEntry {
+        AuditEntry {
+            seq,
+            at,
+            actor: actor.into(),
+            role: None,
+            action: "test".into(),
+            target: target.into(),
+            before_hash: None,
+            after_hash: None,
+            detail: Value::Null,
+        }
+    }
+
+    #[test]
+    fn hashes_ignore_key_order() {
+        let a = json!({"id": "lobby", "name": "Lobby"});
+        let b: Value = serde_json::from_str(r#"{"name":"Lobby","id":"lobby"}"#).unwrap();
+        assert_eq!(content_hash(&a), content_hash(&b));
+        assert_eq!(content_hash(&a).len(), 64);
+        assert_ne!(content_hash(&a), content_hash(&json!({"id": "lobby"})));
+    }
+
+    #[test]
+    fn queries_filter_by_actor_target_prefix_and_time() {
+        let query = AuditQuery {
+            actor: Some("alice".into()),
+            target: Some("core.room".into()),
+            since: Some(10),
+            until: Some(20),
+            limit: 10,
+        };
+        assert!(query.matches(&entry(1, "alice", "core.room/lobby", 15)));
+        assert!(!query.matches(&entry(2, "bob", "core.room/lobby", 15)));
+        assert!(!query.matches(&entry(3, "alice", "core.roomy/x", 15)));
+        assert!(!query.matches(&entry(4, "alice", "core.room/lobby", 25)));
+        assert!(AuditQuery::default().matches(&entry(5, "", "session:1", 0)));
+    }
+
+    #[test]
+    fn oversized_details_are_summarized() {
+        let state = observability();
+        let config = StorageConfig {
+            limits: RecordLimits {
+                max_payload_bytes: 1024,
+                ..RecordLimits::default()
+            },
+            ..StorageConfig::default()
+        };
+        let engine = Engine::with_auth(
+            DummyBackend::default(),
+            config,
+            auth_config(),
+            state.clone(),
+        )
+        .unwrap();
+        let mut core = engine.core();
+        let detail = json!({ "message": "x".repeat(4096), "delivered": 3 });
+        let entry = core.audit(None, "admin.broadcast", "sessions", detail.clone());
+        assert_eq!(entry.detail["summarized"], true);
+        assert_eq!(entry.detail["hash"], content_hash(&detail));
+        assert_eq!(entry.detail["fields"], json!(["delivered", "message"]));
+        let query = AuditQuery {
+            limit: 10,
+            ..AuditQuery::default()
+        };
+        assert_eq!(core.query_audit(&query).unwrap(), vec![entry]);
+        assert!(state
+            .render_metrics()
+            .contains("aqevia_audit_details_summarized_total 1"));
+    }
+}
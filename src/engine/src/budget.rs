@@ -0,0 +1,77 @@
+//! Persistence and metrics for AI usage budgets. Usage lives in [`Budgets`]; the Engine
+//! restores it at boot and writes changed scopes back on the flush cadence.
+
+use std::time::SystemTime;
+
+use aqevia_ai::{Budgets, Usage, WORLD_SCOPE};
+use aqevia_storage::{StorageBackend, StorageResult, WorldRecord};
+use aqevia_transport::ObservabilityState;
+
+use crate::EngineCore;
+
+/// Storage `kind` for AI usage. The key is the budget scope (`world` or `account/<name>`).
+pub const AI_USAGE_KIND: &str = "ai.usage";
+
+/// Counter of AI calls skipped because a budget was exhausted, labelled by `source` and
+/// `scope` (`world` or `account`).
+pub const BUDGET_EXHAUSTED_METRIC: &str = "aqevia_ai_budget_exhausted_total";
+
+impl<B: StorageBackend> EngineCore<B> {
+    /// Request and token budgets shared by builder assists and narration.
+    pub fn budgets(&self) -> &std::sync::Arc<Budgets> {
+        &self.budgets
+    }
+
+    /// Restore persisted usage. Records that no longer decode are skipped.
+    pub(crate) fn load_ai_usage(&mut self) -> StorageResult<usize> {
+        let mut loaded = 0;
+        for record in self.storage.load_records(AI_USAGE_KIND)? {
+            if let Ok(usage) = serde_json::from_str::<Usage>(&record.payload) {
+                self.budgets.restore(&record.key, usage);
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Queue usage changed since the last call for the next flush and publish the World's
+    /// usage in the current window as gauges.
+    pub(crate) fn sync_ai_usage(&mut self) {
+        for (scope, usage) in self.budgets.take_dirty() {
+            let payload = serde_json::to_string(&usage).expect("usage serializes");
+            let record = WorldRecord::new(&self.world_id, AI_USAGE_KIND, &scope, &payload);
+            // A usage record is a few numbers; `record` logs and counts a refusal.
+            let _ = self.record(record);
+        }
+        let world = self.budgets.usage(WORLD_SCOPE, SystemTime::now());
+        let labels = [("scope", WORLD_SCOPE)];
+        self.observability.set_gauge(
+            "aqevia_ai_budget_requests_used",
+            "AI requests charged in the current budget window.",
+            &labels,
+            world.requests,
+        );
+        self.observability.set_gauge(
+            "aqevia_ai_budget_tokens_used",
+            "Estimated AI tokens charged in the current budget window.",
+            &labels,
+            world.tokens,
+        );
+    }
+}
+
+/// Count an AI call from `source` (`assist` or `narration`) skipped because the budget of
+/// `scope` ran out.
+pub(crate) fn note_exhausted(observability: &ObservabilityState, source: &str, scope: &str) {
+    let scope = if scope == WORLD_SCOPE {
+        WORLD_SCOPE
+    } else {
+        "account"
+    };
+    observability.add_counter(
+        BUDGET_EXHAUSTED_METRIC,
+        "AI calls skipped because a usage budget was exhausted.",
+        &[("source", source), ("scope", scope)],
+        1,
+    );
+}
@@ -0,0 +1,616 @@
+//! Builder control-plane API: CRUD for rooms, exits, items, and NPC templates under
+//! `/api/builder/<collection>`. Writes are validated by the kernel, applied to the live
+//! World, and then persisted through the `StorageController`.
+
+use aqevia_ai::AiError;
+use aqevia_auth::{AuthError, Principal, Role};
+use aqevia_kernel::{ContentError, ContentKind, KindSchema, SchemaError};
+use aqevia_storage::{LimitError, StorageBackend, StorageError, WorldRecord};
+use aqevia_transport::auth::{auth_error_response, require_role};
+use aqevia_transport::{HttpHandler, HttpRequest, HttpResponse};
+use serde_json::{json, Map, Value};
+
+use crate::config::ConfigError;
+use crate::{content_hash, EngineCore, SharedCore};
+
+/// Failure of a content write.
+#[derive(thiserror::Error, Debug)]
+pub enum BuilderError {
+    #[error(transparent)]
+    Content(#[from] ContentError),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Missing(String),
+    #[error("{0}")]
+    Conflict(String),
+    /// Staged content that would leave the World inconsistent; one entry per problem.
+    #[error("changeset failed validation with {} error(s)", .0.len())]
+    Rejected(Vec<String>),
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error(transparent)]
+    Ai(#[from] AiError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+}
+
+impl BuilderError {
+    pub fn to_response(&self) -> HttpResponse {
+        let (status, code) = match self {
+            BuilderError::Content(ContentError::Schema(SchemaError::Invalid {
+                violations,
+                ..
+            })) => {
+                return HttpResponse::json(
+                    422,
+                    &json!({
+                        "status": "validation_failed",
+                        "message": self.to_string(),
+                        "errors": violations,
+                    }),
+                );
+            }
+            BuilderError::Content(ContentError::Invalid { .. } | ContentError::Schema(_)) => {
+                (422, "validation_failed")
+            }
+            BuilderError::Content(ContentError::NotFound { .. }) => (404, "missing"),
+            BuilderError::Content(
+                ContentError::AlreadyExists { .. } | ContentError::InUse { .. },
+            ) => (409, "conflict"),
+            BuilderError::BadRequest(_) => (400, "invalid_request"),
+            BuilderError::Missing(_) => (404, "missing"),
+            BuilderError::Conflict(_) => (409, "conflict"),
+            BuilderError::Rejected(errors) => {
+                return HttpResponse::json(
+                    422,
+                    &json!({
+                        "status": "validation_failed",
+                        "message": self.to_string(),
+                        "errors": errors,
+                    }),
+                );
+            }
+            BuilderError::Limit(LimitError::PayloadTooLarge { .. }) => (413, "payload_too_large"),
+            BuilderError::Limit(_) => (422, "limit_exceeded"),
+            BuilderError::Storage(StorageError::LimitExceeded(LimitError::PayloadTooLarge {
+                ..
+            })) => (413, "payload_too_large"),
+            BuilderError::Storage(StorageError::LimitExceeded(_)) => (422, "limit_exceeded"),
+            BuilderError::Storage(StorageError::Constraint { .. }) => (409, "storage_conflict"),
+            BuilderError::Storage(StorageError::Busy { .. }) => (503, "storage_busy"),
+            BuilderError::Storage(_) => (503, "storage_unavailable"),
+            BuilderError::Ai(AiError::Timeout) => (504, "ai_timeout"),
+            BuilderError::Ai(AiError::BudgetExhausted { .. }) => (429, "ai_budget_exhausted"),
+            BuilderError::Ai(_) => (502, "ai_unavailable"),
+            BuilderError::Config(ConfigError::RestartRequired(_)) => (409, "restart_required"),
+            BuilderError::Config(ConfigError::Unavailable) => (409, "reload_unavailable"),
+            BuilderError::Config(_) => (422, "invalid_config"),
+            BuilderError::Auth(err) => return auth_error_response(err),
+        };
+        HttpResponse::error(status, code, self.to_string())
+    }
+}
+
+impl<B: StorageBackend> EngineCore<B> {
+    /// Validate and apply a content record, then persist it along with its audit entry.
+    /// Returns the normalized record.
+    ///
+    /// If the flush fails the kernel keeps the change and the record stays queued, so the
+    /// regular flush cadence retries it.
+    pub fn put_content(
+        &mut self,
+        actor: Option<&Principal>,
+        kind: ContentKind,
+        payload: &Value,
+    ) -> Result<Value, BuilderError> {
+        let id = payload
+            .get("id")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        self.check_limits(payload)?;
+        self.kernel().check_schema(kind, payload)?;
+        let before = self.kernel().content().get(kind, id);
+        let normalized = self.kernel_mut().content_mut().upsert(kind, payload)?;
+        let id = normalized["id"].as_str().unwrap_or_default().to_string();
+        let action = if before.is_some() {
+            "builder.update"
+        } else {
+            "builder.create"
+        };
+        let target = format!("{}/{}", kind.record_kind(), id);
+        let record = WorldRecord::new(
+            self.world_id.clone(),
+            kind.record_kind(),
+            id,
+            normalized.to_string(),
+        );
+        self.record(record)?;
+        self.audit_change(actor, action, &target, before.as_ref(), Some(&normalized));
+        self.flush_all()?;
+        Ok(normalized)
+    }
+
+    /// Remove an unreferenced content record and persist the deletion and its audit entry.
+    pub fn delete_content(
+        &mut self,
+        actor: Option<&Principal>,
+        kind: ContentKind,
+        id: &str,
+    ) -> Result<Value, BuilderError> {
+        let removed = self.kernel_mut().content_mut().remove(kind, id)?;
+        let tombstone = WorldRecord::tombstone(self.world_id.clone(), kind.record_kind(), id);
+        self.record(tombstone)?;
+        let target = format!("{}/{}", kind.record_kind(), id);
+        self.audit_change(actor, "builder.delete", &target, Some(&removed), None);
+        self.flush_all()?;
+        Ok(removed)
+    }
+}
+
+/// Apply an RFC 7396 JSON Merge Patch to `target`.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().expect("target is an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+pub struct BuilderApi<B: StorageBackend> {
+    core: SharedCore<B>,
+}
+
+impl<B: StorageBackend> BuilderApi<B> {
+    pub fn new(core: SharedCore<B>) -> Self {
+        BuilderApi { core }
+    }
+
+    /// Every registered record schema, with the collection that edits each content kind, so
+    /// the SPA can render its forms.
+    fn schemas(&self) -> HttpResponse {
+        let core = self.core.lock().expect("lock poisoning");
+        let schemas: Vec<Value> = core.kernel().schemas().list().map(schema_view).collect();
+        HttpResponse::json(200, &json!({ "schemas": schemas }))
+    }
+
+    fn schema(&self, kind: &str) -> Result<HttpResponse, BuilderError> {
+        let core = self.core.lock().expect("lock poisoning");
+        let schema = core
+            .kernel()
+            .schemas()
+            .get(kind)
+            .ok_or_else(|| BuilderError::Missing(format!("no schema for kind '{}'", kind)))?;
+        Ok(HttpResponse::json(200, &schema_view(schema)))
+    }
+
+    /// Every record of `kind`, with each record's content hash by id so sync tools can compare
+    /// without diffing bodies.
+    fn list(&self, kind: ContentKind) -> HttpResponse {
+        let core = self.core.lock().expect("lock poisoning");
+        let records = core.kernel().content().list(kind);
+        let hashes: Map<String, Value> = records
+            .iter()
+            .map(|record| {
+                let id = record["id"].as_str().unwrap_or_default().to_string();
+                (id, Value::String(content_hash(record)))
+            })
+            .collect();
+        HttpResponse::json(200, &json!({ "records": records, "hashes": hashes }))
+    }
+
+    /// One record, tagged with its content hash as a strong `ETag`; a matching `If-None-Match`
+    /// gets `304` without a body.
+    fn get(&self, kind: ContentKind, id: &str, request: &HttpRequest) -> HttpResponse {
+        let core = self.core.lock().expect("lock poisoning");
+        let Some(record) = core.kernel().content().get(kind, id) else {
+            return not_found(kind, id).to_response();
+        };
+        let etag = format!("\"{}\"", content_hash(&record));
+        let unchanged = request.header("if-none-match").is_some_and(|tags| {
+            tags.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+        let response = if unchanged {
+            HttpResponse::new(304, "application/json", Vec::new())
+        } else {
+            HttpResponse::json(200, &record)
+        };
+        response.with_header("ETag", &etag)
+    }
+
+    fn create(
+        &self,
+        kind: ContentKind,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, BuilderError> {
+        let payload: Value = request
+            .json()
+            .map_err(|_| BuilderError::BadRequest("body must be a JSON object".into()))?;
+        let id = payload
+            .get("id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| BuilderError::BadRequest("body must include a string 'id'".into()))?;
+        let mut core = self.core.lock().expect("lock poisoning");
+        if core.kernel().content().contains(kind, id) {
+            return Err(ContentError::AlreadyExists {
+                kind: kind.record_kind(),
+                id: id.to_string(),
+            }
+            .into());
+        }
+        let created = core.put_content(request.principal.as_ref(), kind, &payload)?;
+        Ok(HttpResponse::json(201, &created))
+    }
+
+    fn patch(
+        &self,
+        kind: ContentKind,
+        id: &str,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, BuilderError> {
+        let patch: Value = request.json().map_err(|_| {
+            BuilderError::BadRequest("body must be a JSON Merge Patch document".into())
+        })?;
+        let mut core = self.core.lock().expect("lock poisoning");
+        let mut record = core
+            .kernel()
+            .content()
+            .get(kind, id)
+            .ok_or_else(|| not_found(kind, id))?;
+        merge_patch(&mut record, &patch);
+        if record.get("id").and_then(Value::as_str) != Some(id) {
+            return Err(BuilderError::BadRequest(
+                "'id' cannot be changed by a patch".into(),
+            ));
+        }
+        let updated = core.put_content(request.principal.as_ref(), kind, &record)?;
+        Ok(HttpResponse::json(200, &updated))
+    }
+
+    fn delete(
+        &self,
+        kind: ContentKind,
+        id: &str,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, BuilderError> {
+        let mut core = self.core.lock().expect("lock poisoning");
+        core.delete_content(request.principal.as_ref(), kind, id)?;
+        Ok(HttpResponse::json(
+            200,
+            &json!({ "status": "deleted", "id": id }),
+        ))
+    }
+}
+
+impl<B: StorageBackend> HttpHandler for BuilderApi<B> {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let segments = request.segments();
+        let ["api", "builder", collection, rest @ ..] = segments.as_slice() else {
+            return None;
+        };
+        if let Err(rejection) = require_role(request, Role::Builder) {
+            return Some(rejection);
+        }
+        if *collection == "schemas" {
+            let result = match (request.method.as_str(), rest) {
+                ("GET", []) => Ok(self.schemas()),
+                ("GET", [kind]) => self.schema(kind),
+                (_, [] | [_]) => Ok(HttpResponse::error(
+                    405,
+                    "method_not_allowed",
+                    "method not allowed",
+                )),
+                _ => return None,
+            };
+            return Some(result.unwrap_or_else(|err| err.to_response()));
+        }
+        let kind = ContentKind::from_collection(collection)?;
+        let result = match (request.method.as_str(), rest) {
+            ("GET", []) => Ok(self.list(kind)),
+            ("POST", []) => self.create(kind, request),
+            ("GET", [id]) => Ok(self.get(kind, id, request)),
+            ("PATCH", [id]) => self.patch(kind, id, request),
+            ("DELETE", [id]) => self.delete(kind, id, request),
+            (_, [] | [_]) => Ok(HttpResponse::error(
+                405,
+                "method_not_allowed",
+                "method not allowed",
+            )),
+            _ => return None,
+        };
+        Some(result.unwrap_or_else(|err| err.to_response()))
+    }
+}
+
+fn not_found(kind: ContentKind, id: &str) -> BuilderError {
+    ContentError::NotFound {
+        kind: kind.record_kind(),
+        id: id.to_string(),
+    }
+    .into()
+}
+
+/// A schema as the Builder API lists it; `collection` is `null` for kinds without one.
+fn schema_view(schema: &KindSchema) -> Value {
+    json!({
+        "kind": schema.kind,
+        "version": schema.version,
+        "collection": ContentKind::from_record_kind(&schema.kind).map(ContentKind::collection),
+        "schema": schema.schema,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{DummyBackend, Harness};
+    use aqevia_auth::Role;
+
+    #[test]
+    fn merge_patch_follows_rfc_7396() {
+        let mut target = json!({"a": "b", "c": {"d": "e", "f": "g"}});
+        merge_patch(&mut target, &json!({"a": "z", "c": {"f": null}}));
+        assert_eq!(target, json!({"a": "z", "c": {"d": "e"}}));
+        let mut scalar = json!([1, 2]);
+        merge_patch(&mut scalar, &json!({"a": 1}));
+        assert_eq!(scalar, json!({"a": 1}));
+    }
+
+    #[test]
+    fn rooms_crud_round_trip() {
+        let harness = Harness::builder(DummyBackend::default());
+        let (status, _) = harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"lobby","name":"Lobby"}"#,
+        );
+        assert_eq!(status, 201);
+        let (status, body) = harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"lobby","name":"Again"}"#,
+        );
+        assert_eq!((status, body["status"].as_str()), (409, Some("conflict")));
+
+        let (status, body) = harness.call(
+            "PATCH",
+            "/api/builder/rooms/lobby",
+            r#"{"description":"A bright entry hall."}"#,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["description"], "A bright entry hall.");
+        assert_eq!(body["name"], "Lobby");
+
+        let (status, body) = harness.call("GET", "/api/builder/rooms", "");
+        assert_eq!(status, 200);
+        assert_eq!(body["records"].as_array().unwrap().len(), 1);
+        let hash = body["hashes"]["lobby"].as_str().unwrap().to_string();
+        assert_eq!(hash, crate::content_hash(&body["records"][0]));
+        let etag = format!("\"{}\"", hash);
+        let request = HttpRequest::new("GET", "/api/builder/rooms/lobby")
+            .with_header("Authorization", &harness.bearer);
+        let fresh = harness.plane.dispatch(request.clone());
+        assert_eq!(
+            (fresh.status, fresh.header("etag")),
+            (200, Some(etag.as_str()))
+        );
+        let cached = harness
+            .plane
+            .dispatch(request.with_header("If-None-Match", &etag));
+        assert_eq!((cached.status, cached.body.len()), (304, 0));
+
+        let (status, _) = harness.call("PATCH", "/api/builder/rooms/lobby", r#"{"id":"other"}"#);
+        assert_eq!(status, 400);
+        let (status, _) = harness.call("DELETE", "/api/builder/rooms/lobby", "");
+        assert_eq!(status, 200);
+        let (status, _) = harness.call("GET", "/api/builder/rooms/lobby", "");
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn payloads_over_the_limits_are_refused_before_the_world_changes() {
+        let harness = Harness::builder(DummyBackend::default());
+        let long = format!(
+            r#"{{"id":"hall","name":"Hall","description":"{}"}}"#,
+            "x".repeat(300 * 1024)
+        );
+        let (status, body) = harness.call("POST", "/api/builder/rooms", &long);
+        assert_eq!(
+            (status, body["status"].as_str()),
+            (413, Some("payload_too_large"))
+        );
+        let deep = format!(
+            r#"{{"id":"hall","name":"Hall","extra":{}1{}}}"#,
+            "[".repeat(40),
+            "]".repeat(40)
+        );
+        let (status, body) = harness.call("POST", "/api/builder/rooms", &deep);
+        assert_eq!(
+            (status, body["status"].as_str()),
+            (422, Some("limit_exceeded"))
+        );
+        let (status, _) = harness.call("GET", "/api/builder/rooms/hall", "");
+        assert_eq!(status, 404);
+        let state = harness.engine.core().observability().clone();
+        for limit in ["payload_bytes", "depth"] {
+            assert_eq!(
+                state.metric(
+                    crate::LIMIT_REJECTIONS_METRIC,
+                    &[("limit", limit), ("source", "control_plane")]
+                ),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn mutations_are_audited_with_record_hashes() {
+        let harness = Harness::builder(DummyBackend::default());
+        harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"lobby","name":"Lobby"}"#,
+        );
+        let (_, updated) = harness.call("PATCH", "/api/builder/rooms/lobby", r#"{"name":"Hall"}"#);
+        harness.call("DELETE", "/api/builder/rooms/lobby", "");
+        let entries = harness
+            .engine
+            .core()
+            .query_audit(&crate::AuditQuery {
+                target: Some("core.room/lobby".into()),
+                limit: 10,
+                ..Default::default()
+            })
+            .unwrap();
+        let actions: Vec<&str> = entries.iter().map(|entry| entry.action.as_str()).collect();
+        assert_eq!(
+            actions,
+            vec!["builder.delete", "builder.update", "builder.create"]
+        );
+        let (deleted, update, create) = (&entries[0], &entries[1], &entries[2]);
+        assert_eq!(update.actor, "builder");
+        assert_eq!(update.role, Some(Role::Builder));
+        assert_eq!(create.before_hash, None);
+        assert_eq!(update.before_hash, create.after_hash);
+        assert_eq!(
+            update.after_hash.as_deref(),
+            Some(crate::content_hash(&updated).as_str())
+        );
+        assert_eq!(deleted.before_hash, update.after_hash);
+        assert_eq!(deleted.after_hash, None);
+    }
+
+    #[test]
+    fn invalid_references_are_rejected_and_nothing_persists() {
+        let harness = Harness::builder(DummyBackend::default());
+        harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"lobby","name":"Lobby"}"#,
+        );
+        let (status, body) = harness.call(
+            "POST",
+            "/api/builder/exits",
+            r#"{"id":"out","from":"lobby","to":"nowhere","direction":"north"}"#,
+        );
+        assert_eq!(
+            (status, body["status"].as_str()),
+            (422, Some("validation_failed"))
+        );
+        let core = harness.engine.core();
+        assert!(core.storage().load_records("core.exit").unwrap().is_empty());
+        drop(core);
+        let (status, _) = harness.call(
+            "POST",
+            "/api/builder/npcs",
+            r#"{"id":"guard","name":"Guard","room":"lobby"}"#,
+        );
+        assert_eq!(status, 201);
+        let (status, body) = harness.call("DELETE", "/api/builder/rooms/lobby", "");
+        assert_eq!((status, body["status"].as_str()), (409, Some("conflict")));
+    }
+
+    #[test]
+    fn persisted_content_reloads_into_the_kernel() {
+        let harness = Harness::builder(DummyBackend::default());
+        harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"lobby","name":"Lobby"}"#,
+        );
+        harness.call(
+            "POST",
+            "/api/builder/items",
+            r#"{"id":"lamp","name":"Lamp","room":"lobby"}"#,
+        );
+        let mut records = Vec::new();
+        for kind in ["auth.account", "core.room", "core.item"] {
+            records.extend(harness.engine.core().storage().load_records(kind).unwrap());
+        }
+        let reloaded = Harness::builder(DummyBackend::with_records(records));
+        let (status, body) = reloaded.call("GET", "/api/builder/items/lamp", "");
+        assert_eq!(status, 200);
+        assert_eq!(body["room"], "lobby");
+    }
+
+    #[test]
+    fn schemas_are_listed_and_checked_on_write_and_load() {
+        let attic = json!({"id": "attic", "name": 7});
+        let harness = Harness::builder(DummyBackend::with_records(vec![WorldRecord::new(
+            "world",
+            "core.room",
+            "attic",
+            attic.to_string(),
+        )]));
+        let (status, _) = harness.call("GET", "/api/builder/rooms/attic", "");
+        assert_eq!(status, 404);
+
+        let (status, body) = harness.call("GET", "/api/builder/schemas", "");
+        assert_eq!(status, 200);
+        let listed: Vec<(&str, &str)> = body["schemas"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|schema| {
+                let kind = schema["kind"].as_str().unwrap();
+                (kind, schema["collection"].as_str().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("core.exit", "exits"),
+                ("core.item", "items"),
+                ("core.npc", "npcs"),
+                ("core.room", "rooms")
+            ]
+        );
+        let (status, body) = harness.call("GET", "/api/builder/schemas/core.room", "");
+        assert_eq!((status, body["version"].as_u64()), (200, Some(1)));
+        assert_eq!(body["schema"]["required"], json!(["id", "name"]));
+        let (status, _) = harness.call("GET", "/api/builder/schemas/core.nope", "");
+        assert_eq!(status, 404);
+
+        let (status, body) = harness.call("POST", "/api/builder/rooms", &attic.to_string());
+        assert_eq!(
+            (status, body["status"].as_str()),
+            (422, Some("validation_failed"))
+        );
+        assert_eq!(
+            body["errors"],
+            json!([{"path": "/name", "message": "expected string"}])
+        );
+        let (status, body) = harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"attic","name":"Attic","area":"north"}"#,
+        );
+        assert_eq!((status, body["area"].as_str()), (201, Some("north")));
+    }
+
+    #[test]
+    fn builder_routes_require_builder_role() {
+        let harness = Harness::builder(DummyBackend::default());
+        let anonymous = harness
+            .plane
+            .dispatch(HttpRequest::new("GET", "/api/builder/rooms"));
+        assert_eq!(anonymous.status, 401);
+        let (status, _) = harness.call("GET", "/api/builder/spells", "");
+        assert_eq!(status, 404);
+    }
+}
@@ -0,0 +1,474 @@
+//! World bundles: authored content as a directory of human-editable files, one per content
+//! collection (`rooms.json`, `exits.yaml`, ...), for version control and seeding new
+//! deployments.
+//!
+//! Each file holds a list of records. Imports go through the same kernel validation as
+//! builder writes and are committed in one storage transaction, or not at all. Exports are
+//! canonical JSON (sorted keys, two-space indent, records ordered by id, trailing newline),
+//! so exporting a World that was imported from an export reproduces it byte for byte.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use aqevia_auth::Principal;
+use aqevia_kernel::{ContentKind, WorldContent};
+use aqevia_storage::{StorageBackend, WorldRecord};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::builder::BuilderError;
+use crate::EngineCore;
+
+/// Extensions read for each collection. Exports always write `.json`.
+const EXTENSIONS: [&str; 3] = ["json", "yaml", "yml"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum BundleError {
+    #[error("{path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{file}: {message}")]
+    Parse { file: String, message: String },
+    #[error("{collection} is defined by more than one file ({first}, {second})")]
+    Ambiguous {
+        collection: &'static str,
+        first: String,
+        second: String,
+    },
+}
+
+impl BundleError {
+    fn io(path: &Path, source: io::Error) -> Self {
+        BundleError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+struct Collection {
+    file: String,
+    records: Vec<Value>,
+}
+
+/// Content records grouped by collection, each remembering the file it came from so import
+/// errors can point at it.
+#[derive(Default)]
+pub struct WorldBundle {
+    collections: BTreeMap<ContentKind, Collection>,
+}
+
+impl WorldBundle {
+    /// Every record in `content`, ordered by id.
+    pub fn from_content(content: &WorldContent) -> Self {
+        let collections = ContentKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let collection = Collection {
+                    file: format!("{}.json", kind.collection()),
+                    records: content.list(kind),
+                };
+                (kind, collection)
+            })
+            .collect();
+        WorldBundle { collections }
+    }
+
+    /// Read `<collection>.json`, `.yaml`, or `.yml` files from `dir`. Other files are
+    /// ignored, and a missing collection counts as empty.
+    pub fn read_dir(dir: &Path) -> Result<Self, BundleError> {
+        let mut bundle = WorldBundle::default();
+        for kind in ContentKind::ALL {
+            for extension in EXTENSIONS {
+                let file = format!("{}.{}", kind.collection(), extension);
+                let path = dir.join(&file);
+                let text = match fs::read_to_string(&path) {
+                    Ok(text) => text,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    Err(err) => return Err(BundleError::io(&path, err)),
+                };
+                let records = parse(&file, extension, &text)?;
+                if let Some(existing) = bundle.collections.get(&kind) {
+                    return Err(BundleError::Ambiguous {
+                        collection: kind.collection(),
+                        first: existing.file.clone(),
+                        second: file,
+                    });
+                }
+                bundle
+                    .collections
+                    .insert(kind, Collection { file, records });
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// A bundle sent as one JSON object keyed by collection, e.g.
+    /// `{"rooms": [...], "exits": [...]}`.
+    pub fn from_json(value: &Value) -> Result<Self, BundleError> {
+        let invalid = |message: String| BundleError::Parse {
+            file: "bundle".into(),
+            message,
+        };
+        let Value::Object(map) = value else {
+            return Err(invalid("expected an object keyed by collection".into()));
+        };
+        let mut bundle = WorldBundle::default();
+        for (name, records) in map {
+            let kind = ContentKind::from_collection(name)
+                .ok_or_else(|| invalid(format!("unknown collection '{}'", name)))?;
+            let Value::Array(records) = records else {
+                return Err(invalid(format!("'{}' must be a list of records", name)));
+            };
+            bundle.collections.insert(
+                kind,
+                Collection {
+                    file: name.clone(),
+                    records: records.clone(),
+                },
+            );
+        }
+        Ok(bundle)
+    }
+
+    /// The bundle as one JSON object keyed by collection.
+    pub fn to_json(&self) -> Value {
+        Value::Object(
+            self.collections
+                .iter()
+                .map(|(kind, collection)| {
+                    (
+                        kind.collection().to_string(),
+                        Value::Array(collection.records.clone()),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Write every collection to `dir` as `<collection>.json` in canonical form, creating the
+    /// directory if needed. Returns the files written. Refuses to write next to a YAML file
+    /// for the same collection, which would make the directory ambiguous to read back.
+    pub fn write_dir(&self, dir: &Path) -> Result<Vec<PathBuf>, BundleError> {
+        fs::create_dir_all(dir).map_err(|err| BundleError::io(dir, err))?;
+        for kind in self.collections.keys() {
+            for extension in &EXTENSIONS[1..] {
+                let file = format!("{}.{}", kind.collection(), extension);
+                if dir.join(&file).exists() {
+                    return Err(BundleError::Ambiguous {
+                        collection: kind.collection(),
+                        first: file,
+                        second: format!("{}.json", kind.collection()),
+                    });
+                }
+            }
+        }
+        let mut written = Vec::new();
+        for (kind, collection) in &self.collections {
+            let path = dir.join(format!("{}.json", kind.collection()));
+            fs::write(&path, canonical_json(&collection.records))
+                .map_err(|err| BundleError::io(&path, err))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /// Number of records across all collections.
+    pub fn len(&self) -> usize {
+        self.collections
+            .values()
+            .map(|collection| collection.records.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records in dependency order (rooms before the exits, items, and NPCs that reference
+    /// them), with their file and position.
+    fn records(&self) -> impl Iterator<Item = (ContentKind, &str, usize, &Value)> {
+        self.collections.iter().flat_map(|(kind, collection)| {
+            collection
+                .records
+                .iter()
+                .enumerate()
+                .map(move |(index, record)| (*kind, collection.file.as_str(), index, record))
+        })
+    }
+}
+
+/// A list of records as canonical JSON: object keys sorted, two-space indent, and a trailing
+/// newline.
+pub fn canonical_json(records: &[Value]) -> String {
+    let mut text = serde_json::to_string_pretty(records).expect("JSON values serialize");
+    text.push('\n');
+    text
+}
+
+fn parse(file: &str, extension: &str, text: &str) -> Result<Vec<Value>, BundleError> {
+    let parsed = if extension == "json" {
+        serde_json::from_str::<Vec<Value>>(text).map_err(|err| err.to_string())
+    } else if text.trim().is_empty() {
+        Ok(Vec::new())
+    } else {
+        serde_yaml_ng::from_str::<Vec<Value>>(text).map_err(|err| err.to_string())
+    };
+    parsed.map_err(|message| BundleError::Parse {
+        file: file.to_string(),
+        message,
+    })
+}
+
+/// Why one bundle record cannot be imported.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RecordError {
+    pub file: String,
+    /// Zero-based position of the record in its file.
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub message: String,
+}
+
+/// Outcome of an import. Nothing is written unless `applied` is true, which requires no
+/// errors and no dry run.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub errors: Vec<RecordError>,
+}
+
+impl<B: StorageBackend> EngineCore<B> {
+    /// The live World's content as a bundle.
+    pub fn export_bundle(&self) -> WorldBundle {
+        WorldBundle::from_content(self.kernel().content())
+    }
+
+    /// Validate every record of `bundle` against the live content with the bundle applied,
+    /// and, unless `dry_run` or any record fails, write the new and changed records with their
+    /// audit entries in one storage transaction before swapping them into the kernel. Records
+    /// the bundle does not mention are kept.
+    pub fn import_bundle(
+        &mut self,
+        actor: Option<&Principal>,
+        bundle: &WorldBundle,
+        dry_run: bool,
+    ) -> Result<ImportReport, BuilderError> {
+        let mut report = ImportReport {
+            dry_run,
+            ..ImportReport::default()
+        };
+        let mut staged = self.kernel().content().clone();
+        let mut seen = BTreeSet::new();
+        let mut inserted = Vec::new();
+        for (kind, file, index, record) in bundle.records() {
+            let id = record.get("id").and_then(Value::as_str).map(str::to_string);
+            let error = |message: String| RecordError {
+                file: file.to_string(),
+                index,
+                id: id.clone(),
+                message,
+            };
+            if let Err(err) = self.check_limits(record) {
+                report.errors.push(error(err.to_string()));
+                continue;
+            }
+            if let Err(err) = self.kernel().check_schema(kind, record) {
+                report.errors.push(error(err.to_string()));
+                continue;
+            }
+            if let Some(id) = &id {
+                if !seen.insert((kind, id.clone())) {
+                    report.errors.push(error(format!(
+                        "duplicate {} '{}' in bundle",
+                        kind.record_kind(),
+                        id
+                    )));
+                    continue;
+                }
+            }
+            match staged.insert_unchecked(kind, record) {
+                Ok(_) => inserted.push((kind, file, index, record)),
+                Err(err) => report.errors.push(error(err.to_string())),
+            }
+        }
+        let mut changes = Vec::new();
+        for (kind, file, index, record) in inserted {
+            match staged.validate(kind, record) {
+                Ok((id, normalized)) => {
+                    let before = self.kernel().content().get(kind, &id);
+                    match &before {
+                        None => report.created += 1,
+                        Some(live) if *live == normalized => {
+                            report.unchanged += 1;
+                            continue;
+                        }
+                        Some(_) => report.updated += 1,
+                    }
+                    changes.push((kind, id, before, normalized));
+                }
+                Err(err) => report.errors.push(RecordError {
+                    file: file.to_string(),
+                    index,
+                    id: record.get("id").and_then(Value::as_str).map(str::to_string),
+                    message: err.to_string(),
+                }),
+            }
+        }
+        if dry_run || !report.errors.is_empty() {
+            return Ok(report);
+        }
+        let audit_seq = self.audit_seq;
+        let mut records = Vec::with_capacity(changes.len() * 2 + 1);
+        for (kind, id, before, after) in &changes {
+            records.push(WorldRecord::new(
+                self.world_id.clone(),
+                kind.record_kind(),
+                id.clone(),
+                after.to_string(),
+            ));
+            let entry = self.audit_entry(
+                actor,
+                "builder.import",
+                &format!("{}/{}", kind.record_kind(), id),
+                before.as_ref(),
+                Some(after),
+                Value::Null,
+            );
+            records.push(entry.to_record(&self.world_id));
+        }
+        let summary = self.audit_entry(
+            actor,
+            "admin.world.import",
+            "world",
+            None,
+            None,
+            json!({
+                "created": report.created,
+                "updated": report.updated,
+                "unchanged": report.unchanged,
+            }),
+        );
+        records.push(summary.to_record(&self.world_id));
+        let result = self.storage.commit_batch(records);
+        self.note_write(&result);
+        if let Err(err) = result {
+            self.audit_seq = audit_seq;
+            return Err(err.into());
+        }
+        *self.kernel_mut().content_mut() = staged;
+        report.applied = true;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::DummyBackend;
+    use crate::Engine;
+
+    fn engine() -> Engine<DummyBackend> {
+        crate::test_support::engine(DummyBackend::default())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("aqevia-bundle-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn yaml_bundles_import_and_export_round_trips_byte_for_byte() {
+        let source = temp_dir("source");
+        fs::write(
+            source.join("rooms.yaml"),
+            "- id: hall\n  name: Hall\n  description: Drafty.\n- name: Cellar\n  id: cellar\n",
+        )
+        .unwrap();
+        fs::write(
+            source.join("exits.json"),
+            r#"[{"direction":"down","from":"hall","id":"hall-down","to":"cellar"}]"#,
+        )
+        .unwrap();
+        fs::write(source.join("README.md"), "not content").unwrap();
+        let bundle = WorldBundle::read_dir(&source).unwrap();
+        assert_eq!(bundle.len(), 3);
+
+        let first = engine();
+        let report = first.core().import_bundle(None, &bundle, false).unwrap();
+        assert!(report.applied);
+        assert_eq!((report.created, report.updated), (3, 0));
+        let exported = temp_dir("first");
+        first.core().export_bundle().write_dir(&exported).unwrap();
+        let rooms = fs::read_to_string(exported.join("rooms.json")).unwrap();
+        assert!(rooms.starts_with("[\n  {\n    \"description\": \"\",\n    \"id\": \"cellar\""));
+
+        let second = engine();
+        let reimported = WorldBundle::read_dir(&exported).unwrap();
+        second
+            .core()
+            .import_bundle(None, &reimported, false)
+            .unwrap();
+        let again = temp_dir("second");
+        second.core().export_bundle().write_dir(&again).unwrap();
+        for kind in ContentKind::ALL {
+            let file = format!("{}.json", kind.collection());
+            assert_eq!(
+                fs::read(exported.join(&file)).unwrap(),
+                fs::read(again.join(&file)).unwrap(),
+                "{}",
+                file
+            );
+        }
+        let report = second
+            .core()
+            .import_bundle(None, &reimported, false)
+            .unwrap();
+        assert_eq!((report.created, report.unchanged), (0, 3));
+        for dir in [source, exported, again] {
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn invalid_records_are_reported_and_nothing_is_written() {
+        let engine = engine();
+        let bundle = WorldBundle::from_json(&json!({
+            "rooms": [{"id": "hall", "name": "Hall"}, {"id": "hall", "name": "Again"}],
+            "exits": [{"id": "north", "from": "hall", "to": "vault", "direction": "north"}],
+            "items": [{"id": "lamp"}],
+        }))
+        .unwrap();
+        let dry = engine.core().import_bundle(None, &bundle, true).unwrap();
+        let errors: Vec<_> = dry
+            .errors
+            .iter()
+            .map(|error| (error.file.as_str(), error.index))
+            .collect();
+        assert_eq!(errors, vec![("rooms", 1), ("items", 0), ("exits", 0)]);
+        assert!(dry.errors[2].message.contains("vault"), "{:?}", dry.errors);
+        assert!(!dry.applied);
+
+        let valid =
+            WorldBundle::from_json(&json!({"rooms": [{"id": "hall", "name": "Hall"}]})).unwrap();
+        let dry = engine.core().import_bundle(None, &valid, true).unwrap();
+        assert_eq!((dry.created, dry.applied), (1, false));
+        let core = engine.core();
+        assert!(core.kernel().content().room("hall").is_none());
+        assert!(core.storage().pending().is_empty());
+        assert!(WorldBundle::from_json(&json!({"spells": []})).is_err());
+    }
+}
@@ -0,0 +1,895 @@
+//! Draft changesets for builder edits under `/api/builder/changesets`.
+//!
+//! A changeset stages record edits without touching the live World. Builders preview the diff,
+//! validate it, and publish it atomically: every record plus the changeset itself is written in
+//! one storage transaction, and only then is the kernel's content swapped. Published changesets
+//! stay in history with the values they replaced, so a bad publish can be reverted.
+
+use std::collections::BTreeSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aqevia_auth::{Principal, Role};
+use aqevia_kernel::{ContentKind, WorldContent};
+use aqevia_storage::{StorageBackend, WorldRecord};
+use aqevia_transport::auth::require_role;
+use aqevia_transport::{HttpHandler, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::builder::{merge_patch, BuilderError};
+use crate::{content_hash, EngineCore, SharedCore};
+
+/// Storage `kind` for changeset records, keyed by changeset id.
+pub const CHANGESET_KIND: &str = "builder.changeset";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangesetStatus {
+    Open,
+    Published,
+    Discarded,
+}
+
+/// One staged edit. `after` is `None` for a deletion. `before` is filled in at publish time
+/// with the live value being replaced, which is what a revert restores.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StagedChange {
+    pub collection: String,
+    pub id: String,
+    pub after: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Changeset {
+    pub id: String,
+    pub title: String,
+    pub author: String,
+    pub status: ChangesetStatus,
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<u64>,
+    /// Id of the changeset this one reverted, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverts: Option<String>,
+    /// Id of the changeset that reverted this one, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverted_by: Option<String>,
+    pub changes: Vec<StagedChange>,
+}
+
+impl Changeset {
+    fn change(&self, kind: ContentKind, id: &str) -> Option<&StagedChange> {
+        self.changes
+            .iter()
+            .find(|change| change.collection == kind.collection() && change.id == id)
+    }
+
+    fn require_open(&self) -> Result<(), BuilderError> {
+        match self.status {
+            ChangesetStatus::Open => Ok(()),
+            status => Err(BuilderError::Conflict(format!(
+                "changeset '{}' is {:?}, not open",
+                self.id, status
+            ))),
+        }
+    }
+
+    /// Audit target naming this changeset, e.g. `builder.changeset/cs-1`.
+    fn target(&self) -> String {
+        format!("{}/{}", CHANGESET_KIND, self.id)
+    }
+}
+
+/// How a staged change differs from the live World.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DiffEntry {
+    pub collection: String,
+    pub id: String,
+    pub op: &'static str,
+    pub live: Option<Value>,
+    pub staged: Option<Value>,
+    /// [`content_hash`] of `live` and `staged`; equal hashes mean the change is a no-op.
+    pub live_hash: Option<String>,
+    pub staged_hash: Option<String>,
+}
+
+impl<B: StorageBackend> EngineCore<B> {
+    pub fn changesets(&self) -> impl Iterator<Item = &Changeset> {
+        self.changesets.values()
+    }
+
+    pub fn changeset(&self, id: &str) -> Result<&Changeset, BuilderError> {
+        self.changesets
+            .get(id)
+            .ok_or_else(|| BuilderError::Missing(format!("changeset '{}' not found", id)))
+    }
+
+    /// Open an empty changeset and persist it.
+    pub fn create_changeset(
+        &mut self,
+        actor: Option<&Principal>,
+        title: &str,
+    ) -> Result<Changeset, BuilderError> {
+        let changeset = Changeset {
+            id: self.next_changeset_id(),
+            title: title.to_string(),
+            author: author_name(actor),
+            status: ChangesetStatus::Open,
+            created_at: now_secs(),
+            published_at: None,
+            reverts: None,
+            reverted_by: None,
+            changes: Vec::new(),
+        };
+        self.audit(
+            actor,
+            "builder.changeset.create",
+            &changeset.target(),
+            json!({ "title": title }),
+        );
+        self.save_changeset(changeset.clone())?;
+        Ok(changeset)
+    }
+
+    /// Value of a record as the changeset would leave it.
+    pub fn staged_value(
+        &self,
+        changeset: &Changeset,
+        kind: ContentKind,
+        id: &str,
+    ) -> Option<Value> {
+        match changeset.change(kind, id) {
+            Some(change) => change.after.clone(),
+            None => self.kernel().content().get(kind, id),
+        }
+    }
+
+    /// Stage an upsert (`Some`) or deletion (`None`) of one record. The payload is checked
+    /// against the World as the rest of the changeset would leave it. Staging the live value
+    /// drops the change.
+    pub fn stage_change(
+        &mut self,
+        actor: Option<&Principal>,
+        changeset_id: &str,
+        kind: ContentKind,
+        id: &str,
+        after: Option<Value>,
+    ) -> Result<Changeset, BuilderError> {
+        let mut changeset = self.changeset(changeset_id)?.clone();
+        changeset.require_open()?;
+        let position = changeset
+            .changes
+            .iter()
+            .position(|change| change.collection == kind.collection() && change.id == id);
+        if let Some(position) = position {
+            changeset.changes.remove(position);
+        }
+        let (view, _) = self.staged_content(&changeset);
+        let staged = after.is_some();
+        let after = match after {
+            Some(payload) => {
+                self.check_limits(&payload)?;
+                self.kernel().check_schema(kind, &payload)?;
+                Some(view.validate(kind, &payload)?.1)
+            }
+            None if view.contains(kind, id) => None,
+            None => {
+                return Err(BuilderError::Missing(format!(
+                    "{} '{}' not found",
+                    kind.record_kind(),
+                    id
+                )))
+            }
+        };
+        if after != self.kernel().content().get(kind, id) {
+            let change = StagedChange {
+                collection: kind.collection().to_string(),
+                id: id.to_string(),
+                after,
+                before: None,
+            };
+            let position = position.unwrap_or(changeset.changes.len());
+            changeset.changes.insert(position, change);
+        }
+        let op = if staged { "put" } else { "delete" };
+        self.audit(
+            actor,
+            "builder.changeset.stage",
+            &changeset.target(),
+            json!({ "record": format!("{}/{}", kind.record_kind(), id), "op": op }),
+        );
+        self.save_changeset(changeset.clone())?;
+        Ok(changeset)
+    }
+
+    /// Every staged change compared with the live World.
+    pub fn changeset_diff(&self, changeset_id: &str) -> Result<Vec<DiffEntry>, BuilderError> {
+        let changeset = self.changeset(changeset_id)?;
+        let content = self.kernel().content();
+        Ok(changeset
+            .changes
+            .iter()
+            .map(|change| {
+                let live = ContentKind::from_collection(&change.collection)
+                    .and_then(|kind| content.get(kind, &change.id));
+                let op = match (&live, &change.after) {
+                    (None, Some(_)) => "create",
+                    (Some(_), Some(_)) => "update",
+                    (_, None) => "delete",
+                };
+                DiffEntry {
+                    collection: change.collection.clone(),
+                    id: change.id.clone(),
+                    op,
+                    live_hash: live.as_ref().map(content_hash),
+                    staged_hash: change.after.as_ref().map(content_hash),
+                    live,
+                    staged: change.after.clone(),
+                }
+            })
+            .collect())
+    }
+
+    /// Problems publishing would introduce. Integrity errors already present in the live
+    /// World are not blamed on the changeset.
+    pub fn validate_changeset(&self, changeset_id: &str) -> Result<Vec<String>, BuilderError> {
+        let changeset = self.changeset(changeset_id)?;
+        Ok(self.staged_content(changeset).1)
+    }
+
+    /// Validate and publish an open changeset in one storage transaction, then swap it into
+    /// the kernel.
+    pub fn publish_changeset(
+        &mut self,
+        actor: Option<&Principal>,
+        changeset_id: &str,
+    ) -> Result<Changeset, BuilderError> {
+        let changeset = self.changeset(changeset_id)?.clone();
+        changeset.require_open()?;
+        if changeset.changes.is_empty() {
+            return Err(BuilderError::BadRequest(format!(
+                "changeset '{}' has no changes",
+                changeset_id
+            )));
+        }
+        self.commit_changeset(actor, changeset, None)
+    }
+
+    /// Close an open changeset without applying it.
+    pub fn discard_changeset(
+        &mut self,
+        actor: Option<&Principal>,
+        changeset_id: &str,
+    ) -> Result<Changeset, BuilderError> {
+        let mut changeset = self.changeset(changeset_id)?.clone();
+        changeset.require_open()?;
+        changeset.status = ChangesetStatus::Discarded;
+        self.audit(
+            actor,
+            "builder.changeset.discard",
+            &changeset.target(),
+            Value::Null,
+        );
+        self.save_changeset(changeset.clone())?;
+        Ok(changeset)
+    }
+
+    /// Publish a new changeset that restores the values a published changeset replaced. Fails
+    /// with a conflict if any of its records changed after it was published.
+    pub fn revert_changeset(
+        &mut self,
+        actor: Option<&Principal>,
+        changeset_id: &str,
+    ) -> Result<Changeset, BuilderError> {
+        let mut original = self.changeset(changeset_id)?.clone();
+        if original.status != ChangesetStatus::Published {
+            return Err(BuilderError::Conflict(format!(
+                "changeset '{}' is not published",
+                changeset_id
+            )));
+        }
+        if let Some(by) = &original.reverted_by {
+            return Err(BuilderError::Conflict(format!(
+                "changeset '{}' was already reverted by '{}'",
+                changeset_id, by
+            )));
+        }
+        let content = self.kernel().content();
+        for change in &original.changes {
+            let live = ContentKind::from_collection(&change.collection)
+                .and_then(|kind| content.get(kind, &change.id));
+            if live != change.after {
+                return Err(BuilderError::Conflict(format!(
+                    "{} '{}' changed after changeset '{}' was published",
+                    change.collection, change.id, changeset_id
+                )));
+            }
+        }
+        let revert = Changeset {
+            id: self.next_changeset_id(),
+            title: format!("Revert {}", original.id),
+            author: author_name(actor),
+            status: ChangesetStatus::Open,
+            created_at: now_secs(),
+            published_at: None,
+            reverts: Some(original.id.clone()),
+            reverted_by: None,
+            changes: original
+                .changes
+                .iter()
+                .rev()
+                .map(|change| StagedChange {
+                    collection: change.collection.clone(),
+                    id: change.id.clone(),
+                    after: change.before.clone(),
+                    before: None,
+                })
+                .collect(),
+        };
+        original.reverted_by = Some(revert.id.clone());
+        self.commit_changeset(actor, revert, Some(original))
+    }
+
+    /// Load stored changesets and continue numbering after the highest stored id, whether or
+    /// not its record still parses.
+    pub(crate) fn load_changesets(&mut self) -> aqevia_storage::StorageResult<usize> {
+        for record in self.storage.load_records(CHANGESET_KIND)? {
+            let seq = record
+                .key
+                .strip_prefix("cs-")
+                .and_then(|seq| seq.parse().ok());
+            self.changeset_seq = self.changeset_seq.max(seq.unwrap_or(0));
+            if let Ok(changeset) = serde_json::from_str::<Changeset>(&record.payload) {
+                self.changesets.insert(changeset.id.clone(), changeset);
+            }
+        }
+        Ok(self.changesets.len())
+    }
+
+    /// Ids are never reused, even for changesets whose write failed.
+    fn next_changeset_id(&mut self) -> String {
+        self.changeset_seq += 1;
+        format!("cs-{}", self.changeset_seq)
+    }
+
+    /// Write the changeset's records, their audit entries, and the changeset itself (plus
+    /// `also`, the changeset a revert closes) in one storage transaction.
+    fn commit_changeset(
+        &mut self,
+        actor: Option<&Principal>,
+        mut changeset: Changeset,
+        also: Option<Changeset>,
+    ) -> Result<Changeset, BuilderError> {
+        let (staged, errors) = self.staged_content(&changeset);
+        if !errors.is_empty() {
+            return Err(BuilderError::Rejected(errors));
+        }
+        let verb = if changeset.reverts.is_some() {
+            "revert"
+        } else {
+            "publish"
+        };
+        let audit_seq = self.audit_seq;
+        let mut records = Vec::with_capacity(changeset.changes.len() * 2 + 3);
+        for change in &mut changeset.changes {
+            let Some(kind) = ContentKind::from_collection(&change.collection) else {
+                continue;
+            };
+            change.before = self.kernel().content().get(kind, &change.id);
+            change.after = staged.get(kind, &change.id);
+            records.push(match &change.after {
+                Some(value) => WorldRecord::new(
+                    self.world_id.clone(),
+                    kind.record_kind(),
+                    change.id.clone(),
+                    value.to_string(),
+                ),
+                None => WorldRecord::tombstone(
+                    self.world_id.clone(),
+                    kind.record_kind(),
+                    change.id.clone(),
+                ),
+            });
+            let entry = self.audit_entry(
+                actor,
+                &format!("builder.{}", verb),
+                &format!("{}/{}", kind.record_kind(), change.id),
+                change.before.as_ref(),
+                change.after.as_ref(),
+                json!({ "changeset": changeset.id }),
+            );
+            records.push(entry.to_record(&self.world_id));
+        }
+        changeset.status = ChangesetStatus::Published;
+        changeset.published_at = Some(now_secs());
+        let detail = match &changeset.reverts {
+            Some(reverts) => json!({ "changes": changeset.changes.len(), "reverts": reverts }),
+            None => json!({ "changes": changeset.changes.len() }),
+        };
+        let entry = self.audit_entry(
+            actor,
+            &format!("builder.changeset.{}", verb),
+            &changeset.target(),
+            None,
+            None,
+            detail,
+        );
+        records.push(entry.to_record(&self.world_id));
+        let updated: Vec<Changeset> = std::iter::once(changeset.clone()).chain(also).collect();
+        for entry in &updated {
+            records.push(self.changeset_record(entry));
+        }
+        let result = self.storage.commit_batch(records);
+        self.note_write(&result);
+        if let Err(err) = result {
+            self.audit_seq = audit_seq;
+            return Err(err.into());
+        }
+        *self.kernel_mut().content_mut() = staged;
+        for entry in updated {
+            self.changesets.insert(entry.id.clone(), entry);
+        }
+        Ok(changeset)
+    }
+
+    /// Live content with the changeset applied, plus every problem that introduces.
+    pub(crate) fn staged_content(&self, changeset: &Changeset) -> (WorldContent, Vec<String>) {
+        let live = self.kernel().content();
+        let mut staged = live.clone();
+        let mut errors = Vec::new();
+        for change in &changeset.changes {
+            let Some(kind) = ContentKind::from_collection(&change.collection) else {
+                errors.push(format!("unknown collection '{}'", change.collection));
+                continue;
+            };
+            let applied = match &change.after {
+                Some(value) => staged.insert_unchecked(kind, value).map(|_| ()),
+                None => staged.remove_unchecked(kind, &change.id).map(|_| ()),
+            };
+            if let Err(err) = applied {
+                errors.push(err.to_string());
+            }
+        }
+        let existing: BTreeSet<String> = live
+            .integrity_errors()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        errors.extend(
+            staged
+                .integrity_errors()
+                .iter()
+                .map(ToString::to_string)
+                .filter(|error| !existing.contains(error)),
+        );
+        (staged, errors)
+    }
+
+    fn changeset_record(&self, changeset: &Changeset) -> WorldRecord {
+        WorldRecord::new(
+            self.world_id.clone(),
+            CHANGESET_KIND,
+            changeset.id.clone(),
+            serde_json::to_string(changeset).expect("changesets serialize"),
+        )
+    }
+
+    fn save_changeset(&mut self, changeset: Changeset) -> Result<(), BuilderError> {
+        let record = self.changeset_record(&changeset);
+        self.record(record)?;
+        self.changesets.insert(changeset.id.clone(), changeset);
+        self.flush_all()?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct CreateChangeset {
+    title: String,
+}
+
+/// `/api/builder/changesets[/{id}[/diff|validate|publish|discard|revert]]` and
+/// `/api/builder/changesets/{id}/changes/{collection}/{record}`.
+pub struct ChangesetApi<B: StorageBackend> {
+    core: SharedCore<B>,
+}
+
+impl<B: StorageBackend> ChangesetApi<B> {
+    pub fn new(core: SharedCore<B>) -> Self {
+        ChangesetApi { core }
+    }
+
+    fn route(
+        &self,
+        request: &HttpRequest,
+        rest: &[&str],
+    ) -> Option<Result<HttpResponse, BuilderError>> {
+        let actor = request.principal.as_ref();
+        let mut core = self.core.lock().expect("lock poisoning");
+        let result = match (request.method.as_str(), rest) {
+            ("GET", []) => {
+                let changesets: Vec<_> = core.changesets().cloned().collect();
+                Ok(HttpResponse::json(
+                    200,
+                    &json!({ "changesets": changesets }),
+                ))
+            }
+            ("POST", []) => match request.json::<CreateChangeset>() {
+                Ok(body) => core
+                    .create_changeset(actor, &body.title)
+                    .map(|changeset| HttpResponse::json(201, &changeset)),
+                Err(response) => Ok(response),
+            },
+            ("GET", [id]) => core
+                .changeset(id)
+                .map(|changeset| HttpResponse::json(200, changeset)),
+            ("GET", [id, "diff"]) => core
+                .changeset_diff(id)
+                .map(|diff| HttpResponse::json(200, &json!({ "changes": diff }))),
+            ("POST", [id, "validate"]) => core.validate_changeset(id).map(|errors| {
+                HttpResponse::json(
+                    200,
+                    &json!({ "valid": errors.is_empty(), "errors": errors }),
+                )
+            }),
+            ("POST", [id, "publish"]) => core
+                .publish_changeset(actor, id)
+                .map(|changeset| HttpResponse::json(200, &changeset)),
+            ("POST", [id, "discard"]) => core
+                .discard_changeset(actor, id)
+                .map(|changeset| HttpResponse::json(200, &changeset)),
+            ("POST", [id, "revert"]) => core
+                .revert_changeset(actor, id)
+                .map(|changeset| HttpResponse::json(201, &changeset)),
+            (method, [id, "changes", collection, record]) => {
+                let kind = ContentKind::from_collection(collection)?;
+                stage(&mut core, request, method, id, kind, record)
+            }
+            (_, [] | [_] | [_, "diff" | "validate" | "publish" | "discard" | "revert"]) => Ok(
+                HttpResponse::error(405, "method_not_allowed", "method not allowed"),
+            ),
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+fn stage<B: StorageBackend>(
+    core: &mut EngineCore<B>,
+    request: &HttpRequest,
+    method: &str,
+    changeset_id: &str,
+    kind: ContentKind,
+    id: &str,
+) -> Result<HttpResponse, BuilderError> {
+    let after = match method {
+        "PUT" => {
+            let mut payload: Value = request
+                .json()
+                .map_err(|_| BuilderError::BadRequest("body must be a JSON object".into()))?;
+            match payload.get("id").and_then(Value::as_str) {
+                None => {
+                    if let Some(object) = payload.as_object_mut() {
+                        object.insert("id".into(), Value::String(id.to_string()));
+                    }
+                }
+                Some(body_id) if body_id != id => {
+                    return Err(BuilderError::BadRequest(
+                        "body 'id' does not match the path".into(),
+                    ))
+                }
+                Some(_) => {}
+            }
+            Some(payload)
+        }
+        "PATCH" => {
+            let patch: Value = request.json().map_err(|_| {
+                BuilderError::BadRequest("body must be a JSON Merge Patch document".into())
+            })?;
+            let changeset = core.changeset(changeset_id)?;
+            let mut record = core.staged_value(changeset, kind, id).ok_or_else(|| {
+                BuilderError::Missing(format!("{} '{}' not found", kind.record_kind(), id))
+            })?;
+            merge_patch(&mut record, &patch);
+            if record.get("id").and_then(Value::as_str) != Some(id) {
+                return Err(BuilderError::BadRequest(
+                    "'id' cannot be changed by a patch".into(),
+                ));
+            }
+            Some(record)
+        }
+        "DELETE" => None,
+        _ => {
+            return Ok(HttpResponse::error(
+                405,
+                "method_not_allowed",
+                "method not allowed",
+            ))
+        }
+    };
+    let changeset = core.stage_change(request.principal.as_ref(), changeset_id, kind, id, after)?;
+    Ok(HttpResponse::json(200, &changeset))
+}
+
+impl<B: StorageBackend> HttpHandler for ChangesetApi<B> {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let segments = request.segments();
+        let ["api", "builder", "changesets", rest @ ..] = segments.as_slice() else {
+            return None;
+        };
+        if let Err(rejection) = require_role(request, Role::Builder) {
+            return Some(rejection);
+        }
+        self.route(request, rest)
+            .map(|result| result.unwrap_or_else(|err| err.to_response()))
+    }
+}
+
+pub(crate) fn author_name(actor: Option<&Principal>) -> String {
+    actor
+        .map(|principal| principal.username.clone())
+        .unwrap_or_default()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{DummyBackend, Harness};
+    use crate::{AuditEntry, AUDIT_KIND};
+
+    #[test]
+    fn staged_edits_stay_out_of_live_until_published() {
+        let harness = Harness::builder(DummyBackend::default());
+        let (status, body) = harness.call("POST", "/api/builder/changesets", r#"{"title":"Hall"}"#);
+        assert_eq!(status, 201);
+        assert_eq!(body["author"], "builder");
+        let id = body["id"].as_str().unwrap().to_string();
+        let base = format!("/api/builder/changesets/{}", id);
+
+        let (status, _) = harness.call(
+            "PUT",
+            &format!("{}/changes/rooms/hall", base),
+            r#"{"name":"Hall"}"#,
+        );
+        assert_eq!(status, 200);
+        let (status, _) = harness.call(
+            "PUT",
+            &format!("{}/changes/exits/in", base),
+            r#"{"from":"hall","to":"hall","direction":"loop"}"#,
+        );
+        assert_eq!(status, 200);
+        let (status, _) = harness.call(
+            "PATCH",
+            &format!("{}/changes/rooms/hall", base),
+            r#"{"description":"Echoing."}"#,
+        );
+        assert_eq!(status, 200);
+        assert!(harness.live(ContentKind::Room, "hall").is_none());
+
+        let (_, diff) = harness.call("GET", &format!("{}/diff", base), "");
+        assert_eq!(diff["changes"][0]["op"], "create");
+        assert_eq!(diff["changes"][0]["staged"]["description"], "Echoing.");
+        assert_eq!(diff["changes"][0]["live_hash"], Value::Null);
+        assert_eq!(
+            diff["changes"][0]["staged_hash"],
+            crate::content_hash(&diff["changes"][0]["staged"])
+        );
+        let (_, report) = harness.call("POST", &format!("{}/validate", base), "");
+        assert_eq!(report["valid"], true);
+
+        let (status, body) = harness.call("POST", &format!("{}/publish", base), "");
+        assert_eq!((status, body["status"].as_str()), (200, Some("published")));
+        assert!(harness.live(ContentKind::Exit, "in").is_some());
+        let (status, _) = harness.call(
+            "PUT",
+            &format!("{}/changes/rooms/x", base),
+            r#"{"name":"X"}"#,
+        );
+        assert_eq!(status, 409);
+    }
+
+    #[test]
+    fn near_limit_records_fit_in_a_changeset() {
+        let harness = Harness::builder(DummyBackend::default());
+        // About 150 KiB and 30 levels deep, inside the default 256 KiB and depth 32.
+        let room = |id: &str, text: &str| {
+            let mut lore = json!(text.repeat(50 * 1024));
+            for _ in 0..29 {
+                lore = json!({ "lore": lore });
+            }
+            json!({
+                "id": id,
+                "name": id,
+                "description": text.repeat(50 * 1024),
+                "notes": text.repeat(50 * 1024),
+                "lore": lore,
+            })
+        };
+        let limits = harness.engine.core().storage().config().limits;
+        assert!(limits.check(&room("hall", "a").to_string()).is_ok());
+
+        for text in ["a", "b"] {
+            let (_, body) = harness.call("POST", "/api/builder/changesets", r#"{"title":"Big"}"#);
+            let base = format!("/api/builder/changesets/{}", body["id"].as_str().unwrap());
+            for id in ["hall", "nave", "apse"] {
+                let (status, _) = harness.call(
+                    "PUT",
+                    &format!("{}/changes/rooms/{}", base, id),
+                    &room(id, text).to_string(),
+                );
+                assert_eq!(status, 200);
+            }
+            let (status, body) = harness.call("POST", &format!("{}/publish", base), "");
+            assert_eq!((status, body["status"].as_str()), (200, Some("published")));
+        }
+        assert_eq!(
+            harness.live(ContentKind::Room, "apse").unwrap()["description"],
+            "b".repeat(50 * 1024)
+        );
+        let saved = harness
+            .engine
+            .core()
+            .storage()
+            .load_records(CHANGESET_KIND)
+            .unwrap();
+        assert_eq!(saved.len(), 2);
+        assert!(saved[1].payload.len() > limits.max_payload_bytes);
+    }
+
+    #[test]
+    fn changeset_ids_are_not_reused() {
+        let harness = Harness::builder(DummyBackend::default());
+        for title in ["One", "Two"] {
+            harness.call(
+                "POST",
+                "/api/builder/changesets",
+                &json!({ "title": title }).to_string(),
+            );
+        }
+        let mut records = harness
+            .engine
+            .core()
+            .storage()
+            .load_records(CHANGESET_KIND)
+            .unwrap();
+        records.retain(|record| record.key == "cs-2");
+        records.push(WorldRecord::new("world", CHANGESET_KIND, "cs-7", "{}"));
+        let reloaded = Harness::builder(DummyBackend::with_records(records));
+        let (status, body) =
+            reloaded.call("POST", "/api/builder/changesets", r#"{"title":"Three"}"#);
+        assert_eq!((status, body["id"].as_str()), (201, Some("cs-8")));
+    }
+
+    #[test]
+    fn publish_rejects_dangling_references() {
+        let harness = Harness::builder(DummyBackend::default());
+        harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"lobby","name":"Lobby"}"#,
+        );
+        harness.call(
+            "POST",
+            "/api/builder/items",
+            r#"{"id":"lamp","name":"Lamp","room":"lobby"}"#,
+        );
+        harness.call("POST", "/api/builder/changesets", r#"{"title":"Demolish"}"#);
+        let (status, _) = harness.call(
+            "DELETE",
+            "/api/builder/changesets/cs-1/changes/rooms/lobby",
+            "",
+        );
+        assert_eq!(status, 200);
+        let (_, report) = harness.call("POST", "/api/builder/changesets/cs-1/validate", "");
+        assert_eq!(report["valid"], false);
+        let (status, body) = harness.call("POST", "/api/builder/changesets/cs-1/publish", "");
+        assert_eq!(
+            (status, body["status"].as_str()),
+            (422, Some("validation_failed"))
+        );
+        assert!(body["errors"][0].as_str().unwrap().contains("lamp"));
+        assert!(harness.live(ContentKind::Room, "lobby").is_some());
+
+        let (status, body) = harness.call("POST", "/api/builder/changesets/cs-1/discard", "");
+        assert_eq!((status, body["status"].as_str()), (200, Some("discarded")));
+    }
+
+    #[test]
+    fn published_changesets_revert_and_survive_restart() {
+        let harness = Harness::builder(DummyBackend::default());
+        harness.call(
+            "POST",
+            "/api/builder/rooms",
+            r#"{"id":"lobby","name":"Lobby"}"#,
+        );
+        harness.call("POST", "/api/builder/changesets", r#"{"title":"Rename"}"#);
+        harness.call(
+            "PATCH",
+            "/api/builder/changesets/cs-1/changes/rooms/lobby",
+            r#"{"name":"Grand Lobby"}"#,
+        );
+        harness.call(
+            "PUT",
+            "/api/builder/changesets/cs-1/changes/rooms/annex",
+            r#"{"name":"Annex"}"#,
+        );
+        harness.call("POST", "/api/builder/changesets/cs-1/publish", "");
+        assert_eq!(
+            harness.live(ContentKind::Room, "lobby").unwrap()["name"],
+            "Grand Lobby"
+        );
+
+        let (status, body) = harness.call("POST", "/api/builder/changesets/cs-1/revert", "");
+        assert_eq!(status, 201);
+        assert_eq!(body["reverts"], "cs-1");
+        assert_eq!(
+            harness.live(ContentKind::Room, "lobby").unwrap()["name"],
+            "Lobby"
+        );
+        assert!(harness.live(ContentKind::Room, "annex").is_none());
+        let (status, _) = harness.call("POST", "/api/builder/changesets/cs-1/revert", "");
+        assert_eq!(status, 409);
+
+        let mut records = Vec::new();
+        for kind in ["auth.account", "core.room", CHANGESET_KIND] {
+            records.extend(harness.engine.core().storage().load_records(kind).unwrap());
+        }
+        let audit = harness
+            .engine
+            .core()
+            .storage()
+            .load_records(AUDIT_KIND)
+            .unwrap();
+        let actions: Vec<String> = audit
+            .iter()
+            .map(|record| serde_json::from_str::<AuditEntry>(&record.payload).unwrap())
+            .filter(|entry| {
+                entry.target.starts_with("core.room/") && entry.action != "builder.create"
+            })
+            .map(|entry| format!("{} {}", entry.action, entry.target))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                "builder.publish core.room/lobby",
+                "builder.publish core.room/annex",
+                "builder.revert core.room/annex",
+                "builder.revert core.room/lobby",
+            ]
+        );
+
+        let reloaded = Harness::builder(DummyBackend::with_records(records));
+        let (_, original) = reloaded.call("GET", "/api/builder/changesets/cs-1", "");
+        assert_eq!(original["reverted_by"], "cs-2");
+        assert_eq!(original["changes"][0]["before"]["name"], "Lobby");
+        assert!(reloaded.live(ContentKind::Room, "annex").is_none());
+    }
+
+    #[test]
+    fn revert_conflicts_when_live_content_moved_on() {
+        let harness = Harness::builder(DummyBackend::default());
+        harness.call("POST", "/api/builder/changesets", r#"{"title":"Add"}"#);
+        harness.call(
+            "PUT",
+            "/api/builder/changesets/cs-1/changes/rooms/lobby",
+            r#"{"name":"Lobby"}"#,
+        );
+        harness.call("POST", "/api/builder/changesets/cs-1/publish", "");
+        harness.call(
+            "PATCH",
+            "/api/builder/rooms/lobby",
+            r#"{"name":"Edited live"}"#,
+        );
+        let (status, body) = harness.call("POST", "/api/builder/changesets/cs-1/revert", "");
+        assert_eq!((status, body["status"].as_str()), (409, Some("conflict")));
+    }
+}
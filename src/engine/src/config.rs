@@ -0,0 +1,1120 @@
+//! Typed Engine configuration, layered from defaults, an optional TOML file, environment
+//! variables, and command-line overrides, in that order, then validated as a whole.
+//!
+//! Every setting has a dotted key (`storage.batch_capacity`) that names it in the file and in
+//! `--set` overrides, plus the environment variable that sets it. Values that do not parse are
+//! errors, never silent fallbacks. Secrets are redacted whenever the configuration is printed.
+
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use aqevia_ai::{BudgetConfig, BudgetLimit, GuardrailConfig, Guardrails, ProviderConfig, Secret};
+use aqevia_auth::{password, AuthConfig};
+use aqevia_storage::{RecordLimits, StorageConfig};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::logging::{self, LogFormat};
+use crate::NarrationConfig;
+
+/// Environment variable naming the config file when `--config` is not given.
+pub const CONFIG_ENV: &str = "AQEVIA_CONFIG";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("cannot read config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid config file {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+    /// A value from the environment or an override that does not parse. `origin` is the
+    /// variable or `--set` key it came from.
+    #[error("{origin}: {message}")]
+    Invalid { origin: String, message: String },
+    #[error("unknown setting '{0}'")]
+    UnknownKey(String),
+    #[error("invalid configuration: {}", .0.join("; "))]
+    Validation(Vec<String>),
+    /// A reload changed settings a running Engine cannot take; nothing was applied.
+    #[error("changing {} requires a restart; nothing was applied", .0.join(", "))]
+    RestartRequired(Vec<String>),
+    /// The Engine was not started from a configuration source it can re-read.
+    #[error("configuration reload is not available for this Engine")]
+    Unavailable,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageSection {
+    pub sqlite_path: PathBuf,
+    pub flush_interval_ms: u64,
+    pub batch_capacity: usize,
+    /// SQLite `journal_mode`: `wal`, `delete`, `truncate`, or `persist`.
+    pub journal_mode: String,
+    /// SQLite `synchronous`: `off`, `normal`, `full`, or `extra`.
+    pub synchronous: String,
+    pub busy_timeout_ms: u64,
+    pub cache_size_kib: u32,
+    /// Read-only connections serving queries; `0` runs them on the writer connection.
+    pub read_pool_size: usize,
+    /// Per-record payload limits; `0` disables one.
+    pub max_payload_bytes: usize,
+    pub max_depth: usize,
+    pub max_string_bytes: usize,
+}
+
+impl Default for StorageSection {
+    fn default() -> Self {
+        StorageSection {
+            sqlite_path: "storage.sqlite".into(),
+            flush_interval_ms: 1000,
+            batch_capacity: 50,
+            journal_mode: "wal".into(),
+            synchronous: "normal".into(),
+            busy_timeout_ms: 5000,
+            cache_size_kib: 8192,
+            read_pool_size: 4,
+            max_payload_bytes: 256 * 1024,
+            max_depth: 32,
+            max_string_bytes: 64 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupSection {
+    /// Directory for `backup` snapshots; defaults to `backups` beside the store.
+    pub dir: Option<PathBuf>,
+    /// Snapshots kept in `dir`; `0` keeps every one.
+    pub keep: usize,
+}
+
+impl Default for BackupSection {
+    fn default() -> Self {
+        BackupSection { dir: None, keep: 7 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ObservabilitySection {
+    /// Listener for the observability endpoints, the control plane, and the Web UI.
+    pub addr: SocketAddr,
+    pub ui_dir: PathBuf,
+}
+
+impl Default for ObservabilitySection {
+    fn default() -> Self {
+        ObservabilitySection {
+            addr: SocketAddr::from(([127, 0, 0, 1], 7878)),
+            ui_dir: "ui/dist".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionSection {
+    /// Lifetime of a login token.
+    pub ttl_secs: u64,
+}
+
+impl Default for SessionSection {
+    fn default() -> Self {
+        SessionSection {
+            ttl_secs: AuthConfig::default().session_ttl.as_secs(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TickSection {
+    pub interval_ms: u64,
+}
+
+impl Default for TickSection {
+    fn default() -> Self {
+        TickSection { interval_ms: 50 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSection {
+    /// PBKDF2 rounds for new password hashes.
+    pub hash_iterations: u32,
+    /// Whether `POST /api/auth/register` creates player accounts.
+    pub open_registration: bool,
+}
+
+impl Default for AuthSection {
+    fn default() -> Self {
+        let defaults = AuthConfig::default();
+        AuthSection {
+            hash_iterations: defaults.hash_iterations,
+            open_registration: defaults.open_registration,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditSection {
+    /// Days to keep audit entries; `0` keeps them forever.
+    pub retention_days: u64,
+}
+
+impl Default for AuditSection {
+    fn default() -> Self {
+        AuditSection { retention_days: 90 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSection {
+    /// Default level plus per-module overrides, e.g. `info,aqevia_storage=debug`.
+    pub level: String,
+    pub format: LogFormat,
+    /// Recent log events kept in memory for `/logs`; `0` keeps none.
+    pub buffer_events: usize,
+}
+
+impl Default for LogSection {
+    fn default() -> Self {
+        LogSection {
+            level: "info".into(),
+            format: LogFormat::Human,
+            buffer_events: 1000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AiSection {
+    /// `local` or `http`.
+    pub provider: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    pub model: String,
+    #[serde(
+        serialize_with = "redact",
+        deserialize_with = "secret",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub api_key: Option<Secret>,
+    pub timeout_ms: u64,
+    pub max_attempts: u32,
+    pub narration: NarrationSection,
+    pub guardrails: GuardrailSection,
+    pub budget: BudgetSection,
+}
+
+impl Default for AiSection {
+    fn default() -> Self {
+        let provider = ProviderConfig::default();
+        AiSection {
+            provider: provider.kind,
+            endpoint: provider.endpoint,
+            model: provider.model,
+            api_key: provider.api_key,
+            timeout_ms: provider.timeout.as_millis() as u64,
+            max_attempts: provider.max_attempts,
+            narration: NarrationSection::default(),
+            guardrails: GuardrailSection::default(),
+            budget: BudgetSection::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NarrationSection {
+    pub workers: usize,
+    pub deadline_ms: u64,
+    pub capacity: usize,
+}
+
+impl Default for NarrationSection {
+    fn default() -> Self {
+        let defaults = NarrationConfig::default();
+        NarrationSection {
+            workers: defaults.workers,
+            deadline_ms: defaults.deadline.as_millis() as u64,
+            capacity: defaults.capacity,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuardrailSection {
+    pub max_chars: usize,
+    pub blocklist: Vec<String>,
+    pub block_patterns: Vec<String>,
+}
+
+impl Default for GuardrailSection {
+    fn default() -> Self {
+        let defaults = GuardrailConfig::default();
+        GuardrailSection {
+            max_chars: defaults.max_chars,
+            blocklist: defaults.blocklist,
+            block_patterns: defaults.patterns,
+        }
+    }
+}
+
+/// Unset limits are unlimited.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BudgetSection {
+    pub window_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub world_max_requests: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub world_max_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_max_requests: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_max_tokens: Option<u64>,
+}
+
+impl Default for BudgetSection {
+    fn default() -> Self {
+        BudgetSection {
+            window_secs: BudgetConfig::default().window.as_secs(),
+            world_max_requests: None,
+            world_max_tokens: None,
+            account_max_requests: None,
+            account_max_tokens: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub storage: StorageSection,
+    pub backup: BackupSection,
+    pub observability: ObservabilitySection,
+    pub sessions: SessionSection,
+    pub tick: TickSection,
+    pub auth: AuthSection,
+    pub audit: AuditSection,
+    pub log: LogSection,
+    pub ai: AiSection,
+}
+
+/// One configurable value: its dotted key, the environment variable that sets it, whether a
+/// running Engine can take a new value without a restart, and how to parse a raw string into
+/// the config.
+pub struct Setting {
+    pub key: &'static str,
+    pub env: &'static str,
+    pub reloadable: bool,
+    apply: fn(&mut EngineConfig, &str) -> Result<(), String>,
+    same: fn(&EngineConfig, &EngineConfig) -> bool,
+}
+
+macro_rules! setting {
+    (reload $key:literal, $env:literal, $($field:ident).+) => {
+        Setting { reloadable: true, ..setting!($key, $env, $($field).+) }
+    };
+    ($key:literal, $env:literal, $($field:ident).+) => {
+        Setting {
+            key: $key,
+            env: $env,
+            reloadable: false,
+            apply: |config, raw| {
+                config.$($field).+ = SettingValue::parse_setting(raw)?;
+                Ok(())
+            },
+            same: |a, b| a.$($field).+ == b.$($field).+,
+        }
+    };
+}
+
+/// Every setting, in the order `config dump` and the docs list them.
+pub const SETTINGS: &[Setting] = &[
+    setting!(
+        "storage.sqlite_path",
+        "AQEVIA_SQLITE_PATH",
+        storage.sqlite_path
+    ),
+    setting!(
+        reload "storage.flush_interval_ms",
+        "PERSIST_FLUSH_INTERVAL_MS",
+        storage.flush_interval_ms
+    ),
+    setting!(
+        reload "storage.batch_capacity",
+        "PERSIST_BATCH_CAPACITY",
+        storage.batch_capacity
+    ),
+    setting!(
+        "storage.journal_mode",
+        "AQEVIA_SQLITE_JOURNAL_MODE",
+        storage.journal_mode
+    ),
+    setting!(
+        "storage.synchronous",
+        "AQEVIA_SQLITE_SYNCHRONOUS",
+        storage.synchronous
+    ),
+    setting!(
+        "storage.busy_timeout_ms",
+        "AQEVIA_SQLITE_BUSY_TIMEOUT_MS",
+        storage.busy_timeout_ms
+    ),
+    setting!(
+        "storage.cache_size_kib",
+        "AQEVIA_SQLITE_CACHE_KIB",
+        storage.cache_size_kib
+    ),
+    setting!(
+        "storage.read_pool_size",
+        "AQEVIA_SQLITE_READ_POOL_SIZE",
+        storage.read_pool_size
+    ),
+    setting!(
+        reload "storage.max_payload_bytes",
+        "AQEVIA_MAX_PAYLOAD_BYTES",
+        storage.max_payload_bytes
+    ),
+    setting!(
+        reload "storage.max_depth",
+        "AQEVIA_MAX_PAYLOAD_DEPTH",
+        storage.max_depth
+    ),
+    setting!(
+        reload "storage.max_string_bytes",
+        "AQEVIA_MAX_STRING_BYTES",
+        storage.max_string_bytes
+    ),
+    setting!(reload "backup.dir", "AQEVIA_BACKUP_DIR", backup.dir),
+    setting!(reload "backup.keep", "AQEVIA_BACKUP_KEEP", backup.keep),
+    setting!(
+        "observability.addr",
+        "AQEVIA_OBSERVABILITY_ADDR",
+        observability.addr
+    ),
+    setting!(
+        "observability.ui_dir",
+        "AQEVIA_UI_DIR",
+        observability.ui_dir
+    ),
+    setting!(
+        reload "sessions.ttl_secs",
+        "AQEVIA_SESSION_TTL_SECS",
+        sessions.ttl_secs
+    ),
+    setting!(reload "tick.interval_ms", "AQEVIA_TICK_MS", tick.interval_ms),
+    setting!(
+        reload "auth.hash_iterations",
+        "AQEVIA_HASH_ITERATIONS",
+        auth.hash_iterations
+    ),
+    setting!(
+        reload "auth.open_registration",
+        "AQEVIA_OPEN_REGISTRATION",
+        auth.open_registration
+    ),
+    setting!(
+        reload "audit.retention_days",
+        "AQEVIA_AUDIT_RETENTION_DAYS",
+        audit.retention_days
+    ),
+    setting!(reload "log.level", "AQEVIA_LOG", log.level),
+    setting!("log.format", "AQEVIA_LOG_FORMAT", log.format),
+    setting!(
+        "log.buffer_events",
+        "AQEVIA_LOG_BUFFER_EVENTS",
+        log.buffer_events
+    ),
+    setting!("ai.provider", "AQEVIA_AI_PROVIDER", ai.provider),
+    setting!("ai.endpoint", "AQEVIA_AI_ENDPOINT", ai.endpoint),
+    setting!("ai.model", "AQEVIA_AI_MODEL", ai.model),
+    setting!("ai.api_key", "AQEVIA_AI_API_KEY", ai.api_key),
+    setting!("ai.timeout_ms", "AQEVIA_AI_TIMEOUT_MS", ai.timeout_ms),
+    setting!("ai.max_attempts", "AQEVIA_AI_MAX_ATTEMPTS", ai.max_attempts),
+    setting!(
+        "ai.narration.workers",
+        "AQEVIA_NARRATION_WORKERS",
+        ai.narration.workers
+    ),
+    setting!(
+        "ai.narration.deadline_ms",
+        "AQEVIA_NARRATION_DEADLINE_MS",
+        ai.narration.deadline_ms
+    ),
+    setting!(
+        "ai.narration.capacity",
+        "AQEVIA_NARRATION_CAPACITY",
+        ai.narration.capacity
+    ),
+    setting!(
+        reload "ai.guardrails.max_chars",
+        "AQEVIA_AI_MAX_CHARS",
+        ai.guardrails.max_chars
+    ),
+    setting!(
+        reload "ai.guardrails.blocklist",
+        "AQEVIA_AI_BLOCKLIST",
+        ai.guardrails.blocklist
+    ),
+    // Regular expressions may contain commas, so one value is one pattern; use `|` alternation
+    // for several.
+    Setting {
+        key: "ai.guardrails.block_patterns",
+        env: "AQEVIA_AI_BLOCK_PATTERN",
+        reloadable: true,
+        apply: |config, raw| {
+            config.ai.guardrails.block_patterns =
+                Option::<String>::parse_setting(raw)?.into_iter().collect();
+            Ok(())
+        },
+        same: |a, b| a.ai.guardrails.block_patterns == b.ai.guardrails.block_patterns,
+    },
+    setting!(
+        reload "ai.budget.window_secs",
+        "AQEVIA_AI_BUDGET_WINDOW_SECS",
+        ai.budget.window_secs
+    ),
+    setting!(
+        reload "ai.budget.world_max_requests",
+        "AQEVIA_AI_WORLD_MAX_REQUESTS",
+        ai.budget.world_max_requests
+    ),
+    setting!(
+        reload "ai.budget.world_max_tokens",
+        "AQEVIA_AI_WORLD_MAX_TOKENS",
+        ai.budget.world_max_tokens
+    ),
+    setting!(
+        reload "ai.budget.account_max_requests",
+        "AQEVIA_AI_ACCOUNT_MAX_REQUESTS",
+        ai.budget.account_max_requests
+    ),
+    setting!(
+        reload "ai.budget.account_max_tokens",
+        "AQEVIA_AI_ACCOUNT_MAX_TOKENS",
+        ai.budget.account_max_tokens
+    ),
+];
+
+impl EngineConfig {
+    /// Defaults, then the TOML file at `file` if given, then every set and non-empty variable
+    /// `env` returns, then `overrides` as `(key, value)` pairs. The result is validated.
+    pub fn load(
+        file: Option<&Path>,
+        env: impl Fn(&str) -> Option<String>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        let mut config = match file {
+            Some(path) => EngineConfig::from_file(path)?,
+            None => EngineConfig::default(),
+        };
+        config.apply_env(env)?;
+        for (key, value) in overrides {
+            config.set(key, value)?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|err| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: err.message().to_string(),
+        })
+    }
+
+    /// Apply every setting whose variable `env` returns a non-empty value for.
+    pub fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        for setting in SETTINGS {
+            let Some(raw) = env(setting.env).filter(|raw| !raw.trim().is_empty()) else {
+                continue;
+            };
+            (setting.apply)(self, &raw).map_err(|message| ConfigError::Invalid {
+                origin: format!("{}={:?}", setting.env, redacted(setting, &raw)),
+                message,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Set one value by dotted key, parsing it the way its environment variable is parsed.
+    pub fn set(&mut self, key: &str, raw: &str) -> Result<(), ConfigError> {
+        let setting = SETTINGS
+            .iter()
+            .find(|setting| setting.key == key)
+            .ok_or_else(|| ConfigError::UnknownKey(key.to_string()))?;
+        (setting.apply)(self, raw).map_err(|message| ConfigError::Invalid {
+            origin: format!("--set {}", key),
+            message,
+        })
+    }
+
+    /// Check ranges and cross-field rules, reporting every problem at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut positive = |key: &str, value: u64| {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", key));
+            }
+        };
+        positive("storage.flush_interval_ms", self.storage.flush_interval_ms);
+        positive("storage.batch_capacity", self.storage.batch_capacity as u64);
+        positive("sessions.ttl_secs", self.sessions.ttl_secs);
+        positive("tick.interval_ms", self.tick.interval_ms);
+        positive("auth.hash_iterations", u64::from(self.auth.hash_iterations));
+        positive("ai.timeout_ms", self.ai.timeout_ms);
+        positive("ai.max_attempts", u64::from(self.ai.max_attempts));
+        positive("ai.narration.workers", self.ai.narration.workers as u64);
+        positive("ai.narration.deadline_ms", self.ai.narration.deadline_ms);
+        positive("ai.narration.capacity", self.ai.narration.capacity as u64);
+        positive(
+            "ai.guardrails.max_chars",
+            self.ai.guardrails.max_chars as u64,
+        );
+        positive("ai.budget.window_secs", self.ai.budget.window_secs);
+        if self.auth.hash_iterations > password::MAX_ITERATIONS {
+            problems.push(format!(
+                "auth.hash_iterations must be at most {}",
+                password::MAX_ITERATIONS
+            ));
+        }
+        match self.ai.provider.as_str() {
+            "local" => {}
+            "http" if self.ai.endpoint.is_none() => {
+                problems.push("ai.endpoint is required when ai.provider is \"http\"".into())
+            }
+            "http" => {
+                if let Err(err) = self.provider_config().build() {
+                    problems.push(format!("ai: {}", err));
+                }
+            }
+            other => problems.push(format!(
+                "ai.provider must be \"local\" or \"http\", not {:?}",
+                other
+            )),
+        }
+        if let Err(err) = logging::parse_filter(&self.log.level) {
+            problems.push(format!("log.level: {}", err));
+        }
+        if let Err(err) = Guardrails::new(self.guardrail_config()) {
+            problems.push(format!("ai.guardrails: {}", err));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Validation(problems))
+        }
+    }
+
+    /// Keys whose values differ between `self` and `other`, in [`SETTINGS`] order. Secrets are
+    /// compared by value even though they print redacted.
+    pub fn changed_keys(&self, other: &EngineConfig) -> Vec<&'static str> {
+        SETTINGS
+            .iter()
+            .filter(|setting| !(setting.same)(self, other))
+            .map(|setting| setting.key)
+            .collect()
+    }
+
+    /// The effective configuration as TOML, with secrets replaced by `***`.
+    pub fn dump(&self) -> String {
+        toml::to_string(self).expect("config serializes")
+    }
+
+    pub fn storage_config(&self) -> StorageConfig {
+        StorageConfig {
+            flush_interval_ms: self.storage.flush_interval_ms,
+            batch_capacity: self.storage.batch_capacity,
+            limits: RecordLimits {
+                max_payload_bytes: self.storage.max_payload_bytes,
+                max_depth: self.storage.max_depth,
+                max_string_bytes: self.storage.max_string_bytes,
+            },
+        }
+    }
+
+    /// Where `backup` writes snapshots: `backup.dir`, or `backups` beside the store.
+    pub fn backup_dir(&self) -> PathBuf {
+        self.backup.dir.clone().unwrap_or_else(|| {
+            self.storage
+                .sqlite_path
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join("backups")
+        })
+    }
+
+    pub fn auth_config(&self) -> AuthConfig {
+        AuthConfig {
+            session_ttl: Duration::from_secs(self.sessions.ttl_secs),
+            hash_iterations: self.auth.hash_iterations,
+            open_registration: self.auth.open_registration,
+        }
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_millis(self.tick.interval_ms)
+    }
+
+    /// `None` keeps audit entries forever.
+    pub fn audit_retention(&self) -> Option<Duration> {
+        let days = self.audit.retention_days;
+        (days > 0).then(|| Duration::from_secs(days * 24 * 60 * 60))
+    }
+
+    pub fn provider_config(&self) -> ProviderConfig {
+        ProviderConfig {
+            kind: self.ai.provider.clone(),
+            endpoint: self.ai.endpoint.clone(),
+            model: self.ai.model.clone(),
+            api_key: self.ai.api_key.clone(),
+            timeout: Duration::from_millis(self.ai.timeout_ms),
+            max_attempts: self.ai.max_attempts,
+        }
+    }
+
+    pub fn narration_config(&self) -> NarrationConfig {
+        NarrationConfig {
+            workers: self.ai.narration.workers,
+            deadline: Duration::from_millis(self.ai.narration.deadline_ms),
+            capacity: self.ai.narration.capacity,
+        }
+    }
+
+    pub fn guardrail_config(&self) -> GuardrailConfig {
+        GuardrailConfig {
+            max_chars: self.ai.guardrails.max_chars,
+            blocklist: self.ai.guardrails.blocklist.clone(),
+            patterns: self.ai.guardrails.block_patterns.clone(),
+        }
+    }
+
+    pub fn budget_config(&self) -> BudgetConfig {
+        let budget = &self.ai.budget;
+        BudgetConfig {
+            window: Duration::from_secs(budget.window_secs),
+            world: BudgetLimit {
+                requests: budget.world_max_requests,
+                tokens: budget.world_max_tokens,
+            },
+            account: BudgetLimit {
+                requests: budget.account_max_requests,
+                tokens: budget.account_max_tokens,
+            },
+        }
+    }
+}
+
+fn redacted<'a>(setting: &Setting, raw: &'a str) -> &'a str {
+    if setting.key == "ai.api_key" {
+        "***"
+    } else {
+        raw
+    }
+}
+
+/// Parsing for a raw setting string from the environment or a `--set` override.
+trait SettingValue: Sized {
+    fn parse_setting(raw: &str) -> Result<Self, String>;
+}
+
+fn parse_with<T: FromStr>(raw: &str, expected: &str) -> Result<T, String> {
+    raw.trim()
+        .parse()
+        .map_err(|_| format!("expected {}", expected))
+}
+
+impl SettingValue for u64 {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        parse_with(raw, "a non-negative integer")
+    }
+}
+
+impl SettingValue for u32 {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        parse_with(raw, "a non-negative integer below 2^32")
+    }
+}
+
+impl SettingValue for usize {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        parse_with(raw, "a non-negative integer")
+    }
+}
+
+impl SettingValue for bool {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        parse_with(raw, "true or false")
+    }
+}
+
+impl SettingValue for SocketAddr {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        parse_with(raw, "a socket address such as 127.0.0.1:7878")
+    }
+}
+
+impl SettingValue for String {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        Ok(raw.trim().to_string())
+    }
+}
+
+impl SettingValue for PathBuf {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        Ok(PathBuf::from(raw.trim()))
+    }
+}
+
+impl SettingValue for LogFormat {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        raw.trim().parse()
+    }
+}
+
+impl SettingValue for Secret {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        Ok(Secret::new(raw.trim()))
+    }
+}
+
+/// Comma-separated; blank entries are dropped.
+impl SettingValue for Vec<String> {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        Ok(raw
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// An empty value clears the setting.
+impl<T: SettingValue> SettingValue for Option<T> {
+    fn parse_setting(raw: &str) -> Result<Self, String> {
+        if raw.trim().is_empty() {
+            Ok(None)
+        } else {
+            T::parse_setting(raw).map(Some)
+        }
+    }
+}
+
+fn redact<S: Serializer>(secret: &Option<Secret>, serializer: S) -> Result<S::Ok, S::Error> {
+    match secret {
+        Some(secret) => serializer.serialize_str(&secret.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn secret<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Secret>, D::Error> {
+    let value = Option::<String>::deserialize(deserializer)?;
+    Ok(value.filter(|value| !value.is_empty()).map(Secret::new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn file_env_and_overrides_layer_in_order() {
+        let path = std::env::temp_dir().join(format!("aqevia-config-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "[storage]\nbatch_capacity = 50\nflush_interval_ms = 250\n\n[ai]\nprovider = \"http\"\nendpoint = \"http://127.0.0.1:9/v1\"\napi_key = \"sk-file\"\n\n[ai.budget]\nworld_max_requests = 100\n",
+        )
+        .unwrap();
+        let config = EngineConfig::load(
+            Some(&path),
+            env(&[
+                ("PERSIST_BATCH_CAPACITY", "20"),
+                ("AQEVIA_TICK_MS", ""),
+                ("AQEVIA_AI_BLOCK_PATTERN", "x{2,3}"),
+            ]),
+            &[("storage.batch_capacity".into(), "30".into())],
+        )
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.storage.batch_capacity, 30);
+        assert_eq!(config.storage.flush_interval_ms, 250);
+        assert_eq!(config.tick.interval_ms, 50);
+        assert_eq!(
+            config.guardrail_config().patterns,
+            vec!["x{2,3}".to_string()]
+        );
+        assert_eq!(config.budget_config().world.requests, Some(100));
+        assert_eq!(
+            config.provider_config().api_key,
+            Some(Secret::new("sk-file"))
+        );
+        let dump = config.dump();
+        assert!(dump.contains("api_key = \"***\""), "{}", dump);
+        assert!(!dump.contains("sk-file"));
+        let reparsed: EngineConfig = toml::from_str(&dump).unwrap();
+        assert_eq!(reparsed.storage, config.storage);
+    }
+
+    #[test]
+    fn bad_values_are_errors_not_defaults() {
+        let err =
+            EngineConfig::load(None, env(&[("PERSIST_BATCH_CAPACITY", "abc")]), &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "PERSIST_BATCH_CAPACITY=\"abc\": expected a non-negative integer"
+        );
+        let err = EngineConfig::load(
+            None,
+            env(&[("AQEVIA_AI_API_KEY", "")]),
+            &[("nope.key".into(), "1".into())],
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "unknown setting 'nope.key'");
+        let err = EngineConfig::load(
+            None,
+            env(&[("AQEVIA_TICK_MS", "0"), ("AQEVIA_AI_PROVIDER", "http")]),
+            &[],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid configuration: tick.interval_ms must be greater than 0; ai.endpoint is required when ai.provider is \"http\""
+        );
+        let path =
+            std::env::temp_dir().join(format!("aqevia-config-bad-{}.toml", std::process::id()));
+        fs::write(&path, "[storage]\nbatch_size = 5\n").unwrap();
+        let err = EngineConfig::from_file(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("batch_size"), "{}", err);
+    }
+
+    fn overrides(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn problems(err: ConfigError) -> Vec<String> {
+        match err {
+            ConfigError::Validation(problems) => problems,
+            other => panic!("expected validation errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validation_reports_every_out_of_range_value() {
+        let zeroed = [
+            "storage.flush_interval_ms",
+            "storage.batch_capacity",
+            "sessions.ttl_secs",
+            "tick.interval_ms",
+            "auth.hash_iterations",
+            "ai.timeout_ms",
+            "ai.max_attempts",
+            "ai.narration.workers",
+            "ai.narration.deadline_ms",
+            "ai.narration.capacity",
+            "ai.guardrails.max_chars",
+            "ai.budget.window_secs",
+        ];
+        let pairs: Vec<(&str, &str)> = zeroed.iter().map(|key| (*key, "0")).collect();
+        let err = EngineConfig::load(None, env(&[]), &overrides(&pairs)).unwrap_err();
+        let expected: Vec<String> = zeroed
+            .iter()
+            .map(|key| format!("{} must be greater than 0", key))
+            .collect();
+        assert_eq!(problems(err), expected);
+
+        let err = EngineConfig::load(
+            None,
+            env(&[]),
+            &overrides(&[
+                ("ai.provider", "cloud"),
+                ("log.level", "info,aqevia=loud"),
+                ("ai.guardrails.block_patterns", "(unclosed"),
+            ]),
+        )
+        .unwrap_err();
+        let reported = problems(err);
+        assert_eq!(reported.len(), 3, "{:?}", reported);
+        assert_eq!(
+            reported[0],
+            "ai.provider must be \"local\" or \"http\", not \"cloud\""
+        );
+        assert!(reported[1].starts_with("log.level: "), "{}", reported[1]);
+        assert!(
+            reported[2].starts_with("ai.guardrails: "),
+            "{}",
+            reported[2]
+        );
+
+        let err = EngineConfig::load(None, env(&[("AQEVIA_HASH_ITERATIONS", "1000001")]), &[])
+            .unwrap_err();
+        assert_eq!(
+            problems(err),
+            vec!["auth.hash_iterations must be at most 1000000".to_string()]
+        );
+
+        // Limits where zero means "off" are not range-checked.
+        let config = EngineConfig::load(
+            None,
+            env(&[]),
+            &overrides(&[
+                ("storage.max_payload_bytes", "0"),
+                ("storage.read_pool_size", "0"),
+                ("audit.retention_days", "0"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.audit_retention(), None);
+        assert_eq!(config.storage_config().limits.max_payload_bytes, 0);
+    }
+
+    #[test]
+    fn each_layer_overrides_only_the_keys_it_sets() {
+        let path =
+            std::env::temp_dir().join(format!("aqevia-config-layers-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "[storage]\nflush_interval_ms = 100\nbatch_capacity = 10\n\n[tick]\ninterval_ms = 0\n\n[sessions]\nttl_secs = 60\n",
+        )
+        .unwrap();
+        let vars = [
+            ("PERSIST_BATCH_CAPACITY", "20"),
+            ("AQEVIA_TICK_MS", "25"),
+            ("AQEVIA_SESSION_TTL_SECS", "  "),
+            ("AQEVIA_LOG", "debug"),
+        ];
+        let config = EngineConfig::load(
+            Some(&path),
+            env(&vars),
+            &overrides(&[("tick.interval_ms", "75"), ("log.level", "warn")]),
+        );
+        let without_overrides = EngineConfig::load(Some(&path), env(&vars), &[]);
+        let file_only = EngineConfig::load(Some(&path), env(&[]), &[]);
+        fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.storage.flush_interval_ms, 100);
+        assert_eq!(config.storage.batch_capacity, 20);
+        assert_eq!(config.sessions.ttl_secs, 60);
+        assert_eq!(config.tick.interval_ms, 75);
+        assert_eq!(config.log.level, "warn");
+        assert_eq!(
+            config.storage.journal_mode,
+            EngineConfig::default().storage.journal_mode
+        );
+
+        let without_overrides = without_overrides.unwrap();
+        assert_eq!(without_overrides.tick.interval_ms, 25);
+        assert_eq!(without_overrides.log.level, "debug");
+
+        // Validation runs on the layered result, so the file's zero tick only fails alone.
+        assert_eq!(
+            problems(file_only.unwrap_err()),
+            vec!["tick.interval_ms must be greater than 0".to_string()]
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let path =
+            std::env::temp_dir().join(format!("aqevia-config-unknown-{}.toml", std::process::id()));
+        fs::write(&path, "[telemetry]\nenabled = true\n").unwrap();
+        let section = EngineConfig::from_file(&path).unwrap_err();
+        fs::write(&path, "[ai.narration]\nworker = 4\n").unwrap();
+        let nested = EngineConfig::from_file(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(section, ConfigError::Parse { .. }));
+        assert!(section.to_string().contains("telemetry"), "{}", section);
+        assert!(nested.to_string().contains("worker"), "{}", nested);
+
+        let mut config = EngineConfig::default();
+        for key in ["storage", "storage.", "tick.interval", "TICK.INTERVAL_MS"] {
+            match config.set(key, "1") {
+                Err(ConfigError::UnknownKey(unknown)) => assert_eq!(unknown, key),
+                other => panic!("expected {} to be unknown, got {:?}", key, other),
+            }
+        }
+        // Only the documented variables are read; others are never consulted.
+        let config = EngineConfig::load(None, env(&[("AQEVIA_TICK", "0")]), &[]).unwrap();
+        assert_eq!(config, EngineConfig::default());
+    }
+
+    #[test]
+    fn bad_env_values_name_the_variable() {
+        let cases = [
+            ("AQEVIA_OPEN_REGISTRATION", "yes", "true or false"),
+            (
+                "AQEVIA_HASH_ITERATIONS",
+                "4294967296",
+                "a non-negative integer below 2^32",
+            ),
+            ("AQEVIA_TICK_MS", "-5", "a non-negative integer"),
+            ("AQEVIA_TICK_MS", "1.5", "a non-negative integer"),
+            (
+                "AQEVIA_OBSERVABILITY_ADDR",
+                "localhost",
+                "a socket address such as 127.0.0.1:7878",
+            ),
+        ];
+        for (name, value, expected) in cases {
+            let err = EngineConfig::load(None, env(&[(name, value)]), &[]).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("{}={:?}: expected {}", name, value, expected)
+            );
+        }
+        let err = EngineConfig::load(None, env(&[("AQEVIA_LOG_FORMAT", "xml")]), &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "AQEVIA_LOG_FORMAT=\"xml\": expected \"human\" or \"json\", not \"xml\""
+        );
+        // Surrounding whitespace is trimmed rather than rejected.
+        let config = EngineConfig::load(None, env(&[("AQEVIA_TICK_MS", " 40 ")]), &[]).unwrap();
+        assert_eq!(config.tick.interval_ms, 40);
+
+        // An override is reported by key, and a bad value is an error even when the same
+        // variable was valid.
+        let err = EngineConfig::load(
+            None,
+            env(&[("AQEVIA_TICK_MS", "40")]),
+            &overrides(&[("tick.interval_ms", "soon")]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--set tick.interval_ms: expected a non-negative integer"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Glue between the AI guardrails and the Engine: World facts drawn from kernel content, and
+//! logging plus metrics for rejected output.
+
+use aqevia_ai::{Violation, WorldFacts};
+use aqevia_kernel::WorldContent;
+use aqevia_transport::ObservabilityState;
+use tracing::warn;
+
+/// Counter of AI outputs rejected by the guardrails, labelled by `source` and `reason`.
+pub const REJECTIONS_METRIC: &str = "aqevia_ai_guardrail_rejections_total";
+
+/// Facts for text set in `room`: its exit directions, plus the names of rooms it does not
+/// lead to and characters placed elsewhere. Without a room nothing is checked.
+pub fn world_facts(content: &WorldContent, room: Option<&str>) -> WorldFacts {
+    let Some(room) = room else {
+        return WorldFacts::default();
+    };
+    let exits: Vec<_> = content.exits().filter(|exit| exit.from == room).collect();
+    let mut present: Vec<&str> = content
+        .rooms()
+        .filter(|candidate| {
+            candidate.id == room || exits.iter().any(|exit| exit.to == candidate.id)
+        })
+        .map(|candidate| candidate.name.as_str())
+        .collect();
+    present.extend(
+        content
+            .npcs()
+            .filter(|npc| npc.room.as_deref() == Some(room))
+            .map(|npc| npc.name.as_str()),
+    );
+    let mut absent: Vec<String> = content
+        .rooms()
+        .map(|candidate| candidate.name.as_str())
+        .chain(content.npcs().map(|npc| npc.name.as_str()))
+        .filter(|name| !name.trim().is_empty() && !present.contains(name))
+        .map(str::to_string)
+        .collect();
+    absent.sort();
+    absent.dedup();
+    WorldFacts {
+        exits: Some(exits.iter().map(|exit| exit.direction.clone()).collect()),
+        absent,
+    }
+}
+
+/// Log why an output from `source` (`assist` or `narration`) was rejected and count each
+/// reason.
+pub(crate) fn report_rejection(
+    observability: &ObservabilityState,
+    source: &str,
+    violations: &[Violation],
+) {
+    let reasons: Vec<String> = violations.iter().map(ToString::to_string).collect();
+    warn!(source, reasons = %reasons.join("; "), "AI guardrail rejected output");
+    for violation in violations {
+        observability.add_counter(
+            REJECTIONS_METRIC,
+            "AI outputs rejected by guardrails.",
+            &[("source", source), ("reason", violation.kind.as_str())],
+            1,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aqevia_kernel::ContentKind;
+    use serde_json::json;
+
+    #[test]
+    fn facts_cover_exits_and_names_placed_elsewhere() {
+        let mut content = WorldContent::default();
+        for (kind, payload) in [
+            (ContentKind::Room, json!({"id": "hall", "name": "Hall"})),
+            (ContentKind::Room, json!({"id": "vault", "name": "Vault"})),
+            (ContentKind::Room, json!({"id": "tower", "name": "Tower"})),
+            (
+                ContentKind::Exit,
+                json!({"id": "hall-n", "from": "hall", "to": "vault", "direction": "north"}),
+            ),
+            (
+                ContentKind::Npc,
+                json!({"id": "guard", "name": "Guard", "room": "hall"}),
+            ),
+            (
+                ContentKind::Npc,
+                json!({"id": "mage", "name": "Mage", "room": "tower"}),
+            ),
+        ] {
+            content.upsert(kind, &payload).unwrap();
+        }
+        assert_eq!(
+            world_facts(&content, Some("hall")),
+            WorldFacts {
+                exits: Some(vec!["north".into()]),
+                absent: vec!["Mage".into(), "Tower".into()],
+            }
+        );
+        assert_eq!(world_facts(&content, None), WorldFacts::default());
+    }
+}
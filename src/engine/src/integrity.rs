@@ -0,0 +1,386 @@
+//! Referential integrity of stored World content. [`EngineCore::check_integrity`] reads every
+//! content record back from storage and reports the records that no longer decode or match
+//! their kind's schema, and the ones that fail the kernel's cross-record checks, such as exits to missing rooms or items in
+//! missing containers. Builder writes are held to the same checks by the kernel before they
+//! are applied; this scan catches what reached the store another way.
+//!
+//! A check can also fix what it finds. Repairing clears references to missing rooms and
+//! containers, which leaves the item or NPC unplaced. Records that cannot be repaired that
+//! way are quarantined: moved to an `integrity.quarantine` record holding the original
+//! payload, and deleted from their collection.
+
+use std::str::FromStr;
+
+use aqevia_auth::Principal;
+use aqevia_kernel::{ContentKind, IntegrityProblem, WorldContent};
+use aqevia_storage::{StorageBackend, StorageResult, WorldRecord};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::audit::unix_now;
+use crate::EngineCore;
+
+/// Storage `kind` for quarantined records, keyed `<kind>/<key>` of the record they replace.
+pub const QUARANTINE_KIND: &str = "integrity.quarantine";
+
+/// What [`EngineCore::check_integrity`] does about the problems it finds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityMode {
+    /// Report problems and change nothing.
+    #[default]
+    Report,
+    /// Clear dangling room and container references, and quarantine records that still fail.
+    Repair,
+    /// Quarantine every record with a problem.
+    Quarantine,
+}
+
+impl FromStr for IntegrityMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "report" => Ok(IntegrityMode::Report),
+            "repair" => Ok(IntegrityMode::Repair),
+            "quarantine" => Ok(IntegrityMode::Quarantine),
+            other => Err(format!(
+                "unknown integrity mode '{}'; expected report, repair, or quarantine",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The payload is not valid JSON or not a valid record of its kind.
+    Undecodable,
+    /// The payload does not match the schema registered for its kind.
+    SchemaViolation,
+    /// The record names a record that does not exist.
+    DanglingReference,
+    /// Any other failed cross-record check, such as a container cycle.
+    Invalid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityAction {
+    None,
+    Repaired,
+    Quarantined,
+}
+
+/// A reference to a record that does not exist.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MissingReference {
+    pub field: &'static str,
+    pub kind: &'static str,
+    pub id: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct IntegrityIssue {
+    /// Storage `kind` of the record, e.g. `core.exit`.
+    pub kind: String,
+    pub key: String,
+    pub problem: IssueKind,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<MissingReference>,
+    pub action: IntegrityAction,
+}
+
+/// Outcome of [`EngineCore::check_integrity`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct IntegrityReport {
+    pub mode: IntegrityMode,
+    /// Content records read from storage.
+    pub checked: usize,
+    pub issues: Vec<IntegrityIssue>,
+    pub repaired: usize,
+    pub quarantined: usize,
+}
+
+impl IntegrityReport {
+    /// The counts recorded in the check's audit entry.
+    pub fn summary(&self) -> Value {
+        json!({
+            "mode": self.mode,
+            "issues": self.issues.len(),
+            "repaired": self.repaired,
+            "quarantined": self.quarantined,
+        })
+    }
+
+    /// Issues the check found but did not fix.
+    pub fn unresolved(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.action == IntegrityAction::None)
+            .count()
+    }
+}
+
+impl<B: StorageBackend> EngineCore<B> {
+    /// Check every stored content record and, unless `mode` is [`IntegrityMode::Report`], fix
+    /// what was found. Fixes are written in one transaction with an `admin.world.check` audit
+    /// entry, then applied to the live World; a check that fixes nothing writes nothing.
+    /// Pending records are flushed first, so the scan sees everything the World has accepted.
+    pub fn check_integrity(
+        &mut self,
+        actor: Option<&Principal>,
+        mode: IntegrityMode,
+    ) -> StorageResult<IntegrityReport> {
+        self.flush_all()?;
+        let mut report = IntegrityReport {
+            mode,
+            ..IntegrityReport::default()
+        };
+        let mut stored = WorldContent::default();
+        let mut records = Vec::new();
+        for kind in ContentKind::ALL {
+            for record in self.storage.load_records(kind.record_kind())? {
+                report.checked += 1;
+                let decoded = serde_json::from_str::<Value>(&record.payload)
+                    .map_err(|err| (IssueKind::Undecodable, err.to_string()))
+                    .and_then(|payload| {
+                        self.kernel()
+                            .check_schema(kind, &payload)
+                            .map_err(|err| (IssueKind::SchemaViolation, err.to_string()))?;
+                        stored
+                            .insert_unchecked(kind, &payload)
+                            .map_err(|err| (IssueKind::Undecodable, err.to_string()))
+                    });
+                let Err((problem, message)) = decoded else {
+                    continue;
+                };
+                let action = if mode == IntegrityMode::Report {
+                    IntegrityAction::None
+                } else {
+                    records.extend(self.quarantine(kind, &record.key, &record.payload, &message));
+                    IntegrityAction::Quarantined
+                };
+                report.issues.push(IntegrityIssue {
+                    kind: kind.record_kind().to_string(),
+                    key: record.key,
+                    problem,
+                    message,
+                    missing: Vec::new(),
+                    action,
+                });
+            }
+        }
+
+        let mut live = self.kernel().content().clone();
+        // Quarantining a container can strand the items inside it, so fixes repeat until a
+        // pass finds nothing. Every pass removes a record or a reference, so this is bounded.
+        for _ in 0..=report.checked {
+            let problems = stored.integrity_problems();
+            if problems.is_empty() {
+                break;
+            }
+            for problem in problems {
+                let action = match mode {
+                    IntegrityMode::Report => IntegrityAction::None,
+                    IntegrityMode::Repair if self.repair(&mut stored, &problem, &mut records) => {
+                        IntegrityAction::Repaired
+                    }
+                    _ => {
+                        let payload = stored
+                            .remove_unchecked(problem.kind, &problem.id)
+                            .expect("problems name stored records")
+                            .to_string();
+                        records.extend(self.quarantine(
+                            problem.kind,
+                            &problem.id,
+                            &payload,
+                            &problem.message,
+                        ));
+                        IntegrityAction::Quarantined
+                    }
+                };
+                report.issues.push(issue(&problem, action));
+            }
+            if mode == IntegrityMode::Report {
+                break;
+            }
+        }
+        report.repaired = count(&report, IntegrityAction::Repaired);
+        report.quarantined = count(&report, IntegrityAction::Quarantined);
+
+        if records.is_empty() {
+            return Ok(report);
+        }
+        for record in &records {
+            let Some(kind) = ContentKind::from_record_kind(&record.kind) else {
+                continue;
+            };
+            // A record that failed to load never reached the live World, so it may not be there to
+            // remove.
+            if record.deleted {
+                let _ = live.remove_unchecked(kind, &record.key);
+            } else if let Ok(payload) = serde_json::from_str(&record.payload) {
+                let _ = live.insert_unchecked(kind, &payload);
+            }
+        }
+        let audit_seq = self.audit_seq;
+        let entry = self.audit_entry(
+            actor,
+            "admin.world.check",
+            "world",
+            None,
+            None,
+            report.summary(),
+        );
+        records.push(entry.to_record(&self.world_id));
+        let result = self.storage.commit_batch(records);
+        self.note_write(&result);
+        if let Err(err) = result {
+            self.audit_seq = audit_seq;
+            return Err(err);
+        }
+        *self.kernel_mut().content_mut() = live;
+        Ok(report)
+    }
+
+    /// Clear a problem record's dangling references in `stored`, queueing the fixed record,
+    /// if that leaves it passing every check.
+    fn repair(
+        &self,
+        stored: &mut WorldContent,
+        problem: &IntegrityProblem,
+        records: &mut Vec<WorldRecord>,
+    ) -> bool {
+        let Some(mut value) = stored.get(problem.kind, &problem.id) else {
+            return false;
+        };
+        let Some(fields) = value.as_object_mut() else {
+            return false;
+        };
+        for reference in &problem.dangling {
+            fields.remove(reference.field);
+        }
+        if problem.dangling.is_empty() || stored.validate(problem.kind, &value).is_err() {
+            return false;
+        }
+        stored
+            .insert_unchecked(problem.kind, &value)
+            .expect("validated content inserts");
+        records.push(WorldRecord::new(
+            self.world_id.clone(),
+            problem.kind.record_kind(),
+            problem.id.clone(),
+            value.to_string(),
+        ));
+        true
+    }
+
+    /// Records that move `key` out of its collection into quarantine.
+    fn quarantine(
+        &self,
+        kind: ContentKind,
+        key: &str,
+        payload: &str,
+        message: &str,
+    ) -> [WorldRecord; 2] {
+        let held = json!({
+            "kind": kind.record_kind(),
+            "key": key,
+            "payload": payload,
+            "reason": message,
+            "at": unix_now(),
+        });
+        [
+            WorldRecord::tombstone(&self.world_id, kind.record_kind(), key),
+            WorldRecord::new(
+                self.world_id.clone(),
+                QUARANTINE_KIND,
+                format!("{}/{}", kind.record_kind(), key),
+                held.to_string(),
+            ),
+        ]
+    }
+}
+
+fn issue(problem: &IntegrityProblem, action: IntegrityAction) -> IntegrityIssue {
+    IntegrityIssue {
+        kind: problem.kind.record_kind().to_string(),
+        key: problem.id.clone(),
+        problem: if problem.dangling.is_empty() {
+            IssueKind::Invalid
+        } else {
+            IssueKind::DanglingReference
+        },
+        message: problem.message.clone(),
+        missing: problem
+            .dangling
+            .iter()
+            .map(|reference| MissingReference {
+                field: reference.field,
+                kind: reference.target.record_kind(),
+                id: reference.id.clone(),
+            })
+            .collect(),
+        action,
+    }
+}
+
+fn count(report: &IntegrityReport, action: IntegrityAction) -> usize {
+    report
+        .issues
+        .iter()
+        .filter(|issue| issue.action == action)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{engine, DummyBackend};
+
+    #[test]
+    fn quarantine_follows_references_it_leaves_dangling() {
+        let record = |kind: &str, key: &str, payload: Value| {
+            WorldRecord::new("world", kind, key, payload.to_string())
+        };
+        let backend = DummyBackend::with_records(vec![
+            record("core.room", "hall", json!({"id": "hall", "name": "Hall"})),
+            record("core.room", "void", json!({"id": "void", "name": " "})),
+            record("core.room", "loft", json!({"id": "loft", "name": ["Loft"]})),
+            record(
+                "core.exit",
+                "in",
+                json!({"id": "in", "from": "hall", "to": "void", "direction": "in"}),
+            ),
+        ]);
+        let engine = engine(backend);
+        let mut core = engine.core();
+
+        let report = core
+            .check_integrity(None, IntegrityMode::Quarantine)
+            .unwrap();
+        let found: Vec<(&str, IssueKind)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.key.as_str(), issue.problem))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("loft", IssueKind::SchemaViolation),
+                ("void", IssueKind::Invalid),
+                ("in", IssueKind::DanglingReference)
+            ]
+        );
+        assert_eq!((report.quarantined, report.unresolved()), (3, 0));
+        assert_eq!(core.kernel().content().rooms().count(), 1);
+        assert!(core
+            .check_integrity(None, IntegrityMode::Report)
+            .unwrap()
+            .issues
+            .is_empty());
+    }
+}
@@ -1,21 +1,265 @@
 //! Engine crate: composes kernel, router, and transport layers into a single-world runner.
 
-use std::sync::Arc;
-use std::time::{Instant, SystemTime};
+pub mod accounts;
+pub mod admin;
+pub mod assist;
+pub mod audit;
+pub mod budget;
+pub mod builder;
+pub mod bundle;
+pub mod changeset;
+pub mod config;
+pub mod guardrail;
+pub mod integrity;
+pub mod logging;
+pub mod narration;
+pub mod reload;
 
-use aqevia_kernel::Kernel;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use aqevia_ai::{AiProvider, BudgetConfig, Budgets, Guardrails, TemplateProvider};
+use aqevia_auth::{Account, AuthConfig, AuthError, AuthService, Role, ACCOUNT_KIND, BAN_KIND};
+use aqevia_kernel::{ContentKind, Kernel};
 use aqevia_router::Router;
 use aqevia_storage::{
-    StorageBackend, StorageConfig, StorageController, StorageResult, WorldRecord,
+    LimitError, Recovery, StorageBackend, StorageConfig, StorageController, StorageError,
+    StorageResult, WorldRecord,
 };
-use aqevia_transport::{ObservabilityState, Transport};
+use aqevia_transport::{ControlPlane, ObservabilityState, Transport};
+use serde_json::Value;
+use tracing::{debug_span, warn};
 
-pub struct Engine<B: StorageBackend> {
+pub use accounts::RegistrationApi;
+pub use admin::AdminApi;
+pub use assist::{AssistApi, AssistTask, Proposal, ProposalStatus, ProposedRecord};
+pub use audit::{
+    content_hash, AuditEntry, AuditQuery, AUDIT_KIND, AUDIT_SUMMARIZED_METRIC,
+    DEFAULT_AUDIT_RETENTION,
+};
+pub use budget::{AI_USAGE_KIND, BUDGET_EXHAUSTED_METRIC};
+pub use builder::BuilderApi;
+pub use bundle::{canonical_json, BundleError, ImportReport, RecordError, WorldBundle};
+pub use changeset::{Changeset, ChangesetApi, ChangesetStatus, CHANGESET_KIND};
+pub use config::{ConfigError, EngineConfig, Setting, CONFIG_ENV, SETTINGS};
+pub use guardrail::{world_facts, REJECTIONS_METRIC};
+pub use integrity::{
+    IntegrityAction, IntegrityIssue, IntegrityMode, IntegrityReport, IssueKind, MissingReference,
+    QUARANTINE_KIND,
+};
+pub use logging::{LogError, LogFormat};
+pub use narration::{narration_job, NarrationConfig, NarrationJob, NarrationQueue, NarrationStats};
+pub use reload::{ConfigLoader, ReloadReport, CONFIG_RELOADS_METRIC};
+
+/// How often [`Engine::tick`] prunes audit entries past their retention.
+const AUDIT_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Storage `kind` for the snapshot payload written by [`Engine::run_one_world`].
+pub const SNAPSHOT_KIND: &str = "core.snapshot";
+
+/// Counter of records refused for exceeding a [`RecordLimits`](aqevia_storage::RecordLimits) limit, labelled by `limit` and
+/// by `source`: `control_plane` for submitted content, `storage` for records refused on entry
+/// to the `StorageController`.
+pub const LIMIT_REJECTIONS_METRIC: &str = "aqevia_record_limit_rejections_total";
+
+/// Counter of failed storage operations, labelled by the error's `kind`.
+pub const STORAGE_ERRORS_METRIC: &str = "aqevia_storage_errors_total";
+
+/// Counter of queued records a flush dropped because storage refused them.
+pub const DROPPED_RECORDS_METRIC: &str = "aqevia_storage_dropped_records_total";
+
+/// Mutable world state shared between the engine loop and control-plane handlers.
+pub struct EngineCore<B: StorageBackend> {
     transport: Transport,
     storage: StorageController<B>,
     observability: Arc<ObservabilityState>,
+    world_id: String,
+    changesets: BTreeMap<String, Changeset>,
+    /// Number in the newest changeset id handed out, `cs-<n>`.
+    changeset_seq: u64,
+    proposals: BTreeMap<String, Proposal>,
+    proposal_seq: u64,
+    guardrails: Arc<Guardrails>,
+    budgets: Arc<Budgets>,
+    audit_seq: u64,
+    audit_retention: Option<Duration>,
+    paused: bool,
+    config: Option<reload::ConfigSource>,
+}
+
+impl<B: StorageBackend> EngineCore<B> {
+    pub fn kernel(&self) -> &Kernel {
+        self.transport.router().kernel()
+    }
+
+    pub fn kernel_mut(&mut self) -> &mut Kernel {
+        self.transport.router_mut().kernel_mut()
+    }
+
+    pub fn world_id(&self) -> &str {
+        &self.world_id
+    }
+
+    /// Whether an operator has paused the tick loop.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.observability.set_paused(paused);
+    }
+
+    /// How long audit entries are kept; `None` keeps them forever.
+    pub fn audit_retention(&self) -> Option<Duration> {
+        self.audit_retention
+    }
+
+    pub fn set_audit_retention(&mut self, retention: Option<Duration>) {
+        self.audit_retention = retention;
+    }
+
+    /// Filters applied to AI output before it reaches builders or players.
+    pub fn guardrails(&self) -> &Arc<Guardrails> {
+        &self.guardrails
+    }
+
+    pub fn observability(&self) -> &Arc<ObservabilityState> {
+        &self.observability
+    }
+
+    pub fn storage(&self) -> &StorageController<B> {
+        &self.storage
+    }
+
+    /// Queue a record for the next flush. A record over the storage limits is refused, logged,
+    /// and counted in `aqevia_record_limit_rejections_total`.
+    pub fn record(&mut self, record: WorldRecord) -> Result<(), LimitError> {
+        let result = self.storage.record(record);
+        if let Err(err) = &result {
+            warn!(limit = err.limit(), error = %err, "record refused");
+            self.count_limit_rejection(err, "storage");
+        }
+        result
+    }
+
+    /// Hold a value submitted through the control plane to the storage limits before it
+    /// touches the World.
+    pub fn check_limits(&self, value: &Value) -> Result<(), LimitError> {
+        let result = self.storage.config().limits.check(&value.to_string());
+        if let Err(err) = &result {
+            self.count_limit_rejection(err, "control_plane");
+        }
+        result
+    }
+
+    fn count_limit_rejection(&self, err: &LimitError, source: &str) {
+        self.observability.add_counter(
+            LIMIT_REJECTIONS_METRIC,
+            "Records refused for exceeding a payload limit.",
+            &[("limit", err.limit()), ("source", source)],
+            1,
+        );
+    }
+
+    /// Flush everything pending and publish the outcome to observability.
+    pub fn flush_all(&mut self) -> StorageResult<()> {
+        let result = self.storage.flush_all();
+        self.note_write(&result);
+        result
+    }
+
+    fn flush_if_due(&mut self) -> StorageResult<()> {
+        match self.storage.flush_if_due() {
+            Ok(false) => Ok(()),
+            result => {
+                self.note_write(&result);
+                result.map(drop)
+            }
+        }
+    }
+
+    /// Publish the outcome of a write to observability. A successful write makes storage ready
+    /// again. A failure is counted in `aqevia_storage_errors_total` and reported by its
+    /// [`Recovery`]: a busy store or refused records leave storage ready, while anything that
+    /// degrades or aborts marks it not ready.
+    pub(crate) fn note_write<T>(&mut self, result: &StorageResult<T>) {
+        let dropped = self.storage.take_dropped();
+        if dropped > 0 {
+            self.observability.add_counter(
+                DROPPED_RECORDS_METRIC,
+                "Queued records dropped because storage refused them.",
+                &[],
+                dropped,
+            );
+        }
+        match result {
+            Ok(_) => {
+                let stats = self.storage.stats();
+                self.observability
+                    .note_flush(stats.flush_count, stats.last_flush);
+                self.observability.mark_storage_ready(true);
+            }
+            Err(err) => {
+                self.observability.add_counter(
+                    STORAGE_ERRORS_METRIC,
+                    "Failed storage operations, by error kind.",
+                    &[("kind", err.kind())],
+                    1,
+                );
+                match err.recovery() {
+                    Recovery::Retry | Recovery::Skip => {
+                        self.observability.note_transient_error(err.to_string())
+                    }
+                    Recovery::Degrade | Recovery::Abort => {
+                        self.observability.note_error(err.to_string())
+                    }
+                }
+            }
+        }
+    }
+
+    /// Load persisted rooms, exits, items, and NPC templates into the kernel. Records that fail
+    /// their schema or no longer decode are skipped, and dangling references are kept, so a single bad row cannot
+    /// keep the World from booting; both are logged for `aqevia-engine check` to fix.
+    fn load_content(&mut self) -> StorageResult<usize> {
+        let (mut loaded, mut skipped) = (0, 0);
+        for kind in ContentKind::ALL {
+            for record in self.storage.load_records(kind.record_kind())? {
+                let Ok(payload) = serde_json::from_str(&record.payload) else {
+                    skipped += 1;
+                    continue;
+                };
+                if self.kernel_mut().load(kind, &payload).is_ok() {
+                    loaded += 1;
+                } else {
+                    skipped += 1;
+                }
+            }
+        }
+        let problems = self.kernel().content().integrity_problems().len();
+        if skipped > 0 || problems > 0 {
+            warn!(
+                skipped,
+                problems, "stored content has integrity problems; run `aqevia-engine check`"
+            );
+        }
+        Ok(loaded)
+    }
+}
+
+pub type SharedCore<B> = Arc<Mutex<EngineCore<B>>>;
+
+pub struct Engine<B: StorageBackend> {
+    core: SharedCore<B>,
+    auth: Arc<AuthService>,
     start: Instant,
     world_id: String,
+    ai: Arc<dyn AiProvider>,
+    narration: NarrationQueue,
+    last_audit_prune: Option<Instant>,
+    tick_count: u64,
 }
 
 impl<B: StorageBackend> Engine<B> {
@@ -23,45 +267,194 @@ impl<B: StorageBackend> Engine<B> {
         backend: B,
         config: StorageConfig,
         observability: Arc<ObservabilityState>,
+    ) -> StorageResult<Self> {
+        Self::with_auth(backend, config, AuthConfig::default(), observability)
+    }
+
+    /// Build an engine with explicit auth tunables (session lifetime, hashing cost).
+    pub fn with_auth(
+        backend: B,
+        config: StorageConfig,
+        auth_config: AuthConfig,
+        observability: Arc<ObservabilityState>,
     ) -> StorageResult<Self> {
         let kernel = Kernel::new();
         let router = Router::new(kernel);
         let world_id = router.world_context().to_string();
         let transport = Transport::new(router);
-        let storage = StorageController::new(backend, config)?;
+        let mut storage = StorageController::new(backend, config)?;
+        // A changeset record embeds every staged payload and its before-image, each of which
+        // was checked when it was staged.
+        storage.exempt_from_limits(CHANGESET_KIND);
+        let auth = Arc::new(AuthService::new(auth_config));
+        auth.load_accounts(&storage.load_records(ACCOUNT_KIND)?)
+            .map_err(unreadable)?;
+        auth.load_bans(&storage.load_records(BAN_KIND)?)
+            .map_err(unreadable)?;
+        let mut core = EngineCore {
+            transport,
+            storage,
+            observability: observability.clone(),
+            world_id: world_id.clone(),
+            changesets: BTreeMap::new(),
+            changeset_seq: 0,
+            proposals: BTreeMap::new(),
+            proposal_seq: 0,
+            guardrails: Arc::new(Guardrails::default()),
+            budgets: Arc::new(Budgets::default()),
+            audit_seq: 0,
+            audit_retention: Some(DEFAULT_AUDIT_RETENTION),
+            paused: false,
+            config: None,
+        };
+        core.load_content()?;
+        core.load_changesets()?;
+        core.load_audit_seq()?;
+        core.load_ai_usage()?;
         observability.mark_storage_ready(true);
-        let stats = storage.stats();
+        let stats = core.storage.stats();
         observability.note_flush(stats.flush_count, stats.last_flush);
+        observability.note_read_pool(stats.read_pool);
+        let ai: Arc<dyn AiProvider> = Arc::new(TemplateProvider::default());
+        let narration = NarrationQueue::start(
+            ai.clone(),
+            core.guardrails.clone(),
+            core.budgets.clone(),
+            core.transport.router().sessions().clone(),
+            observability.clone(),
+            NarrationConfig::default(),
+        );
         Ok(Engine {
-            transport,
-            storage,
-            observability,
+            core: Arc::new(Mutex::new(core)),
+            auth,
             start: Instant::now(),
             world_id,
+            ai,
+            narration,
+            last_audit_prune: None,
+            tick_count: 0,
         })
     }
 
+    /// Lock the shared world state.
+    pub fn core(&self) -> MutexGuard<'_, EngineCore<B>> {
+        self.core.lock().expect("lock poisoning")
+    }
+
     pub fn run_one_world(&mut self, payload: &str) -> StorageResult<String> {
-        let record = WorldRecord {
-            world_id: self.world_id.clone(),
-            payload: payload.to_string(),
-            timestamp: SystemTime::now(),
-        };
-        self.storage.record(record);
-        if self.storage.flush_if_due()? {
-            let stats = self.storage.stats();
-            self.observability
-                .note_flush(stats.flush_count, stats.last_flush);
-        }
-        Ok(self.transport.deliver(payload))
+        let record = WorldRecord::new(&self.world_id, SNAPSHOT_KIND, &self.world_id, payload);
+        let mut core = self.core();
+        core.record(record)?;
+        core.flush_if_due()?;
+        Ok(core.transport.deliver(payload))
     }
 
     pub fn flush_all(&mut self) -> StorageResult<()> {
-        self.storage.flush_all()?;
-        let stats = self.storage.stats();
-        self.observability
-            .note_flush(stats.flush_count, stats.last_flush);
-        Ok(())
+        self.core().flush_all()
+    }
+
+    /// Create an account and persist it immediately so it survives a restart.
+    pub fn create_account(
+        &mut self,
+        username: &str,
+        password: &str,
+        role: Role,
+    ) -> Result<Account, EngineError> {
+        let account = self.auth.create_account(username, password, role)?;
+        let mut core = self.core();
+        core.record(account.to_record(&self.world_id))
+            .map_err(StorageError::from)?;
+        core.flush_all()?;
+        Ok(account)
+    }
+
+    pub fn auth(&self) -> &Arc<AuthService> {
+        &self.auth
+    }
+
+    /// AI Provider behind builder assists; the deterministic template provider by default.
+    pub fn ai_provider(&self) -> &Arc<dyn AiProvider> {
+        &self.ai
+    }
+
+    /// Use `provider` for builder assists and for narration jobs picked up from now on.
+    pub fn set_ai_provider(&mut self, provider: Arc<dyn AiProvider>) {
+        self.narration.set_provider(provider.clone());
+        self.ai = provider;
+    }
+
+    /// Use `guardrails` for builder assists and for narration jobs picked up from now on.
+    pub fn set_guardrails(&mut self, guardrails: Arc<Guardrails>) {
+        self.narration.set_guardrails(guardrails.clone());
+        self.core().guardrails = guardrails;
+    }
+
+    /// Change the AI request and token budgets. Usage already charged is kept.
+    pub fn set_ai_budgets(&self, config: BudgetConfig) {
+        self.core().budgets.set_config(config);
+    }
+
+    /// Re-read the configuration source and apply its reloadable settings; see
+    /// [`EngineCore::reload_config`]. Narration workers pick up new guardrails on the next tick.
+    pub fn reload_config(&self) -> Result<ReloadReport, ConfigError> {
+        self.core().reload_config(None, &self.auth)
+    }
+
+    pub fn narration(&self) -> &NarrationQueue {
+        &self.narration
+    }
+
+    /// Restart the narration queue with new tunables. Jobs still pending on the old queue are
+    /// answered with their fallback text.
+    pub fn configure_narration(&mut self, config: NarrationConfig) {
+        let core = self.core();
+        let queue = NarrationQueue::start(
+            self.ai.clone(),
+            core.guardrails.clone(),
+            core.budgets.clone(),
+            core.transport.router().sessions().clone(),
+            core.observability.clone(),
+            config,
+        );
+        drop(core);
+        let previous = std::mem::replace(&mut self.narration, queue);
+        previous.expire(Instant::now() + previous.config().deadline);
+    }
+
+    /// One pass of the main loop: hand reloaded guardrails to the narration workers, route
+    /// queued session commands and queue narration for the events they raise, answer overdue narration with fallback text, queue changed AI usage,
+    /// flush on the storage cadence, and prune expired audit entries once an hour. Never waits
+    /// on AI. Returns `false` without doing anything while the World is paused.
+    pub fn tick(&mut self) -> StorageResult<bool> {
+        self.tick_count += 1;
+        let _span = debug_span!("tick", tick = self.tick_count).entered();
+        let prune_due = self
+            .last_audit_prune
+            .is_none_or(|last| last.elapsed() >= AUDIT_PRUNE_INTERVAL);
+        let mut core = self.core.lock().expect("lock poisoning");
+        if !Arc::ptr_eq(&self.narration.guardrails(), &core.guardrails) {
+            self.narration.set_guardrails(core.guardrails.clone());
+        }
+        if core.paused {
+            return Ok(false);
+        }
+        pump(&core, &self.narration);
+        self.narration.expire(Instant::now());
+        core.sync_ai_usage();
+        core.flush_if_due()?;
+        core.observability
+            .note_read_pool(core.storage.stats().read_pool);
+        if prune_due {
+            self.last_audit_prune = Some(Instant::now());
+            core.prune_audit(SystemTime::now())?;
+        }
+        Ok(true)
+    }
+
+    /// Route commands queued by WebSocket sessions, deliver their output, and queue narration
+    /// for the kernel events they raise.
+    pub fn pump_sessions(&mut self) -> usize {
+        pump(&self.core(), &self.narration)
     }
 
     pub fn uptime_seconds(&self) -> u64 {
@@ -69,19 +462,91 @@ impl<B: StorageBackend> Engine<B> {
     }
 
     pub fn storage_backend_name(&self) -> &'static str {
-        self.storage.backend_name()
+        self.core().storage.backend_name()
+    }
+}
+
+impl<B: StorageBackend + 'static> Engine<B> {
+    /// Control plane (auth middleware, `/api/auth/*`, `/api/builder/*` including changesets and
+    /// AI assists, `/api/admin/*`, and `/ws`) bound to this engine's state.
+    pub fn control_plane(&self) -> ControlPlane {
+        let sessions = self.core().transport.router().sessions().clone();
+        ControlPlane::new(self.auth.clone(), sessions)
+            .with_handler(Arc::new(RegistrationApi::new(
+                self.core.clone(),
+                self.auth.clone(),
+            )))
+            .with_handler(Arc::new(BuilderApi::new(self.core.clone())))
+            .with_handler(Arc::new(ChangesetApi::new(self.core.clone())))
+            .with_handler(Arc::new(AssistApi::new(self.core.clone(), self.ai.clone())))
+            .with_handler(Arc::new(AdminApi::new(
+                self.core.clone(),
+                self.auth.clone(),
+            )))
+    }
+}
+
+fn pump<B: StorageBackend>(core: &EngineCore<B>, narration: &NarrationQueue) -> usize {
+    let router = core.transport.router();
+    router.pump_sessions_with(|session, event| {
+        if let Some(job) = narration_job(router.kernel().content(), session, &event) {
+            narration.enqueue(job);
+        }
+    })
+}
+
+/// A stored record the engine cannot read back, which only a damaged store produces.
+fn unreadable(err: AuthError) -> StorageError {
+    StorageError::Corrupt {
+        message: err.to_string(),
+        source: Some(Box::new(err)),
     }
 }
 
+/// Errors surfaced by engine operations that span auth and storage.
+#[derive(thiserror::Error, Debug)]
+pub enum EngineError {
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
 #[cfg(test)]
-mod tests {
-    use super::*;
-    use aqevia_storage::{StorageBackend, StorageStats};
+pub(crate) mod test_support {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::SystemTime;
+
+    use aqevia_auth::{AuthConfig, Role};
+    use aqevia_kernel::ContentKind;
+    use aqevia_storage::{
+        StorageBackend, StorageConfig, StorageError, StorageResult, StorageStats, WorldRecord,
+    };
+    use aqevia_transport::{ControlPlane, HttpRequest, ObservabilityState};
+    use serde_json::Value;
 
+    use crate::audit::AUDIT_KIND;
+    use crate::Engine;
+
+    /// Builds the error a [`DummyBackend`] fails writes with.
+    pub type Failure = Arc<Mutex<Option<fn() -> StorageError>>>;
+
+    /// In-memory backend with upsert/tombstone semantics matching the SQLite backend.
     #[derive(Default)]
-    struct DummyBackend {
+    pub struct DummyBackend {
         stats: StorageStats,
-        persisted: usize,
+        persisted: BTreeMap<(String, String), WorldRecord>,
+        /// While set, every write fails with the error it builds.
+        pub fail: Failure,
+    }
+
+    impl DummyBackend {
+        pub fn with_records(records: Vec<WorldRecord>) -> Self {
+            let mut backend = DummyBackend::default();
+            backend.persist_batch(&records).unwrap();
+            backend
+        }
     }
 
     impl StorageBackend for DummyBackend {
@@ -92,12 +557,31 @@ mod tests {
         }
 
         fn persist_batch(&mut self, batch: &[WorldRecord]) -> StorageResult<()> {
+            if let Some(fail) = *self.fail.lock().unwrap() {
+                return Err(fail());
+            }
             self.stats.flush_count += 1;
             self.stats.last_flush = Some(SystemTime::now());
-            self.persisted += batch.len();
+            for record in batch {
+                let identity = (record.kind.clone(), record.key.clone());
+                if record.deleted {
+                    self.persisted.remove(&identity);
+                } else {
+                    self.persisted.insert(identity, record.clone());
+                }
+            }
             Ok(())
         }
 
+        fn load_records(&self, kind: &str) -> StorageResult<Vec<WorldRecord>> {
+            Ok(self
+                .persisted
+                .values()
+                .filter(|record| record.kind == kind)
+                .cloned()
+                .collect())
+        }
+
         fn stats(&self) -> StorageStats {
             self.stats
         }
@@ -107,14 +591,109 @@ mod tests {
         }
     }
 
+    pub fn observability() -> Arc<ObservabilityState> {
+        Arc::new(ObservabilityState::new("0.2.0", "world", "dummy"))
+    }
+
+    /// Default auth settings with a cheap password hash.
+    pub fn auth_config() -> AuthConfig {
+        AuthConfig {
+            hash_iterations: 16,
+            ..AuthConfig::default()
+        }
+    }
+
+    /// An Engine over `backend` with [`auth_config`] and its own observability state.
+    pub fn engine(backend: DummyBackend) -> Engine<DummyBackend> {
+        Engine::with_auth(
+            backend,
+            StorageConfig::default(),
+            auth_config(),
+            observability(),
+        )
+        .unwrap()
+    }
+
+    /// An Engine, its control plane, and a signed-in account to call it as.
+    pub struct Harness {
+        pub engine: Engine<DummyBackend>,
+        pub plane: ControlPlane,
+        pub bearer: String,
+    }
+
+    impl Harness {
+        /// Signed in as `builder`.
+        pub fn builder(backend: DummyBackend) -> Self {
+            Harness::with_engine(engine(backend), "builder", Role::Builder)
+        }
+
+        /// Signed in as `root`, an admin.
+        pub fn admin(backend: DummyBackend) -> Self {
+            Harness::with_engine(engine(backend), "root", Role::Admin)
+        }
+
+        /// Sign in to an Engine the caller has set up, creating the account unless the
+        /// Engine already has it. The control plane is built last, so it sees that setup.
+        pub fn with_engine(mut engine: Engine<DummyBackend>, username: &str, role: Role) -> Self {
+            if engine.auth().account(username).is_none() {
+                engine
+                    .create_account(username, "password123", role)
+                    .unwrap();
+            }
+            let token = engine.auth().login(username, "password123").unwrap().token;
+            let plane = engine.control_plane();
+            Harness {
+                engine,
+                plane,
+                bearer: format!("Bearer {}", token),
+            }
+        }
+
+        /// Dispatch a request as the signed-in account; a body that is not JSON reads as null.
+        pub fn call(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+            let request = HttpRequest::new(method, path)
+                .with_header("Authorization", &self.bearer)
+                .with_body(body);
+            let response = self.plane.dispatch(request);
+            let value = serde_json::from_slice(&response.body).unwrap_or(Value::Null);
+            (response.status, value)
+        }
+
+        pub fn live(&self, kind: ContentKind, id: &str) -> Option<Value> {
+            self.engine.core().kernel().content().get(kind, id)
+        }
+
+        /// Actions of the stored audit entries, oldest first.
+        pub fn audit_actions(&self) -> Vec<String> {
+            self.engine
+                .core()
+                .storage()
+                .load_records(AUDIT_KIND)
+                .unwrap()
+                .iter()
+                .map(|record| {
+                    let entry: Value = serde_json::from_str(&record.payload).unwrap();
+                    entry["action"].as_str().unwrap().to_string()
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::{auth_config, engine, observability, DummyBackend};
+    use super::*;
+
     #[test]
     fn engine_records_and_delivers() {
-        let state = Arc::new(ObservabilityState::new("0.2.0", "world", "dummy"));
+        let state = observability();
         let mut engine = Engine::new(
             DummyBackend::default(),
             StorageConfig {
                 flush_interval_ms: 1,
                 batch_capacity: 1,
+                ..StorageConfig::default()
             },
             state,
         )
@@ -125,12 +704,13 @@ mod tests {
 
     #[test]
     fn engine_flushes_collection() {
-        let state = Arc::new(ObservabilityState::new("0.2.0", "world", "dummy"));
+        let state = observability();
         let mut engine = Engine::new(
             DummyBackend::default(),
             StorageConfig {
                 flush_interval_ms: 1,
                 batch_capacity: 10,
+                ..StorageConfig::default()
             },
             state,
         )
@@ -138,4 +718,110 @@ mod tests {
         engine.run_one_world("say").unwrap();
         assert!(engine.flush_all().is_ok());
     }
+
+    #[test]
+    fn storage_errors_decide_readiness() {
+        let state = observability();
+        let backend = DummyBackend::default();
+        let fail = backend.fail.clone();
+        let mut engine = Engine::new(backend, StorageConfig::default(), state.clone()).unwrap();
+        let busy = || StorageError::Busy {
+            message: "database is locked".into(),
+            source: None,
+        };
+        let full = || StorageError::Io(std::io::ErrorKind::StorageFull.into());
+
+        *fail.lock().unwrap() = Some(busy);
+        engine.run_one_world("look").unwrap();
+        assert_eq!(engine.flush_all().unwrap_err().kind(), "busy");
+        assert!(state.storage_ready());
+        assert_eq!(engine.core().storage().pending().len(), 1);
+
+        *fail.lock().unwrap() = Some(full);
+        assert_eq!(
+            engine.flush_all().unwrap_err().recovery(),
+            Recovery::Degrade
+        );
+        assert!(!state.storage_ready());
+        assert!(state
+            .render_metrics()
+            .contains("aqevia_storage_errors_total{kind=\"io\"} 1"));
+
+        *fail.lock().unwrap() = None;
+        engine.flush_all().unwrap();
+        assert!(state.storage_ready());
+        assert!(engine.core().storage().pending().is_empty());
+    }
+
+    #[test]
+    fn created_accounts_are_persisted_and_reloaded() {
+        let state = observability();
+        let mut engine = Engine::with_auth(
+            DummyBackend::default(),
+            StorageConfig::default(),
+            auth_config(),
+            state.clone(),
+        )
+        .unwrap();
+        engine
+            .create_account("admin", "password123", Role::Admin)
+            .unwrap();
+        assert!(matches!(
+            engine.create_account("admin", "password123", Role::Admin),
+            Err(EngineError::Auth(AuthError::AccountExists(_)))
+        ));
+        let backend =
+            DummyBackend::with_records(engine.core().storage().load_records(ACCOUNT_KIND).unwrap());
+        let reloaded =
+            Engine::with_auth(backend, StorageConfig::default(), auth_config(), state).unwrap();
+        assert!(reloaded.auth().login("admin", "password123").is_ok());
+    }
+
+    #[test]
+    fn control_plane_and_sessions_share_engine_auth() {
+        let mut engine = engine(DummyBackend::default());
+        engine
+            .create_account("player", "password123", Role::Player)
+            .unwrap();
+        let plane = engine.control_plane();
+        let login = plane.dispatch(
+            aqevia_transport::HttpRequest::new("POST", "/api/auth/login")
+                .with_body(r#"{"username":"player","password":"password123"}"#),
+        );
+        assert_eq!(login.status, 200);
+
+        let (id, events) = plane.sessions().open("player", None);
+        plane.sessions().submit(id, "look");
+        assert_eq!(engine.pump_sessions(), 1);
+        assert!(matches!(
+            events.recv().unwrap(),
+            aqevia_router::SessionEvent::Message(text) if text.contains("look")
+        ));
+    }
+
+    #[test]
+    fn examined_items_are_narrated_after_the_routed_reply() {
+        let mut engine = engine(DummyBackend::default());
+        engine
+            .core()
+            .kernel_mut()
+            .content_mut()
+            .upsert(
+                ContentKind::Item,
+                &serde_json::json!({"id": "orb", "name": "Glass Orb"}),
+            )
+            .unwrap();
+        let sessions = engine.core().transport.router().sessions().clone();
+        let (id, events) = sessions.open("player", None);
+        sessions.submit(id, "examine the glass orb");
+        assert!(engine.tick().unwrap());
+
+        let next = || match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            aqevia_router::SessionEvent::Message(text) => text,
+            other => panic!("unexpected event {:?}", other),
+        };
+        assert!(next().contains("Routing"));
+        assert!(next().contains("Glass Orb"));
+        assert_eq!(engine.narration().stats().completed, 1);
+    }
 }
@@ -0,0 +1,235 @@
+//! Process-wide log output. Every crate emits `tracing` events and spans; this module installs
+//! the one subscriber that filters them per module and writes them to standard error as
+//! human-readable lines or JSON lines.
+//!
+//! The same subscriber copies each event it lets through into a [`LogBuffer`], which the
+//! control plane serves on `/logs`.
+//!
+//! Spans carry context onto the events inside them: `tick{tick}` for each Engine tick,
+//! `command{session_id, account, verb}` for routed commands, `flush{flush_id, records}` for
+//! storage flushes, `http{method, path, peer}` for control-plane requests, and
+//! `narration{job_id, session_id}` for narration jobs.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aqevia_transport::{LogBuffer, LogRecord, LogSpan};
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+/// How log lines are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One readable line per event, with span context before the message.
+    #[default]
+    Human,
+    /// One JSON object per line with `timestamp`, `level`, `target`, `fields`, and `spans`.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "human" => Ok(LogFormat::Human),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("expected \"human\" or \"json\", not {:?}", other)),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LogError {
+    #[error("invalid log filter {filter:?}: {message}")]
+    Filter { filter: String, message: String },
+    #[error("logging is already initialized")]
+    AlreadyInitialized,
+}
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Parse a filter such as `info,aqevia_storage=debug`: a default level followed by
+/// per-module overrides.
+pub fn parse_filter(filter: &str) -> Result<EnvFilter, LogError> {
+    EnvFilter::try_new(filter).map_err(|err| LogError::Filter {
+        filter: filter.to_string(),
+        message: err.to_string(),
+    })
+}
+
+/// Install the global subscriber, keeping recent events in `buffer`. Can only succeed once
+/// per process.
+pub fn init(filter: &str, format: LogFormat, buffer: Arc<LogBuffer>) -> Result<(), LogError> {
+    let (filter, handle) = reload::Layer::new(parse_filter(filter)?);
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(BufferLayer { buffer });
+    let ansi = io::stderr().is_terminal();
+    let result = match format {
+        LogFormat::Human => registry
+            .with(fmt::layer().with_writer(io::stderr).with_ansi(ansi))
+            .try_init(),
+        LogFormat::Json => registry
+            .with(
+                fmt::layer()
+                    .json()
+                    .with_current_span(false)
+                    .with_span_list(true)
+                    .with_writer(io::stderr),
+            )
+            .try_init(),
+    };
+    result.map_err(|_| LogError::AlreadyInitialized)?;
+    let _ = FILTER.set(handle);
+    Ok(())
+}
+
+/// Replace the filter of the installed subscriber. Does nothing when [`init`] was never
+/// called, as in tests and embedded Engines.
+pub fn set_filter(filter: &str) -> Result<(), LogError> {
+    let parsed = parse_filter(filter)?;
+    if let Some(handle) = FILTER.get() {
+        handle.reload(parsed).map_err(|err| LogError::Filter {
+            filter: filter.to_string(),
+            message: err.to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Copies each event, with the fields of its enclosing spans, into a [`LogBuffer`].
+struct BufferLayer {
+    buffer: Arc<LogBuffer>,
+}
+
+/// Fields recorded on a span, kept in its extensions for the events inside it.
+struct SpanFields(BTreeMap<String, String>);
+
+#[derive(Default)]
+struct FieldMap {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldMap {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.insert(field, format!("{:?}", value));
+    }
+}
+
+impl FieldMap {
+    fn insert(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = value;
+        } else {
+            self.fields.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl<S> Layer<S> for BufferLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = FieldMap::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(fields.fields));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldMap::default();
+        values.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            if let Some(SpanFields(existing)) = span.extensions_mut().get_mut::<SpanFields>() {
+                existing.extend(fields.fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldMap::default();
+        event.record(&mut fields);
+        let spans = ctx
+            .event_scope(event)
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| LogSpan {
+                name: span.name().to_string(),
+                fields: span
+                    .extensions()
+                    .get::<SpanFields>()
+                    .map(|SpanFields(fields)| fields.clone())
+                    .unwrap_or_default(),
+            })
+            .collect();
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        self.buffer.push(LogRecord {
+            seq: 0,
+            timestamp_ms,
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_string(),
+            message: fields.message,
+            fields: fields.fields,
+            spans,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aqevia_transport::LogFilter;
+
+    #[test]
+    fn filters_and_formats_parse() {
+        assert!(parse_filter("info,aqevia_storage=debug,aqevia_router::session=warn").is_ok());
+        let err = parse_filter("info,aqevia_storage=loud").unwrap_err();
+        assert!(err.to_string().starts_with("invalid log filter"), "{}", err);
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert!("xml".parse::<LogFormat>().is_err());
+        assert!(set_filter("debug").is_ok());
+    }
+
+    #[test]
+    fn buffered_events_carry_span_fields() {
+        let buffer = Arc::new(LogBuffer::new(10));
+        let subscriber = tracing_subscriber::registry().with(BufferLayer {
+            buffer: buffer.clone(),
+        });
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("flush", flush_id = 3, records = 2).entered();
+            tracing::error!(backend = "sqlite", error = %"disk full", "flush failed");
+        });
+        let records = buffer.query(&LogFilter::default(), 10);
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.message, "flush failed");
+        assert_eq!(record.level, tracing::Level::ERROR);
+        assert_eq!(record.fields["backend"], "sqlite");
+        assert_eq!(record.fields["error"], "disk full");
+        assert_eq!(record.spans[0].name, "flush");
+        assert_eq!(record.spans[0].fields["flush_id"], "3");
+    }
+}
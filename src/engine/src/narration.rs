@@ -0,0 +1,560 @@
+//! Runtime narration: kernel events become AI jobs that run off the tick loop.
+//!
+//! The tick loop only enqueues. Worker threads call the AI Provider and deliver the text to
+//! the session that triggered the event. Every job has a deadline; once it passes, the tick
+//! loop delivers the job's canned fallback text and any late provider reply is dropped. A full
+//! queue falls back immediately, so the tick loop never waits on AI. Replies that fail the
+//! guardrails fall back too, as do jobs whose account or World has exhausted its AI budget.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use aqevia_ai::{AiError, AiProvider, Budgets, CompletionRequest, Guardrails, WorldFacts};
+use aqevia_kernel::{KernelEvent, WorldContent};
+use aqevia_router::{SessionId, SessionRegistry};
+use aqevia_transport::ObservabilityState;
+use serde::Serialize;
+use tracing::{debug, info_span, warn};
+
+use crate::budget::note_exhausted;
+use crate::guardrail::{report_rejection, world_facts};
+
+/// Counter of finished narration jobs, labelled by `outcome` (`completed` or `fallback`).
+pub const NARRATION_METRIC: &str = "aqevia_narration_jobs_total";
+
+/// Worker count, per-job deadline, and queue capacity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NarrationConfig {
+    pub workers: usize,
+    pub deadline: Duration,
+    pub capacity: usize,
+}
+
+impl Default for NarrationConfig {
+    fn default() -> Self {
+        NarrationConfig {
+            workers: 2,
+            deadline: Duration::from_secs(5),
+            capacity: 64,
+        }
+    }
+}
+
+/// Counters since the queue started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct NarrationStats {
+    /// Jobs accepted onto the queue.
+    pub queued: u64,
+    /// Jobs answered by the provider before their deadline.
+    pub completed: u64,
+    /// Jobs answered with fallback text: deadline passed, provider failed, budget exhausted,
+    /// or queue full.
+    pub fallbacks: u64,
+}
+
+/// One narration request bound for a session.
+pub struct NarrationJob {
+    pub session: SessionId,
+    pub request: CompletionRequest,
+    /// Delivered instead of the provider's text when the job cannot finish in time.
+    pub fallback: String,
+    /// What the reply may say about the World, checked by the guardrails.
+    pub facts: WorldFacts,
+}
+
+struct Queued {
+    id: u64,
+    request: CompletionRequest,
+    facts: WorldFacts,
+}
+
+struct Pending {
+    session: SessionId,
+    fallback: String,
+    deadline: Instant,
+}
+
+struct Shared {
+    provider: RwLock<Arc<dyn AiProvider>>,
+    guardrails: RwLock<Arc<Guardrails>>,
+    budgets: Arc<Budgets>,
+    sessions: Arc<SessionRegistry>,
+    observability: Arc<ObservabilityState>,
+    pending: Mutex<BTreeMap<u64, Pending>>,
+    queued: AtomicU64,
+    completed: AtomicU64,
+    fallbacks: AtomicU64,
+}
+
+impl Shared {
+    /// Deliver `text`, or the fallback when `None`, unless the job was already answered.
+    fn resolve(&self, id: u64, text: Option<String>) -> bool {
+        let Some(job) = self.pending.lock().expect("lock poisoning").remove(&id) else {
+            return false;
+        };
+        let (counter, outcome) = match text {
+            Some(_) => (&self.completed, "completed"),
+            None => (&self.fallbacks, "fallback"),
+        };
+        counter.fetch_add(1, Ordering::SeqCst);
+        self.observability.add_counter(
+            NARRATION_METRIC,
+            "Finished narration jobs.",
+            &[("outcome", outcome)],
+            1,
+        );
+        self.sessions
+            .send(job.session, text.unwrap_or(job.fallback));
+        true
+    }
+}
+
+/// Bounded job queue drained by a pool of worker threads. Dropping the queue closes it; idle
+/// workers exit and busy ones exit after their current job.
+pub struct NarrationQueue {
+    shared: Arc<Shared>,
+    sender: SyncSender<Queued>,
+    next_id: AtomicU64,
+    config: NarrationConfig,
+}
+
+impl NarrationQueue {
+    pub fn start(
+        provider: Arc<dyn AiProvider>,
+        guardrails: Arc<Guardrails>,
+        budgets: Arc<Budgets>,
+        sessions: Arc<SessionRegistry>,
+        observability: Arc<ObservabilityState>,
+        config: NarrationConfig,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            provider: RwLock::new(provider),
+            guardrails: RwLock::new(guardrails),
+            budgets,
+            sessions,
+            observability,
+            pending: Mutex::new(BTreeMap::new()),
+            queued: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            fallbacks: AtomicU64::new(0),
+        });
+        let (sender, receiver) = mpsc::sync_channel(config.capacity.max(1));
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..config.workers.max(1) {
+            let shared = shared.clone();
+            let receiver = receiver.clone();
+            thread::spawn(move || work(&shared, &receiver));
+        }
+        NarrationQueue {
+            shared,
+            sender,
+            next_id: AtomicU64::new(0),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> NarrationConfig {
+        self.config
+    }
+
+    /// Swap the provider used for jobs picked up from now on.
+    pub fn set_provider(&self, provider: Arc<dyn AiProvider>) {
+        *self.shared.provider.write().expect("lock poisoning") = provider;
+    }
+
+    pub fn guardrails(&self) -> Arc<Guardrails> {
+        self.shared
+            .guardrails
+            .read()
+            .expect("lock poisoning")
+            .clone()
+    }
+
+    pub fn set_guardrails(&self, guardrails: Arc<Guardrails>) {
+        *self.shared.guardrails.write().expect("lock poisoning") = guardrails;
+    }
+
+    /// Queue a job without blocking. Returns `false` when the queue is full, in which case the
+    /// fallback has already been delivered.
+    pub fn enqueue(&self, job: NarrationJob) -> bool {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        self.shared.pending.lock().expect("lock poisoning").insert(
+            id,
+            Pending {
+                session: job.session,
+                fallback: job.fallback,
+                deadline: Instant::now() + self.config.deadline,
+            },
+        );
+        match self.sender.try_send(Queued {
+            id,
+            request: job.request,
+            facts: job.facts,
+        }) {
+            Ok(()) => {
+                self.shared.queued.fetch_add(1, Ordering::SeqCst);
+                true
+            }
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                warn!(job_id = id, "narration queue full; delivering fallback");
+                self.shared.resolve(id, None);
+                false
+            }
+        }
+    }
+
+    /// Deliver fallbacks for every job whose deadline is at or before `now`. Returns how many
+    /// jobs expired.
+    pub fn expire(&self, now: Instant) -> usize {
+        let expired: Vec<u64> = self
+            .shared
+            .pending
+            .lock()
+            .expect("lock poisoning")
+            .iter()
+            .filter(|(_, job)| job.deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        let count = expired
+            .into_iter()
+            .filter(|id| self.shared.resolve(*id, None))
+            .count();
+        if count > 0 {
+            debug!(count, "narration jobs passed their deadline");
+        }
+        count
+    }
+
+    /// Jobs queued or running that have not been answered yet.
+    pub fn pending(&self) -> usize {
+        self.shared.pending.lock().expect("lock poisoning").len()
+    }
+
+    pub fn stats(&self) -> NarrationStats {
+        NarrationStats {
+            queued: self.shared.queued.load(Ordering::SeqCst),
+            completed: self.shared.completed.load(Ordering::SeqCst),
+            fallbacks: self.shared.fallbacks.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn work(shared: &Shared, receiver: &Mutex<Receiver<Queued>>) {
+    loop {
+        let next = receiver.lock().expect("lock poisoning").recv();
+        let Ok(job) = next else {
+            return;
+        };
+        let pending = shared
+            .pending
+            .lock()
+            .expect("lock poisoning")
+            .get(&job.id)
+            .map(|pending| (pending.session, pending.deadline));
+        // Expired or already answered while waiting in the queue.
+        let Some((session, _)) = pending.filter(|(_, deadline)| *deadline > Instant::now()) else {
+            shared.resolve(job.id, None);
+            continue;
+        };
+        let _span = info_span!("narration", job_id = job.id, session_id = session).entered();
+        let provider = shared.provider.read().expect("lock poisoning").clone();
+        let guardrails = shared.guardrails.read().expect("lock poisoning").clone();
+        let account = shared.sessions.get(session).map(|info| info.account);
+        let completion = shared
+            .budgets
+            .metered(account.as_deref(), &job.request, || {
+                provider.complete(&job.request)
+            });
+        let text = match completion {
+            Ok(completion) => match guardrails.check(&completion.text, &job.facts) {
+                Ok(text) => Some(text),
+                Err(violations) => {
+                    report_rejection(&shared.observability, "narration", &violations);
+                    None
+                }
+            },
+            Err(AiError::BudgetExhausted { scope, .. }) => {
+                debug!(%scope, "AI budget exhausted; delivering fallback");
+                note_exhausted(&shared.observability, "narration", &scope);
+                None
+            }
+            Err(err) => {
+                warn!(error = %err, "narration provider failed; delivering fallback");
+                None
+            }
+        };
+        shared.resolve(job.id, text);
+    }
+}
+
+/// Provider request and fallback text for a kernel event, or `None` when the event has
+/// nothing to narrate (for example content removed since the command was routed).
+pub fn narration_job(
+    content: &WorldContent,
+    session: SessionId,
+    event: &KernelEvent,
+) -> Option<NarrationJob> {
+    let (prompt, context, fallback, room) = match event {
+        KernelEvent::Examined { item } => {
+            let item = content.items().find(|candidate| &candidate.id == item)?;
+            let fallback = if item.description.is_empty() {
+                format!("You see nothing special about the {}.", item.name)
+            } else {
+                item.description.clone()
+            };
+            (
+                format!("you study the {}", item.name),
+                format!("item: {} — {}", item.name, item.description),
+                fallback,
+                item.room.as_deref(),
+            )
+        }
+        KernelEvent::SpokeTo { npc } => {
+            let npc = content.npcs().find(|candidate| &candidate.id == npc)?;
+            let fallback = match npc.dialogue.first() {
+                Some(line) => format!("{} says, \"{}\"", npc.name, line),
+                None => format!("{} has nothing to say.", npc.name),
+            };
+            (
+                format!("{} turns to face you", npc.name),
+                format!(
+                    "character: {} — {}\nknown lines: {}",
+                    npc.name,
+                    npc.description,
+                    npc.dialogue.join(" | ")
+                ),
+                fallback,
+                npc.room.as_deref(),
+            )
+        }
+    };
+    let mut request = CompletionRequest::new("narration", prompt).with_system(format!(
+        "You narrate a text world. Describe the moment in one or two sentences, in second \
+         person, without inventing new places or characters.\n\n{}",
+        context
+    ));
+    request.max_tokens = 80;
+    Some(NarrationJob {
+        session,
+        request,
+        fallback,
+        facts: world_facts(content, room),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::observability;
+    use aqevia_ai::{
+        AiResult, BudgetConfig, BudgetLimit, Capabilities, Completion, TemplateProvider,
+    };
+    use aqevia_router::SessionEvent;
+
+    /// Signals on `started` when a request arrives, then blocks until the test sends on
+    /// `release`.
+    struct Stalled {
+        started: Mutex<mpsc::Sender<()>>,
+        release: Mutex<Receiver<()>>,
+    }
+
+    impl AiProvider for Stalled {
+        fn name(&self) -> &str {
+            "stalled"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+
+        fn complete(&self, _request: &CompletionRequest) -> AiResult<Completion> {
+            let _ = self.started.lock().unwrap().send(());
+            let _ = self.release.lock().unwrap().recv();
+            Ok(Completion {
+                text: "too late".into(),
+                provider: "stalled".into(),
+                model: "none".into(),
+            })
+        }
+    }
+
+    fn job(session: SessionId) -> NarrationJob {
+        NarrationJob {
+            session,
+            request: CompletionRequest::new("narration", "a door creaks"),
+            fallback: "Nothing happens.".into(),
+            facts: WorldFacts::default(),
+        }
+    }
+
+    fn message(event: SessionEvent) -> String {
+        match event {
+            SessionEvent::Message(text) => text,
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn workers_deliver_provider_text_to_the_session() {
+        let sessions = Arc::new(SessionRegistry::new());
+        let (id, events) = sessions.open("alice", None);
+        let queue = NarrationQueue::start(
+            Arc::new(TemplateProvider::default()),
+            Arc::new(Guardrails::default()),
+            Arc::new(Budgets::default()),
+            sessions,
+            observability(),
+            NarrationConfig::default(),
+        );
+        assert!(queue.enqueue(job(id)));
+        let text = message(events.recv_timeout(Duration::from_secs(5)).unwrap());
+        assert!(text.contains("a door creaks"), "got {}", text);
+        assert_eq!(
+            queue.stats(),
+            NarrationStats {
+                queued: 1,
+                completed: 1,
+                fallbacks: 0
+            }
+        );
+    }
+
+    #[test]
+    fn guardrail_rejections_fall_back_and_are_counted() {
+        let sessions = Arc::new(SessionRegistry::new());
+        let (id, events) = sessions.open("alice", None);
+        let metrics = observability();
+        let queue = NarrationQueue::start(
+            Arc::new(
+                TemplateProvider::default()
+                    .with_templates("narration", ["A door to the west swings open."]),
+            ),
+            Arc::new(Guardrails::default()),
+            Arc::new(Budgets::default()),
+            sessions,
+            metrics.clone(),
+            NarrationConfig::default(),
+        );
+        let mut narration = job(id);
+        narration.facts.exits = Some(vec!["north".into()]);
+        assert!(queue.enqueue(narration));
+        let text = message(events.recv_timeout(Duration::from_secs(5)).unwrap());
+        assert_eq!(text, "Nothing happens.");
+        let labels = [("source", "narration"), ("reason", "unknown_exit")];
+        assert_eq!(metrics.metric(crate::REJECTIONS_METRIC, &labels), 1);
+        assert_eq!(
+            metrics.metric(NARRATION_METRIC, &[("outcome", "fallback")]),
+            1
+        );
+    }
+
+    #[test]
+    fn exhausted_budgets_fall_back_without_calling_the_provider() {
+        let sessions = Arc::new(SessionRegistry::new());
+        let (id, events) = sessions.open("alice", None);
+        let metrics = observability();
+        let budgets = Arc::new(Budgets::new(BudgetConfig {
+            world: BudgetLimit {
+                requests: Some(1),
+                tokens: None,
+            },
+            ..BudgetConfig::default()
+        }));
+        let queue = NarrationQueue::start(
+            Arc::new(TemplateProvider::default()),
+            Arc::new(Guardrails::default()),
+            budgets.clone(),
+            sessions,
+            metrics.clone(),
+            NarrationConfig::default(),
+        );
+        assert!(queue.enqueue(job(id)));
+        let first = message(events.recv_timeout(Duration::from_secs(5)).unwrap());
+        assert!(first.contains("a door creaks"), "got {}", first);
+        assert!(queue.enqueue(job(id)));
+        let second = message(events.recv_timeout(Duration::from_secs(5)).unwrap());
+        assert_eq!(second, "Nothing happens.");
+        let usage = budgets.usage("account/alice", std::time::SystemTime::now());
+        assert_eq!(usage.requests, 1);
+        let labels = [("source", "narration"), ("scope", "world")];
+        assert_eq!(metrics.metric(crate::BUDGET_EXHAUSTED_METRIC, &labels), 1);
+    }
+
+    #[test]
+    fn deadlines_and_full_queues_fall_back_without_waiting() {
+        let sessions = Arc::new(SessionRegistry::new());
+        let (id, events) = sessions.open("alice", None);
+        let (started_tx, started) = mpsc::channel();
+        let (release, release_rx) = mpsc::channel();
+        let queue = NarrationQueue::start(
+            Arc::new(Stalled {
+                started: Mutex::new(started_tx),
+                release: Mutex::new(release_rx),
+            }),
+            Arc::new(Guardrails::default()),
+            Arc::new(Budgets::default()),
+            sessions,
+            observability(),
+            NarrationConfig {
+                workers: 1,
+                deadline: Duration::from_secs(60),
+                capacity: 1,
+            },
+        );
+        // The first job occupies the only worker and the second fills the queue, so the third
+        // falls back on the spot.
+        assert!(queue.enqueue(job(id)));
+        started.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(queue.enqueue(job(id)));
+        assert!(!queue.enqueue(job(id)));
+        assert_eq!(message(events.try_recv().unwrap()), "Nothing happens.");
+
+        assert_eq!(queue.expire(Instant::now()), 0);
+        assert_eq!(queue.expire(Instant::now() + Duration::from_secs(61)), 2);
+        for _ in 0..2 {
+            assert_eq!(message(events.try_recv().unwrap()), "Nothing happens.");
+        }
+        // The stalled reply arrives after its deadline and is dropped.
+        release.send(()).unwrap();
+        assert!(events.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(
+            queue.stats(),
+            NarrationStats {
+                queued: 2,
+                completed: 0,
+                fallbacks: 3
+            }
+        );
+    }
+
+    #[test]
+    fn events_become_jobs_with_canned_fallbacks() {
+        let mut content = WorldContent::default();
+        content
+            .upsert(
+                aqevia_kernel::ContentKind::Npc,
+                &serde_json::json!({"id": "warden", "name": "Warden", "dialogue": ["Halt."]}),
+            )
+            .unwrap();
+        let job = narration_job(
+            &content,
+            7,
+            &KernelEvent::SpokeTo {
+                npc: "warden".into(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            (job.session, job.fallback.as_str()),
+            (7, "Warden says, \"Halt.\"")
+        );
+        assert_eq!(job.request.task, "narration");
+        assert!(
+            narration_job(&content, 7, &KernelEvent::Examined { item: "key".into() }).is_none()
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Configuration hot reload. The Engine keeps the configuration it started with and a way to
+//! re-read its source; a reload re-reads and validates it, then applies the changed settings
+//! only if every one of them is marked reloadable.
+
+use std::sync::Arc;
+
+use aqevia_ai::Guardrails;
+use aqevia_auth::{AuthService, Principal};
+use aqevia_storage::StorageBackend;
+use serde::Serialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::config::{ConfigError, EngineConfig, SETTINGS};
+use crate::logging;
+use crate::EngineCore;
+
+/// Counter of configuration reloads, labelled by `result` (`applied`, `rejected`, or
+/// `invalid`).
+pub const CONFIG_RELOADS_METRIC: &str = "aqevia_config_reloads_total";
+
+/// Re-reads the configuration from wherever the Engine was started from.
+pub type ConfigLoader = Box<dyn Fn() -> Result<EngineConfig, ConfigError> + Send>;
+
+pub(crate) struct ConfigSource {
+    current: EngineConfig,
+    load: ConfigLoader,
+}
+
+/// Outcome of a reload that was applied. `changed` is empty when the source had not changed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReloadReport {
+    pub changed: Vec<String>,
+}
+
+impl<B: StorageBackend> EngineCore<B> {
+    /// Configuration the Engine is running with, when it was started from one.
+    pub fn config(&self) -> Option<&EngineConfig> {
+        self.config.as_ref().map(|source| &source.current)
+    }
+
+    /// Record the configuration the Engine was started with and how to re-read it for
+    /// [`EngineCore::reload_config`].
+    pub fn set_config_source(&mut self, current: EngineConfig, load: ConfigLoader) {
+        self.config = Some(ConfigSource { current, load });
+    }
+
+    /// Re-read and validate the configuration source and apply what changed to storage, auth,
+    /// guardrails, budgets, audit retention, and the log filter. If any changed setting is not reloadable,
+    /// nothing is applied and the error names those settings. An applied reload that changed
+    /// something is audited as `admin.config.reload`.
+    pub fn reload_config(
+        &mut self,
+        actor: Option<&Principal>,
+        auth: &AuthService,
+    ) -> Result<ReloadReport, ConfigError> {
+        let source = self.config.as_ref().ok_or(ConfigError::Unavailable)?;
+        let next = match (source.load)() {
+            Ok(next) => next,
+            Err(err) => {
+                warn!(error = %err, "config reload failed");
+                self.note_reload("invalid");
+                return Err(err);
+            }
+        };
+        let changed = source.current.changed_keys(&next);
+        let restart: Vec<String> = SETTINGS
+            .iter()
+            .filter(|setting| !setting.reloadable && changed.contains(&setting.key))
+            .map(|setting| setting.key.to_string())
+            .collect();
+        if !restart.is_empty() {
+            warn!(settings = %restart.join(", "), "config reload rejected; restart required");
+            self.note_reload("rejected");
+            return Err(ConfigError::RestartRequired(restart));
+        }
+        // Validation already compiled these, so neither can fail.
+        let guardrails = Guardrails::new(next.guardrail_config())
+            .map_err(|err| ConfigError::Validation(vec![format!("ai.guardrails: {}", err)]))?;
+        logging::set_filter(&next.log.level)
+            .map_err(|err| ConfigError::Validation(vec![format!("log.level: {}", err)]))?;
+        self.storage.set_config(next.storage_config());
+        auth.set_config(next.auth_config());
+        self.guardrails = Arc::new(guardrails);
+        self.budgets.set_config(next.budget_config());
+        self.audit_retention = next.audit_retention();
+        if let Some(source) = self.config.as_mut() {
+            source.current = next;
+        }
+        info!(changed = %changed.join(", "), "config reloaded");
+        self.note_reload("applied");
+        if !changed.is_empty() {
+            self.audit(
+                actor,
+                "admin.config.reload",
+                "config",
+                json!({ "changed": changed }),
+            );
+        }
+        Ok(ReloadReport {
+            changed: changed.into_iter().map(str::to_string).collect(),
+        })
+    }
+
+    fn note_reload(&self, result: &str) {
+        self.observability.add_counter(
+            CONFIG_RELOADS_METRIC,
+            "Configuration reloads by result.",
+            &[("result", result)],
+            1,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{DummyBackend, Harness};
+    use aqevia_ai::{Secret, WorldFacts};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Signed in as an admin, with a configuration source the test rewrites through the
+    /// returned handle.
+    fn reloadable() -> (Harness, Arc<Mutex<EngineConfig>>) {
+        let harness = Harness::admin(DummyBackend::default());
+        let source = Arc::new(Mutex::new(EngineConfig::default()));
+        let loader = source.clone();
+        harness.engine.core().set_config_source(
+            EngineConfig::default(),
+            Box::new(move || Ok(loader.lock().unwrap().clone())),
+        );
+        (harness, source)
+    }
+
+    #[test]
+    fn changed_keys_follow_the_settings_table() {
+        let base = EngineConfig::default();
+        assert!(base.changed_keys(&base.clone()).is_empty());
+
+        let mut next = base.clone();
+        next.ai.budget.account_max_tokens = Some(500);
+        next.tick.interval_ms = 10;
+        next.storage.sqlite_path = "other.sqlite".into();
+        next.ai.guardrails.block_patterns = vec!["x+".into()];
+        assert_eq!(
+            base.changed_keys(&next),
+            vec![
+                "storage.sqlite_path",
+                "tick.interval_ms",
+                "ai.guardrails.block_patterns",
+                "ai.budget.account_max_tokens",
+            ]
+        );
+        assert_eq!(next.changed_keys(&base), base.changed_keys(&next));
+
+        // Secrets print redacted but are still compared by value.
+        let (mut a, mut b) = (base.clone(), base.clone());
+        a.ai.api_key = Some(Secret::new("sk-one"));
+        b.ai.api_key = Some(Secret::new("sk-two"));
+        assert_eq!(a.dump(), b.dump());
+        assert_eq!(a.changed_keys(&b), vec!["ai.api_key"]);
+    }
+
+    #[test]
+    fn restart_only_changes_reject_the_whole_reload() {
+        let (harness, source) = reloadable();
+        {
+            let mut next = source.lock().unwrap();
+            next.storage.batch_capacity = 7;
+            next.ai.narration.workers = 9;
+            next.log.format = crate::logging::LogFormat::Json;
+        }
+        let err = harness.engine.reload_config().unwrap_err();
+        match &err {
+            ConfigError::RestartRequired(keys) => {
+                assert_eq!(keys, &vec!["log.format", "ai.narration.workers"])
+            }
+            other => panic!("expected a restart to be required, got {:?}", other),
+        }
+
+        let mut core = harness.engine.core();
+        assert_ne!(core.storage().config().batch_capacity, 7);
+        assert_eq!(core.config(), Some(&EngineConfig::default()));
+        assert_eq!(
+            core.observability()
+                .metric(CONFIG_RELOADS_METRIC, &[("result", "rejected")]),
+            1
+        );
+        core.flush_all().unwrap();
+        drop(core);
+        assert!(harness.audit_actions().is_empty());
+
+        // Once the restart-only values match again, the rest applies.
+        {
+            let mut next = source.lock().unwrap();
+            next.ai.narration.workers = EngineConfig::default().ai.narration.workers;
+            next.log.format = EngineConfig::default().log.format;
+        }
+        let report = harness.engine.reload_config().unwrap();
+        assert_eq!(report.changed, vec!["storage.batch_capacity"]);
+        assert_eq!(harness.engine.core().storage().config().batch_capacity, 7);
+    }
+
+    #[test]
+    fn reloadable_changes_take_effect() {
+        let (mut harness, source) = reloadable();
+        let old_guardrails = harness.engine.core().guardrails().clone();
+        {
+            let mut next = source.lock().unwrap();
+            next.ai.guardrails.blocklist = vec!["dragon".into()];
+            next.ai.guardrails.max_chars = 20;
+            next.ai.budget.world_max_requests = Some(3);
+            next.audit.retention_days = 7;
+            next.auth.open_registration = false;
+        }
+        let mut core = harness.engine.core();
+        let report = core.reload_config(None, harness.engine.auth()).unwrap();
+        assert_eq!(
+            report.changed,
+            vec![
+                "auth.open_registration",
+                "audit.retention_days",
+                "ai.guardrails.max_chars",
+                "ai.guardrails.blocklist",
+                "ai.budget.world_max_requests",
+            ]
+        );
+        let facts = WorldFacts::default();
+        assert!(core.guardrails().check("A dragon waits.", &facts).is_err());
+        let trimmed = core
+            .guardrails()
+            .check("The cellar is cold. Water drips from the ceiling.", &facts)
+            .unwrap();
+        assert_eq!(trimmed, "The cellar is cold.");
+        assert_eq!(core.budgets().config().world.requests, Some(3));
+        assert_eq!(
+            core.audit_retention(),
+            Some(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+        assert!(!harness.engine.auth().config().open_registration);
+        core.flush_all().unwrap();
+        drop(core);
+        assert_eq!(harness.audit_actions(), vec!["admin.config.reload"]);
+
+        // Narration workers keep the old guardrails until the next tick hands them over.
+        assert!(Arc::ptr_eq(
+            &harness.engine.narration().guardrails(),
+            &old_guardrails
+        ));
+        harness.engine.tick().unwrap();
+        let narration = harness.engine.narration().guardrails();
+        assert!(narration.check("A dragon waits.", &facts).is_err());
+        assert!(narration.check("A cat waits.", &facts).is_ok());
+
+        // Reloading an unchanged source applies nothing new and is not audited.
+        let report = harness.engine.reload_config().unwrap();
+        assert!(report.changed.is_empty());
+        harness.engine.core().flush_all().unwrap();
+        assert_eq!(harness.audit_actions(), vec!["admin.config.reload"]);
+    }
+}
@@ -0,0 +1,656 @@
+//! Authored world content: rooms, exits, items, and NPC templates.
+//!
+//! Content crosses the kernel boundary as JSON so the control plane and storage stay
+//! decoupled from the typed structs; the kernel decodes and validates every write.
+
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::schema::SchemaError;
+
+/// The content collections a builder can edit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ContentKind {
+    Room,
+    Exit,
+    Item,
+    Npc,
+}
+
+impl ContentKind {
+    /// Every kind, ordered so that referenced records load before the records that point at them.
+    pub const ALL: [ContentKind; 4] = [
+        ContentKind::Room,
+        ContentKind::Exit,
+        ContentKind::Item,
+        ContentKind::Npc,
+    ];
+
+    /// Storage `kind` for records of this collection.
+    pub fn record_kind(self) -> &'static str {
+        match self {
+            ContentKind::Room => "core.room",
+            ContentKind::Exit => "core.exit",
+            ContentKind::Item => "core.item",
+            ContentKind::Npc => "core.npc",
+        }
+    }
+
+    /// Collection name used in Builder API paths.
+    pub fn collection(self) -> &'static str {
+        match self {
+            ContentKind::Room => "rooms",
+            ContentKind::Exit => "exits",
+            ContentKind::Item => "items",
+            ContentKind::Npc => "npcs",
+        }
+    }
+
+    pub fn from_collection(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.collection() == name)
+    }
+
+    pub fn from_record_kind(kind: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.record_kind() == kind)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Room {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Fields this version does not recognize, preserved but ignored.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A one-way connection between two rooms.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Exit {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub direction: String,
+    /// Fields this version does not recognize, preserved but ignored.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An item placed in a room, inside another item, or unplaced (a template).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// Fields this version does not recognize, preserved but ignored.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NpcTemplate {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dialogue: Vec<String>,
+    /// Fields this version does not recognize, preserved but ignored.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Errors raised when content fails to decode or validate.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ContentError {
+    #[error("invalid {kind} payload: {message}")]
+    Invalid { kind: &'static str, message: String },
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+    #[error("{kind} '{id}' not found")]
+    NotFound { kind: &'static str, id: String },
+    #[error("{kind} '{id}' already exists")]
+    AlreadyExists { kind: &'static str, id: String },
+    #[error("{kind} '{id}' is still referenced by {referrer}")]
+    InUse {
+        kind: &'static str,
+        id: String,
+        referrer: String,
+    },
+}
+
+/// A field of one record that names another record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference {
+    pub field: &'static str,
+    pub target: ContentKind,
+    pub id: String,
+}
+
+/// A record that fails its cross-record checks, as found by
+/// [`WorldContent::integrity_problems`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegrityProblem {
+    pub kind: ContentKind,
+    pub id: String,
+    pub message: String,
+    /// The record's references to records that do not exist; empty when the problem is
+    /// something else, such as a container cycle.
+    pub dangling: Vec<Reference>,
+}
+
+/// In-memory authored content for the World.
+#[derive(Clone, Debug, Default)]
+pub struct WorldContent {
+    rooms: BTreeMap<String, Room>,
+    exits: BTreeMap<String, Exit>,
+    items: BTreeMap<String, Item>,
+    npcs: BTreeMap<String, NpcTemplate>,
+}
+
+impl WorldContent {
+    pub fn room(&self, id: &str) -> Option<&Room> {
+        self.rooms.get(id)
+    }
+
+    pub fn rooms(&self) -> impl Iterator<Item = &Room> {
+        self.rooms.values()
+    }
+
+    pub fn exits(&self) -> impl Iterator<Item = &Exit> {
+        self.exits.values()
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        self.items.values()
+    }
+
+    pub fn npcs(&self) -> impl Iterator<Item = &NpcTemplate> {
+        self.npcs.values()
+    }
+
+    pub fn contains(&self, kind: ContentKind, id: &str) -> bool {
+        match kind {
+            ContentKind::Room => self.rooms.contains_key(id),
+            ContentKind::Exit => self.exits.contains_key(id),
+            ContentKind::Item => self.items.contains_key(id),
+            ContentKind::Npc => self.npcs.contains_key(id),
+        }
+    }
+
+    /// JSON view of one record.
+    pub fn get(&self, kind: ContentKind, id: &str) -> Option<Value> {
+        match kind {
+            ContentKind::Room => self.rooms.get(id).map(to_value),
+            ContentKind::Exit => self.exits.get(id).map(to_value),
+            ContentKind::Item => self.items.get(id).map(to_value),
+            ContentKind::Npc => self.npcs.get(id).map(to_value),
+        }
+    }
+
+    /// JSON view of a collection, ordered by id.
+    pub fn list(&self, kind: ContentKind) -> Vec<Value> {
+        match kind {
+            ContentKind::Room => self.rooms.values().map(to_value).collect(),
+            ContentKind::Exit => self.exits.values().map(to_value).collect(),
+            ContentKind::Item => self.items.values().map(to_value).collect(),
+            ContentKind::Npc => self.npcs.values().map(to_value).collect(),
+        }
+    }
+
+    /// Decode and validate `payload` against the current content without applying it.
+    /// Returns the record id and its normalized JSON.
+    pub fn validate(
+        &self,
+        kind: ContentKind,
+        payload: &Value,
+    ) -> Result<(String, Value), ContentError> {
+        match kind {
+            ContentKind::Room => self.checked::<Room>(kind, payload),
+            ContentKind::Exit => self.checked::<Exit>(kind, payload),
+            ContentKind::Item => self.checked::<Item>(kind, payload),
+            ContentKind::Npc => self.checked::<NpcTemplate>(kind, payload),
+        }
+    }
+
+    /// Validate and insert or replace a record, returning its normalized JSON.
+    pub fn upsert(&mut self, kind: ContentKind, payload: &Value) -> Result<Value, ContentError> {
+        let (_, normalized) = self.validate(kind, payload)?;
+        self.insert_unchecked(kind, &normalized)?;
+        Ok(normalized)
+    }
+
+    /// Insert a record after decoding it but without cross-record checks. Used when loading
+    /// persisted content, where the kernel degrades gracefully instead of refusing to boot.
+    pub fn insert_unchecked(
+        &mut self,
+        kind: ContentKind,
+        payload: &Value,
+    ) -> Result<String, ContentError> {
+        match kind {
+            ContentKind::Room => insert(&mut self.rooms, kind, payload),
+            ContentKind::Exit => insert(&mut self.exits, kind, payload),
+            ContentKind::Item => insert(&mut self.items, kind, payload),
+            ContentKind::Npc => insert(&mut self.npcs, kind, payload),
+        }
+    }
+
+    /// Remove a record that nothing else references, returning its last JSON value.
+    pub fn remove(&mut self, kind: ContentKind, id: &str) -> Result<Value, ContentError> {
+        if let Some(referrer) = self.referrer_of(kind, id) {
+            return Err(ContentError::InUse {
+                kind: kind.record_kind(),
+                id: id.to_string(),
+                referrer,
+            });
+        }
+        self.remove_unchecked(kind, id)
+    }
+
+    /// Remove a record regardless of inbound references.
+    pub fn remove_unchecked(&mut self, kind: ContentKind, id: &str) -> Result<Value, ContentError> {
+        let removed = match kind {
+            ContentKind::Room => self.rooms.remove(id).map(|record| to_value(&record)),
+            ContentKind::Exit => self.exits.remove(id).map(|record| to_value(&record)),
+            ContentKind::Item => self.items.remove(id).map(|record| to_value(&record)),
+            ContentKind::Npc => self.npcs.remove(id).map(|record| to_value(&record)),
+        };
+        removed.ok_or_else(|| ContentError::NotFound {
+            kind: kind.record_kind(),
+            id: id.to_string(),
+        })
+    }
+
+    /// Cross-record problems in the current content, such as references left dangling by
+    /// unchecked inserts and removals.
+    pub fn integrity_errors(&self) -> Vec<ContentError> {
+        self.integrity_problems()
+            .into_iter()
+            .map(|problem| ContentError::Invalid {
+                kind: problem.kind.record_kind(),
+                message: format!("{}: {}", problem.id, problem.message),
+            })
+            .collect()
+    }
+
+    /// Every record that fails the checks a write of it would, in [`ContentKind::ALL`] order
+    /// and then by id. These are the same rules [`WorldContent::upsert`] applies to builder
+    /// writes.
+    pub fn integrity_problems(&self) -> Vec<IntegrityProblem> {
+        let mut problems = Vec::new();
+        collect_problems(&mut problems, ContentKind::Room, self.rooms.values(), self);
+        collect_problems(&mut problems, ContentKind::Exit, self.exits.values(), self);
+        collect_problems(&mut problems, ContentKind::Item, self.items.values(), self);
+        collect_problems(&mut problems, ContentKind::Npc, self.npcs.values(), self);
+        problems
+    }
+
+    /// The references held by every record that has any, as `(kind, id, references)`.
+    fn references(&self) -> impl Iterator<Item = (ContentKind, &str, Vec<Reference>)> {
+        let exits = self
+            .exits
+            .values()
+            .map(|exit| (ContentKind::Exit, exit.id(), exit.references()));
+        let items = self
+            .items
+            .values()
+            .map(|item| (ContentKind::Item, item.id(), item.references()));
+        let npcs = self
+            .npcs
+            .values()
+            .map(|npc| (ContentKind::Npc, npc.id(), npc.references()));
+        exits.chain(items).chain(npcs)
+    }
+
+    fn checked<T: ContentRecord>(
+        &self,
+        kind: ContentKind,
+        payload: &Value,
+    ) -> Result<(String, Value), ContentError> {
+        let record: T = decode(kind, payload)?;
+        validate_id(kind, record.id())?;
+        record
+            .check(self)
+            .map_err(|message| ContentError::Invalid {
+                kind: kind.record_kind(),
+                message,
+            })?;
+        Ok((record.id().to_string(), to_value(&record)))
+    }
+
+    /// First record that references `kind`/`id`, formatted as `<kind> '<id>'`.
+    fn referrer_of(&self, kind: ContentKind, id: &str) -> Option<String> {
+        self.references()
+            .find(|(_, _, references)| {
+                references
+                    .iter()
+                    .any(|reference| reference.target == kind && reference.id == id)
+            })
+            .map(|(referrer_kind, referrer, _)| {
+                format!("{} '{}'", referrer_kind.record_kind(), referrer)
+            })
+    }
+}
+
+trait ContentRecord: Serialize + DeserializeOwned {
+    fn id(&self) -> &str;
+    fn check(&self, world: &WorldContent) -> Result<(), String>;
+    /// Other records this one names.
+    fn references(&self) -> Vec<Reference> {
+        Vec::new()
+    }
+}
+
+impl ContentRecord for Room {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn check(&self, _world: &WorldContent) -> Result<(), String> {
+        require_text("name", &self.name)
+    }
+}
+
+impl ContentRecord for Exit {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn check(&self, world: &WorldContent) -> Result<(), String> {
+        require_text("direction", &self.direction)?;
+        require_room(world, "from", &self.from)?;
+        require_room(world, "to", &self.to)
+    }
+
+    fn references(&self) -> Vec<Reference> {
+        vec![
+            reference("from", ContentKind::Room, &self.from),
+            reference("to", ContentKind::Room, &self.to),
+        ]
+    }
+}
+
+impl ContentRecord for Item {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn check(&self, world: &WorldContent) -> Result<(), String> {
+        require_text("name", &self.name)?;
+        match (&self.room, &self.container) {
+            (Some(_), Some(_)) => Err("an item may be in a room or a container, not both".into()),
+            (Some(room), None) => require_room(world, "room", room),
+            (None, Some(container)) if container == &self.id => {
+                Err("an item cannot contain itself".into())
+            }
+            (None, Some(container)) if !world.items.contains_key(container) => {
+                Err(format!("container '{}' does not exist", container))
+            }
+            (None, Some(container)) => {
+                // Bounded walk so a cycle elsewhere in loaded content cannot spin forever.
+                let mut cursor = world.items.get(container);
+                for _ in 0..=world.items.len() {
+                    let Some(parent) = cursor else {
+                        return Ok(());
+                    };
+                    if parent.id == self.id {
+                        break;
+                    }
+                    cursor = parent.container.as_ref().and_then(|id| world.items.get(id));
+                }
+                Err(format!("container '{}' would form a cycle", container))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn references(&self) -> Vec<Reference> {
+        let room = self
+            .room
+            .iter()
+            .map(|room| reference("room", ContentKind::Room, room));
+        let container = self
+            .container
+            .iter()
+            .map(|container| reference("container", ContentKind::Item, container));
+        room.chain(container).collect()
+    }
+}
+
+impl ContentRecord for NpcTemplate {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn check(&self, world: &WorldContent) -> Result<(), String> {
+        require_text("name", &self.name)?;
+        match &self.room {
+            Some(room) => require_room(world, "room", room),
+            None => Ok(()),
+        }
+    }
+
+    fn references(&self) -> Vec<Reference> {
+        self.room
+            .iter()
+            .map(|room| reference("room", ContentKind::Room, room))
+            .collect()
+    }
+}
+
+fn reference(field: &'static str, target: ContentKind, id: &str) -> Reference {
+    Reference {
+        field,
+        target,
+        id: id.to_string(),
+    }
+}
+
+fn require_text(field: &str, value: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        Err(format!("'{}' must not be empty", field))
+    } else {
+        Ok(())
+    }
+}
+
+fn require_room(world: &WorldContent, field: &str, room: &str) -> Result<(), String> {
+    if world.rooms.contains_key(room) {
+        Ok(())
+    } else {
+        Err(format!("'{}' references missing room '{}'", field, room))
+    }
+}
+
+/// Ids are slugs so they stay readable in paths and exported bundles.
+fn validate_id(kind: ContentKind, id: &str) -> Result<(), ContentError> {
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|ch| {
+            ch.is_ascii_lowercase() || ch.is_ascii_digit() || matches!(ch, '-' | '_' | '.')
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(ContentError::Invalid {
+            kind: kind.record_kind(),
+            message: format!("id '{}' must be 1-64 characters of [a-z0-9._-]", id),
+        })
+    }
+}
+
+fn decode<T: DeserializeOwned>(kind: ContentKind, payload: &Value) -> Result<T, ContentError> {
+    T::deserialize(payload).map_err(|err| ContentError::Invalid {
+        kind: kind.record_kind(),
+        message: err.to_string(),
+    })
+}
+
+fn insert<T: ContentRecord>(
+    map: &mut BTreeMap<String, T>,
+    kind: ContentKind,
+    payload: &Value,
+) -> Result<String, ContentError> {
+    let record: T = decode(kind, payload)?;
+    let id = record.id().to_string();
+    map.insert(id.clone(), record);
+    Ok(id)
+}
+
+fn collect_problems<'a, T: ContentRecord + 'a>(
+    problems: &mut Vec<IntegrityProblem>,
+    kind: ContentKind,
+    records: impl Iterator<Item = &'a T>,
+    world: &WorldContent,
+) {
+    for record in records {
+        if let Err(message) = record.check(world) {
+            problems.push(IntegrityProblem {
+                kind,
+                id: record.id().to_string(),
+                message,
+                dangling: record
+                    .references()
+                    .into_iter()
+                    .filter(|reference| !world.contains(reference.target, &reference.id))
+                    .collect(),
+            });
+        }
+    }
+}
+
+fn to_value<T: Serialize>(record: &T) -> Value {
+    serde_json::to_value(record).expect("content serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn world() -> WorldContent {
+        let mut world = WorldContent::default();
+        world
+            .upsert(ContentKind::Room, &json!({"id": "lobby", "name": "Lobby"}))
+            .unwrap();
+        world
+            .upsert(ContentKind::Room, &json!({"id": "hall", "name": "Hall"}))
+            .unwrap();
+        world
+    }
+
+    #[test]
+    fn upsert_validates_references_and_fields() {
+        let mut world = world();
+        let exit =
+            json!({"id": "lobby-north", "from": "lobby", "to": "hall", "direction": "north"});
+        world.upsert(ContentKind::Exit, &exit).unwrap();
+        let dangling = json!({"id": "bad", "from": "lobby", "to": "void", "direction": "up"});
+        assert!(matches!(
+            world.upsert(ContentKind::Exit, &dangling),
+            Err(ContentError::Invalid { .. })
+        ));
+        let unknown = json!({"id": "torch", "name": "Torch", "description": "", "glow": true});
+        assert_eq!(world.upsert(ContentKind::Item, &unknown).unwrap(), unknown);
+        assert_eq!(world.get(ContentKind::Item, "torch"), Some(unknown));
+        let bad_id = json!({"id": "Not A Slug", "name": "x"});
+        assert!(world.upsert(ContentKind::Room, &bad_id).is_err());
+        let both = json!({"id": "lamp", "name": "Lamp", "room": "lobby", "container": "box"});
+        assert!(world.upsert(ContentKind::Item, &both).is_err());
+
+        world
+            .upsert(ContentKind::Item, &json!({"id": "box", "name": "Box"}))
+            .unwrap();
+        world
+            .upsert(
+                ContentKind::Item,
+                &json!({"id": "bag", "name": "Bag", "container": "box"}),
+            )
+            .unwrap();
+        let cycle = json!({"id": "box", "name": "Box", "container": "bag"});
+        assert!(world.upsert(ContentKind::Item, &cycle).is_err());
+    }
+
+    #[test]
+    fn referenced_records_cannot_be_removed() {
+        let mut world = world();
+        world
+            .upsert(
+                ContentKind::Npc,
+                &json!({"id": "guard", "name": "Guard", "room": "hall", "dialogue": ["Halt!"]}),
+            )
+            .unwrap();
+        let err = world.remove(ContentKind::Room, "hall").unwrap_err();
+        assert!(matches!(err, ContentError::InUse { .. }), "{:?}", err);
+        world.remove(ContentKind::Npc, "guard").unwrap();
+        world.remove(ContentKind::Room, "hall").unwrap();
+        assert!(matches!(
+            world.remove(ContentKind::Room, "hall"),
+            Err(ContentError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn integrity_errors_report_dangling_references() {
+        let mut world = world();
+        let exit = json!({"id": "out", "from": "lobby", "to": "hall", "direction": "east"});
+        world.upsert(ContentKind::Exit, &exit).unwrap();
+        assert!(world.integrity_errors().is_empty());
+        world.remove_unchecked(ContentKind::Room, "hall").unwrap();
+        let errors = world.integrity_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("out"));
+
+        world
+            .upsert(
+                ContentKind::Item,
+                &json!({"id": "coin", "name": "Coin", "container": "purse"}),
+            )
+            .unwrap_err();
+        world
+            .insert_unchecked(
+                ContentKind::Item,
+                &json!({"id": "coin", "name": "Coin", "container": "purse"}),
+            )
+            .unwrap();
+        let problems = world.integrity_problems();
+        let ids: Vec<&str> = problems.iter().map(|problem| problem.id.as_str()).collect();
+        assert_eq!(ids, ["out", "coin"]);
+        assert_eq!(
+            problems[0].dangling,
+            [Reference {
+                field: "to",
+                target: ContentKind::Room,
+                id: "hall".into()
+            }]
+        );
+        assert_eq!(problems[1].dangling[0].field, "container");
+    }
+
+    #[test]
+    fn kinds_map_to_collections_and_record_kinds() {
+        assert_eq!(ContentKind::from_collection("npcs"), Some(ContentKind::Npc));
+        assert_eq!(
+            ContentKind::from_record_kind("core.exit"),
+            Some(ContentKind::Exit)
+        );
+        assert_eq!(ContentKind::from_collection("spells"), None);
+        let listed = world().list(ContentKind::Room);
+        assert_eq!(listed[0]["id"], "hall");
+        assert_eq!(listed[1]["description"], "");
+    }
+}
@@ -0,0 +1,112 @@
+//! Gameplay events the kernel recognises in player commands. Events only describe what
+//! happened; reacting to them (for example narrating) is left to the Engine.
+
+use crate::Kernel;
+
+/// Something a player did that the rest of the Engine may react to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KernelEvent {
+    /// The player examined an item, identified by id.
+    Examined { item: String },
+    /// The player spoke to an NPC template, identified by id.
+    SpokeTo { npc: String },
+}
+
+const EXAMINE_VERBS: [&str; 4] = ["look at", "examine", "inspect", "x"];
+const TALK_VERBS: [&str; 4] = ["talk to", "speak to", "talk", "greet"];
+
+impl Kernel {
+    /// Recognise an examine or talk command whose target names existing content, either by
+    /// id or by case-insensitive name. Anything else yields `None`.
+    pub fn interpret(&self, command: &str) -> Option<KernelEvent> {
+        let command = command.trim();
+        let content = &self.content;
+        let event = if let Some(target) = strip_verb(command, &EXAMINE_VERBS) {
+            let items = content.items().map(|item| (&item.id, &item.name));
+            KernelEvent::Examined {
+                item: find(items, target)?,
+            }
+        } else {
+            let target = strip_verb(command, &TALK_VERBS)?;
+            KernelEvent::SpokeTo {
+                npc: find(content.npcs().map(|npc| (&npc.id, &npc.name)), target)?,
+            }
+        };
+        tracing::debug!(?event, "kernel event");
+        Some(event)
+    }
+}
+
+/// The rest of `command` after the first matching verb, with a leading article removed.
+fn strip_verb<'a>(command: &'a str, verbs: &[&str]) -> Option<&'a str> {
+    let lower = command.to_ascii_lowercase();
+    let verb = verbs.iter().find(|verb| {
+        lower
+            .strip_prefix(*verb)
+            .is_some_and(|rest| rest.starts_with(' '))
+    })?;
+    let rest = command[verb.len()..].trim();
+    let rest = ["the ", "a ", "an "]
+        .iter()
+        .find_map(|article| {
+            rest.to_ascii_lowercase()
+                .starts_with(article)
+                .then(|| rest[article.len()..].trim())
+        })
+        .unwrap_or(rest);
+    (!rest.is_empty()).then_some(rest)
+}
+
+/// Id of the first `(id, name)` candidate matching `target`.
+fn find<'a>(
+    mut candidates: impl Iterator<Item = (&'a String, &'a String)>,
+    target: &str,
+) -> Option<String> {
+    candidates
+        .find(|(id, name)| *id == target || name.eq_ignore_ascii_case(target))
+        .map(|(id, _)| id.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::ContentKind;
+    use serde_json::json;
+
+    #[test]
+    fn examine_and_talk_commands_resolve_to_content() {
+        let mut kernel = Kernel::new();
+        let content = kernel.content_mut();
+        for (kind, payload) in [
+            (ContentKind::Room, json!({"id": "hall", "name": "Hall"})),
+            (
+                ContentKind::Item,
+                json!({"id": "key", "name": "Brass Key", "room": "hall"}),
+            ),
+            (
+                ContentKind::Npc,
+                json!({"id": "warden", "name": "Old Warden", "room": "hall"}),
+            ),
+        ] {
+            content.upsert(kind, &payload).unwrap();
+        }
+
+        assert_eq!(
+            kernel.interpret("examine the brass key"),
+            Some(KernelEvent::Examined { item: "key".into() })
+        );
+        assert_eq!(
+            kernel.interpret("x key"),
+            Some(KernelEvent::Examined { item: "key".into() })
+        );
+        assert_eq!(
+            kernel.interpret("Talk to Old Warden"),
+            Some(KernelEvent::SpokeTo {
+                npc: "warden".into()
+            })
+        );
+        assert_eq!(kernel.interpret("examine the moon"), None);
+        assert_eq!(kernel.interpret("xylophone"), None);
+        assert_eq!(kernel.interpret("look"), None);
+    }
+}
@@ -1,9 +1,25 @@
 //! Kernel crate: authoritative world state and simulation primitives.
-//! It never performs network or direct database I/O.
+//! It never performs network or direct database I/O. Diagnostics are `tracing` events; the
+//! Engine's subscriber decides where they go.
+
+pub mod content;
+pub mod event;
+pub mod schema;
+
+pub use content::{
+    ContentError, ContentKind, Exit, IntegrityProblem, Item, NpcTemplate, Reference, Room,
+    WorldContent,
+};
+pub use event::KernelEvent;
+pub use schema::{KindSchema, SchemaError, SchemaRegistry, SchemaViolation};
+
+use serde_json::Value;
 
 /// Represents the single World that this Engine will host.
 pub struct Kernel {
     world_id: &'static str,
+    content: WorldContent,
+    schemas: SchemaRegistry,
 }
 
 impl Kernel {
@@ -11,6 +27,8 @@ impl Kernel {
     pub fn new() -> Self {
         Kernel {
             world_id: "aqevia-default-world",
+            content: WorldContent::default(),
+            schemas: SchemaRegistry::new(),
         }
     }
 
@@ -18,6 +36,36 @@ impl Kernel {
     pub fn world_id(&self) -> &'static str {
         self.world_id
     }
+
+    /// Authored rooms, exits, items, and NPC templates.
+    pub fn content(&self) -> &WorldContent {
+        &self.content
+    }
+
+    pub fn content_mut(&mut self) -> &mut WorldContent {
+        &mut self.content
+    }
+
+    /// Schemas for every record kind the World knows, core and modded.
+    pub fn schemas(&self) -> &SchemaRegistry {
+        &self.schemas
+    }
+
+    pub fn schemas_mut(&mut self) -> &mut SchemaRegistry {
+        &mut self.schemas
+    }
+
+    /// Check a content payload against its kind's schema, before any decoding.
+    pub fn check_schema(&self, kind: ContentKind, payload: &Value) -> Result<(), ContentError> {
+        Ok(self.schemas.validate(kind.record_kind(), payload)?)
+    }
+
+    /// Load a stored record: check it against its schema and decode it, leaving cross-record
+    /// checks to the integrity checker so one bad reference cannot keep the World from booting.
+    pub fn load(&mut self, kind: ContentKind, payload: &Value) -> Result<String, ContentError> {
+        self.check_schema(kind, payload)?;
+        self.content.insert_unchecked(kind, payload)
+    }
 }
 
 impl Default for Kernel {
@@ -0,0 +1,583 @@
+//! Schemas for record kinds. Each storage `kind` can declare a JSON Schema and a version in
+//! the [`SchemaRegistry`]; payloads are checked against it when the control plane accepts them
+//! and when the kernel loads them. The core content kinds are registered at version 1, and mods
+//! register their own kinds beside them.
+//!
+//! Schemas use the subset of JSON Schema that record payloads need: `type`, `properties`,
+//! `required`, `additionalProperties`, `items`, `enum`, `const`, `minLength`, `maxLength`,
+//! `pattern`, `minimum`, `maximum`, `minItems`, and `maxItems`. Annotations (`$schema`, `$id`,
+//! `title`, `description`, `default`, `examples`, and any `x-` key) are kept for clients and
+//! ignored by validation. A schema using any other keyword is refused when it is registered, so
+//! a constraint is never silently skipped.
+//!
+//! Fields a schema does not list are allowed unless it sets `additionalProperties`. The core
+//! schemas leave it unset: unknown fields are preserved in storage and ignored, so records
+//! written by a newer version still load without losing data.
+
+use std::collections::{BTreeMap, HashMap};
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::content::ContentKind;
+
+/// Version of the built-in `core.*` schemas.
+pub const CORE_SCHEMA_VERSION: u32 = 1;
+
+/// Pattern every content id must match.
+const SLUG: &str = "^[a-z0-9._-]{1,64}$";
+
+const KEYWORDS: [&str; 14] = [
+    "type",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "enum",
+    "const",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "minItems",
+    "maxItems",
+];
+
+const ANNOTATIONS: [&str; 6] = [
+    "$schema",
+    "$id",
+    "title",
+    "description",
+    "default",
+    "examples",
+];
+
+const TYPES: [&str; 7] = [
+    "null", "boolean", "object", "array", "number", "integer", "string",
+];
+
+/// The schema a record kind is validated against.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct KindSchema {
+    /// Storage `kind`, e.g. `core.room`.
+    pub kind: String,
+    pub version: u32,
+    pub schema: Value,
+}
+
+/// One way a payload fails its schema.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SchemaViolation {
+    /// JSON Pointer to the failing value; empty for the payload itself.
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SchemaError {
+    #[error(
+        "{kind} payload does not match schema version {version}: {}",
+        describe(.violations)
+    )]
+    Invalid {
+        kind: String,
+        version: u32,
+        violations: Vec<SchemaViolation>,
+    },
+    #[error("schema for {kind} is not supported: {message}")]
+    Unsupported { kind: String, message: String },
+    #[error("{kind} schema version {version} is not newer than the registered version {current}")]
+    Outdated {
+        kind: String,
+        version: u32,
+        current: u32,
+    },
+}
+
+/// Schemas by storage `kind`.
+#[derive(Clone, Debug)]
+pub struct SchemaRegistry {
+    schemas: BTreeMap<String, KindSchema>,
+    /// Compiled `pattern` keywords, by source.
+    patterns: HashMap<String, Regex>,
+}
+
+impl SchemaRegistry {
+    /// A registry holding the schemas of the core content kinds.
+    pub fn new() -> Self {
+        let mut registry = SchemaRegistry {
+            schemas: BTreeMap::new(),
+            patterns: HashMap::new(),
+        };
+        for kind in ContentKind::ALL {
+            registry
+                .register(kind.record_kind(), CORE_SCHEMA_VERSION, core_schema(kind))
+                .expect("core schemas are supported");
+        }
+        registry
+    }
+
+    /// Register `schema` for `kind`, replacing any earlier version. `kind` must be namespaced
+    /// (`user.<mod>.<thing>`), and a replacement must carry a higher version.
+    pub fn register(&mut self, kind: &str, version: u32, schema: Value) -> Result<(), SchemaError> {
+        let unsupported = |message: String| SchemaError::Unsupported {
+            kind: kind.to_string(),
+            message,
+        };
+        let namespaced = kind.contains('.')
+            && kind
+                .split('.')
+                .all(|part| !part.is_empty() && part.chars().all(is_slug_char));
+        if !namespaced {
+            return Err(unsupported(
+                "kind must be a namespaced name such as 'user.mod.thing'".into(),
+            ));
+        }
+        if let Some(current) = self.schemas.get(kind) {
+            if version <= current.version {
+                return Err(SchemaError::Outdated {
+                    kind: kind.to_string(),
+                    version,
+                    current: current.version,
+                });
+            }
+        }
+        let mut patterns = HashMap::new();
+        check_schema(&schema, "", &mut patterns).map_err(unsupported)?;
+        self.patterns.extend(patterns);
+        self.schemas.insert(
+            kind.to_string(),
+            KindSchema {
+                kind: kind.to_string(),
+                version,
+                schema,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, kind: &str) -> Option<&KindSchema> {
+        self.schemas.get(kind)
+    }
+
+    /// Every registered schema, ordered by kind.
+    pub fn list(&self) -> impl Iterator<Item = &KindSchema> {
+        self.schemas.values()
+    }
+
+    /// Check `payload` against the schema registered for `kind`. Kinds without a schema
+    /// accept any payload.
+    pub fn validate(&self, kind: &str, payload: &Value) -> Result<(), SchemaError> {
+        let Some(registered) = self.schemas.get(kind) else {
+            return Ok(());
+        };
+        let mut violations = Vec::new();
+        self.check(&registered.schema, payload, "", &mut violations);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaError::Invalid {
+                kind: kind.to_string(),
+                version: registered.version,
+                violations,
+            })
+        }
+    }
+
+    fn check(&self, schema: &Value, value: &Value, path: &str, out: &mut Vec<SchemaViolation>) {
+        if let Some(expected) = schema.get("type") {
+            let names: Vec<&str> = match expected {
+                Value::String(name) => vec![name],
+                other => other
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect(),
+            };
+            if !names.iter().any(|name| has_type(value, name)) {
+                // Nothing else can be said about a value of the wrong type.
+                push(out, path, format!("expected {}", names.join(" or ")));
+                return;
+            }
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                push(
+                    out,
+                    path,
+                    format!("must be one of {}", Value::from(allowed.clone())),
+                );
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                push(out, path, format!("must be {}", expected));
+            }
+        }
+        match value {
+            Value::String(text) => {
+                let length = text.chars().count() as u64;
+                if let Some(min) = bound(schema, "minLength").filter(|min| length < *min) {
+                    push(out, path, format!("must be at least {} characters", min));
+                }
+                if let Some(max) = bound(schema, "maxLength").filter(|max| length > *max) {
+                    push(out, path, format!("must be at most {} characters", max));
+                }
+                if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                    if !self.patterns[pattern].is_match(text) {
+                        push(out, path, format!("must match {}", pattern));
+                    }
+                }
+            }
+            Value::Number(number) => {
+                let number = number.as_f64().unwrap_or_default();
+                if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                    if number < min {
+                        push(out, path, format!("must be at least {}", min));
+                    }
+                }
+                if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                    if number > max {
+                        push(out, path, format!("must be at most {}", max));
+                    }
+                }
+            }
+            Value::Array(values) => {
+                let length = values.len() as u64;
+                if let Some(min) = bound(schema, "minItems").filter(|min| length < *min) {
+                    push(out, path, format!("must have at least {} items", min));
+                }
+                if let Some(max) = bound(schema, "maxItems").filter(|max| length > *max) {
+                    push(out, path, format!("must have at most {} items", max));
+                }
+                if let Some(items) = schema.get("items") {
+                    for (index, item) in values.iter().enumerate() {
+                        self.check(items, item, &format!("{}/{}", path, index), out);
+                    }
+                }
+            }
+            Value::Object(fields) => self.check_object(schema, fields, path, out),
+            Value::Null | Value::Bool(_) => {}
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &Value,
+        fields: &Map<String, Value>,
+        path: &str,
+        out: &mut Vec<SchemaViolation>,
+    ) {
+        let field_path = |name: &str| format!("{}/{}", path, escape(name));
+        for name in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !fields.contains_key(name) {
+                push(out, &field_path(name), "is required".into());
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, value) in fields {
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => self.check(property, value, &field_path(name), out),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        push(out, &field_path(name), "is not allowed".into())
+                    }
+                    Some(extra @ Value::Object(_)) => {
+                        self.check(extra, value, &field_path(name), out)
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+}
+
+impl Default for SchemaRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Walk a schema, refusing keywords the validator does not apply and compiling its patterns.
+fn check_schema(
+    schema: &Value,
+    path: &str,
+    patterns: &mut HashMap<String, Regex>,
+) -> Result<(), String> {
+    let at = |message: String| {
+        if path.is_empty() {
+            message
+        } else {
+            format!("{} at {}", message, path)
+        }
+    };
+    let Some(keywords) = schema.as_object() else {
+        return Err(at("a schema must be an object".into()));
+    };
+    for (keyword, value) in keywords {
+        let nested = format!("{}/{}", path, escape(keyword));
+        let valid = match keyword.as_str() {
+            "type" => match value {
+                Value::String(name) => TYPES.contains(&name.as_str()),
+                Value::Array(names) => names
+                    .iter()
+                    .all(|name| name.as_str().is_some_and(|name| TYPES.contains(&name))),
+                _ => false,
+            },
+            "properties" => {
+                let Some(properties) = value.as_object() else {
+                    return Err(at("'properties' must be an object".into()));
+                };
+                for (name, property) in properties {
+                    check_schema(property, &format!("{}/{}", nested, escape(name)), patterns)?;
+                }
+                true
+            }
+            "items" => {
+                check_schema(value, &nested, patterns)?;
+                true
+            }
+            "additionalProperties" => {
+                if !value.is_boolean() {
+                    check_schema(value, &nested, patterns)?;
+                }
+                true
+            }
+            "required" => value
+                .as_array()
+                .is_some_and(|names| names.iter().all(Value::is_string)),
+            "enum" => value.is_array(),
+            "minLength" | "maxLength" | "minItems" | "maxItems" => value.is_u64(),
+            "minimum" | "maximum" => value.is_number(),
+            "pattern" => {
+                let Some(source) = value.as_str() else {
+                    return Err(at("'pattern' must be a string".into()));
+                };
+                let regex = Regex::new(source)
+                    .map_err(|err| at(format!("invalid pattern '{}': {}", source, err)))?;
+                patterns.insert(source.to_string(), regex);
+                true
+            }
+            "const" => true,
+            annotation if ANNOTATIONS.contains(&annotation) || annotation.starts_with("x-") => true,
+            unknown => {
+                return Err(at(format!(
+                    "keyword '{}' is not supported; use one of {}",
+                    unknown,
+                    KEYWORDS.join(", ")
+                )))
+            }
+        };
+        if !valid {
+            return Err(at(format!("invalid value for '{}'", keyword)));
+        }
+    }
+    Ok(())
+}
+
+fn core_schema(kind: ContentKind) -> Value {
+    let id = json!({"type": "string", "title": "Id", "pattern": SLUG});
+    let name = json!({"type": "string", "title": "Name", "minLength": 1});
+    let description = json!({"type": "string", "title": "Description", "default": ""});
+    let room = |title: &str| json!({"type": "string", "title": title, "x-references": "core.room"});
+    let (title, properties, required) = match kind {
+        ContentKind::Room => (
+            "Room",
+            json!({"id": id, "name": name, "description": description}),
+            json!(["id", "name"]),
+        ),
+        ContentKind::Exit => (
+            "Exit",
+            json!({
+                "id": id,
+                "from": room("From"),
+                "to": room("To"),
+                "direction": {"type": "string", "title": "Direction", "minLength": 1},
+            }),
+            json!(["id", "from", "to", "direction"]),
+        ),
+        ContentKind::Item => (
+            "Item",
+            json!({
+                "id": id,
+                "name": name,
+                "description": description,
+                "room": room("Room"),
+                "container": {"type": "string", "title": "Container", "x-references": "core.item"},
+            }),
+            json!(["id", "name"]),
+        ),
+        ContentKind::Npc => (
+            "NPC template",
+            json!({
+                "id": id,
+                "name": name,
+                "description": description,
+                "room": room("Room"),
+                "dialogue": {
+                    "type": "array",
+                    "title": "Dialogue",
+                    "items": {"type": "string"},
+                    "default": [],
+                },
+            }),
+            json!(["id", "name"]),
+        ),
+    };
+    json!({
+        "title": title,
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+fn push(out: &mut Vec<SchemaViolation>, path: &str, message: String) {
+    out.push(SchemaViolation {
+        path: path.to_string(),
+        message,
+    });
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        "string" => value.is_string(),
+        _ => false,
+    }
+}
+
+fn bound(schema: &Value, keyword: &str) -> Option<u64> {
+    schema.get(keyword).and_then(Value::as_u64)
+}
+
+fn is_slug_char(ch: char) -> bool {
+    ch.is_ascii_lowercase() || ch.is_ascii_digit() || matches!(ch, '_' | '-')
+}
+
+/// Escape a key for use as a JSON Pointer segment (RFC 6901).
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn describe(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(|violation| {
+            let path = if violation.path.is_empty() {
+                "payload"
+            } else {
+                &violation.path
+            };
+            format!("{} {}", path, violation.message)
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_schemas_validate_payloads_and_allow_unknown_fields() {
+        let registry = SchemaRegistry::new();
+        let kinds: Vec<&str> = registry.list().map(|schema| schema.kind.as_str()).collect();
+        assert_eq!(kinds, ["core.exit", "core.item", "core.npc", "core.room"]);
+
+        let room = json!({"id": "lobby", "name": "Lobby", "glow": true});
+        assert_eq!(registry.validate("core.room", &room), Ok(()));
+        let npc = json!({"id": "Guard", "dialogue": ["hi", 3]});
+        let Err(SchemaError::Invalid { violations, .. }) = registry.validate("core.npc", &npc)
+        else {
+            panic!("the NPC should fail its schema");
+        };
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["/name", "/dialogue/1", "/id"]);
+        assert_eq!(
+            registry
+                .validate("core.exit", &json!([]))
+                .unwrap_err()
+                .to_string(),
+            "core.exit payload does not match schema version 1: payload expected object"
+        );
+        assert_eq!(registry.validate("user.unknown", &json!(7)), Ok(()));
+    }
+
+    #[test]
+    fn registration_refuses_unsupported_schemas_and_older_versions() {
+        let mut registry = SchemaRegistry::new();
+        let lamp = json!({
+            "type": "object",
+            "required": ["lumens"],
+            "additionalProperties": false,
+            "x-form": {"order": ["lumens"]},
+            "properties": {
+                "lumens": {"type": "integer", "minimum": 0, "maximum": 1000},
+                "color": {"enum": ["red", "green"]},
+            },
+        });
+        registry
+            .register("user.lamps.lamp", 2, lamp.clone())
+            .unwrap();
+        assert_eq!(
+            registry.validate("user.lamps.lamp", &json!({"lumens": 10, "color": "red"})),
+            Ok(())
+        );
+        let Err(SchemaError::Invalid { violations, .. }) = registry.validate(
+            "user.lamps.lamp",
+            &json!({"lumens": 1.5, "color": "blue", "hue": 1}),
+        ) else {
+            panic!("the lamp should fail its schema");
+        };
+        let found: Vec<(&str, &str)> = violations
+            .iter()
+            .map(|v| (v.path.as_str(), v.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("/color", "must be one of [\"red\",\"green\"]"),
+                ("/hue", "is not allowed"),
+                ("/lumens", "expected integer"),
+            ]
+        );
+
+        assert!(matches!(
+            registry.register("user.lamps.lamp", 2, lamp),
+            Err(SchemaError::Outdated { current: 2, .. })
+        ));
+        assert!(matches!(
+            registry.register("lamp", 1, json!({})),
+            Err(SchemaError::Unsupported { .. })
+        ));
+        let unsupported = registry
+            .register(
+                "user.lamps.bulb",
+                1,
+                json!({"properties": {"a": {"oneOf": []}}}),
+            )
+            .unwrap_err();
+        assert!(unsupported.to_string().contains("'oneOf' is not supported"));
+        assert!(registry
+            .register("user.lamps.bulb", 1, json!({"pattern": "("}))
+            .is_err());
+        assert!(registry.get("user.lamps.bulb").is_none());
+    }
+}
@@ -1,16 +1,27 @@
 //! Router crate: handles session delivery boundaries without embedding gameplay rules.
 //! It depends on the Kernel for world metadata but never performs network I/O itself.
 
-use aqevia_kernel::Kernel;
+pub mod session;
+
+use std::sync::Arc;
+
+use aqevia_kernel::{Kernel, KernelEvent};
+use tracing::{debug, info_span};
+
+pub use session::{SessionEvent, SessionId, SessionInfo, SessionRegistry};
 
 pub struct Router {
     kernel: Kernel,
+    sessions: Arc<SessionRegistry>,
 }
 
 impl Router {
     /// Create a new router around the provided kernel.
     pub fn new(kernel: Kernel) -> Self {
-        Router { kernel }
+        Router {
+            kernel,
+            sessions: Arc::new(SessionRegistry::new()),
+        }
     }
 
     /// Route a command string to the kernel.
@@ -23,6 +34,46 @@ impl Router {
         self.kernel.world_id()
     }
 
+    pub fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+
+    pub fn kernel_mut(&mut self) -> &mut Kernel {
+        &mut self.kernel
+    }
+
+    /// Registry of connected sessions, shared with transport connection threads.
+    pub fn sessions(&self) -> &Arc<SessionRegistry> {
+        &self.sessions
+    }
+
+    /// Route every queued session command and deliver the output back to its session.
+    pub fn pump_sessions(&self) -> usize {
+        self.pump_sessions_with(|_, _| {})
+    }
+
+    /// Like [`Router::pump_sessions`], also passing each kernel event a command raised to
+    /// `on_event` along with the session that issued it. Each command runs inside a `command`
+    /// span carrying the session id, account, and verb.
+    pub fn pump_sessions_with(&self, mut on_event: impl FnMut(SessionId, KernelEvent)) -> usize {
+        let commands = self.sessions.drain_commands();
+        for (id, command) in &commands {
+            let account = self
+                .sessions
+                .get(*id)
+                .map(|info| info.account)
+                .unwrap_or_default();
+            let verb = command.split_whitespace().next().unwrap_or_default();
+            let _span = info_span!("command", session_id = id, %account, verb).entered();
+            debug!("routing command");
+            self.sessions.send(*id, self.route(command));
+            if let Some(event) = self.kernel.interpret(command) {
+                on_event(*id, event);
+            }
+        }
+        commands.len()
+    }
+
     /// Create the default router tied to the default kernel.
     pub fn with_default_kernel() -> Self {
         Router::new(Kernel::new())
@@ -50,4 +101,45 @@ mod tests {
             output
         );
     }
+
+    #[test]
+    fn pump_sessions_delivers_routed_output() {
+        let router = Router::default();
+        let (id, receiver) = router.sessions().open("alice", None);
+        router.sessions().submit(id, "look");
+        assert_eq!(router.pump_sessions(), 1);
+        match receiver.recv().unwrap() {
+            SessionEvent::Message(text) => assert!(text.contains("Routing 'look'")),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pump_sessions_with_reports_kernel_events() {
+        let mut router = Router::default();
+        let content = router.kernel_mut().content_mut();
+        content
+            .upsert(
+                aqevia_kernel::ContentKind::Item,
+                &serde_json::json!({"id": "lamp", "name": "Lamp"}),
+            )
+            .unwrap();
+        let (id, _receiver) = router.sessions().open("alice", None);
+        router.sessions().submit(id, "look");
+        router.sessions().submit(id, "examine lamp");
+        let mut events = Vec::new();
+        assert_eq!(
+            router.pump_sessions_with(|session, event| events.push((session, event))),
+            2
+        );
+        assert_eq!(
+            events,
+            vec![(
+                id,
+                KernelEvent::Examined {
+                    item: "lamp".into()
+                }
+            )]
+        );
+    }
 }
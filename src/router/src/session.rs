@@ -0,0 +1,265 @@
+//! Session registry shared between transport connection threads and the engine loop.
+//!
+//! Transports open a session once a connection is authenticated, push inbound commands,
+//! and drain outbound events; the engine drains commands and delivers output.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::info;
+
+pub type SessionId = u64;
+
+/// Events delivered to a session's transport.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionEvent {
+    Message(String),
+    Close(String),
+}
+
+/// Operator-visible description of a connected session.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: SessionId,
+    pub account: String,
+    pub ip: Option<IpAddr>,
+    pub connected_at: u64,
+    pub muted: bool,
+}
+
+struct SessionEntry {
+    info: SessionInfo,
+    outbound: Sender<SessionEvent>,
+}
+
+#[derive(Default)]
+pub struct SessionRegistry {
+    next_id: AtomicU64,
+    sessions: Mutex<HashMap<SessionId, SessionEntry>>,
+    inbound: Mutex<VecDeque<(SessionId, String)>>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        SessionRegistry::default()
+    }
+
+    /// Register an authenticated connection and return its id plus the outbound event stream.
+    pub fn open(
+        &self,
+        account: impl Into<String>,
+        ip: Option<IpAddr>,
+    ) -> (SessionId, Receiver<SessionEvent>) {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let (outbound, receiver) = mpsc::channel();
+        let info = SessionInfo {
+            id,
+            account: account.into(),
+            ip,
+            connected_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            muted: false,
+        };
+        info!(session_id = id, account = %info.account, ip = ?info.ip, "session opened");
+        self.sessions
+            .lock()
+            .expect("lock poisoning")
+            .insert(id, SessionEntry { info, outbound });
+        (id, receiver)
+    }
+
+    /// Forget a session. Returns `false` if it was already gone.
+    pub fn close(&self, id: SessionId) -> bool {
+        let entry = self.sessions.lock().expect("lock poisoning").remove(&id);
+        if let Some(entry) = &entry {
+            info!(session_id = id, account = %entry.info.account, "session closed");
+        }
+        entry.is_some()
+    }
+
+    /// Ask the transport to disconnect a session, then forget it.
+    pub fn disconnect(&self, id: SessionId, reason: &str) -> bool {
+        let entry = self.sessions.lock().expect("lock poisoning").remove(&id);
+        match entry {
+            Some(entry) => {
+                info!(session_id = id, account = %entry.info.account, reason, "session disconnected");
+                let _ = entry.outbound.send(SessionEvent::Close(reason.to_string()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disconnect every session held by `account`, returning how many were closed.
+    pub fn disconnect_account(&self, account: &str, reason: &str) -> usize {
+        let ids: Vec<SessionId> = self
+            .list()
+            .into_iter()
+            .filter(|info| info.account == account)
+            .map(|info| info.id)
+            .collect();
+        ids.into_iter()
+            .filter(|id| self.disconnect(*id, reason))
+            .count()
+    }
+
+    /// Mute or unmute a session. Returns `false` if the session is gone.
+    pub fn set_muted(&self, id: SessionId, muted: bool) -> bool {
+        match self.sessions.lock().expect("lock poisoning").get_mut(&id) {
+            Some(entry) => {
+                entry.info.muted = muted;
+                info!(session_id = id, account = %entry.info.account, muted, "session mute changed");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Queue a command received from a session. Commands from unknown sessions are dropped;
+    /// muted sessions get a notice instead.
+    pub fn submit(&self, id: SessionId, command: impl Into<String>) -> bool {
+        match self.sessions.lock().expect("lock poisoning").get(&id) {
+            None => return false,
+            Some(entry) if entry.info.muted => {
+                let _ = entry
+                    .outbound
+                    .send(SessionEvent::Message("You are muted.".into()));
+                return false;
+            }
+            Some(_) => {}
+        }
+        self.inbound
+            .lock()
+            .expect("lock poisoning")
+            .push_back((id, command.into()));
+        true
+    }
+
+    /// Take every queued command in arrival order.
+    pub fn drain_commands(&self) -> Vec<(SessionId, String)> {
+        self.inbound
+            .lock()
+            .expect("lock poisoning")
+            .drain(..)
+            .collect()
+    }
+
+    /// Deliver a message to one session. Returns `false` if the session is gone.
+    pub fn send(&self, id: SessionId, message: impl Into<String>) -> bool {
+        let sessions = self.sessions.lock().expect("lock poisoning");
+        match sessions.get(&id) {
+            Some(entry) => entry
+                .outbound
+                .send(SessionEvent::Message(message.into()))
+                .is_ok(),
+            None => false,
+        }
+    }
+
+    /// Deliver a message to every session, returning how many received it.
+    pub fn broadcast(&self, message: &str) -> usize {
+        self.sessions
+            .lock()
+            .expect("lock poisoning")
+            .values()
+            .filter(|entry| {
+                entry
+                    .outbound
+                    .send(SessionEvent::Message(message.to_string()))
+                    .is_ok()
+            })
+            .count()
+    }
+
+    pub fn get(&self, id: SessionId) -> Option<SessionInfo> {
+        self.sessions
+            .lock()
+            .expect("lock poisoning")
+            .get(&id)
+            .map(|entry| entry.info.clone())
+    }
+
+    /// Snapshot of connected sessions ordered by id.
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .lock()
+            .expect("lock poisoning")
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect();
+        sessions.sort_by_key(|info| info.id);
+        sessions
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().expect("lock poisoning").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_receive_messages_and_close_events() {
+        let registry = SessionRegistry::new();
+        let (id, receiver) = registry.open("alice", None);
+        assert!(registry.send(id, "hello"));
+        assert_eq!(
+            receiver.recv().unwrap(),
+            SessionEvent::Message("hello".into())
+        );
+        assert!(registry.disconnect(id, "bye"));
+        assert_eq!(receiver.recv().unwrap(), SessionEvent::Close("bye".into()));
+        assert!(!registry.send(id, "gone"));
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn commands_drain_in_order_and_unknown_sessions_are_dropped() {
+        let registry = SessionRegistry::new();
+        let (first, _rx1) = registry.open("a", None);
+        let (second, _rx2) = registry.open("b", None);
+        assert!(registry.submit(first, "look"));
+        assert!(registry.submit(second, "say hi"));
+        assert!(!registry.submit(99, "ghost"));
+        assert_eq!(
+            registry.drain_commands(),
+            vec![(first, "look".to_string()), (second, "say hi".to_string())]
+        );
+        assert!(registry.drain_commands().is_empty());
+    }
+
+    #[test]
+    fn muted_sessions_are_told_and_broadcasts_reach_everyone() {
+        let registry = SessionRegistry::new();
+        let (first, rx1) = registry.open("a", None);
+        let (_second, rx2) = registry.open("a", None);
+        assert!(registry.set_muted(first, true));
+        assert!(!registry.submit(first, "say hi"));
+        assert_eq!(
+            rx1.recv().unwrap(),
+            SessionEvent::Message("You are muted.".into())
+        );
+        assert!(registry.get(first).unwrap().muted);
+        assert_eq!(registry.broadcast("Reboot soon"), 2);
+        assert_eq!(
+            rx2.recv().unwrap(),
+            SessionEvent::Message("Reboot soon".into())
+        );
+        assert_eq!(registry.disconnect_account("a", "banned"), 2);
+        assert!(registry.is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+//! Flush throughput of the SQLite backend: records per second written through
+//! `persist_batch` for a range of batch sizes, under the default pragmas and under SQLite's
+//! own defaults (`journal_mode = DELETE`, `synchronous = FULL`).
+//!
+//! Run with `cargo bench -p aqevia-storage-sqlite`. `AQEVIA_BENCH_RECORDS` sets how many
+//! records each measurement writes (default 2000).
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use aqevia_storage::{StorageBackend, WorldRecord};
+use aqevia_storage_sqlite::{JournalMode, SqliteOptions, SqliteStorage, Synchronous};
+
+const BATCH_SIZES: [usize; 7] = [1, 5, 10, 20, 50, 100, 250];
+
+/// Distinct keys written per run; later records update earlier ones, as a busy World does.
+const KEYS: usize = 500;
+
+fn main() {
+    let records: usize = env::var("AQEVIA_BENCH_RECORDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(2000);
+    let dir = env::temp_dir().join(format!("aqevia_bench_{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("create bench directory");
+    let presets = [
+        ("wal/normal", SqliteOptions::default()),
+        (
+            "delete/full",
+            SqliteOptions {
+                journal_mode: JournalMode::Delete,
+                synchronous: Synchronous::Full,
+                ..SqliteOptions::default()
+            },
+        ),
+    ];
+
+    println!("{} records per run, {} distinct keys\n", records, KEYS);
+    print!("| batch |");
+    for (name, _) in &presets {
+        print!(" {} (records/s) |", name);
+    }
+    println!();
+    print!("| ---: |");
+    for _ in &presets {
+        print!(" ---: |");
+    }
+    println!();
+    for batch in BATCH_SIZES {
+        print!("| {} |", batch);
+        for (name, options) in &presets {
+            let path = dir.join(format!("{}-{}.sqlite", name.replace('/', "-"), batch));
+            let elapsed = run(&path, *options, batch, records);
+            print!(" {:.0} |", records as f64 / elapsed.as_secs_f64());
+            remove_store(&path);
+        }
+        println!();
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+/// Time writing `records` records to a fresh store in batches of `batch`.
+fn run(path: &Path, options: SqliteOptions, batch: usize, records: usize) -> Duration {
+    let mut storage = SqliteStorage::with_options(path, options).expect("open store");
+    storage.init().expect("init store");
+    let payload = format!(
+        r#"{{"name":"A room","description":"{}","exits":["north","south"]}}"#,
+        "x".repeat(200)
+    );
+    let all: Vec<WorldRecord> = (0..records)
+        .map(|n| WorldRecord::new("bench", "core.room", format!("room-{}", n % KEYS), &payload))
+        .collect();
+    let started = Instant::now();
+    for chunk in all.chunks(batch) {
+        storage.persist_batch(chunk).expect("persist batch");
+    }
+    started.elapsed()
+}
+
+fn remove_store(path: &Path) {
+    let _ = fs::remove_file(path);
+    for journal in ["-wal", "-shm", "-journal"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(journal);
+        let _ = fs::remove_file(PathBuf::from(sidecar));
+    }
+}
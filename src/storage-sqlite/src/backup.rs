@@ -0,0 +1,353 @@
+//! Online snapshots of a live store, their rotation, and restoring a store from one.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aqevia_storage::{StorageError, StorageResult};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{to_storage_error, upgrades_in_place, SqliteStorage, StoreLock, SCHEMA_VERSION};
+
+/// Pages copied per backup step. Between steps the source is unlocked, so writers wait at
+/// most one step.
+const PAGES_PER_STEP: i32 = 256;
+const PAUSE_BETWEEN_STEPS: Duration = Duration::from_millis(10);
+
+const SNAPSHOT_PREFIX: &str = "aqevia-";
+const SNAPSHOT_EXTENSION: &str = ".sqlite";
+
+/// Copy the database open on `source` to `dest` with SQLite's online backup API. A write to
+/// the source from another connection restarts the copy, so `dest` always holds the store as
+/// it was at one moment. The copy is written next to `dest` and renamed into place, so `dest`
+/// never holds a partial snapshot. Fails if `dest` already exists.
+pub(crate) fn copy_into(source: &Connection, dest: &Path) -> StorageResult<()> {
+    if dest.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("backup target {} already exists", dest.display()),
+        )
+        .into());
+    }
+    if let Some(parent) = dest
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = sidecar(dest, "partial");
+    let result = copy_pages(source, &partial).and_then(|()| Ok(fs::rename(&partial, dest)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+fn copy_pages(source: &Connection, dest: &Path) -> StorageResult<()> {
+    let mut target = Connection::open(dest).map_err(to_storage_error)?;
+    let backup = Backup::new(source, &mut target).map_err(to_storage_error)?;
+    loop {
+        match backup.step(PAGES_PER_STEP).map_err(to_storage_error)? {
+            StepResult::Done => return Ok(()),
+            // More, or the source is busy with a writer: let it run, then continue.
+            _ => thread::sleep(PAUSE_BETWEEN_STEPS),
+        }
+    }
+}
+
+/// Snapshot the store at `db` into `dest` through a read-only connection of its own, so a
+/// running Engine keeps writing while the copy is made.
+pub fn snapshot(db: &Path, dest: &Path) -> StorageResult<()> {
+    let source = open_existing(db)?;
+    copy_into(&source, dest)
+}
+
+/// Timestamped snapshots in one directory, of which the newest `keep` are retained.
+#[derive(Clone, Debug)]
+pub struct Snapshots {
+    dir: PathBuf,
+    keep: usize,
+}
+
+/// What [`Snapshots::take`] wrote and rotated out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotReport {
+    pub path: PathBuf,
+    pub removed: Vec<PathBuf>,
+}
+
+impl Snapshots {
+    /// Snapshots in `dir`, keeping the newest `keep`; `0` keeps every snapshot.
+    pub fn new(dir: impl Into<PathBuf>, keep: usize) -> Self {
+        Snapshots {
+            dir: dir.into(),
+            keep,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Snapshot `db` into a new `aqevia-<unix millis>.sqlite` file, then delete the oldest
+    /// snapshots beyond the retention limit.
+    pub fn take(&self, db: &Path) -> StorageResult<SnapshotReport> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = self.dir.join(format!(
+            "{}{}{}",
+            SNAPSHOT_PREFIX, millis, SNAPSHOT_EXTENSION
+        ));
+        snapshot(db, &path)?;
+        let removed = self.rotate()?;
+        Ok(SnapshotReport { path, removed })
+    }
+
+    /// Snapshots in the directory, oldest first. Other files are ignored.
+    pub fn list(&self) -> StorageResult<Vec<PathBuf>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut snapshots: Vec<(u128, PathBuf)> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter_map(|path| {
+                let name = path.file_name()?.to_str()?;
+                let millis = name
+                    .strip_prefix(SNAPSHOT_PREFIX)?
+                    .strip_suffix(SNAPSHOT_EXTENSION)?
+                    .parse()
+                    .ok()?;
+                Some((millis, path))
+            })
+            .collect();
+        snapshots.sort();
+        Ok(snapshots.into_iter().map(|(_, path)| path).collect())
+    }
+
+    fn rotate(&self) -> StorageResult<Vec<PathBuf>> {
+        let snapshots = self.list()?;
+        if self.keep == 0 || snapshots.len() <= self.keep {
+            return Ok(Vec::new());
+        }
+        let excess = snapshots.len() - self.keep;
+        let mut removed = Vec::with_capacity(excess);
+        for path in snapshots.into_iter().take(excess) {
+            fs::remove_file(&path)?;
+            removed.push(path);
+        }
+        Ok(removed)
+    }
+}
+
+/// Replace the store at `db` with the snapshot at `from`. The snapshot must pass SQLite's
+/// integrity check and carry [`SCHEMA_VERSION`] or a version [`upgrades_in_place`] accepts,
+/// which the next start upgrades; otherwise nothing changes. The replaced store is moved,
+/// with any journal files, to `<db>.before-restore`, which is returned and replaces an
+/// earlier one. Fails with [`StorageError::Busy`] while an Engine holds the [`StoreLock`].
+pub fn restore(from: &Path, db: &Path) -> StorageResult<Option<PathBuf>> {
+    let _lock = StoreLock::acquire(db)?;
+    {
+        let candidate = SqliteStorage {
+            connection: open_existing(from)?,
+            readers: None,
+            stats: Default::default(),
+        };
+        let problems = candidate.integrity_check()?;
+        if !problems.is_empty() {
+            return Err(StorageError::Corrupt {
+                message: format!(
+                    "{} fails the integrity check: {}",
+                    from.display(),
+                    problems.join("; ")
+                ),
+                source: None,
+            });
+        }
+        match candidate.schema_version()? {
+            Some(version) if version == SCHEMA_VERSION || upgrades_in_place(version) => {}
+            Some(found) => {
+                return Err(StorageError::SchemaMismatch {
+                    found,
+                    expected: SCHEMA_VERSION,
+                })
+            }
+            None => {
+                return Err(StorageError::Corrupt {
+                    message: format!("{} is not an Aqevia store", from.display()),
+                    source: None,
+                })
+            }
+        }
+    }
+    // Stage the copy beside the store so the final swap is a rename on one filesystem.
+    let staged = sidecar(db, "restoring");
+    let _ = fs::remove_file(&staged);
+    snapshot(from, &staged)?;
+    let previous = sidecar(db, "before-restore");
+    let replaced = db.exists();
+    if replaced {
+        fs::rename(db, &previous)?;
+    }
+    for journal in ["journal", "wal", "shm"] {
+        let (live, kept) = (sidecar_dash(db, journal), sidecar_dash(&previous, journal));
+        let _ = fs::remove_file(&kept);
+        if live.exists() {
+            fs::rename(live, kept)?;
+        }
+    }
+    fs::rename(&staged, db)?;
+    Ok(replaced.then_some(previous))
+}
+
+fn open_existing(path: &Path) -> StorageResult<Connection> {
+    if !path.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", path.display()),
+        )
+        .into());
+    }
+    Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(to_storage_error)
+}
+
+/// `<path>.<suffix>`.
+pub(crate) fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// `<path>-<suffix>`, as SQLite names its journal files.
+fn sidecar_dash(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push("-");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aqevia_storage::{StorageBackend, WorldRecord};
+    use std::env;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("aqevia_backup_test_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn store(path: &Path, rooms: &[&str]) -> SqliteStorage {
+        let mut storage = SqliteStorage::new(path).unwrap();
+        storage.init().unwrap();
+        let records: Vec<WorldRecord> = rooms
+            .iter()
+            .map(|room| WorldRecord::new("world", "core.room", *room, "{}"))
+            .collect();
+        storage.persist_batch(&records).unwrap();
+        storage
+    }
+
+    #[test]
+    fn snapshots_copy_a_live_store_and_rotate() {
+        let dir = scratch("rotate");
+        let db = dir.join("storage.sqlite");
+        let mut live = store(&db, &["hall"]);
+        let snapshots = Snapshots::new(dir.join("backups"), 2);
+        let mut taken = Vec::new();
+        for room in ["cellar", "attic", "garden"] {
+            live.persist_batch(&[WorldRecord::new("world", "core.room", room, "{}")])
+                .unwrap();
+            let report = snapshots.take(&db).unwrap();
+            taken.push(report.path.clone());
+            std::thread::sleep(Duration::from_millis(2));
+            if taken.len() == 3 {
+                assert_eq!(report.removed, vec![taken[0].clone()]);
+            }
+        }
+        assert_eq!(snapshots.list().unwrap(), taken[1..].to_vec());
+        let newest = SqliteStorage::new(&taken[2]).unwrap();
+        assert_eq!(newest.load_records("core.room").unwrap().len(), 4);
+        assert!(snapshot(&db, &taken[2]).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restore_validates_before_swapping() {
+        let dir = scratch("restore");
+        let db = dir.join("storage.sqlite");
+        let saved = dir.join("saved.sqlite");
+        store(&db, &["hall"]).backup_to(&saved).unwrap();
+        store(&db, &["cellar"]);
+
+        let stale = dir.join("stale.sqlite");
+        store(&stale, &[])
+            .connection
+            .execute("UPDATE schema_meta SET version = ?1", [1])
+            .unwrap();
+        let err = restore(&stale, &db).unwrap_err();
+        assert!(err.to_string().contains("schema version"), "{}", err);
+        assert!(matches!(err, StorageError::SchemaMismatch { found: 1, .. }));
+        let garbage = dir.join("garbage.sqlite");
+        fs::write(&garbage, b"not a database").unwrap();
+        assert_eq!(restore(&garbage, &db).unwrap_err().kind(), "corrupt");
+        let missing = restore(&dir.join("missing.sqlite"), &db).unwrap_err();
+        assert!(matches!(&missing, StorageError::Io(err) if err.kind() == io::ErrorKind::NotFound));
+        let current = SqliteStorage::new(&db).unwrap();
+        assert_eq!(current.load_records("core.room").unwrap().len(), 2);
+        drop(current);
+
+        let previous = restore(&saved, &db).unwrap().unwrap();
+        let restored = SqliteStorage::new(&db).unwrap();
+        assert_eq!(restored.load_records("core.room").unwrap().len(), 1);
+        let kept = SqliteStorage::new(&previous).unwrap();
+        assert_eq!(kept.load_records("core.room").unwrap().len(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restore_waits_for_the_engine_and_accepts_upgradable_snapshots() {
+        let dir = scratch("restore_lock");
+        let db = dir.join("storage.sqlite");
+        store(&db, &["hall"]);
+        let older = dir.join("older.sqlite");
+        Connection::open(&older)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE schema_meta (id INTEGER PRIMARY KEY, version INTEGER NOT NULL);
+                 INSERT INTO schema_meta (version) VALUES (2);
+                 CREATE TABLE world_records (
+                     id INTEGER PRIMARY KEY, world_id TEXT NOT NULL, kind TEXT NOT NULL,
+                     key TEXT NOT NULL, payload TEXT NOT NULL, timestamp INTEGER NOT NULL,
+                     UNIQUE (world_id, kind, key));
+                 INSERT INTO world_records (world_id, kind, key, payload, timestamp)
+                 VALUES ('world', 'core.room', 'cellar', '{}', 0);",
+            )
+            .unwrap();
+
+        let engine = StoreLock::acquire(&db).unwrap();
+        let err = restore(&older, &db).unwrap_err();
+        assert_eq!(err.kind(), "busy", "{}", err);
+        assert!(StoreLock::acquire(&db).is_err());
+        drop(engine);
+
+        restore(&older, &db).unwrap();
+        let mut restored = SqliteStorage::new(&db).unwrap();
+        assert_eq!(restored.schema_version().unwrap(), Some(2));
+        restored.init().unwrap();
+        let rooms = restored.load_records("core.room").unwrap();
+        assert_eq!(rooms[0].key, "cellar");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
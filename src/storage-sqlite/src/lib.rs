@@ -1,39 +1,149 @@
 //! SQLite storage backend.
 
+pub mod backup;
+mod lock;
+mod options;
+mod pool;
+
 use std::fs;
+use std::io;
 use std::path::Path;
 
 #[cfg(test)]
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aqevia_storage::{
+    canonicalize, payload_hash, StorageBackend, StorageError, StorageResult, StorageStats,
+    WorldRecord,
+};
+use rusqlite::{params, Connection, Error as RusqliteError, ErrorCode, OptionalExtension};
+
+use pool::ReadPool;
 
-use aqevia_storage::{StorageBackend, StorageError, StorageResult, StorageStats, WorldRecord};
-use rusqlite::{params, Connection, Error as RusqliteError, OptionalExtension};
+pub use backup::{restore, snapshot, SnapshotReport, Snapshots};
+pub use lock::StoreLock;
+pub use options::{JournalMode, SqliteOptions, Synchronous};
 
-const SCHEMA_VERSION: i64 = 1;
+/// Schema version this build writes. [`StorageBackend::init`] upgrades a store stamped with
+/// a version [`upgrades_in_place`] accepts and refuses one stamped with any other; only
+/// [`SqliteStorage::reset_schema`] discards such a store.
+pub const SCHEMA_VERSION: i64 = 3;
+
+/// Whether [`StorageBackend::init`] brings a store at `version` to [`SCHEMA_VERSION`] without
+/// discarding its records.
+pub fn upgrades_in_place(version: i64) -> bool {
+    version == 2
+}
 
+/// Classify a SQLite failure by its result code, keeping it as the error's source.
 fn to_storage_error(err: RusqliteError) -> StorageError {
-    StorageError(err.to_string())
+    let message = err.to_string();
+    let code = match &err {
+        RusqliteError::SqliteFailure(failure, _) => Some(failure.code),
+        _ => None,
+    };
+    match code {
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => StorageError::Busy {
+            message,
+            source: Some(Box::new(err)),
+        },
+        Some(ErrorCode::ConstraintViolation | ErrorCode::TooBig) => StorageError::Constraint {
+            message,
+            source: Some(Box::new(err)),
+        },
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => StorageError::Corrupt {
+            message,
+            source: Some(Box::new(err)),
+        },
+        Some(ErrorCode::DiskFull) => {
+            StorageError::Io(io::Error::new(io::ErrorKind::StorageFull, err))
+        }
+        Some(
+            ErrorCode::SystemIoFailure
+            | ErrorCode::CannotOpen
+            | ErrorCode::ReadOnly
+            | ErrorCode::PermissionDenied,
+        ) => StorageError::Io(io::Error::other(err)),
+        _ => StorageError::Backend {
+            message,
+            source: Some(Box::new(err)),
+        },
+    }
 }
 
+/// A store with one writer connection, which persists batches, and a pool of read-only
+/// connections for [`StorageBackend::load_records`].
 pub struct SqliteStorage {
     connection: Connection,
+    readers: Option<ReadPool>,
     stats: StorageStats,
 }
 
 impl SqliteStorage {
+    /// Open the store at `path` with the default [`SqliteOptions`].
     pub fn new(path: impl AsRef<Path>) -> StorageResult<Self> {
+        SqliteStorage::with_options(path, SqliteOptions::default())
+    }
+
+    pub fn with_options(path: impl AsRef<Path>, options: SqliteOptions) -> StorageResult<Self> {
         let db_path = path.as_ref();
         if let Some(parent) = db_path.parent() {
             fs::create_dir_all(parent)?;
         }
         let connection = Connection::open(db_path).map_err(to_storage_error)?;
+        options.apply(&connection)?;
         Ok(SqliteStorage {
             connection,
+            readers: (options.read_pool_size > 0).then(|| ReadPool::new(db_path, options)),
             stats: StorageStats::default(),
         })
     }
 
+    /// Version stamped in the store, or `None` if it has never been initialized.
+    pub fn schema_version(&self) -> StorageResult<Option<i64>> {
+        let initialized: bool = self
+            .connection
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_meta')",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(to_storage_error)?;
+        if !initialized {
+            return Ok(None);
+        }
+        self.connection
+            .query_row(
+                "SELECT version FROM schema_meta ORDER BY id DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(to_storage_error)
+    }
+
+    /// Problems reported by SQLite's `PRAGMA integrity_check`; empty when the file is sound.
+    pub fn integrity_check(&self) -> StorageResult<Vec<String>> {
+        let mut stmt = self
+            .connection
+            .prepare("PRAGMA integrity_check")
+            .map_err(to_storage_error)?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(to_storage_error)?;
+        let problems = rows
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(to_storage_error)?;
+        Ok(problems.into_iter().filter(|line| line != "ok").collect())
+    }
+
+    /// Write a consistent copy of the store to `dest` with SQLite's online backup API. Fails
+    /// if `dest` already exists.
+    pub fn backup_to(&self, dest: impl AsRef<Path>) -> StorageResult<()> {
+        backup::copy_into(&self.connection, dest.as_ref())
+    }
+
     fn run_migrations(&self) -> StorageResult<()> {
         self.connection
             .execute_batch(
@@ -46,16 +156,61 @@ impl SqliteStorage {
             CREATE TABLE IF NOT EXISTS world_records (
                 id INTEGER PRIMARY KEY,
                 world_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                key TEXT NOT NULL,
                 payload TEXT NOT NULL,
-                timestamp INTEGER NOT NULL
+                payload_hash TEXT NOT NULL DEFAULT '',
+                timestamp INTEGER NOT NULL,
+                UNIQUE (world_id, kind, key)
             );
+
+            CREATE INDEX IF NOT EXISTS world_records_kind ON world_records (kind, key);
         ",
             )
             .map_err(to_storage_error)?;
         Ok(())
     }
 
-    fn reset_schema(&self) -> StorageResult<()> {
+    /// Version 2 stored payloads as received and had no `payload_hash`: add the column, then
+    /// canonicalize and hash every row.
+    fn upgrade_from_v2(&mut self) -> StorageResult<()> {
+        let tx = self.connection.transaction().map_err(to_storage_error)?;
+        tx.execute_batch(
+            "ALTER TABLE world_records ADD COLUMN payload_hash TEXT NOT NULL DEFAULT ''",
+        )
+        .map_err(to_storage_error)?;
+        let rows: Vec<(i64, String)> = {
+            let mut stmt = tx
+                .prepare("SELECT id, payload FROM world_records")
+                .map_err(to_storage_error)?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(to_storage_error)?;
+            rows.collect::<Result<_, _>>().map_err(to_storage_error)?
+        };
+        {
+            let mut update = tx
+                .prepare("UPDATE world_records SET payload = ?2, payload_hash = ?3 WHERE id = ?1")
+                .map_err(to_storage_error)?;
+            for (id, payload) in rows {
+                let payload = canonicalize(&payload);
+                update
+                    .execute(params![id, payload, payload_hash(&payload)])
+                    .map_err(to_storage_error)?;
+            }
+        }
+        tx.execute(
+            "INSERT INTO schema_meta (version) VALUES (?1)",
+            params![SCHEMA_VERSION],
+        )
+        .map_err(to_storage_error)?;
+        tx.commit().map_err(to_storage_error)
+    }
+
+    /// Drop every record and recreate an empty schema stamped with [`SCHEMA_VERSION`]. This is
+    /// the only way past a store [`StorageBackend::init`] refuses with
+    /// [`StorageError::SchemaMismatch`].
+    pub fn reset_schema(&mut self) -> StorageResult<()> {
         self.connection
             .execute_batch(
                 "
@@ -64,69 +219,114 @@ impl SqliteStorage {
         ",
             )
             .map_err(to_storage_error)?;
-        self.run_migrations()?;
-        Ok(())
+        self.init()
     }
 }
 
 impl StorageBackend for SqliteStorage {
     fn init(&mut self) -> StorageResult<()> {
-        self.run_migrations()?;
-        let version: Option<i64> = self
-            .connection
-            .query_row(
-                "SELECT version FROM schema_meta ORDER BY id DESC LIMIT 1",
-                [],
-                |row| row.get(0),
-            )
-            .optional()
-            .map_err(to_storage_error)?;
-        match version {
-            None => {
-                self.connection
-                    .execute(
-                        "INSERT INTO schema_meta (version) VALUES (?1)",
-                        params![SCHEMA_VERSION],
-                    )
-                    .map_err(to_storage_error)?;
-            }
-            Some(v) if v != SCHEMA_VERSION => {
-                self.reset_schema()?;
-                self.connection
-                    .execute(
-                        "INSERT INTO schema_meta (version) VALUES (?1)",
-                        params![SCHEMA_VERSION],
-                    )
-                    .map_err(to_storage_error)?;
+        match self.schema_version()? {
+            Some(version) if upgrades_in_place(version) => self.upgrade_from_v2()?,
+            Some(version) if version != SCHEMA_VERSION => {
+                return Err(StorageError::SchemaMismatch {
+                    found: version,
+                    expected: SCHEMA_VERSION,
+                })
             }
             _ => {}
         }
+        self.run_migrations()?;
+        if self.schema_version()?.is_none() {
+            self.connection
+                .execute(
+                    "INSERT INTO schema_meta (version) VALUES (?1)",
+                    params![SCHEMA_VERSION],
+                )
+                .map_err(to_storage_error)?;
+        }
         Ok(())
     }
 
     fn persist_batch(&mut self, batch: &[WorldRecord]) -> StorageResult<()> {
         let tx = self.connection.transaction().map_err(to_storage_error)?;
+        // Both statements stay in the connection's statement cache between flushes.
         let mut stmt = tx
-            .prepare("INSERT INTO world_records (world_id, payload, timestamp) VALUES (?1, ?2, ?3)")
+            .prepare_cached(
+                "INSERT INTO world_records (world_id, kind, key, payload, payload_hash, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (world_id, kind, key)
+                 DO UPDATE SET payload = excluded.payload,
+                     payload_hash = excluded.payload_hash,
+                     timestamp = excluded.timestamp",
+            )
+            .map_err(to_storage_error)?;
+        let mut delete = tx
+            .prepare_cached(
+                "DELETE FROM world_records WHERE world_id = ?1 AND kind = ?2 AND key = ?3",
+            )
             .map_err(to_storage_error)?;
         for record in batch {
+            if record.deleted {
+                delete
+                    .execute(params![record.world_id, record.kind, record.key])
+                    .map_err(to_storage_error)?;
+                continue;
+            }
             let secs = record
                 .timestamp
                 .duration_since(UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs() as i64;
-            stmt.execute(params![record.world_id, record.payload, secs])
-                .map_err(to_storage_error)?;
+            stmt.execute(params![
+                record.world_id,
+                record.kind,
+                record.key,
+                record.payload,
+                record.payload_hash,
+                secs
+            ])
+            .map_err(to_storage_error)?;
         }
         drop(stmt);
+        drop(delete);
         tx.commit().map_err(to_storage_error)?;
         self.stats.flush_count += 1;
         self.stats.last_flush = Some(SystemTime::now());
         Ok(())
     }
 
+    fn load_records(&self, kind: &str) -> StorageResult<Vec<WorldRecord>> {
+        let reader = self.readers.as_ref().map(ReadPool::acquire).transpose()?;
+        let connection = reader.as_deref().unwrap_or(&self.connection);
+        let mut stmt = connection
+            .prepare_cached(
+                "SELECT world_id, kind, key, payload, payload_hash, timestamp FROM world_records
+                 WHERE kind = ?1 ORDER BY key",
+            )
+            .map_err(to_storage_error)?;
+        let rows = stmt
+            .query_map(params![kind], |row| {
+                let secs: i64 = row.get(5)?;
+                Ok(WorldRecord {
+                    world_id: row.get(0)?,
+                    kind: row.get(1)?,
+                    key: row.get(2)?,
+                    payload: row.get(3)?,
+                    payload_hash: row.get(4)?,
+                    timestamp: UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64),
+                    deleted: false,
+                })
+            })
+            .map_err(to_storage_error)?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(to_storage_error)
+    }
+
     fn stats(&self) -> StorageStats {
-        self.stats
+        StorageStats {
+            read_pool: self.readers.as_ref().map(ReadPool::stats),
+            ..self.stats
+        }
     }
 
     fn backend_name(&self) -> &'static str {
@@ -147,6 +347,11 @@ mod tests {
 
     fn cleanup(path: &Path) {
         let _ = fs::remove_file(path);
+        for journal in ["-wal", "-shm"] {
+            let mut sidecar = path.as_os_str().to_owned();
+            sidecar.push(journal);
+            let _ = fs::remove_file(sidecar);
+        }
     }
 
     #[test]
@@ -171,7 +376,7 @@ mod tests {
     }
 
     #[test]
-    fn schema_reset_on_version_mismatch() {
+    fn mismatched_versions_are_refused_until_reset() {
         let path = test_db_path("mismatch");
         cleanup(&path);
         {
@@ -180,34 +385,68 @@ mod tests {
         }
         {
             let connection = Connection::open(&path).unwrap();
+            connection
+                .execute("UPDATE schema_meta SET version = ?1", params![1])
+                .unwrap();
             connection
                 .execute(
-                    "UPDATE schema_meta SET version = ?1",
-                    params![SCHEMA_VERSION - 1],
+                    "INSERT INTO world_records (world_id, kind, key, payload, timestamp)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params!["world", "core.snapshot", "world", "payload", 0],
                 )
                 .unwrap();
+        }
+        let mut storage = SqliteStorage::new(&path).unwrap();
+        assert!(matches!(
+            storage.init(),
+            Err(StorageError::SchemaMismatch { found: 1, .. })
+        ));
+        let count = |connection: &Connection| -> i64 {
             connection
-                .execute(
-                    "INSERT INTO world_records (world_id, payload, timestamp) VALUES (?1, ?2, ?3)",
-                    params!["world", "payload", 0],
+                .query_row("SELECT COUNT(*) FROM world_records", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(count(&Connection::open(&path).unwrap()), 1);
+
+        storage.reset_schema().unwrap();
+        let connection = Connection::open(&path).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), Some(SCHEMA_VERSION));
+        assert_eq!(count(&connection), 0);
+    }
+
+    #[test]
+    fn version_2_stores_are_canonicalized_in_place() {
+        let path = test_db_path("upgrade");
+        cleanup(&path);
+        {
+            let connection = Connection::open(&path).unwrap();
+            connection
+                .execute_batch(
+                    "CREATE TABLE schema_meta (id INTEGER PRIMARY KEY, version INTEGER NOT NULL);
+                     INSERT INTO schema_meta (version) VALUES (2);
+                     CREATE TABLE world_records (
+                         id INTEGER PRIMARY KEY, world_id TEXT NOT NULL, kind TEXT NOT NULL,
+                         key TEXT NOT NULL, payload TEXT NOT NULL, timestamp INTEGER NOT NULL,
+                         UNIQUE (world_id, kind, key));
+                     INSERT INTO world_records (world_id, kind, key, payload, timestamp)
+                     VALUES ('world', 'core.room', 'hall', '{ \"name\": \"Hall\", \"id\": \"hall\" }', 0);",
                 )
                 .unwrap();
         }
         let mut storage = SqliteStorage::new(&path).unwrap();
         storage.init().unwrap();
-        let connection = Connection::open(&path).unwrap();
-        let version: i64 = connection
-            .query_row(
-                "SELECT version FROM schema_meta ORDER BY id DESC LIMIT 1",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(version, SCHEMA_VERSION);
-        let count: i64 = connection
-            .query_row("SELECT COUNT(*) FROM world_records", [], |row| row.get(0))
-            .unwrap();
-        assert_eq!(count, 0);
+        assert_eq!(storage.schema_version().unwrap(), Some(SCHEMA_VERSION));
+        let rooms = storage.load_records("core.room").unwrap();
+        let expected = WorldRecord::new(
+            "world",
+            "core.room",
+            "hall",
+            r#"{"id":"hall","name":"Hall"}"#,
+        );
+        assert_eq!(rooms[0].payload, expected.payload);
+        assert_eq!(rooms[0].payload_hash, expected.payload_hash);
+        drop(storage);
+        cleanup(&path);
     }
 
     #[test]
@@ -220,14 +459,18 @@ mod tests {
             StorageConfig {
                 flush_interval_ms: 1,
                 batch_capacity: 1,
+                ..StorageConfig::default()
             },
         )
         .unwrap();
-        controller.record(WorldRecord {
-            world_id: "world".into(),
-            payload: "payload".into(),
-            timestamp: SystemTime::now(),
-        });
+        controller
+            .record(WorldRecord::new(
+                "world",
+                "core.snapshot",
+                "world",
+                "payload",
+            ))
+            .unwrap();
         controller.flush_pending().unwrap();
         drop(controller);
         let connection = Connection::open(&path).unwrap();
@@ -236,4 +479,126 @@ mod tests {
             .unwrap();
         assert_eq!(count, 1);
     }
+
+    #[test]
+    fn sqlite_upserts_records_by_kind_and_key() {
+        let path = test_db_path("upsert");
+        cleanup(&path);
+        let mut storage = SqliteStorage::new(&path).unwrap();
+        storage.init().unwrap();
+        storage
+            .persist_batch(&[
+                WorldRecord::new("world", "auth.account", "bob", "v1"),
+                WorldRecord::new("world", "auth.account", "alice", "v1"),
+            ])
+            .unwrap();
+        storage
+            .persist_batch(&[WorldRecord::new("world", "auth.account", "bob", "v2")])
+            .unwrap();
+        let records = storage.load_records("auth.account").unwrap();
+        let keys: Vec<_> = records.iter().map(|record| record.key.as_str()).collect();
+        assert_eq!(keys, vec!["alice", "bob"]);
+        assert_eq!(records[1].payload, "v2");
+        assert!(storage.load_records("core.room").unwrap().is_empty());
+        storage
+            .persist_batch(&[WorldRecord::tombstone("world", "auth.account", "bob")])
+            .unwrap();
+        assert_eq!(storage.load_records("auth.account").unwrap().len(), 1);
+        let pool = storage.stats().read_pool.unwrap();
+        assert_eq!((pool.size, pool.open, pool.acquired_total), (4, 1, 3));
+    }
+
+    #[test]
+    fn schema_version_and_backup_copy() {
+        let path = test_db_path("backup_source");
+        let backup = test_db_path("backup_copy");
+        cleanup(&path);
+        cleanup(&backup);
+        let storage = SqliteStorage::new(&path).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), None);
+        let mut controller = StorageController::new(storage, StorageConfig::default()).unwrap();
+        controller
+            .record(WorldRecord::new("world", "core.room", "hall", "{}"))
+            .unwrap();
+        controller.flush_all().unwrap();
+
+        let storage = SqliteStorage::new(&path).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), Some(SCHEMA_VERSION));
+        assert!(storage.integrity_check().unwrap().is_empty());
+        storage.backup_to(&backup).unwrap();
+        assert!(storage.backup_to(&backup).is_err());
+        let copy = SqliteStorage::new(&backup).unwrap();
+        assert_eq!(copy.load_records("core.room").unwrap().len(), 1);
+        cleanup(&path);
+        cleanup(&backup);
+    }
+
+    #[test]
+    fn sqlite_failures_are_classified() {
+        let path = test_db_path("classified");
+        cleanup(&path);
+        let options = SqliteOptions {
+            busy_timeout: Duration::from_millis(50),
+            ..SqliteOptions::default()
+        };
+        let mut storage = SqliteStorage::with_options(&path, options).unwrap();
+        storage.init().unwrap();
+        let holder = Connection::open(&path).unwrap();
+        holder.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let err = storage
+            .persist_batch(&[WorldRecord::new("w", "core.room", "hall", "{}")])
+            .unwrap_err();
+        assert_eq!(err.kind(), "busy", "{}", err);
+        assert!(std::error::Error::source(&err).is_some());
+        holder.execute_batch("ROLLBACK").unwrap();
+
+        let err = storage
+            .connection
+            .execute("INSERT INTO schema_meta (id, version) VALUES (1, 1)", [])
+            .map_err(to_storage_error)
+            .unwrap_err();
+        assert_eq!(err.kind(), "constraint", "{}", err);
+        cleanup(&path);
+    }
+
+    #[test]
+    fn options_set_connection_pragmas() {
+        let path = test_db_path("pragmas");
+        cleanup(&path);
+        let pragma = |storage: &SqliteStorage, name: &str| -> String {
+            storage
+                .connection
+                .query_row(&format!("PRAGMA {}", name), [], |row| {
+                    row.get::<_, rusqlite::types::Value>(0)
+                })
+                .map(|value| match value {
+                    rusqlite::types::Value::Integer(n) => n.to_string(),
+                    rusqlite::types::Value::Text(text) => text,
+                    other => format!("{:?}", other),
+                })
+                .unwrap()
+        };
+        let storage = SqliteStorage::new(&path).unwrap();
+        assert_eq!(pragma(&storage, "journal_mode"), "wal");
+        assert_eq!(pragma(&storage, "synchronous"), "1");
+        assert_eq!(pragma(&storage, "cache_size"), "-8192");
+        drop(storage);
+
+        let options = SqliteOptions {
+            journal_mode: "DELETE".parse().unwrap(),
+            synchronous: "full".parse().unwrap(),
+            busy_timeout: Duration::from_millis(250),
+            cache_size_kib: 1024,
+            read_pool_size: 0,
+        };
+        let storage = SqliteStorage::with_options(&path, options).unwrap();
+        assert_eq!(pragma(&storage, "journal_mode"), "delete");
+        assert_eq!(pragma(&storage, "synchronous"), "2");
+        assert_eq!(pragma(&storage, "busy_timeout"), "250");
+        assert!("memory"
+            .parse::<JournalMode>()
+            .unwrap_err()
+            .contains("wal, delete"));
+        cleanup(&path);
+    }
 }
@@ -0,0 +1,44 @@
+//! Advisory lock that marks a store as open by a running Engine, so offline operations that
+//! replace the store can refuse to run under it.
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::Path;
+
+use aqevia_storage::{StorageError, StorageResult};
+
+use crate::backup::sidecar;
+
+/// Exclusive lock on `<db>.lock`. The lock is released when the value is dropped or the
+/// process exits, however it exits, so a crashed Engine never leaves a store locked. The lock
+/// file itself is left in place.
+#[derive(Debug)]
+pub struct StoreLock {
+    _file: File,
+}
+
+impl StoreLock {
+    /// Take the lock for the store at `db`, creating the lock file if needed. Fails with
+    /// [`StorageError::Busy`] while another holder has it.
+    pub fn acquire(db: &Path) -> StorageResult<StoreLock> {
+        if let Some(parent) = db.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(sidecar(db, "lock"))?;
+        match file.try_lock() {
+            Ok(()) => Ok(StoreLock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(StorageError::Busy {
+                message: format!(
+                    "{} is in use by a running Engine; stop it first",
+                    db.display()
+                ),
+                source: None,
+            }),
+            Err(TryLockError::Error(err)) => Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Connection tuning applied when a store is opened.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use aqevia_storage::StorageResult;
+use rusqlite::Connection;
+
+use crate::to_storage_error;
+
+/// Pragmas and timeouts for the Engine's connections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SqliteOptions {
+    pub journal_mode: JournalMode,
+    pub synchronous: Synchronous,
+    /// How long a statement waits for another connection's lock before failing.
+    pub busy_timeout: Duration,
+    /// Page cache size in KiB, per connection.
+    pub cache_size_kib: u32,
+    /// Read-only connections that serve queries beside the writer; `0` runs queries on the
+    /// writer connection.
+    pub read_pool_size: usize,
+}
+
+impl Default for SqliteOptions {
+    /// WAL with `synchronous = NORMAL`: a flush is one sequential append to the log, and a
+    /// power loss can only drop the last flushes, never corrupt the store.
+    fn default() -> Self {
+        SqliteOptions {
+            journal_mode: JournalMode::Wal,
+            synchronous: Synchronous::Normal,
+            busy_timeout: Duration::from_secs(5),
+            cache_size_kib: 8 * 1024,
+            read_pool_size: 4,
+        }
+    }
+}
+
+impl SqliteOptions {
+    pub(crate) fn apply(&self, connection: &Connection) -> StorageResult<()> {
+        connection
+            .busy_timeout(self.busy_timeout)
+            .map_err(to_storage_error)?;
+        // `journal_mode` reports the mode it ended up in, so read it instead of expecting
+        // no rows.
+        connection
+            .pragma_update_and_check(None, "journal_mode", self.journal_mode.as_str(), |_| Ok(()))
+            .map_err(to_storage_error)?;
+        connection
+            .pragma_update(None, "synchronous", self.synchronous.as_str())
+            .map_err(to_storage_error)?;
+        self.apply_cache_size(connection)
+    }
+
+    /// The reader's share of the options: a read-only connection inherits the store's journal
+    /// mode and never syncs.
+    pub(crate) fn apply_to_reader(&self, connection: &Connection) -> StorageResult<()> {
+        connection
+            .busy_timeout(self.busy_timeout)
+            .map_err(to_storage_error)?;
+        self.apply_cache_size(connection)
+    }
+
+    fn apply_cache_size(&self, connection: &Connection) -> StorageResult<()> {
+        // A negative cache size is in KiB rather than pages.
+        connection
+            .pragma_update(None, "cache_size", -i64::from(self.cache_size_kib))
+            .map_err(to_storage_error)
+    }
+}
+
+macro_rules! pragma_enum {
+    ($(#[$doc:meta])* $name:ident { $($variant:ident => $value:literal),+ $(,)? }) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum $name {
+            $($variant),+
+        }
+
+        impl $name {
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $($name::$variant => $value),+
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = String;
+
+            /// Case-insensitive, as SQLite spells them either way.
+            fn from_str(value: &str) -> Result<Self, Self::Err> {
+                $(if value.eq_ignore_ascii_case($value) {
+                    return Ok($name::$variant);
+                })+
+                Err(format!(
+                    "expected one of {}, not {:?}",
+                    [$($value),+].join(", "),
+                    value
+                ))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+pragma_enum!(
+    /// `PRAGMA journal_mode`. The in-memory and disabled modes are left out because a crash
+    /// in them can corrupt the store.
+    JournalMode {
+        Wal => "wal",
+        Delete => "delete",
+        Truncate => "truncate",
+        Persist => "persist",
+    }
+);
+
+pragma_enum!(
+    /// `PRAGMA synchronous`: how often SQLite waits for the disk.
+    Synchronous {
+        Off => "off",
+        Normal => "normal",
+        Full => "full",
+        Extra => "extra",
+    }
+);
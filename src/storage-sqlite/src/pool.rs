@@ -0,0 +1,233 @@
+//! Read-only connections that serve queries beside the writer connection.
+
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use aqevia_storage::{ReadPoolStats, StorageError, StorageResult};
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{to_storage_error, SqliteOptions};
+
+/// Up to `size` read-only connections, opened on demand. Readers queue for a free connection
+/// and are served first come, first served, so a burst of readers cannot starve one that
+/// arrived earlier. In WAL mode the readers never block the writer, nor the writer them.
+pub(crate) struct ReadPool {
+    path: PathBuf,
+    options: SqliteOptions,
+    state: Mutex<PoolState>,
+    released: Condvar,
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: Vec<Connection>,
+    open: usize,
+    /// Tickets of queued readers; the front one is served next.
+    queue: VecDeque<u64>,
+    next_ticket: u64,
+    acquired: u64,
+    waited: u64,
+    wait_time: Duration,
+    timeouts: u64,
+}
+
+impl ReadPool {
+    pub(crate) fn new(path: &Path, options: SqliteOptions) -> Self {
+        ReadPool {
+            path: path.to_path_buf(),
+            options,
+            state: Mutex::new(PoolState::default()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait for a connection, in arrival order. Gives up after the configured busy timeout.
+    pub(crate) fn acquire(&self) -> StorageResult<PooledReader<'_>> {
+        let started = Instant::now();
+        let mut state = self.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.queue.push_back(ticket);
+        let mut queued = false;
+        while state.queue.front() != Some(&ticket)
+            || (state.idle.is_empty() && state.open >= self.options.read_pool_size)
+        {
+            queued = true;
+            let remaining = self.options.busy_timeout.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                state.queue.retain(|queued| *queued != ticket);
+                state.timeouts += 1;
+                drop(state);
+                // The reader behind this one may now be at the front.
+                self.released.notify_all();
+                return Err(StorageError::Busy {
+                    message: format!(
+                        "no read connection free after {} ms",
+                        self.options.busy_timeout.as_millis()
+                    ),
+                    source: None,
+                });
+            }
+            state = self
+                .released
+                .wait_timeout(state, remaining)
+                .expect("lock poisoning")
+                .0;
+        }
+        state.queue.pop_front();
+        state.acquired += 1;
+        if queued {
+            state.waited += 1;
+            state.wait_time += started.elapsed();
+        }
+        let idle = state.idle.pop();
+        if idle.is_none() {
+            state.open += 1;
+        }
+        drop(state);
+        // The next reader may be able to take another connection.
+        self.released.notify_all();
+        let connection = match idle {
+            Some(connection) => connection,
+            None => self.open().inspect_err(|_| {
+                self.lock().open -= 1;
+                self.released.notify_all();
+            })?,
+        };
+        Ok(PooledReader {
+            pool: self,
+            connection: Some(connection),
+        })
+    }
+
+    pub(crate) fn stats(&self) -> ReadPoolStats {
+        let state = self.lock();
+        ReadPoolStats {
+            size: self.options.read_pool_size,
+            open: state.open,
+            in_use: state.open - state.idle.len(),
+            waiting: state.queue.len(),
+            acquired_total: state.acquired,
+            waited_total: state.waited,
+            wait_ms_total: state.wait_time.as_millis() as u64,
+            timeouts_total: state.timeouts,
+        }
+    }
+
+    fn open(&self) -> StorageResult<Connection> {
+        let connection = Connection::open_with_flags(
+            &self.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(to_storage_error)?;
+        self.options.apply_to_reader(&connection)?;
+        Ok(connection)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().expect("lock poisoning")
+    }
+}
+
+/// A pooled connection, returned to the pool when dropped.
+pub(crate) struct PooledReader<'a> {
+    pool: &'a ReadPool,
+    connection: Option<Connection>,
+}
+
+impl Deref for PooledReader<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.connection
+            .as_ref()
+            .expect("connection held until drop")
+    }
+}
+
+impl Drop for PooledReader<'_> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.lock().idle.push(connection);
+            self.pool.released.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SqliteStorage;
+    use aqevia_storage::{Recovery, StorageBackend};
+    use std::sync::{mpsc, Arc};
+    use std::{env, fs, thread};
+
+    fn store(name: &str, options: SqliteOptions) -> (PathBuf, SqliteStorage) {
+        let path = env::temp_dir().join(format!("aqevia_pool_test_{}.db", name));
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.as_os_str().to_owned();
+            file.push(suffix);
+            let _ = fs::remove_file(file);
+        }
+        let mut storage = SqliteStorage::with_options(&path, options).unwrap();
+        storage.init().unwrap();
+        (path, storage)
+    }
+
+    #[test]
+    fn queued_readers_are_served_in_arrival_order() {
+        let options = SqliteOptions {
+            read_pool_size: 1,
+            ..SqliteOptions::default()
+        };
+        let (path, _writer) = store("fifo", options);
+        let pool = Arc::new(ReadPool::new(&path, options));
+        let held = pool.acquire().unwrap();
+        let (order_tx, order_rx) = mpsc::channel();
+        let mut readers = Vec::new();
+        for reader in 0..3 {
+            let (shared, order_tx) = (pool.clone(), order_tx.clone());
+            readers.push(thread::spawn(move || {
+                let _connection = shared.acquire().unwrap();
+                order_tx.send(reader).unwrap();
+                thread::sleep(Duration::from_millis(5));
+            }));
+            // Let each reader join the queue before the next one arrives.
+            while pool.stats().waiting <= reader {
+                thread::yield_now();
+            }
+        }
+        assert_eq!(pool.stats().in_use, 1);
+        drop(held);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        let order: Vec<usize> = order_rx.try_iter().collect();
+        assert_eq!(order, vec![0, 1, 2]);
+        let stats = pool.stats();
+        assert_eq!((stats.open, stats.in_use, stats.waiting), (1, 0, 0));
+        assert_eq!((stats.acquired_total, stats.waited_total), (4, 3));
+    }
+
+    #[test]
+    fn waiting_past_the_busy_timeout_fails() {
+        let options = SqliteOptions {
+            read_pool_size: 1,
+            busy_timeout: Duration::from_millis(20),
+            ..SqliteOptions::default()
+        };
+        let (path, _writer) = store("timeout", options);
+        let pool = ReadPool::new(&path, options);
+        let held = pool.acquire().unwrap();
+        let err = pool.acquire().err().unwrap();
+        assert!(err.to_string().contains("no read connection"), "{}", err);
+        assert_eq!(err.recovery(), Recovery::Retry);
+        assert_eq!(pool.stats().timeouts_total, 1);
+        assert_eq!(pool.stats().waiting, 0);
+        drop(held);
+        assert!(pool.acquire().is_ok());
+    }
+}
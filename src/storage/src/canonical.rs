@@ -0,0 +1,38 @@
+//! Canonical payload text and the content hash stored beside it.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// `payload` in canonical form: JSON is re-serialized compactly with object keys sorted, so
+/// equal values always produce equal text. Anything that is not JSON is kept as it is.
+pub fn canonicalize(payload: &str) -> String {
+    match serde_json::from_str::<Value>(payload) {
+        Ok(value) => value.to_string(),
+        Err(_) => payload.to_string(),
+    }
+}
+
+/// Lowercase hex SHA-256 of a canonical payload.
+pub fn payload_hash(canonical: &str) -> String {
+    Sha256::digest(canonical.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_json_values_share_text_and_hash() {
+        let a = canonicalize(r#"{ "name": "Hall", "exits": ["north"], "id": "hall" }"#);
+        let b = canonicalize(r#"{"id":"hall","exits":["north"],"name":"Hall"}"#);
+        assert_eq!(a, r#"{"exits":["north"],"id":"hall","name":"Hall"}"#);
+        assert_eq!(a, b);
+        assert_eq!(payload_hash(&a), payload_hash(&b));
+        assert_eq!(payload_hash(&a).len(), 64);
+        assert_eq!(canonicalize("not json {"), "not json {");
+        assert_ne!(payload_hash("look"), payload_hash("looks"));
+    }
+}
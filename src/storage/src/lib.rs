@@ -1,14 +1,25 @@
 //! Storage contract shared by all persistence backends.
 
+pub mod canonical;
+mod limits;
+
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant, SystemTime};
 
+use serde::Serialize;
+use tracing::{debug, error, info_span, warn};
+
+pub use canonical::{canonicalize, payload_hash};
+pub use limits::{LimitError, RecordLimits};
+
 pub type StorageResult<T> = Result<T, StorageError>;
 
-/// Configuration that controls persistence cadence and batching.
+/// Configuration that controls persistence cadence, batching, and the records accepted.
 #[derive(Clone, Copy)]
 pub struct StorageConfig {
     pub flush_interval_ms: u64,
     pub batch_capacity: usize,
+    pub limits: RecordLimits,
 }
 
 impl Default for StorageConfig {
@@ -16,25 +27,77 @@ impl Default for StorageConfig {
         StorageConfig {
             flush_interval_ms: 500,
             batch_capacity: 20,
+            limits: RecordLimits::default(),
         }
     }
 }
 
 /// A single durable record describing the in-memory world state snapshot.
+///
+/// Records are identified by `(world_id, kind, key)`; persisting a record with an
+/// identity that already exists replaces the stored payload, and persisting a tombstone
+/// deletes it.
+#[derive(Clone, Debug)]
 pub struct WorldRecord {
     pub world_id: String,
+    pub kind: String,
+    pub key: String,
+    /// Canonical payload text (see [`canonicalize`]).
     pub payload: String,
+    /// [`payload_hash`] of `payload`; empty for tombstones.
+    pub payload_hash: String,
     pub timestamp: SystemTime,
+    pub deleted: bool,
 }
 
 impl WorldRecord {
+    /// Build a record stamped with the current time. A JSON payload is canonicalized and
+    /// hashed here, so every record reaches storage in canonical form.
+    pub fn new(
+        world_id: impl Into<String>,
+        kind: impl Into<String>,
+        key: impl Into<String>,
+        payload: impl AsRef<str>,
+    ) -> Self {
+        let payload = canonicalize(payload.as_ref());
+        WorldRecord {
+            world_id: world_id.into(),
+            kind: kind.into(),
+            key: key.into(),
+            payload_hash: payload_hash(&payload),
+            payload,
+            timestamp: SystemTime::now(),
+            deleted: false,
+        }
+    }
+
+    /// Build a record that removes `(world_id, kind, key)` when flushed.
+    pub fn tombstone(
+        world_id: impl Into<String>,
+        kind: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Self {
+        WorldRecord {
+            world_id: world_id.into(),
+            kind: kind.into(),
+            key: key.into(),
+            payload: String::new(),
+            payload_hash: String::new(),
+            timestamp: SystemTime::now(),
+            deleted: true,
+        }
+    }
+
     pub fn summary(&self) -> String {
         let since_epoch = self
             .timestamp
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
-        format!("{}@{}", self.world_id, since_epoch)
+        format!(
+            "{}/{}/{}@{}",
+            self.world_id, self.kind, self.key, since_epoch
+        )
     }
 }
 
@@ -43,12 +106,36 @@ impl WorldRecord {
 pub struct StorageStats {
     pub flush_count: usize,
     pub last_flush: Option<SystemTime>,
+    /// Read connection pool, for backends that serve reads from one.
+    pub read_pool: Option<ReadPoolStats>,
+}
+
+/// Occupancy and contention of a backend's read connection pool.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ReadPoolStats {
+    /// Most connections the pool opens.
+    pub size: usize,
+    /// Connections opened so far; the pool opens them on demand.
+    pub open: usize,
+    pub in_use: usize,
+    /// Readers queued for a connection right now.
+    pub waiting: usize,
+    /// Connections handed out since the store was opened.
+    pub acquired_total: u64,
+    /// Acquisitions that had to queue.
+    pub waited_total: u64,
+    /// Time spent queued, summed over all acquisitions.
+    pub wait_ms_total: u64,
+    /// Readers that gave up after the busy timeout.
+    pub timeouts_total: u64,
 }
 
 /// Modular storage backend interface for durable persistence.
 pub trait StorageBackend: Send {
     fn init(&mut self) -> StorageResult<()>;
     fn persist_batch(&mut self, batch: &[WorldRecord]) -> StorageResult<()>;
+    /// Load every persisted record of the given `kind`, ordered by key.
+    fn load_records(&self, kind: &str) -> StorageResult<Vec<WorldRecord>>;
     fn stats(&self) -> StorageStats;
     fn backend_name(&self) -> &'static str;
 }
@@ -59,6 +146,13 @@ pub struct StorageController<B: StorageBackend> {
     config: StorageConfig,
     pending: Vec<WorldRecord>,
     last_flush: Instant,
+    flush_seq: u64,
+    /// Hash of every record this controller has flushed, by `(world_id, kind, key)`.
+    flushed: HashMap<(String, String, String), String>,
+    /// Records dropped by flushes since [`StorageController::take_dropped`] was last called.
+    dropped: u64,
+    /// Kinds not held to the [`RecordLimits`].
+    exempt: HashSet<String>,
 }
 
 impl<B: StorageBackend> StorageController<B> {
@@ -69,11 +163,43 @@ impl<B: StorageBackend> StorageController<B> {
             config,
             pending: Vec::with_capacity(config.batch_capacity),
             last_flush: Instant::now(),
+            flush_seq: 0,
+            flushed: HashMap::new(),
+            dropped: 0,
+            exempt: HashSet::new(),
         })
     }
 
-    pub fn record(&mut self, payload: WorldRecord) {
-        self.pending.push(payload);
+    /// Queue a record for the next flush. A record over the configured [`RecordLimits`] is
+    /// refused and not queued.
+    pub fn record(&mut self, record: WorldRecord) -> Result<(), LimitError> {
+        self.check(&record)?;
+        self.pending.push(record);
+        Ok(())
+    }
+
+    /// Stop holding records of `kind` to the [`RecordLimits`]. Meant for records the caller
+    /// builds itself out of payloads that were already checked, which can be larger and
+    /// nest deeper than any one of them.
+    pub fn exempt_from_limits(&mut self, kind: impl Into<String>) {
+        self.exempt.insert(kind.into());
+    }
+
+    fn check(&self, record: &WorldRecord) -> Result<(), LimitError> {
+        if record.deleted || self.exempt.contains(&record.kind) {
+            return Ok(());
+        }
+        self.config.limits.check(&record.payload)
+    }
+
+    pub fn config(&self) -> StorageConfig {
+        self.config
+    }
+
+    /// Change the flush cadence and batch size. Takes effect on the next `flush_if_due`; records
+    /// already pending are kept.
+    pub fn set_config(&mut self, config: StorageConfig) {
+        self.config = config;
     }
 
     pub fn pending(&self) -> &[WorldRecord] {
@@ -93,12 +219,15 @@ impl<B: StorageBackend> StorageController<B> {
         }
     }
 
+    /// Write everything pending. A batch the backend refuses for its contents (see
+    /// [`Recovery::Skip`]) is retried one record at a time, and the records refused again are
+    /// dropped. On any other error the unwritten records stay queued.
     pub fn flush_pending(&mut self) -> StorageResult<bool> {
         if self.pending.is_empty() {
             return Ok(false);
         }
 
-        self.backend.persist_batch(&self.pending)?;
+        self.persist(true)?;
         self.pending.clear();
         self.last_flush = Instant::now();
         Ok(true)
@@ -109,6 +238,130 @@ impl<B: StorageBackend> StorageController<B> {
         Ok(())
     }
 
+    /// Persist `batch` together with everything pending in a single backend call, so the
+    /// backend commits them in one transaction. On failure `batch` is dropped and only the
+    /// previously pending records stay queued.
+    pub fn commit_batch(&mut self, batch: Vec<WorldRecord>) -> StorageResult<()> {
+        for record in &batch {
+            self.check(record)?;
+        }
+        let queued = self.pending.len();
+        self.pending.extend(batch);
+        if let Err(err) = self.persist(false) {
+            self.pending.truncate(queued);
+            return Err(err);
+        }
+        self.pending.clear();
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Number of records flushes have dropped since the last call.
+    pub fn take_dropped(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// Hand everything pending to the backend inside a `flush` span numbered per controller,
+    /// logging the outcome. Only the newest record per identity is written, and not at all if
+    /// its hash matches what this controller last flushed for it. With `isolate`, a batch
+    /// refused for its contents is written record by record instead.
+    fn persist(&mut self, isolate: bool) -> StorageResult<()> {
+        self.flush_seq += 1;
+        let batch = self.changed();
+        let skipped = self.pending.len() - batch.len();
+        let _span = info_span!(
+            "flush",
+            flush_id = self.flush_seq,
+            records = batch.len(),
+            skipped
+        )
+        .entered();
+        if batch.is_empty() {
+            debug!("nothing changed");
+            return Ok(());
+        }
+        match self.write(&batch) {
+            Err(err) if isolate && err.recovery() == Recovery::Skip => {
+                warn!("writing the refused batch one record at a time");
+                for (index, record) in batch.iter().enumerate() {
+                    match self.write(std::slice::from_ref(record)) {
+                        Ok(()) => {}
+                        Err(err) if err.recovery() == Recovery::Skip => {
+                            error!(
+                                kind = %record.kind,
+                                key = %record.key,
+                                error = %err,
+                                "record dropped"
+                            );
+                            self.dropped += 1;
+                        }
+                        Err(err) => {
+                            self.pending = batch[index..].to_vec();
+                            return Err(err);
+                        }
+                    }
+                }
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn write(&mut self, batch: &[WorldRecord]) -> StorageResult<()> {
+        let started = Instant::now();
+        match self.backend.persist_batch(batch) {
+            Ok(()) => {
+                debug!(elapsed_ms = started.elapsed().as_millis() as u64, "flushed");
+                for record in batch {
+                    if record.deleted {
+                        self.flushed.remove(&identity(record));
+                    } else {
+                        self.flushed
+                            .insert(identity(record), record.payload_hash.clone());
+                    }
+                }
+                Ok(())
+            }
+            Err(err) => {
+                error!(
+                    backend = self.backend.backend_name(),
+                    kind = err.kind(),
+                    error = %err,
+                    "flush failed"
+                );
+                Err(err)
+            }
+        }
+    }
+
+    /// Pending records that change storage, in queue order: the last record per identity,
+    /// unless it carries the hash already flushed for it.
+    fn changed(&self) -> Vec<WorldRecord> {
+        let mut newest = HashMap::new();
+        for (index, record) in self.pending.iter().enumerate() {
+            newest.insert((&record.world_id, &record.kind, &record.key), index);
+        }
+        self.pending
+            .iter()
+            .enumerate()
+            .filter(|(index, record)| {
+                newest[&(&record.world_id, &record.kind, &record.key)] == *index
+                    && (record.deleted
+                        || self
+                            .flushed
+                            .get(&identity(record))
+                            .is_none_or(|hash| *hash != record.payload_hash))
+            })
+            .map(|(_, record)| record.clone())
+            .collect()
+    }
+
+    /// Read persisted records of `kind`. Pending records are not included, so callers that
+    /// need read-your-writes semantics should flush first.
+    pub fn load_records(&self, kind: &str) -> StorageResult<Vec<WorldRecord>> {
+        self.backend.load_records(kind)
+    }
+
     pub fn stats(&self) -> StorageStats {
         self.backend.stats()
     }
@@ -118,14 +371,106 @@ impl<B: StorageBackend> StorageController<B> {
     }
 }
 
-/// Error returned by storage operations.
+fn identity(record: &WorldRecord) -> (String, String, String) {
+    (
+        record.world_id.clone(),
+        record.kind.clone(),
+        record.key.clone(),
+    )
+}
+
+/// Error returned by storage operations, classified by cause so callers can choose a
+/// [`Recovery`]. Backend errors keep the error they were raised from as their source.
 #[derive(thiserror::Error, Debug)]
-#[error("storage error: {0}")]
-pub struct StorageError(pub String);
+pub enum StorageError {
+    /// The filesystem failed: a full disk, a missing file, or a read or write that did not
+    /// complete.
+    #[error("storage I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+    /// Another connection held a lock for longer than the backend waits.
+    #[error("storage is busy: {message}")]
+    Busy {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+    /// A write broke a uniqueness, foreign key, or other integrity constraint.
+    #[error("storage constraint violated: {message}")]
+    Constraint {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+    /// The store is damaged, or is not a store at all.
+    #[error("storage is corrupt: {message}")]
+    Corrupt {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+    /// The store is stamped with a schema version this build does not use.
+    #[error("storage is at schema version {found}, but this build expects {expected}")]
+    SchemaMismatch { found: i64, expected: i64 },
+    /// A record over the configured [`RecordLimits`].
+    #[error("record refused: {0}")]
+    LimitExceeded(#[from] LimitError),
+    /// Any other backend failure.
+    #[error("storage error: {message}")]
+    Backend {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// What to do about a failed write, by [`StorageError::recovery`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// The store is momentarily unavailable. Keep the records queued and write them on the next
+    /// flush; storage stays ready.
+    Retry,
+    /// Keep the records queued and write them on the next flush, but report storage not ready
+    /// until a flush succeeds.
+    Degrade,
+    /// The records themselves were refused and would be refused again. Drop them so they do
+    /// not hold back the rest of the queue.
+    Skip,
+    /// The store cannot be trusted. Stop writing to it.
+    Abort,
+}
 
-impl From<std::io::Error> for StorageError {
-    fn from(value: std::io::Error) -> Self {
-        StorageError(value.to_string())
+impl StorageError {
+    /// A [`StorageError::Backend`] error with no source.
+    pub fn backend(message: impl Into<String>) -> Self {
+        StorageError::Backend {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            StorageError::Busy { .. } => Recovery::Retry,
+            StorageError::Io(_) | StorageError::Backend { .. } => Recovery::Degrade,
+            StorageError::Constraint { .. } | StorageError::LimitExceeded(_) => Recovery::Skip,
+            StorageError::Corrupt { .. } | StorageError::SchemaMismatch { .. } => Recovery::Abort,
+        }
+    }
+
+    /// The variant, as used in metric labels: `io`, `busy`, `constraint`, `corrupt`,
+    /// `schema_mismatch`, `limit_exceeded`, or `backend`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StorageError::Io(_) => "io",
+            StorageError::Busy { .. } => "busy",
+            StorageError::Constraint { .. } => "constraint",
+            StorageError::Corrupt { .. } => "corrupt",
+            StorageError::SchemaMismatch { .. } => "schema_mismatch",
+            StorageError::LimitExceeded(_) => "limit_exceeded",
+            StorageError::Backend { .. } => "backend",
+        }
     }
 }
 
@@ -134,9 +479,12 @@ mod tests {
     use super::*;
 
     struct DummyBackend {
-        persisted: Vec<String>,
+        persisted: Vec<WorldRecord>,
         stats: StorageStats,
         name: &'static str,
+        /// Key of a record every batch containing it is refused for.
+        refuse: Option<&'static str>,
+        busy: bool,
     }
 
     impl Default for DummyBackend {
@@ -145,6 +493,8 @@ mod tests {
                 persisted: Vec::new(),
                 stats: StorageStats::default(),
                 name: "dummy",
+                refuse: None,
+                busy: false,
             }
         }
     }
@@ -155,13 +505,37 @@ mod tests {
         }
 
         fn persist_batch(&mut self, batch: &[WorldRecord]) -> StorageResult<()> {
+            if self.busy {
+                return Err(StorageError::Busy {
+                    message: "database is locked".into(),
+                    source: None,
+                });
+            }
+            if let Some(key) = batch
+                .iter()
+                .find(|record| Some(record.key.as_str()) == self.refuse)
+                .map(|record| record.key.clone())
+            {
+                return Err(StorageError::Constraint {
+                    message: format!("{key} is not unique"),
+                    source: None,
+                });
+            }
             self.stats.flush_count += 1;
             self.stats.last_flush = Some(SystemTime::now());
-            self.persisted
-                .extend(batch.iter().map(|record| record.summary()));
+            self.persisted.extend(batch.iter().cloned());
             Ok(())
         }
 
+        fn load_records(&self, kind: &str) -> StorageResult<Vec<WorldRecord>> {
+            Ok(self
+                .persisted
+                .iter()
+                .filter(|record| record.kind == kind)
+                .cloned()
+                .collect())
+        }
+
         fn stats(&self) -> StorageStats {
             self.stats
         }
@@ -177,23 +551,232 @@ mod tests {
         let config = StorageConfig {
             flush_interval_ms: 1000,
             batch_capacity: 2,
+            ..StorageConfig::default()
         };
         let mut controller = StorageController::new(backend, config).unwrap();
-        controller.record(WorldRecord {
-            world_id: "w".into(),
-            payload: "one".into(),
-            timestamp: SystemTime::now(),
-        });
+        controller
+            .record(WorldRecord::new("w", "core.snapshot", "w", "one"))
+            .unwrap();
         assert!(!controller.flush_if_due().unwrap());
-        controller.record(WorldRecord {
-            world_id: "w".into(),
-            payload: "two".into(),
-            timestamp: SystemTime::now(),
-        });
+        controller
+            .record(WorldRecord::new("w", "core.snapshot", "w", "two"))
+            .unwrap();
         assert!(controller.flush_if_due().unwrap());
         assert!(controller.pending().is_empty());
     }
 
+    #[test]
+    fn load_records_filters_by_kind() {
+        let backend = DummyBackend::default();
+        let mut controller = StorageController::new(backend, StorageConfig::default()).unwrap();
+        controller
+            .record(WorldRecord::new("w", "auth.account", "alice", "{}"))
+            .unwrap();
+        controller
+            .record(WorldRecord::new("w", "core.snapshot", "w", "look"))
+            .unwrap();
+        controller.flush_all().unwrap();
+        let accounts = controller.load_records("auth.account").unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(
+            accounts[0].summary().split('@').next(),
+            Some("w/auth.account/alice")
+        );
+    }
+
+    #[test]
+    fn unchanged_and_superseded_records_are_not_rewritten() {
+        let backend = DummyBackend::default();
+        let mut controller = StorageController::new(backend, StorageConfig::default()).unwrap();
+        controller
+            .record(WorldRecord::new(
+                "w",
+                "core.room",
+                "hall",
+                r#"{"a":1,"b":2}"#,
+            ))
+            .unwrap();
+        controller.flush_all().unwrap();
+        controller
+            .record(WorldRecord::new(
+                "w",
+                "core.room",
+                "hall",
+                r#"{"b":2, "a":1}"#,
+            ))
+            .unwrap();
+        controller.flush_all().unwrap();
+        assert!(controller.pending().is_empty());
+        assert_eq!(controller.stats().flush_count, 1);
+
+        controller
+            .record(WorldRecord::new("w", "core.room", "hall", r#"{"a":2}"#))
+            .unwrap();
+        controller
+            .record(WorldRecord::new(
+                "w",
+                "core.room",
+                "hall",
+                r#"{"a":1,"b":2}"#,
+            ))
+            .unwrap();
+        controller
+            .record(WorldRecord::new("w", "core.room", "cellar", "{}"))
+            .unwrap();
+        controller.flush_all().unwrap();
+        let rooms = controller.load_records("core.room").unwrap();
+        assert_eq!(controller.stats().flush_count, 2);
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[1].key, "cellar");
+
+        // Deleted and recreated unchanged within one batch: storage already holds it.
+        controller
+            .record(WorldRecord::tombstone("w", "core.room", "hall"))
+            .unwrap();
+        controller
+            .record(WorldRecord::new(
+                "w",
+                "core.room",
+                "hall",
+                r#"{"a":1,"b":2}"#,
+            ))
+            .unwrap();
+        controller.flush_all().unwrap();
+        assert_eq!(controller.stats().flush_count, 2);
+        controller
+            .record(WorldRecord::tombstone("w", "core.room", "hall"))
+            .unwrap();
+        controller.flush_all().unwrap();
+        controller
+            .record(WorldRecord::new(
+                "w",
+                "core.room",
+                "hall",
+                r#"{"a":1,"b":2}"#,
+            ))
+            .unwrap();
+        controller.flush_all().unwrap();
+        assert_eq!(controller.stats().flush_count, 4);
+    }
+
+    #[test]
+    fn records_over_the_limits_are_refused() {
+        let backend = DummyBackend::default();
+        let config = StorageConfig {
+            limits: RecordLimits {
+                max_payload_bytes: 16,
+                ..RecordLimits::default()
+            },
+            ..StorageConfig::default()
+        };
+        let mut controller = StorageController::new(backend, config).unwrap();
+        let oversized = WorldRecord::new("w", "core.room", "hall", r#"{"name":"A long hall"}"#);
+        let err = controller.record(oversized.clone()).unwrap_err();
+        assert_eq!(err.limit(), "payload_bytes");
+        assert!(controller.pending().is_empty());
+        controller
+            .record(WorldRecord::tombstone("w", "core.room", "hall"))
+            .unwrap();
+        assert!(controller.commit_batch(vec![oversized]).is_err());
+        assert_eq!(controller.pending().len(), 1);
+
+        controller.exempt_from_limits("builder.changeset");
+        controller
+            .record(WorldRecord::new(
+                "w",
+                "builder.changeset",
+                "cs-1",
+                r#"{"title":"A long changeset"}"#,
+            ))
+            .unwrap();
+        assert_eq!(controller.pending().len(), 2);
+    }
+
+    #[test]
+    fn refused_records_are_dropped_and_busy_flushes_keep_the_queue() {
+        let backend = DummyBackend {
+            refuse: Some("bad"),
+            ..DummyBackend::default()
+        };
+        let mut controller = StorageController::new(backend, StorageConfig::default()).unwrap();
+        for key in ["lobby", "bad", "hall"] {
+            controller
+                .record(WorldRecord::new("w", "core.room", key, "{}"))
+                .unwrap();
+        }
+        assert!(controller.flush_pending().unwrap());
+        assert!(controller.pending().is_empty());
+        assert_eq!(controller.take_dropped(), 1);
+        assert_eq!(controller.take_dropped(), 0);
+        let keys: Vec<String> = controller
+            .load_records("core.room")
+            .unwrap()
+            .into_iter()
+            .map(|record| record.key)
+            .collect();
+        assert_eq!(keys, ["lobby", "hall"]);
+
+        // The whole batch is refused in one transaction, with nothing dropped.
+        let err = controller
+            .commit_batch(vec![WorldRecord::new("w", "core.room", "bad", "{}")])
+            .unwrap_err();
+        assert_eq!(err.recovery(), Recovery::Skip);
+        assert_eq!(controller.take_dropped(), 0);
+
+        controller.backend.busy = true;
+        controller
+            .record(WorldRecord::new("w", "core.room", "cellar", "{}"))
+            .unwrap();
+        let err = controller.flush_pending().unwrap_err();
+        assert_eq!((err.kind(), err.recovery()), ("busy", Recovery::Retry));
+        assert_eq!(controller.pending().len(), 1);
+        controller.backend.busy = false;
+        assert!(controller.flush_pending().unwrap());
+        assert!(controller.pending().is_empty());
+    }
+
+    #[test]
+    fn backend_errors_keep_their_source() {
+        let io = std::io::Error::new(std::io::ErrorKind::StorageFull, "disk full");
+        let err = StorageError::Backend {
+            message: "write failed".into(),
+            source: Some(Box::new(io)),
+        };
+        assert_eq!(err.to_string(), "storage error: write failed");
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "disk full"
+        );
+        let limit = StorageError::from(LimitError::TooDeep { depth: 4, limit: 3 });
+        assert_eq!(limit.recovery(), Recovery::Skip);
+        assert_eq!(
+            StorageError::SchemaMismatch {
+                found: 1,
+                expected: 3
+            }
+            .recovery(),
+            Recovery::Abort
+        );
+    }
+
+    #[test]
+    fn commit_batch_persists_pending_and_batch_together() {
+        let backend = DummyBackend::default();
+        let mut controller = StorageController::new(backend, StorageConfig::default()).unwrap();
+        controller
+            .record(WorldRecord::new("w", "core.snapshot", "w", "look"))
+            .unwrap();
+        controller
+            .commit_batch(vec![
+                WorldRecord::new("w", "core.room", "lobby", "{}"),
+                WorldRecord::new("w", "core.room", "hall", "{}"),
+            ])
+            .unwrap();
+        assert!(controller.pending().is_empty());
+        assert_eq!(controller.stats().flush_count, 1);
+        assert_eq!(controller.load_records("core.room").unwrap().len(), 2);
+    }
+
     #[test]
     fn flush_all_with_no_pending() {
         let backend = DummyBackend::default();
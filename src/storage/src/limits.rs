@@ -0,0 +1,147 @@
+//! Caps on the size and shape of record payloads.
+
+/// Per-record payload limits. A limit of `0` is not enforced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordLimits {
+    /// Payload size in bytes.
+    pub max_payload_bytes: usize,
+    /// Nesting of JSON arrays and objects; a flat object is depth 1.
+    pub max_depth: usize,
+    /// Length in bytes of any JSON string, object keys included, as written in the payload.
+    pub max_string_bytes: usize,
+}
+
+impl Default for RecordLimits {
+    fn default() -> Self {
+        RecordLimits {
+            max_payload_bytes: 256 * 1024,
+            max_depth: 32,
+            max_string_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// A payload over one of the [`RecordLimits`].
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub enum LimitError {
+    #[error("payload is {size} bytes, over the limit of {limit}")]
+    PayloadTooLarge { size: usize, limit: usize },
+    #[error("payload nests {depth} levels deep, over the limit of {limit}")]
+    TooDeep { depth: usize, limit: usize },
+    #[error("payload has a {length}-byte string, over the limit of {limit}")]
+    StringTooLong { length: usize, limit: usize },
+}
+
+impl LimitError {
+    /// Which limit was exceeded, as used in metric labels: `payload_bytes`, `depth`, or
+    /// `string_bytes`.
+    pub fn limit(&self) -> &'static str {
+        match self {
+            LimitError::PayloadTooLarge { .. } => "payload_bytes",
+            LimitError::TooDeep { .. } => "depth",
+            LimitError::StringTooLong { .. } => "string_bytes",
+        }
+    }
+}
+
+impl RecordLimits {
+    /// Check a serialized payload. Depth and string length are measured in one pass over the
+    /// text, without parsing, so a payload too deep for a JSON parser is still reported as too
+    /// deep. Payloads that are not JSON documents are only held to the size limit.
+    pub fn check(&self, payload: &str) -> Result<(), LimitError> {
+        if self.max_payload_bytes > 0 && payload.len() > self.max_payload_bytes {
+            return Err(LimitError::PayloadTooLarge {
+                size: payload.len(),
+                limit: self.max_payload_bytes,
+            });
+        }
+        if !payload.trim_start().starts_with(['{', '[', '"']) {
+            return Ok(());
+        }
+        let (mut depth, mut string, mut escaped) = (0usize, None::<usize>, false);
+        for byte in payload.bytes() {
+            if let Some(length) = string.as_mut() {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => {
+                        string = None;
+                        continue;
+                    }
+                    _ => {}
+                }
+                *length += 1;
+                if self.max_string_bytes > 0 && *length > self.max_string_bytes {
+                    return Err(LimitError::StringTooLong {
+                        length: *length,
+                        limit: self.max_string_bytes,
+                    });
+                }
+                continue;
+            }
+            match byte {
+                b'"' => string = Some(0),
+                b'{' | b'[' => {
+                    depth += 1;
+                    if self.max_depth > 0 && depth > self.max_depth {
+                        return Err(LimitError::TooDeep {
+                            depth,
+                            limit: self.max_depth,
+                        });
+                    }
+                }
+                b'}' | b']' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_report_the_first_violation() {
+        let limits = RecordLimits {
+            max_payload_bytes: 64,
+            max_depth: 3,
+            max_string_bytes: 8,
+        };
+        assert_eq!(limits.check(r#"{"a":[{"b":"12345678"}]}"#), Ok(()));
+        assert_eq!(
+            limits.check(&format!(r#"{{"a":"{}"}}"#, "x".repeat(70))),
+            Err(LimitError::PayloadTooLarge {
+                size: 78,
+                limit: 64
+            })
+        );
+        let deep = limits.check(r#"{"a":[{"b":[1]}]}"#).unwrap_err();
+        assert_eq!(deep.limit(), "depth");
+        assert_eq!(
+            limits.check(r#"{"a":"123456789"}"#),
+            Err(LimitError::StringTooLong {
+                length: 9,
+                limit: 8
+            })
+        );
+        // Brackets and escaped quotes inside strings are text, not structure.
+        assert_eq!(limits.check(r#"{"a":"[[[\"]]"}"#), Ok(()));
+        assert_eq!(limits.check("[[[[ not json"), Err(deep_error(4)));
+        assert_eq!(limits.check("plain text [[[["), Ok(()));
+        assert_eq!(
+            RecordLimits {
+                max_payload_bytes: 0,
+                max_depth: 0,
+                max_string_bytes: 0
+            }
+            .check(&"[".repeat(1000)),
+            Ok(())
+        );
+    }
+
+    fn deep_error(depth: usize) -> LimitError {
+        LimitError::TooDeep { depth, limit: 3 }
+    }
+}
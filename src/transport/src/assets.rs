@@ -0,0 +1,264 @@
+//! Static hosting for the Aqevia Web UI single-page app.
+//!
+//! `/`, `/client/*`, `/builder/*`, and `/admin/*` all return the SPA shell (`index.html`) so
+//! deep links survive a reload; any other path is looked up as a file under the asset root.
+//! The shell is served `no-store` so a deploy is picked up on the next navigation. Hashed build
+//! output (`index-3f9a1c2b.js`) is cached as immutable, and everything else revalidates with
+//! its `ETag`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha1::{Digest, Sha1};
+
+use crate::http::{HttpHandler, HttpRequest, HttpResponse};
+
+/// Entry HTML returned for the root and every SPA area.
+pub const SHELL: &str = "index.html";
+
+/// Path prefixes whose deep links are rewritten to the shell.
+pub const SPA_AREAS: [&str; 3] = ["/client", "/builder", "/admin"];
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+enum AssetSource {
+    Directory(PathBuf),
+    Embedded(BTreeMap<&'static str, &'static [u8]>),
+}
+
+/// Serves UI files either from a directory on disk or from a table of files compiled into the
+/// binary (e.g. with `include_bytes!`). Register it after the API handlers: it never claims
+/// `/api/*` or `/ws`.
+pub struct StaticAssets {
+    source: AssetSource,
+}
+
+impl StaticAssets {
+    /// Serve the build output in `root`, re-reading files on every request.
+    pub fn from_dir(root: impl Into<PathBuf>) -> Self {
+        StaticAssets {
+            source: AssetSource::Directory(root.into()),
+        }
+    }
+
+    /// Serve files embedded at compile time, keyed by their path relative to the build output.
+    pub fn embedded(files: &[(&'static str, &'static [u8])]) -> Self {
+        StaticAssets {
+            source: AssetSource::Embedded(
+                files
+                    .iter()
+                    .map(|(path, bytes)| (path.trim_start_matches('/'), *bytes))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn read(&self, relative: &str) -> Option<Vec<u8>> {
+        match &self.source {
+            AssetSource::Directory(root) => {
+                let path = root.join(relative);
+                if path.is_file() {
+                    fs::read(path).ok()
+                } else {
+                    None
+                }
+            }
+            AssetSource::Embedded(files) => files.get(relative).map(|bytes| bytes.to_vec()),
+        }
+    }
+}
+
+impl HttpHandler for StaticAssets {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        if request.method != "GET" || request.path == "/api" || request.path.starts_with("/api/") {
+            return None;
+        }
+        let (relative, cache_control) = if is_shell_route(&request.path) {
+            (SHELL.to_string(), "no-store")
+        } else {
+            let relative = safe_relative_path(&request.path)?;
+            let cache_control = if is_hashed(&relative) {
+                IMMUTABLE
+            } else {
+                "no-cache"
+            };
+            (relative, cache_control)
+        };
+        let body = self.read(&relative)?;
+        let etag = format!("\"{}\"", hex(&Sha1::digest(&body)[..8]));
+        let response = if request
+            .header("if-none-match")
+            .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag))
+        {
+            HttpResponse {
+                status: 304,
+                headers: Vec::new(),
+                body: Vec::new(),
+            }
+        } else {
+            HttpResponse::new(200, content_type(&relative), body)
+        };
+        Some(
+            response
+                .with_header("ETag", &etag)
+                .with_header("Cache-Control", cache_control),
+        )
+    }
+}
+
+fn is_shell_route(path: &str) -> bool {
+    path == "/"
+        || SPA_AREAS.iter().any(|area| {
+            path.strip_prefix(area)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+}
+
+/// Map a request path onto a relative file path, refusing anything that could escape the root.
+fn safe_relative_path(path: &str) -> Option<String> {
+    let relative = path.trim_start_matches('/');
+    let valid = !relative.is_empty()
+        && !relative.contains(['\\', '\0'])
+        && relative
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+    (valid && Path::new(relative).is_relative()).then(|| relative.to_string())
+}
+
+/// Whether the file name carries a content hash: a final `-` or `.` separated stem segment of
+/// at least eight `[A-Za-z0-9_]` characters including a digit, as bundlers emit.
+fn is_hashed(relative: &str) -> bool {
+    let name = relative.rsplit('/').next().unwrap_or(relative);
+    let Some((stem, _extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+    stem.rsplit(['-', '.']).next().is_some_and(|segment| {
+        segment.len() != stem.len()
+            && segment.len() >= 8
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && segment.chars().any(|c| c.is_ascii_digit())
+    })
+}
+
+fn content_type(relative: &str) -> &'static str {
+    let extension = relative
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: &[(&str, &[u8])] = &[
+        ("index.html", b"<!doctype html><div id=app></div>"),
+        ("assets/index-3f9a1c2b.js", b"console.log(1)"),
+        ("favicon.svg", b"<svg/>"),
+    ];
+
+    fn get(assets: &StaticAssets, path: &str) -> Option<HttpResponse> {
+        assets.handle(&HttpRequest::new("GET", path))
+    }
+
+    #[test]
+    fn spa_routes_rewrite_to_the_shell() {
+        let assets = StaticAssets::embedded(FILES);
+        for path in [
+            "/",
+            "/client",
+            "/builder/rooms/lobby",
+            "/admin/audit?actor=x",
+        ] {
+            let response = get(&assets, path).unwrap();
+            assert_eq!(response.status, 200, "{}", path);
+            assert_eq!(
+                response.header("content-type"),
+                Some("text/html; charset=utf-8")
+            );
+            assert_eq!(response.header("cache-control"), Some("no-store"));
+        }
+        assert!(get(&assets, "/clientele").is_none());
+        assert!(get(&assets, "/api/admin/sessions").is_none());
+        assert!(assets
+            .handle(&HttpRequest::new("POST", "/client"))
+            .is_none());
+    }
+
+    #[test]
+    fn assets_get_content_types_etags_and_cache_policy() {
+        let assets = StaticAssets::embedded(FILES);
+        let script = get(&assets, "/assets/index-3f9a1c2b.js").unwrap();
+        assert_eq!(
+            script.header("content-type"),
+            Some("text/javascript; charset=utf-8")
+        );
+        assert_eq!(script.header("cache-control"), Some(IMMUTABLE));
+        let icon = get(&assets, "/favicon.svg").unwrap();
+        assert_eq!(icon.header("content-type"), Some("image/svg+xml"));
+        assert_eq!(icon.header("cache-control"), Some("no-cache"));
+
+        let etag = script.header("etag").unwrap().to_string();
+        let cached = assets
+            .handle(
+                &HttpRequest::new("GET", "/assets/index-3f9a1c2b.js")
+                    .with_header("If-None-Match", &etag),
+            )
+            .unwrap();
+        assert_eq!(cached.status, 304);
+        assert!(cached.body.is_empty());
+        assert!(get(&assets, "/missing.js").is_none());
+    }
+
+    #[test]
+    fn directory_source_refuses_traversal() {
+        let root = std::env::temp_dir().join(format!("aqevia-assets-{}", std::process::id()));
+        fs::create_dir_all(root.join("assets")).unwrap();
+        fs::write(root.join(SHELL), "<!doctype html>").unwrap();
+        fs::write(root.join("assets/app.css"), "body{}").unwrap();
+        let assets = StaticAssets::from_dir(&root);
+        assert_eq!(
+            get(&assets, "/admin/bans").unwrap().body,
+            b"<!doctype html>"
+        );
+        assert_eq!(get(&assets, "/assets/app.css").unwrap().body, b"body{}");
+        assert!(get(&assets, "/assets/../index.html").is_none());
+        assert!(get(&assets, "/assets").is_none());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn hashed_names_are_detected() {
+        assert!(is_hashed("assets/index-BQ2x9f3a.js"));
+        assert!(is_hashed("app.3f9a1c2b.css"));
+        assert!(!is_hashed("component-selectors.js"));
+        assert!(!is_hashed("3f9a1c2b3.js"));
+        assert!(!is_hashed("favicon.svg"));
+    }
+}
@@ -0,0 +1,232 @@
+//! Auth middleware for control-plane routes plus the `/api/auth/*` session endpoints.
+
+use std::sync::Arc;
+
+use aqevia_auth::{AuthError, AuthService, Principal, Role};
+use serde::Deserialize;
+
+use crate::http::{path_segments, HttpHandler, HttpRequest, HttpResponse};
+
+/// Maps path prefixes to the minimum role required to call them.
+#[derive(Clone, Debug)]
+pub struct AccessPolicy {
+    rules: Vec<(String, Role)>,
+}
+
+impl AccessPolicy {
+    pub fn new(rules: Vec<(String, Role)>) -> Self {
+        AccessPolicy { rules }
+    }
+
+    /// Minimum role for `path`, or `None` when the path is public. Rules match whole segments,
+    /// the same way handlers route, so `/api/adminx` is not under `/api/admin/`.
+    pub fn required_role(&self, path: &str) -> Option<Role> {
+        let segments = path_segments(path);
+        self.rules
+            .iter()
+            .filter(|(prefix, _)| segments.starts_with(&path_segments(prefix)))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, role)| *role)
+    }
+
+    /// Attach the caller's principal to `request` and reject calls that lack the required role.
+    pub fn authorize(
+        &self,
+        auth: &AuthService,
+        request: &mut HttpRequest,
+    ) -> Result<(), HttpResponse> {
+        let principal = match request.bearer_token().map(|token| auth.authenticate(token)) {
+            Some(Ok(principal)) => Some(principal),
+            Some(Err(err)) if self.required_role(&request.path).is_some() => {
+                return Err(auth_error_response(&err));
+            }
+            _ => None,
+        };
+        if let Some(required) = self.required_role(&request.path) {
+            let principal = principal
+                .as_ref()
+                .ok_or_else(|| auth_error_response(&AuthError::InvalidToken))?;
+            principal
+                .require(required)
+                .map_err(|err| auth_error_response(&err))?;
+        }
+        request.principal = principal;
+        Ok(())
+    }
+}
+
+impl Default for AccessPolicy {
+    fn default() -> Self {
+        AccessPolicy::new(vec![
+            ("/api/client/".into(), Role::Player),
+            ("/api/builder/".into(), Role::Builder),
+            ("/api/admin/".into(), Role::Admin),
+            ("/logs/".into(), Role::Admin),
+        ])
+    }
+}
+
+/// The principal the middleware attached to `request`, provided it holds at least `role`.
+/// Handlers of role-gated routes check this themselves rather than trusting that the policy
+/// ran and covered their path.
+pub fn require_role(request: &HttpRequest, role: Role) -> Result<&Principal, HttpResponse> {
+    let principal = request
+        .principal
+        .as_ref()
+        .ok_or_else(|| auth_error_response(&AuthError::InvalidToken))?;
+    principal
+        .require(role)
+        .map_err(|err| auth_error_response(&err))?;
+    Ok(principal)
+}
+
+/// Translate an auth failure into the standard error body.
+pub fn auth_error_response(err: &AuthError) -> HttpResponse {
+    let (status, code) = match err {
+        AuthError::InvalidCredentials | AuthError::InvalidToken | AuthError::Expired => {
+            (401, "unauthorized")
+        }
+        AuthError::Forbidden { .. } | AuthError::Banned(_) | AuthError::RegistrationClosed => {
+            (403, "forbidden")
+        }
+        AuthError::UnknownAccount(_) => (404, "missing"),
+        AuthError::AccountExists(_) | AuthError::LastAdmin(_) => (409, "conflict"),
+        AuthError::InvalidUsername(_)
+        | AuthError::WeakPassword
+        | AuthError::UnknownRole(_)
+        | AuthError::InvalidBan(_) => (400, "invalid_request"),
+        AuthError::CorruptAccount(..) | AuthError::CorruptRecord(..) => (500, "internal_error"),
+    };
+    let response = HttpResponse::error(status, code, err.to_string());
+    if status == 401 {
+        response.with_header("WWW-Authenticate", "Bearer")
+    } else {
+        response
+    }
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+/// `POST /api/auth/login`, `POST /api/auth/logout`, and `GET /api/auth/session`.
+pub struct AuthApi {
+    auth: Arc<AuthService>,
+}
+
+impl AuthApi {
+    pub fn new(auth: Arc<AuthService>) -> Self {
+        AuthApi { auth }
+    }
+}
+
+impl HttpHandler for AuthApi {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let response = match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/api/auth/login") => match request.json::<LoginRequest>() {
+                Ok(login) => match self.auth.login(&login.username, &login.password) {
+                    Ok(grant) => HttpResponse::json(200, &grant),
+                    Err(err) => auth_error_response(&err),
+                },
+                Err(response) => response,
+            },
+            ("POST", "/api/auth/logout") => match request.bearer_token() {
+                Some(token) if self.auth.revoke(token) => {
+                    HttpResponse::json(200, &serde_json::json!({ "status": "logged_out" }))
+                }
+                _ => auth_error_response(&AuthError::InvalidToken),
+            },
+            ("GET", "/api/auth/session") => match &request.principal {
+                Some(principal) => HttpResponse::json(200, principal),
+                None => auth_error_response(&AuthError::InvalidToken),
+            },
+            (_, "/api/auth/login" | "/api/auth/logout" | "/api/auth/session") => {
+                HttpResponse::error(405, "method_not_allowed", "method not allowed")
+            }
+            _ => return None,
+        };
+        Some(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aqevia_auth::AuthConfig;
+    use std::time::Duration;
+
+    fn auth() -> Arc<AuthService> {
+        let auth = Arc::new(AuthService::new(AuthConfig {
+            session_ttl: Duration::from_secs(60),
+            hash_iterations: 16,
+            ..AuthConfig::default()
+        }));
+        auth.create_account("builder", "password123", Role::Builder)
+            .unwrap();
+        auth
+    }
+
+    #[test]
+    fn policy_rejects_missing_and_insufficient_tokens() {
+        let auth = auth();
+        let policy = AccessPolicy::default();
+        let mut anonymous = HttpRequest::new("GET", "/api/builder/rooms");
+        assert_eq!(
+            policy.authorize(&auth, &mut anonymous).unwrap_err().status,
+            401
+        );
+
+        let token = auth.login("builder", "password123").unwrap().token;
+        let bearer = format!("Bearer {}", token);
+        let mut builder =
+            HttpRequest::new("GET", "/api/builder/rooms").with_header("Authorization", &bearer);
+        assert!(policy.authorize(&auth, &mut builder).is_ok());
+        assert_eq!(builder.principal.unwrap().username, "builder");
+
+        let mut admin =
+            HttpRequest::new("GET", "/api/admin/sessions").with_header("Authorization", &bearer);
+        let denied = policy.authorize(&auth, &mut admin).unwrap_err();
+        assert_eq!(denied.status, 403);
+        assert!(denied.body_str().contains(r#""status":"forbidden""#));
+
+        let mut public = HttpRequest::new("GET", "/health");
+        assert!(policy.authorize(&auth, &mut public).is_ok());
+
+        assert_eq!(policy.required_role("//api//admin/x"), Some(Role::Admin));
+        assert_eq!(policy.required_role("/api/admin"), Some(Role::Admin));
+        assert_eq!(policy.required_role("/api/adminx"), None);
+    }
+
+    #[test]
+    fn login_logout_and_session_endpoints() {
+        let auth = auth();
+        let api = AuthApi::new(auth.clone());
+        let bad = HttpRequest::new("POST", "/api/auth/login")
+            .with_body(r#"{"username":"builder","password":"nope"}"#);
+        assert_eq!(api.handle(&bad).unwrap().status, 401);
+
+        let login = HttpRequest::new("POST", "/api/auth/login")
+            .with_body(r#"{"username":"builder","password":"password123"}"#);
+        let response = api.handle(&login).unwrap();
+        assert_eq!(response.status, 200);
+        let grant: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        let token = grant["token"].as_str().unwrap().to_string();
+        assert_eq!(grant["principal"]["role"], "builder");
+
+        let bearer = format!("Bearer {}", token);
+        let mut session =
+            HttpRequest::new("GET", "/api/auth/session").with_header("Authorization", &bearer);
+        AccessPolicy::default()
+            .authorize(&auth, &mut session)
+            .unwrap();
+        assert_eq!(api.handle(&session).unwrap().status, 200);
+
+        let logout =
+            HttpRequest::new("POST", "/api/auth/logout").with_header("Authorization", &bearer);
+        assert_eq!(api.handle(&logout).unwrap().status, 200);
+        assert!(auth.authenticate(&token).is_err());
+        assert!(api.handle(&HttpRequest::new("GET", "/api/other")).is_none());
+    }
+}
@@ -0,0 +1,141 @@
+//! Control-plane request dispatch: auth middleware in front of registered route handlers.
+
+use std::sync::Arc;
+
+use aqevia_auth::AuthService;
+use aqevia_router::SessionRegistry;
+
+use crate::auth::{auth_error_response, AccessPolicy, AuthApi};
+use crate::http::{HttpHandler, HttpRequest, HttpResponse};
+use crate::logs::{LogBuffer, LogsApi};
+
+/// Route handlers plus the auth state shared with the WebSocket adapter.
+pub struct ControlPlane {
+    auth: Arc<AuthService>,
+    sessions: Arc<SessionRegistry>,
+    policy: AccessPolicy,
+    handlers: Vec<Arc<dyn HttpHandler>>,
+    logs: Option<Arc<LogBuffer>>,
+}
+
+impl ControlPlane {
+    /// Control plane with the default role policy and the `/api/auth/*` endpoints registered.
+    pub fn new(auth: Arc<AuthService>, sessions: Arc<SessionRegistry>) -> Self {
+        let auth_api: Arc<dyn HttpHandler> = Arc::new(AuthApi::new(auth.clone()));
+        ControlPlane {
+            auth,
+            sessions,
+            policy: AccessPolicy::default(),
+            handlers: vec![auth_api],
+            logs: None,
+        }
+    }
+
+    /// Register another route group; handlers are consulted in registration order.
+    pub fn with_handler(mut self, handler: Arc<dyn HttpHandler>) -> Self {
+        self.handlers.push(handler);
+        self
+    }
+
+    /// Serve recent log events from `buffer` on `/logs` and `/logs/stream`.
+    pub fn with_logs(mut self, buffer: Arc<LogBuffer>) -> Self {
+        self.handlers.push(Arc::new(LogsApi::new(buffer.clone())));
+        self.logs = Some(buffer);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: AccessPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn auth(&self) -> &Arc<AuthService> {
+        &self.auth
+    }
+
+    pub fn sessions(&self) -> &Arc<SessionRegistry> {
+        &self.sessions
+    }
+
+    pub fn logs(&self) -> Option<&Arc<LogBuffer>> {
+        self.logs.as_ref()
+    }
+
+    /// Reject banned addresses and non-canonical paths, authorize `request`, and hand it to the
+    /// first handler that claims it.
+    pub fn dispatch(&self, mut request: HttpRequest) -> HttpResponse {
+        if let Some(Err(err)) = request.peer.map(|peer| self.auth.check_ip(peer.ip())) {
+            return auth_error_response(&err);
+        }
+        if !request.has_canonical_path() {
+            return HttpResponse::error(400, "invalid_request", "non-canonical request path");
+        }
+        if let Err(rejection) = self.policy.authorize(&self.auth, &mut request) {
+            return rejection;
+        }
+        self.handlers
+            .iter()
+            .find_map(|handler| handler.handle(&request))
+            .unwrap_or_else(|| HttpResponse::error(404, "missing", "no such endpoint"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aqevia_auth::{AuthConfig, Role};
+
+    struct Echo;
+
+    impl HttpHandler for Echo {
+        fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+            let principal = request.principal.as_ref()?;
+            Some(HttpResponse::json(200, &principal.username))
+        }
+    }
+
+    #[test]
+    fn middleware_runs_before_handlers() {
+        let auth = Arc::new(AuthService::new(AuthConfig {
+            hash_iterations: 16,
+            ..AuthConfig::default()
+        }));
+        auth.create_account("admin", "password123", Role::Admin)
+            .unwrap();
+        let plane = ControlPlane::new(auth.clone(), Arc::new(SessionRegistry::new()))
+            .with_handler(Arc::new(Echo));
+        let rejected = plane.dispatch(HttpRequest::new("GET", "/api/admin/anything"));
+        assert_eq!(rejected.status, 401);
+        assert!(rejected.body_str().contains(r#""status":"unauthorized""#));
+        assert_eq!(plane.dispatch(HttpRequest::new("GET", "/logs")).status, 401);
+        for path in [
+            "//api/admin/anything",
+            "/api/admin//anything",
+            "/api/../admin",
+            "api",
+        ] {
+            assert_eq!(plane.dispatch(HttpRequest::new("GET", path)).status, 400);
+        }
+
+        let token = auth.login("admin", "password123").unwrap().token;
+        let accepted = plane.dispatch(
+            HttpRequest::new("GET", "/api/admin/anything")
+                .with_header("Authorization", &format!("Bearer {}", token)),
+        );
+        assert_eq!(accepted.status, 200);
+        assert_eq!(accepted.body_str(), r#""admin""#);
+        assert_eq!(plane.dispatch(HttpRequest::new("GET", "/nope")).status, 404);
+
+        auth.add_ban(aqevia_auth::Ban {
+            target: aqevia_auth::BanTarget::Ip("192.0.2.0/24".parse().unwrap()),
+            reason: String::new(),
+            banned_by: "admin".into(),
+            created_at: 0,
+        });
+        let banned = HttpRequest {
+            peer: Some("192.0.2.10:4000".parse().unwrap()),
+            ..HttpRequest::new("GET", "/api/auth/session")
+        };
+        assert_eq!(plane.dispatch(banned).status, 403);
+    }
+}
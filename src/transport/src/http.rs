@@ -0,0 +1,399 @@
+//! Minimal HTTP/1.1 request parsing and response helpers shared by control-plane handlers.
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::net::SocketAddr;
+
+use aqevia_auth::Principal;
+use serde::Serialize;
+
+/// Largest request body the control plane accepts.
+pub const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Largest request line plus headers the control plane reads.
+pub const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+/// Most header lines one request may carry.
+pub const MAX_HEADERS: usize = 100;
+
+/// The part of a request that went over its cap; carried inside the `io::Error` returned by
+/// [`HttpRequest::read_from`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Oversized {
+    Head,
+    Body,
+}
+
+impl Oversized {
+    /// Find the cap a read failure hit, if any.
+    pub fn of(err: &io::Error) -> Option<Oversized> {
+        err.get_ref()?.downcast_ref::<Oversized>().copied()
+    }
+
+    pub fn response(self) -> HttpResponse {
+        match self {
+            Oversized::Head => HttpResponse::error(431, "headers_too_large", self.to_string()),
+            Oversized::Body => HttpResponse::error(413, "payload_too_large", self.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Oversized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Oversized::Head => write!(
+                f,
+                "request headers exceed {} bytes or {} lines",
+                MAX_HEADER_BYTES, MAX_HEADERS
+            ),
+            Oversized::Body => write!(f, "request body too large"),
+        }
+    }
+}
+
+impl std::error::Error for Oversized {}
+
+/// A parsed control-plane request.
+#[derive(Clone, Debug, Default)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub peer: Option<SocketAddr>,
+    /// Set by the auth middleware once a bearer token has been validated.
+    pub principal: Option<Principal>,
+}
+
+impl HttpRequest {
+    pub fn new(method: &str, target: &str) -> Self {
+        let (path, query) = split_target(target);
+        HttpRequest {
+            method: method.to_string(),
+            path,
+            query,
+            ..HttpRequest::default()
+        }
+    }
+
+    /// Read a request head and body from `reader`. A head over [`MAX_HEADER_BYTES`] or
+    /// [`MAX_HEADERS`], or a body over [`MAX_BODY_BYTES`], fails with an [`Oversized`] error.
+    pub fn read_from(reader: &mut impl BufRead) -> io::Result<Self> {
+        let mut head = reader.take(MAX_HEADER_BYTES as u64);
+        let mut request_line = String::new();
+        read_head_line(&mut head, &mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("GET").to_string();
+        let target = parts.next().unwrap_or("/");
+        let mut request = HttpRequest::new(&method, target);
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let bytes = read_head_line(&mut head, &mut line)?;
+            if bytes == 0 || line == "\r\n" || line == "\n" {
+                break;
+            }
+            if request.headers.len() == MAX_HEADERS {
+                return Err(oversized(Oversized::Head));
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                request
+                    .headers
+                    .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let length = request
+            .header("content-length")
+            .and_then(|value| value.parse::<usize>().ok())
+            .unwrap_or(0);
+        if length > MAX_BODY_BYTES {
+            return Err(oversized(Oversized::Body));
+        }
+        request.body = vec![0; length];
+        reader.read_exact(&mut request.body)?;
+        Ok(request)
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Case-insensitive header lookup.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Bearer token from the `Authorization` header.
+    pub fn bearer_token(&self) -> Option<&str> {
+        self.header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+    }
+
+    /// Path split into non-empty segments.
+    pub fn segments(&self) -> Vec<&str> {
+        path_segments(&self.path)
+    }
+
+    /// Whether the path is in the one form handlers and the access policy agree on: rooted,
+    /// with no empty, `.`, or `..` segments. A single trailing slash is allowed.
+    pub fn has_canonical_path(&self) -> bool {
+        let Some(rest) = self.path.strip_prefix('/') else {
+            return false;
+        };
+        let rest = rest.strip_suffix('/').unwrap_or(rest);
+        rest.is_empty()
+            || rest
+                .split('/')
+                .all(|segment| !matches!(segment, "" | "." | ".."))
+    }
+
+    /// Decode the body as JSON, mapping failures to a `400 Bad Request`.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, HttpResponse> {
+        serde_json::from_slice(&self.body).map_err(|err| {
+            HttpResponse::error(
+                400,
+                "invalid_request",
+                format!("invalid JSON body: {}", err),
+            )
+        })
+    }
+}
+
+/// A response ready to be written to the wire.
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        HttpResponse {
+            status,
+            headers: vec![("Content-Type".into(), content_type.into())],
+            body: body.into(),
+        }
+    }
+
+    /// JSON response with the control-plane `Cache-Control: no-store` default.
+    pub fn json(status: u16, value: &impl Serialize) -> Self {
+        let body = serde_json::to_vec(value).unwrap_or_default();
+        HttpResponse::new(status, "application/json", body).with_header("Cache-Control", "no-store")
+    }
+
+    /// Standard error body: `{"status":"<code>","message":"<detail>"}`.
+    pub fn error(status: u16, code: &str, message: impl Into<String>) -> Self {
+        HttpResponse::json(
+            status,
+            &ErrorBody {
+                status: code,
+                message: message.into(),
+            },
+        )
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn body_str(&self) -> &str {
+        std::str::from_utf8(&self.body).unwrap_or_default()
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if self.status >= 200 {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+        writer.write_all(head.as_bytes())?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    status: &'a str,
+    message: String,
+}
+
+/// A control-plane route group. Returning `None` lets the next handler try the request.
+pub trait HttpHandler: Send + Sync {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse>;
+}
+
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Unknown",
+    }
+}
+
+/// Read one head line, failing once the head's byte budget runs out mid-line.
+fn read_head_line(head: &mut io::Take<impl BufRead>, line: &mut String) -> io::Result<usize> {
+    let bytes = head.read_line(line)?;
+    if head.limit() == 0 && !line.ends_with('\n') {
+        return Err(oversized(Oversized::Head));
+    }
+    Ok(bytes)
+}
+
+fn oversized(part: Oversized) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, part)
+}
+
+/// `path` split into non-empty segments; shared by request routing and the access policy.
+pub fn path_segments(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+fn split_target(target: &str) -> (String, Vec<(String, String)>) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+    (path.to_string(), query)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => out.push(b' '),
+            b'%' if idx + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[idx + 1..idx + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        out.push(byte);
+                        idx += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn parses_request_line_headers_query_and_body() {
+        let raw = "POST /api/auth/login?next=%2Fbuilder&x=a+b HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer abc\r\nContent-Length: 2\r\n\r\n{}";
+        let request = HttpRequest::read_from(&mut BufReader::new(raw.as_bytes())).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/auth/login");
+        assert_eq!(request.query_param("next"), Some("/builder"));
+        assert_eq!(request.query_param("x"), Some("a b"));
+        assert_eq!(request.bearer_token(), Some("abc"));
+        assert_eq!(request.body, b"{}");
+        assert_eq!(request.segments(), vec!["api", "auth", "login"]);
+    }
+
+    #[test]
+    fn oversized_heads_and_bodies_are_refused() {
+        let read = |raw: String| HttpRequest::read_from(&mut BufReader::new(raw.as_bytes()));
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEADER_BYTES));
+        let crowded = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-A: b\r\n".repeat(MAX_HEADERS + 1)
+        );
+        let heavy = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_BYTES + 1
+        );
+        for (raw, part, status) in [
+            (long, Oversized::Head, 431),
+            (crowded, Oversized::Head, 431),
+            (heavy, Oversized::Body, 413),
+        ] {
+            let err = read(raw).unwrap_err();
+            assert_eq!(Oversized::of(&err), Some(part));
+            assert_eq!(part.response().status, status);
+        }
+        let fits = format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(MAX_HEADERS));
+        assert_eq!(read(fits).unwrap().headers.len(), MAX_HEADERS);
+    }
+
+    #[test]
+    fn error_responses_use_standard_body() {
+        let response = HttpResponse::error(401, "unauthorized", "missing token");
+        let mut wire = Vec::new();
+        response.write_to(&mut wire).unwrap();
+        let text = String::from_utf8(wire).unwrap();
+        assert!(text.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(text.contains("Cache-Control: no-store"));
+        assert!(text.ends_with(r#"{"status":"unauthorized","message":"missing token"}"#));
+    }
+}
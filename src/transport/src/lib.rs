@@ -1,9 +1,20 @@
 //! Transport crate: responsible for WebSocket/HTTP plumbing without touching gameplay logic.
 
+pub mod assets;
+pub mod auth;
+pub mod control_plane;
+pub mod http;
+pub mod logs;
 pub mod observability;
+pub mod ws;
 
 use aqevia_router::Router;
 
+pub use assets::StaticAssets;
+pub use auth::{AccessPolicy, AuthApi};
+pub use control_plane::ControlPlane;
+pub use http::{HttpHandler, HttpRequest, HttpResponse};
+pub use logs::{LogBuffer, LogFilter, LogRecord, LogSpan};
 pub use observability::{ObservabilityServer, ObservabilityState};
 
 pub struct Transport {
@@ -25,6 +36,14 @@ impl Transport {
         )
     }
 
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    pub fn router_mut(&mut self) -> &mut Router {
+        &mut self.router
+    }
+
     /// Retrieve the world id from the router context.
     pub fn world_id(&self) -> &'static str {
         self.router.world_context()
@@ -0,0 +1,419 @@
+//! Recent log events for operators without shell access: a bounded ring buffer filled by the
+//! Engine's log subscriber, `GET /logs` to read it, and `GET /logs/stream` to follow it as
+//! Server-Sent Events.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use aqevia_auth::{AuthError, AuthService, Role};
+use serde::Serialize;
+use tracing::Level;
+
+use crate::auth::auth_error_response;
+use crate::http::{HttpHandler, HttpRequest, HttpResponse};
+
+pub const LOGS_PATH: &str = "/logs";
+pub const STREAM_PATH: &str = "/logs/stream";
+
+/// Records returned by `GET /logs` when `limit` is not given.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// How long a stream stays silent before a keep-alive comment is sent.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// One log event as kept in the buffer.
+#[derive(Clone, Debug, Serialize)]
+pub struct LogRecord {
+    /// Position in the buffer's history; increases by one per event and never repeats.
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    #[serde(serialize_with = "serialize_level")]
+    pub level: Level,
+    /// Module path of the code that logged the event, e.g. `aqevia_storage`.
+    pub target: String,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+    /// Enclosing spans, outermost first.
+    pub spans: Vec<LogSpan>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LogSpan {
+    pub name: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+fn serialize_level<S: serde::Serializer>(level: &Level, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(level.as_str())
+}
+
+/// Which records a reader wants: at least `level` severe, logged under `module`, and newer
+/// than `after`.
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    pub level: Option<Level>,
+    pub module: Option<String>,
+    pub after: Option<u64>,
+}
+
+impl LogFilter {
+    /// Read `level`, `module`, and `after` from the query string.
+    pub fn from_request(request: &HttpRequest) -> Result<Self, HttpResponse> {
+        let level = match request.query_param("level") {
+            Some(level) => Some(level.parse::<Level>().map_err(|_| {
+                HttpResponse::error(
+                    400,
+                    "invalid_request",
+                    format!("unknown log level {:?}", level),
+                )
+            })?),
+            None => None,
+        };
+        let after = match request.query_param("after") {
+            Some(after) => Some(after.parse::<u64>().map_err(|_| {
+                HttpResponse::error(400, "invalid_request", "after must be a sequence number")
+            })?),
+            None => None,
+        };
+        Ok(LogFilter {
+            level,
+            module: request
+                .query_param("module")
+                .filter(|module| !module.is_empty())
+                .map(str::to_string),
+            after,
+        })
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        // `Level` orders by verbosity, so more severe levels compare lower.
+        self.level.is_none_or(|level| record.level <= level)
+            && self.after.is_none_or(|after| record.seq > after)
+            && self.module.as_deref().is_none_or(|module| {
+                record
+                    .target
+                    .strip_prefix(module)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+    }
+}
+
+/// The most recent log events, oldest dropped first once `capacity` is reached.
+pub struct LogBuffer {
+    capacity: usize,
+    state: Mutex<BufferState>,
+    appended: Condvar,
+}
+
+struct BufferState {
+    records: VecDeque<LogRecord>,
+    next_seq: u64,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        LogBuffer {
+            capacity,
+            state: Mutex::new(BufferState {
+                records: VecDeque::with_capacity(capacity),
+                next_seq: 1,
+            }),
+            appended: Condvar::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Append `record`, numbering it after the previous one, and wake streaming readers.
+    pub fn push(&self, mut record: LogRecord) {
+        let mut state = self.state.lock().expect("lock poisoning");
+        record.seq = state.next_seq;
+        state.next_seq += 1;
+        if state.records.len() == self.capacity {
+            state.records.pop_front();
+        }
+        if self.capacity > 0 {
+            state.records.push_back(record);
+        }
+        drop(state);
+        self.appended.notify_all();
+    }
+
+    /// Up to `limit` of the newest records matching `filter`, oldest first.
+    pub fn query(&self, filter: &LogFilter, limit: usize) -> Vec<LogRecord> {
+        let state = self.state.lock().expect("lock poisoning");
+        let mut matched: Vec<LogRecord> = state
+            .records
+            .iter()
+            .rev()
+            .filter(|record| filter.matches(record))
+            .take(limit)
+            .cloned()
+            .collect();
+        matched.reverse();
+        matched
+    }
+
+    /// Sequence number of the newest record, or `0` before the first one.
+    pub fn last_seq(&self) -> u64 {
+        self.state.lock().expect("lock poisoning").next_seq - 1
+    }
+
+    /// Records matching `filter` that are newer than `after`, waiting up to `timeout` for the
+    /// next record when there is none yet, plus the newest sequence number seen. Records that
+    /// do not match still advance that number, so passing it back as `after` skips them.
+    pub fn wait_after(
+        &self,
+        after: u64,
+        filter: &LogFilter,
+        timeout: Duration,
+    ) -> (Vec<LogRecord>, u64) {
+        let filter = LogFilter {
+            after: Some(after),
+            ..filter.clone()
+        };
+        let state = self.state.lock().expect("lock poisoning");
+        let (state, _) = self
+            .appended
+            .wait_timeout_while(state, timeout, |state| state.next_seq - 1 <= after)
+            .expect("lock poisoning");
+        let records = state
+            .records
+            .iter()
+            .filter(|record| filter.matches(record))
+            .cloned()
+            .collect();
+        (records, state.next_seq - 1)
+    }
+}
+
+#[derive(Serialize)]
+struct LogsPage {
+    records: Vec<LogRecord>,
+    /// Pass as `after` to read only what was logged since this page.
+    last_seq: u64,
+}
+
+/// `GET /logs`: the newest buffered records, filtered by `level`, `module`, and `after`, and
+/// capped by `limit`.
+pub struct LogsApi {
+    buffer: Arc<LogBuffer>,
+}
+
+impl LogsApi {
+    pub fn new(buffer: Arc<LogBuffer>) -> Self {
+        LogsApi { buffer }
+    }
+}
+
+impl HttpHandler for LogsApi {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        if request.path != LOGS_PATH {
+            return None;
+        }
+        if request.method != "GET" {
+            return Some(HttpResponse::error(
+                405,
+                "method_not_allowed",
+                "use GET for /logs",
+            ));
+        }
+        let filter = match LogFilter::from_request(request) {
+            Ok(filter) => filter,
+            Err(response) => return Some(response),
+        };
+        let limit = match request.query_param("limit").map(str::parse::<usize>) {
+            None => DEFAULT_LIMIT,
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => {
+                return Some(HttpResponse::error(
+                    400,
+                    "invalid_request",
+                    "limit must be a non-negative integer",
+                ))
+            }
+        };
+        let last_seq = self.buffer.last_seq();
+        Some(HttpResponse::json(
+            200,
+            &LogsPage {
+                records: self.buffer.query(&filter, limit),
+                last_seq,
+            },
+        ))
+    }
+}
+
+/// Validate a `GET /logs/stream` request from an address that is not banned and authenticate
+/// it as an admin. Browsers cannot set headers on `EventSource` requests, so `?token=` is
+/// accepted too. Returns the filter to stream with; a `Last-Event-ID` header resumes after
+/// that record.
+pub fn accept_stream(request: &HttpRequest, auth: &AuthService) -> Result<LogFilter, HttpResponse> {
+    if let Some(Err(err)) = request.peer.map(|peer| auth.check_ip(peer.ip())) {
+        return Err(auth_error_response(&err));
+    }
+    let token = request
+        .bearer_token()
+        .or_else(|| request.query_param("token"))
+        .ok_or_else(|| auth_error_response(&AuthError::InvalidToken))?;
+    auth.authenticate(token)
+        .and_then(|principal| principal.require(Role::Admin))
+        .map_err(|err| auth_error_response(&err))?;
+    if request.method != "GET" {
+        return Err(HttpResponse::error(
+            405,
+            "method_not_allowed",
+            "use GET for /logs/stream",
+        ));
+    }
+    let mut filter = LogFilter::from_request(request)?;
+    if let Some(last) = request
+        .header("last-event-id")
+        .and_then(|id| id.parse::<u64>().ok())
+    {
+        filter.after = Some(last);
+    }
+    Ok(filter)
+}
+
+/// Write every buffered record matching `filter` and then each new one as a `log` event until
+/// the client disconnects. Each event's `id` is the record's `seq`.
+pub fn serve_stream(
+    mut stream: TcpStream,
+    buffer: Arc<LogBuffer>,
+    filter: LogFilter,
+) -> io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\nConnection: keep-alive\r\n\r\n",
+    )?;
+    stream.flush()?;
+    // A cursor from before a restart points past the new history; start over.
+    let mut after = filter
+        .after
+        .filter(|&after| after <= buffer.last_seq())
+        .unwrap_or(0);
+    let mut wait = Duration::ZERO;
+    loop {
+        let (records, newest) = buffer.wait_after(after, &filter, wait);
+        if records.is_empty() {
+            stream.write_all(b": keep-alive\n\n")?;
+        }
+        for record in &records {
+            write_event(&mut stream, record)?;
+        }
+        stream.flush()?;
+        after = newest;
+        wait = KEEP_ALIVE;
+    }
+}
+
+fn write_event(writer: &mut impl Write, record: &LogRecord) -> io::Result<()> {
+    let data = serde_json::to_string(record).unwrap_or_default();
+    write!(writer, "id: {}\nevent: log\ndata: {}\n\n", record.seq, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(level: Level, target: &str, message: &str) -> LogRecord {
+        LogRecord {
+            seq: 0,
+            timestamp_ms: 0,
+            level,
+            target: target.into(),
+            message: message.into(),
+            fields: BTreeMap::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn buffer_keeps_newest_records_and_filters_them() {
+        let buffer = LogBuffer::new(3);
+        buffer.push(record(Level::INFO, "aqevia_router::session", "opened"));
+        buffer.push(record(Level::ERROR, "aqevia_storage", "flush failed"));
+        buffer.push(record(Level::DEBUG, "aqevia_storage_sqlite", "flushed"));
+        buffer.push(record(
+            Level::WARN,
+            "aqevia_engine::narration",
+            "queue full",
+        ));
+        assert_eq!(buffer.last_seq(), 4);
+
+        let all = buffer.query(&LogFilter::default(), 10);
+        let seqs: Vec<u64> = all.iter().map(|record| record.seq).collect();
+        assert_eq!(seqs, vec![2, 3, 4]);
+
+        let storage = LogFilter {
+            module: Some("aqevia_storage".into()),
+            ..LogFilter::default()
+        };
+        let messages: Vec<String> = buffer
+            .query(&storage, 10)
+            .into_iter()
+            .map(|record| record.message)
+            .collect();
+        assert_eq!(messages, vec!["flush failed"]);
+
+        let warnings = LogFilter {
+            level: Some(Level::WARN),
+            ..LogFilter::default()
+        };
+        assert_eq!(buffer.query(&warnings, 10).len(), 2);
+        assert_eq!(buffer.query(&warnings, 1)[0].message, "queue full");
+        let (newer, newest) = buffer.wait_after(2, &storage, Duration::ZERO);
+        assert!(newer.is_empty());
+        assert_eq!(newest, 4);
+    }
+
+    #[test]
+    fn logs_api_reads_filters_from_the_query() {
+        let buffer = Arc::new(LogBuffer::new(10));
+        buffer.push(record(Level::INFO, "aqevia_engine::audit", "audit"));
+        buffer.push(record(Level::WARN, "aqevia_engine::guardrail", "rejected"));
+        let api = LogsApi::new(buffer);
+
+        let response = api
+            .handle(&HttpRequest::new("GET", "/logs?level=warn&limit=5"))
+            .unwrap();
+        assert_eq!(response.status, 200);
+        let page: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(page["last_seq"], 2);
+        assert_eq!(page["records"][0]["level"], "WARN");
+        assert_eq!(page["records"].as_array().unwrap().len(), 1);
+
+        let after = api
+            .handle(&HttpRequest::new("GET", "/logs?after=2"))
+            .unwrap();
+        assert!(after.body_str().contains(r#""records":[]"#));
+        let bad = api
+            .handle(&HttpRequest::new("GET", "/logs?level=loud"))
+            .unwrap();
+        assert_eq!(bad.status, 400);
+        assert_eq!(
+            api.handle(&HttpRequest::new("POST", "/logs"))
+                .unwrap()
+                .status,
+            405
+        );
+        assert!(api.handle(&HttpRequest::new("GET", "/logsx")).is_none());
+    }
+
+    #[test]
+    fn events_are_framed_for_server_sent_events() {
+        let mut out = Vec::new();
+        let mut logged = record(Level::INFO, "aqevia_storage", "flushed");
+        logged.seq = 7;
+        write_event(&mut out, &logged).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("id: 7\nevent: log\ndata: {"), "{}", text);
+        assert!(text.ends_with("}\n\n"));
+    }
+}
@@ -1,26 +1,46 @@
 //! Observability HTTP helpers contained in the Transport layer.
 
+use aqevia_storage::ReadPoolStats;
 use serde::Serialize;
-use std::io::{prelude::*, BufRead, BufReader};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::BufReader;
 use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::sync::{
     atomic::{AtomicBool, AtomicUsize, Ordering},
+    mpsc::{self, Receiver, SyncSender, TrySendError},
     Arc, Mutex,
 };
 use std::thread;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use tracing::{debug, error, info_span, warn};
+
+use crate::control_plane::ControlPlane;
+use crate::http::{HttpRequest, HttpResponse, Oversized};
+use crate::{logs, ws};
+
 pub struct ObservabilityState {
     version: String,
     world_id: String,
     storage_backend: String,
     ready: AtomicBool,
+    paused: AtomicBool,
     flush_count: AtomicUsize,
     last_flush: Mutex<Option<SystemTime>>,
+    read_pool: Mutex<Option<ReadPoolStats>>,
     storage_error: Mutex<Option<String>>,
+    metrics: Mutex<BTreeMap<String, Metric>>,
     start: Instant,
 }
 
+/// A named series family exposed on `/metrics`, keyed by rendered label set.
+struct Metric {
+    kind: &'static str,
+    help: &'static str,
+    series: BTreeMap<String, u64>,
+}
+
 impl ObservabilityState {
     pub fn new(
         version: impl Into<String>,
@@ -32,9 +52,12 @@ impl ObservabilityState {
             world_id: world_id.into(),
             storage_backend: storage_backend.into(),
             ready: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
             flush_count: AtomicUsize::new(0),
             last_flush: Mutex::new(None),
+            read_pool: Mutex::new(None),
             storage_error: Mutex::new(None),
+            metrics: Mutex::new(BTreeMap::new()),
             start: Instant::now(),
         }
     }
@@ -47,6 +70,15 @@ impl ObservabilityState {
         self.ready.load(Ordering::SeqCst)
     }
 
+    /// Record whether the World's tick loop is paused by an operator.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
     pub fn note_flush(&self, flush_count: usize, last_flush: Option<SystemTime>) {
         self.flush_count.store(flush_count, Ordering::SeqCst);
         if let Some(value) = last_flush {
@@ -57,12 +89,111 @@ impl ObservabilityState {
         *error_guard = None;
     }
 
+    /// Record the latest statistics of the storage backend's read connection pool.
+    pub fn note_read_pool(&self, stats: Option<ReadPoolStats>) {
+        *self.read_pool.lock().expect("lock poisoning") = stats;
+    }
+
     pub fn note_error(&self, message: impl Into<String>) {
         self.mark_storage_ready(false);
         let mut guard = self.storage_error.lock().expect("lock poisoning");
         *guard = Some(message.into());
     }
 
+    /// Record a storage error that leaves storage in service, such as a busy store whose
+    /// writes are retried on the next flush.
+    pub fn note_transient_error(&self, message: impl Into<String>) {
+        *self.storage_error.lock().expect("lock poisoning") = Some(message.into());
+    }
+
+    /// Add `by` to a counter series, creating it at zero first.
+    pub fn add_counter(&self, name: &str, help: &'static str, labels: &[(&str, &str)], by: u64) {
+        let mut metrics = self.metrics.lock().expect("lock poisoning");
+        *metrics
+            .entry(name.to_string())
+            .or_insert_with(|| Metric {
+                kind: "counter",
+                help,
+                series: BTreeMap::new(),
+            })
+            .series
+            .entry(label_set(labels))
+            .or_default() += by;
+    }
+
+    /// Set a gauge series to `value`.
+    pub fn set_gauge(&self, name: &str, help: &'static str, labels: &[(&str, &str)], value: u64) {
+        let mut metrics = self.metrics.lock().expect("lock poisoning");
+        metrics
+            .entry(name.to_string())
+            .or_insert_with(|| Metric {
+                kind: "gauge",
+                help,
+                series: BTreeMap::new(),
+            })
+            .series
+            .insert(label_set(labels), value);
+    }
+
+    /// Current value of a counter or gauge series; `0` if it was never touched.
+    pub fn metric(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        self.metrics
+            .lock()
+            .expect("lock poisoning")
+            .get(name)
+            .and_then(|metric| metric.series.get(&label_set(labels)).copied())
+            .unwrap_or(0)
+    }
+
+    /// Built-in runtime gauges plus every registered series, in the Prometheus text format.
+    pub fn render_metrics(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+        for (name, help, kind, value) in [
+            (
+                "aqevia_uptime_seconds",
+                "Seconds since the Engine started.",
+                "gauge",
+                snapshot.uptime_seconds,
+            ),
+            (
+                "aqevia_storage_ready",
+                "Whether persistent storage is ready (1) or not (0).",
+                "gauge",
+                snapshot.storage_ready as u64,
+            ),
+            (
+                "aqevia_paused",
+                "Whether an operator paused the tick loop (1) or not (0).",
+                "gauge",
+                snapshot.paused as u64,
+            ),
+            (
+                "aqevia_storage_flushes_total",
+                "Completed storage batch flushes.",
+                "counter",
+                snapshot.flush_count as u64,
+            ),
+        ] {
+            let _ = writeln!(
+                out,
+                "# HELP {} {}\n# TYPE {} {}\n{} {}",
+                name, help, name, kind, name, value
+            );
+        }
+        for (name, metric) in self.metrics.lock().expect("lock poisoning").iter() {
+            let _ = writeln!(
+                out,
+                "# HELP {} {}\n# TYPE {} {}",
+                name, metric.help, name, metric.kind
+            );
+            for (labels, value) in &metric.series {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        }
+        out
+    }
+
     pub fn snapshot(&self) -> ObservabilitySnapshot {
         let uptime = self.start.elapsed().as_secs();
         let last_flush = *self.last_flush.lock().expect("lock poisoning");
@@ -72,9 +203,11 @@ impl ObservabilityState {
             world_id: self.world_id.clone(),
             storage_backend: self.storage_backend.clone(),
             storage_ready: self.storage_ready(),
+            paused: self.paused(),
             flush_count: self.flush_count.load(Ordering::SeqCst),
             last_flush_at: last_flush
                 .map(|ts| ts.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()),
+            read_pool: *self.read_pool.lock().expect("lock poisoning"),
             uptime_seconds: uptime,
             storage_error,
         }
@@ -93,50 +226,116 @@ impl ObservabilityState {
     }
 }
 
+/// `{key="value",...}` with values escaped, or an empty string without labels.
+fn label_set(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", key, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
 #[derive(Serialize)]
 pub struct ObservabilitySnapshot {
     version: String,
     world_id: String,
     storage_backend: String,
     storage_ready: bool,
+    paused: bool,
     flush_count: usize,
     last_flush_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_pool: Option<ReadPoolStats>,
     uptime_seconds: u64,
     storage_error: Option<String>,
 }
 
+/// Threads that serve accepted connections. WebSocket and log stream sessions move to their
+/// own threads once upgraded, so a worker is only held for one request.
+pub const HTTP_WORKERS: usize = 8;
+
+/// Accepted connections that may wait for a worker before new ones get `503 overloaded`.
+pub const HTTP_QUEUE: usize = 64;
+
+/// How long a connection may take to send its request, or to accept the response, before it
+/// is dropped.
+pub const HTTP_IO_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct ObservabilityServer {
     shutdown: Arc<AtomicBool>,
     handle: Option<thread::JoinHandle<()>>,
+    workers: Vec<thread::JoinHandle<()>>,
     addr: SocketAddr,
 }
 
 impl ObservabilityServer {
     pub fn start(state: Arc<ObservabilityState>, addr: SocketAddr) -> std::io::Result<Self> {
+        Self::spawn(state, None, addr)
+    }
+
+    /// Serve the observability endpoints plus the authenticated control plane and `/ws`.
+    pub fn start_with_control_plane(
+        state: Arc<ObservabilityState>,
+        control_plane: ControlPlane,
+        addr: SocketAddr,
+    ) -> std::io::Result<Self> {
+        Self::spawn(state, Some(Arc::new(control_plane)), addr)
+    }
+
+    fn spawn(
+        state: Arc<ObservabilityState>,
+        control_plane: Option<Arc<ControlPlane>>,
+        addr: SocketAddr,
+    ) -> std::io::Result<Self> {
         let listener = TcpListener::bind(addr)?;
         listener.set_nonblocking(true)?;
         let actual_addr = listener.local_addr()?;
         let shutdown = Arc::new(AtomicBool::new(false));
         let thread_shutdown = Arc::clone(&shutdown);
         let listener_thread = listener.try_clone()?;
+        let (queue, accepted) = mpsc::sync_channel::<TcpStream>(HTTP_QUEUE);
+        let accepted = Arc::new(Mutex::new(accepted));
+        let workers = (0..HTTP_WORKERS)
+            .map(|_| {
+                let accepted = Arc::clone(&accepted);
+                let state = Arc::clone(&state);
+                let control_plane = control_plane.clone();
+                thread::spawn(move || serve_accepted(&accepted, &state, control_plane.as_deref()))
+            })
+            .collect();
         let handle = thread::spawn(move || loop {
             if thread_shutdown.load(Ordering::SeqCst) {
                 break;
             }
             match listener_thread.accept() {
-                Ok((mut stream, _)) => {
-                    let _ = handle_connection(&mut stream, &state);
+                Ok((stream, peer)) => {
+                    if let Err(err) = dispatch_connection(&queue, stream) {
+                        debug!(%peer, error = %err, "connection failed");
+                    }
                 }
                 Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
                     thread::sleep(Duration::from_millis(20));
                     continue;
                 }
-                Err(_) => break,
+                Err(err) => {
+                    error!(addr = %actual_addr, error = %err, "listener stopped");
+                    break;
+                }
             }
         });
         Ok(ObservabilityServer {
             shutdown,
             handle: Some(handle),
+            workers,
             addr: actual_addr,
         })
     }
@@ -150,6 +349,10 @@ impl ObservabilityServer {
         if let Some(handle) = self.handle.take() {
             let _ = handle.join();
         }
+        // The accept thread held the queue's only sender, so workers stop once it is drained.
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
     }
 }
 
@@ -159,48 +362,143 @@ impl Drop for ObservabilityServer {
     }
 }
 
-fn handle_connection(stream: &mut TcpStream, state: &ObservabilityState) -> std::io::Result<()> {
-    let mut reader = BufReader::new(stream.try_clone()?);
-    let mut request_line = String::new();
-    reader.read_line(&mut request_line)?;
-    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
-    let mut discard = String::new();
+/// Bound the accepted connection's I/O and queue it for a worker, answering `503` straight
+/// away when every worker is busy and the queue is full.
+fn dispatch_connection(queue: &SyncSender<TcpStream>, stream: TcpStream) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HTTP_IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(HTTP_IO_TIMEOUT))?;
+    match queue.try_send(stream) {
+        Ok(()) => Ok(()),
+        Err(TrySendError::Full(mut stream)) => {
+            warn!("all HTTP workers are busy; refusing connection");
+            HttpResponse::error(503, "overloaded", "server is busy; retry shortly")
+                .write_to(&mut stream)
+        }
+        Err(TrySendError::Disconnected(_)) => Ok(()),
+    }
+}
+
+fn serve_accepted(
+    accepted: &Mutex<Receiver<TcpStream>>,
+    state: &ObservabilityState,
+    control_plane: Option<&ControlPlane>,
+) {
     loop {
-        let bytes = reader.read_line(&mut discard)?;
-        if bytes == 0 || discard == "\r\n" {
-            break;
+        let next = accepted.lock().expect("lock poisoning").recv();
+        let Ok(stream) = next else {
+            return;
+        };
+        let peer = stream.peer_addr().ok();
+        if let Err(err) = handle_connection(stream, state, control_plane) {
+            debug!(?peer, error = %err, "connection failed");
         }
-        discard.clear();
     }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    state: &ObservabilityState,
+    control_plane: Option<&ControlPlane>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = match HttpRequest::read_from(&mut reader) {
+        Ok(request) => request,
+        Err(err) => match Oversized::of(&err) {
+            Some(part) => {
+                let mut stream = stream;
+                return part.response().write_to(&mut stream);
+            }
+            None => return Err(err),
+        },
+    };
+    request.peer = stream.peer_addr().ok();
+    let mut stream = stream;
+    let _span = info_span!(
+        "http",
+        method = %request.method,
+        path = %request.path,
+        peer = ?request.peer
+    )
+    .entered();
+
+    if let Some(response) = observability_response(&request.path, state) {
+        return response.write_to(&mut stream);
+    }
+    let Some(control_plane) = control_plane else {
+        return missing().write_to(&mut stream);
+    };
+    if request.path == ws::WS_PATH {
+        return match ws::accept_handshake(&request, control_plane.auth()) {
+            Ok((principal, response)) => {
+                response.write_to(&mut stream)?;
+                let sessions = control_plane.sessions().clone();
+                let ip = request.peer.map(|peer| peer.ip());
+                let (id, events) = sessions.open(principal.username, ip);
+                thread::spawn(move || ws::serve_session(stream, sessions, id, events));
+                Ok(())
+            }
+            Err(rejection) => {
+                debug!(status = rejection.status, "websocket handshake rejected");
+                rejection.write_to(&mut stream)
+            }
+        };
+    }
+    if let (logs::STREAM_PATH, Some(buffer)) = (request.path.as_str(), control_plane.logs()) {
+        return match logs::accept_stream(&request, control_plane.auth()) {
+            Ok(filter) => {
+                let buffer = buffer.clone();
+                thread::spawn(move || logs::serve_stream(stream, buffer, filter));
+                Ok(())
+            }
+            Err(rejection) => rejection.write_to(&mut stream),
+        };
+    }
+    let response = control_plane.dispatch(request);
+    if response.status >= 500 {
+        warn!(status = response.status, body = %response.body_str(), "request failed");
+    } else {
+        debug!(status = response.status, "request handled");
+    }
+    response.write_to(&mut stream)
+}
 
+fn observability_response(path: &str, state: &ObservabilityState) -> Option<HttpResponse> {
+    if path == "/metrics" {
+        return Some(
+            HttpResponse::new(
+                200,
+                "text/plain; version=0.0.4; charset=utf-8",
+                state.render_metrics(),
+            )
+            .with_header("Cache-Control", "no-store"),
+        );
+    }
     let (status, body) = match path {
-        "/health" => ("200 OK", r#"{"status":"ok"}"#.to_string()),
+        "/health" => (200, r#"{"status":"ok"}"#.to_string()),
         "/ready" => {
             if state.storage_ready() {
-                ("200 OK", r#"{"status":"ready"}"#.to_string())
+                (200, r#"{"status":"ready"}"#.to_string())
             } else {
-                (
-                    "503 Service Unavailable",
-                    r#"{"status":"initializing"}"#.to_string(),
-                )
+                (503, r#"{"status":"initializing"}"#.to_string())
             }
         }
         "/status" => {
             let snapshot = state.snapshot();
             let body = serde_json::to_string(&snapshot).unwrap_or_default();
-            ("200 OK", body)
+            (200, body)
         }
-        _ => ("404 Not Found", r#"{"status":"missing"}"#.to_string()),
+        _ => return None,
     };
+    Some(
+        HttpResponse::new(status, "application/json", body)
+            .with_header("Cache-Control", "no-store"),
+    )
+}
 
-    let response = format!(
-        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nCache-Control: no-store\r\nContent-Length: {}\r\n\r\n{}",
-        status,
-        body.len(),
-        body
-    );
-    stream.write_all(response.as_bytes())?;
-    Ok(())
+fn missing() -> HttpResponse {
+    HttpResponse::new(404, "application/json", r#"{"status":"missing"}"#)
+        .with_header("Cache-Control", "no-store")
 }
 
 #[cfg(test)]
@@ -235,6 +533,84 @@ mod tests {
         assert!(ready_ok.contains("200 OK"));
         let status = send_request(addr, "/status");
         assert!(status.contains("\"version\":\"0.2.0\""));
+
+        state.add_counter("aqevia_test_total", "Test events.", &[("kind", "a\"b")], 2);
+        state.add_counter("aqevia_test_total", "Test events.", &[("kind", "a\"b")], 1);
+        state.set_gauge("aqevia_test_depth", "Test depth.", &[], 7);
+        assert_eq!(state.metric("aqevia_test_total", &[("kind", "a\"b")]), 3);
+        let metrics = send_request(addr, "/metrics");
+        assert!(metrics.contains("text/plain; version=0.0.4"));
+        assert!(metrics.contains("aqevia_storage_ready 1\n"));
+        assert!(metrics
+            .contains("# TYPE aqevia_test_total counter\naqevia_test_total{kind=\"a\\\"b\"} 3\n"));
+        assert!(metrics.contains("aqevia_test_depth 7\n"));
+        server.shutdown();
+    }
+
+    #[test]
+    fn idle_connections_do_not_hold_up_other_requests() {
+        let state = Arc::new(ObservabilityState::new("0.2.0", "world", "sqlite"));
+        let mut server = ObservabilityServer::start(state, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr();
+        let idle: Vec<TcpStream> = (0..HTTP_WORKERS - 1)
+            .map(|_| TcpStream::connect(addr).expect("connect"))
+            .collect();
+        let started = Instant::now();
+        assert!(send_request(addr, "/health").contains("200 OK"));
+        assert!(started.elapsed() < HTTP_IO_TIMEOUT);
+        drop(idle);
+        server.shutdown();
+    }
+
+    #[test]
+    fn control_plane_guards_api_and_upgrades_websockets() {
+        use aqevia_auth::{AuthConfig, AuthService, Role};
+        use aqevia_router::SessionRegistry;
+
+        let auth = Arc::new(AuthService::new(AuthConfig {
+            hash_iterations: 16,
+            ..AuthConfig::default()
+        }));
+        auth.create_account("player", "password123", Role::Player)
+            .unwrap();
+        let token = auth.login("player", "password123").unwrap().token;
+        let sessions = Arc::new(SessionRegistry::new());
+        let state = Arc::new(ObservabilityState::new("0.2.0", "world", "sqlite"));
+        let plane = ControlPlane::new(auth, sessions.clone());
+        let mut server = ObservabilityServer::start_with_control_plane(
+            state,
+            plane,
+            "127.0.0.1:0".parse().unwrap(),
+        )
+        .unwrap();
+        let addr = server.local_addr();
+
+        let denied = send_request(addr, "/api/admin/sessions");
+        assert!(
+            denied.contains("401 Unauthorized"),
+            "response was {}",
+            denied
+        );
+        assert!(denied.contains(r#"{"status":"unauthorized""#));
+
+        let mut stream = TcpStream::connect(addr).expect("connect");
+        let upgrade = format!(
+            "GET /ws?token={} HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            token
+        );
+        stream.write_all(upgrade.as_bytes()).unwrap();
+        let mut head = [0u8; 256];
+        let read = stream.read(&mut head).unwrap();
+        let head = String::from_utf8_lossy(&head[..read]);
+        assert!(head.starts_with("HTTP/1.1 101"), "response was {}", head);
+        assert!(head.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        for _ in 0..50 {
+            if !sessions.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(sessions.list()[0].account, "player");
         server.shutdown();
     }
 }
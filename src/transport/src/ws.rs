@@ -0,0 +1,393 @@
+//! WebSocket data-plane adapter: authenticated handshake, text framing, and the per-connection
+//! pump between a socket and its Router session.
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use aqevia_auth::{AuthError, AuthService, Principal};
+use aqevia_router::{SessionEvent, SessionId, SessionRegistry};
+use base64::Engine as _;
+use sha1::{Digest, Sha1};
+use tracing::info_span;
+
+use crate::auth::auth_error_response;
+use crate::http::{HttpRequest, HttpResponse};
+
+/// Path that accepts WebSocket upgrades.
+pub const WS_PATH: &str = "/ws";
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Whether `request` asks for a WebSocket upgrade.
+pub fn is_upgrade(request: &HttpRequest) -> bool {
+    request
+        .header("upgrade")
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+/// Validate an upgrade request from an address that is not banned, and authenticate it with
+/// the same bearer tokens the control plane uses. Browsers cannot set headers on WebSocket
+/// requests, so `?token=` is accepted too.
+pub fn accept_handshake(
+    request: &HttpRequest,
+    auth: &AuthService,
+) -> Result<(Principal, HttpResponse), HttpResponse> {
+    if let Some(Err(err)) = request.peer.map(|peer| auth.check_ip(peer.ip())) {
+        return Err(auth_error_response(&err));
+    }
+    if request.method != "GET" || !is_upgrade(request) {
+        return Err(HttpResponse::error(
+            400,
+            "invalid_request",
+            "expected a WebSocket upgrade",
+        ));
+    }
+    let key = request
+        .header("sec-websocket-key")
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| HttpResponse::error(400, "invalid_request", "missing Sec-WebSocket-Key"))?;
+    let token = request
+        .bearer_token()
+        .or_else(|| request.query_param("token"))
+        .ok_or_else(|| auth_error_response(&AuthError::InvalidToken))?;
+    let principal = auth
+        .authenticate(token)
+        .map_err(|err| auth_error_response(&err))?;
+    let response = HttpResponse {
+        status: 101,
+        headers: vec![
+            ("Upgrade".into(), "websocket".into()),
+            ("Connection".into(), "Upgrade".into()),
+            ("Sec-WebSocket-Accept".into(), accept_key(key)),
+        ],
+        body: Vec::new(),
+    };
+    Ok((principal, response))
+}
+
+/// `Sec-WebSocket-Accept` value for a client key (RFC 6455 section 4.2.2).
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.trim().as_bytes());
+    hasher.update(ACCEPT_GUID.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+/// A decoded client frame.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Whether this is the last frame of its message.
+    pub fin: bool,
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+/// A client breaking the framing rules; the connection is closed with [`Self::close_code`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// Client frames must be masked (RFC 6455 section 5.1).
+    Unmasked,
+    /// A continuation with no message to continue, a new message before the last one
+    /// finished, or a fragmented control frame.
+    BadFragment,
+    /// A frame or reassembled message over `MAX_FRAME_BYTES`.
+    TooLarge,
+}
+
+impl FrameError {
+    pub fn close_code(self) -> u16 {
+        match self {
+            FrameError::Unmasked | FrameError::BadFragment => 1002,
+            FrameError::TooLarge => 1009,
+        }
+    }
+}
+
+/// Decode one frame from the front of `buf`, returning it with the number of bytes consumed.
+/// Returns `Ok(None)` when more bytes are needed.
+pub fn decode_frame(buf: &[u8]) -> Result<Option<(Frame, usize)>, FrameError> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let fin = buf[0] & 0x80 != 0;
+    let opcode = buf[0] & 0x0F;
+    if buf[1] & 0x80 == 0 {
+        return Err(FrameError::Unmasked);
+    }
+    let (length, mut offset) = match buf[1] & 0x7F {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as usize, 4),
+        127 if buf.len() >= 10 => {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(&buf[2..10]);
+            (u64::from_be_bytes(raw) as usize, 10)
+        }
+        126 | 127 => return Ok(None),
+        short => (short as usize, 2),
+    };
+    if length > MAX_FRAME_BYTES {
+        return Err(FrameError::TooLarge);
+    }
+    if buf.len() < offset + 4 {
+        return Ok(None);
+    }
+    let key = [
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ];
+    offset += 4;
+    if buf.len() < offset + length {
+        return Ok(None);
+    }
+    let mut payload = buf[offset..offset + length].to_vec();
+    for (idx, byte) in payload.iter_mut().enumerate() {
+        *byte ^= key[idx % 4];
+    }
+    Ok(Some((
+        Frame {
+            fin,
+            opcode,
+            payload,
+        },
+        offset + length,
+    )))
+}
+
+/// Reassembles fragmented text messages (RFC 6455 section 5.4).
+#[derive(Default)]
+struct Reassembly {
+    partial: Option<Vec<u8>>,
+}
+
+impl Reassembly {
+    /// Feed a text or continuation frame, returning the message once its final frame arrives.
+    fn push(&mut self, frame: Frame) -> Result<Option<Vec<u8>>, FrameError> {
+        let message = match (frame.opcode, self.partial.take()) {
+            (OP_TEXT, None) => frame.payload,
+            (OP_CONTINUATION, Some(mut message)) => {
+                message.extend_from_slice(&frame.payload);
+                message
+            }
+            _ => return Err(FrameError::BadFragment),
+        };
+        if message.len() > MAX_FRAME_BYTES {
+            return Err(FrameError::TooLarge);
+        }
+        if frame.fin {
+            Ok(Some(message))
+        } else {
+            self.partial = Some(message);
+            Ok(None)
+        }
+    }
+}
+
+/// Encode an unmasked server frame.
+pub fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 10);
+    out.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => out.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            out.push(126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Close the connection for a framing error, with its close code.
+fn close(stream: &mut TcpStream, err: FrameError) {
+    let _ = stream.write_all(&encode_frame(OP_CLOSE, &err.close_code().to_be_bytes()));
+}
+
+/// Pump frames between `stream` and the session until either side closes.
+pub fn serve_session(
+    mut stream: TcpStream,
+    sessions: Arc<SessionRegistry>,
+    id: SessionId,
+    events: Receiver<SessionEvent>,
+) {
+    let _span = info_span!("ws", session_id = id).entered();
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(25)));
+    let mut buffer = Vec::new();
+    let mut reassembly = Reassembly::default();
+    let mut chunk = [0u8; 4096];
+    'session: loop {
+        loop {
+            match events.try_recv() {
+                Ok(SessionEvent::Message(text)) => {
+                    if stream
+                        .write_all(&encode_frame(OP_TEXT, text.as_bytes()))
+                        .is_err()
+                    {
+                        break 'session;
+                    }
+                }
+                Ok(SessionEvent::Close(reason)) => {
+                    let mut payload = 1000u16.to_be_bytes().to_vec();
+                    payload.extend_from_slice(reason.as_bytes());
+                    let _ = stream.write_all(&encode_frame(OP_CLOSE, &payload));
+                    break 'session;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'session,
+            }
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+            Err(err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(_) => break,
+        }
+        loop {
+            match decode_frame(&buffer) {
+                Ok(Some((frame, used))) => {
+                    buffer.drain(..used);
+                    let handled = match frame.opcode {
+                        OP_TEXT | OP_CONTINUATION => reassembly.push(frame).map(|message| {
+                            let command = message
+                                .map(|message| String::from_utf8_lossy(&message).trim().to_string())
+                                .unwrap_or_default();
+                            if !command.is_empty() {
+                                sessions.submit(id, command);
+                            }
+                        }),
+                        // Control frames may arrive between fragments but are never split.
+                        _ if !frame.fin => Err(FrameError::BadFragment),
+                        OP_PING => {
+                            let _ = stream.write_all(&encode_frame(OP_PONG, &frame.payload));
+                            Ok(())
+                        }
+                        OP_CLOSE => {
+                            let _ = stream.write_all(&encode_frame(OP_CLOSE, &frame.payload));
+                            break 'session;
+                        }
+                        _ => Ok(()),
+                    };
+                    if let Err(err) = handled {
+                        close(&mut stream, err);
+                        break 'session;
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    close(&mut stream, err);
+                    break 'session;
+                }
+            }
+        }
+    }
+    sessions.close(id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aqevia_auth::{AuthConfig, Role};
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    fn client_frame(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![first, 0x80 | payload.len() as u8, 1, 2, 3, 4];
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(idx, byte)| byte ^ [1, 2, 3, 4][idx % 4]),
+        );
+        frame
+    }
+
+    #[test]
+    fn frames_round_trip_with_client_masking() {
+        let masked = client_frame(0x81, b"look");
+        let (frame, used) = decode_frame(&masked).unwrap().unwrap();
+        assert_eq!(used, masked.len());
+        assert_eq!(
+            frame,
+            Frame {
+                fin: true,
+                opcode: OP_TEXT,
+                payload: b"look".to_vec()
+            }
+        );
+        assert!(decode_frame(&masked[..3]).unwrap().is_none());
+        let long = encode_frame(OP_TEXT, &[b'a'; 300]);
+        assert_eq!(&long[..4], &[0x81, 126, 1, 44]);
+    }
+
+    #[test]
+    fn unmasked_frames_are_refused_and_fragments_reassembled() {
+        let err = decode_frame(&[0x81, 4, b'l', b'o', b'o', b'k']).unwrap_err();
+        assert_eq!((err, err.close_code()), (FrameError::Unmasked, 1002));
+
+        let mut reassembly = Reassembly::default();
+        let mut feed = |first: u8, payload: &[u8]| {
+            let (frame, _) = decode_frame(&client_frame(first, payload))
+                .unwrap()
+                .unwrap();
+            reassembly.push(frame)
+        };
+        assert_eq!(feed(0x01, b"say "), Ok(None));
+        assert_eq!(feed(0x00, b"hello "), Ok(None));
+        assert_eq!(feed(0x80, b"there"), Ok(Some(b"say hello there".to_vec())));
+        assert_eq!(feed(0x80, b"stray"), Err(FrameError::BadFragment));
+        assert_eq!(feed(0x01, b"one"), Ok(None));
+        assert_eq!(feed(0x81, b"two"), Err(FrameError::BadFragment));
+    }
+
+    #[test]
+    fn handshake_requires_valid_token() {
+        let auth = AuthService::new(AuthConfig {
+            hash_iterations: 16,
+            ..AuthConfig::default()
+        });
+        auth.create_account("player", "password123", Role::Player)
+            .unwrap();
+        let token = auth.login("player", "password123").unwrap().token;
+        let upgrade = HttpRequest::new("GET", "/ws")
+            .with_header("Upgrade", "websocket")
+            .with_header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==");
+        assert_eq!(accept_handshake(&upgrade, &auth).unwrap_err().status, 401);
+
+        let with_query = HttpRequest {
+            query: vec![("token".into(), token)],
+            ..upgrade.clone()
+        };
+        let (principal, response) = accept_handshake(&with_query, &auth).unwrap();
+        assert_eq!(principal.username, "player");
+        assert_eq!(response.status, 101);
+        assert_eq!(
+            response.header("Sec-WebSocket-Accept"),
+            Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        );
+    }
+}